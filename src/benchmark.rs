@@ -0,0 +1,137 @@
+// benchmark.rs
+use std::time::Instant;
+use glam::DVec3;
+use serde::Serialize;
+use crate::{cinematic::{CameraPath, Keyframe}, config, config_file};
+
+// A loop around the map origin, alternating between rooftop and overview height and looking
+// ahead along it. It only depends on the config, so runs on the same map fly the same way.
+pub fn flight() -> CameraPath {
+    let radius = config::BENCHMARK_RADIUS.min(config_file::world_size() as f64 * 0.4);
+    let keyframes = (0..=config::BENCHMARK_KEYFRAMES).map(|i| {
+        let angle = i as f64 / config::BENCHMARK_KEYFRAMES as f64 * std::f64::consts::TAU;
+        let altitude = config::BENCHMARK_ALTITUDES[i % 2];
+        let eye = DVec3::new(radius * angle.cos(), altitude, radius * angle.sin());
+        // Facing along the loop, steeper from higher up.
+        let yaw = (angle + std::f64::consts::FRAC_PI_2) as f32;
+        let pitch = -(altitude / radius).atan() as f32 - 0.1;
+        Keyframe { eye: eye.to_array(), yaw, pitch }
+    }).collect();
+    CameraPath { keyframes, duration: config::BENCHMARK_DURATION }
+}
+
+// One rendered frame of the flight.
+struct Frame {
+    time: f64,
+    frame_ms: f64,
+    upload_ms: f64,
+    // Chunk meshes still waiting for the GPU.
+    upload_backlog: usize,
+    gpu_mb: f64,
+}
+
+#[derive(Serialize)]
+struct Report {
+    map: String,
+    adapter: String,
+    frames: usize,
+    seconds: f64,
+    average_fps: f64,
+    average_ms: f64,
+    median_ms: f64,
+    p99_ms: f64,
+    worst_ms: f64,
+    // Average frame rate over the slowest 1% of frames.
+    one_percent_low_fps: f64,
+    upload_stalls: usize,
+    worst_upload_ms: f64,
+    peak_upload_backlog: usize,
+    peak_gpu_mb: f64,
+    // Resident memory of the process, where the platform reports it.
+    peak_ram_mb: Option<f64>,
+}
+
+// Frame times, chunk upload stalls and memory recorded over the benchmark flight, written to
+// `report` as a JSON summary and next to it as CSV with a row per frame.
+pub struct Benchmark {
+    report: String,
+    // The map file flown over, named in the report.
+    map: String,
+    started: Instant,
+    frames: Vec<Frame>,
+    peak_ram_mb: Option<f64>,
+}
+
+impl Benchmark {
+    pub fn new(report: String, map: String) -> Self {
+        log::info!("Benchmark started; the report goes to {}", report);
+        Self { report, map, started: Instant::now(), frames: Vec::new(), peak_ram_mb: None }
+    }
+
+    pub fn record(&mut self, frame_time: f64, upload_time: f64, upload_backlog: usize, gpu_bytes: u64) {
+        self.frames.push(Frame {
+            time: self.started.elapsed().as_secs_f64(), frame_ms: frame_time * 1000.0, upload_ms: upload_time * 1000.0,
+            upload_backlog, gpu_mb: gpu_bytes as f64 / (1024.0 * 1024.0),
+        });
+        if let Some(mb) = resident_mb() { self.peak_ram_mb = Some(self.peak_ram_mb.map_or(mb, |peak| peak.max(mb))); }
+    }
+
+    // Writes both reports and logs the summary.
+    pub fn finish(&self, adapter: &wgpu::AdapterInfo) {
+        let Some(report) = self.summary(adapter) else {
+            log::warn!("Benchmark ended without rendering a frame; no report written");
+            return;
+        };
+        log::info!(
+            "Benchmark: {} frames, {:.1} FPS average, {:.1} FPS 1% low, {:.2} ms p99, {} upload stalls",
+            report.frames, report.average_fps, report.one_percent_low_fps, report.p99_ms, report.upload_stalls,
+        );
+        let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())
+            .and_then(|text| std::fs::write(&self.report, text).map_err(|e| e.to_string()));
+        let csv_path = std::path::Path::new(&self.report).with_extension("csv");
+        let mut csv = String::from("time_s,frame_ms,upload_ms,upload_backlog,gpu_mb\n");
+        for f in &self.frames {
+            csv.push_str(&format!("{:.4},{:.3},{:.3},{},{:.1}\n", f.time, f.frame_ms, f.upload_ms, f.upload_backlog, f.gpu_mb));
+        }
+        for (path, result) in [(self.report.clone(), json), (csv_path.display().to_string(), std::fs::write(&csv_path, csv).map_err(|e| e.to_string()))] {
+            match result {
+                Ok(()) => log::info!("Wrote benchmark report {}", path),
+                Err(e) => log::error!("Couldn't write benchmark report {}: {}", path, e),
+            }
+        }
+    }
+
+    fn summary(&self, adapter: &wgpu::AdapterInfo) -> Option<Report> {
+        let mut times: Vec<f64> = self.frames.iter().map(|f| f.frame_ms).collect();
+        if times.is_empty() { return None; }
+        times.sort_by(f64::total_cmp);
+        let total_ms: f64 = times.iter().sum();
+        let percentile = |p: f64| times[((times.len() - 1) as f64 * p).round() as usize];
+        let slowest = &times[times.len() - times.len().div_ceil(100)..];
+        let slowest_ms = slowest.iter().sum::<f64>() / slowest.len() as f64;
+        Some(Report {
+            map: self.map.clone(),
+            adapter: format!("{} ({:?}, {:?})", adapter.name, adapter.device_type, adapter.backend),
+            frames: times.len(),
+            seconds: total_ms / 1000.0,
+            average_fps: times.len() as f64 * 1000.0 / total_ms,
+            average_ms: total_ms / times.len() as f64,
+            median_ms: percentile(0.5),
+            p99_ms: percentile(0.99),
+            worst_ms: times[times.len() - 1],
+            one_percent_low_fps: 1000.0 / slowest_ms,
+            upload_stalls: self.frames.iter().filter(|f| f.upload_ms > config::BENCHMARK_STALL_MS).count(),
+            worst_upload_ms: self.frames.iter().map(|f| f.upload_ms).fold(0.0, f64::max),
+            peak_upload_backlog: self.frames.iter().map(|f| f.upload_backlog).max().unwrap_or(0),
+            peak_gpu_mb: self.frames.iter().map(|f| f.gpu_mb).fold(0.0, f64::max),
+            peak_ram_mb: self.peak_ram_mb,
+        })
+    }
+}
+
+// Resident set size from /proc, so only on Linux. statm counts pages, taken as 4 KiB.
+fn resident_mb() -> Option<f64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some((pages * 4096) as f64 / (1024.0 * 1024.0))
+}
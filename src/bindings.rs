@@ -0,0 +1,213 @@
+// bindings.rs
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use winit::{event::{KeyEvent, MouseButton}, keyboard::{Key, KeyCode, PhysicalKey}};
+use crate::config_file;
+
+// What a player's controller can be asked to do. Look actions turn the view from the keyboard,
+// for a player without a mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward, MoveBack, MoveLeft, MoveRight,
+    Jump,
+    Sprint,
+    // Also sinks while flying.
+    Crouch,
+    Grapple,
+    // Gets in and out of cars.
+    Interact,
+    Zoom,
+    LookUp, LookDown, LookLeft, LookRight,
+}
+
+// A mouse button, named so it can't be mistaken for a key in the bindings file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mouse {
+    #[serde(rename = "MouseLeft")]
+    Left,
+    #[serde(rename = "MouseRight")]
+    Right,
+    #[serde(rename = "MouseMiddle")]
+    Middle,
+}
+
+// One input: a key by winit's KeyCode name (e.g. "KeyW" or "ShiftLeft", matched as
+// controls.key_layout says), the character a key types (e.g. "z", whatever the layout), or a
+// mouse button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Input {
+    Key(KeyCode),
+    Mouse(Mouse),
+    Char(char),
+}
+
+impl Input {
+    pub fn mouse(button: MouseButton) -> Option<Self> {
+        match button {
+            MouseButton::Left => Some(Self::Mouse(Mouse::Left)),
+            MouseButton::Right => Some(Self::Mouse(Mouse::Right)),
+            MouseButton::Middle => Some(Self::Mouse(Mouse::Middle)),
+            _ => None,
+        }
+    }
+
+    // Characters are matched in lower case, however they were written or typed.
+    fn normalized(self) -> Self {
+        match self {
+            Self::Char(c) => Self::Char(c.to_lowercase().next().unwrap_or(c)),
+            input => input,
+        }
+    }
+}
+
+// How letter and digit KeyCodes in the bindings are matched. Other keys (Space, Shift, the
+// arrows) are always matched by position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyLayout {
+    // By where the key sits on a US keyboard: WASD stays in the same place on AZERTY (ZQSD).
+    Physical,
+    // By the character printed on the key: KeyW is whichever key types a W.
+    Logical,
+}
+
+const LETTERS: [KeyCode; 26] = {
+    use KeyCode::*;
+    [KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM, KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ]
+};
+const DIGITS: [KeyCode; 10] = {
+    use KeyCode::*;
+    [Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9]
+};
+
+impl KeyLayout {
+    // The inputs a key press stands for: the key itself, or in Logical the KeyCode of the
+    // letter or digit it types, and the typed character.
+    pub fn inputs(self, event: &KeyEvent) -> Vec<Input> {
+        let PhysicalKey::Code(code) = event.physical_key else { return Vec::new() };
+        let typed = match &event.logical_key {
+            Key::Character(text) => {
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(Input::Char(c).normalized()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let key = match (self, typed) {
+            (Self::Logical, Some(Input::Char(c))) => key_for_char(c).unwrap_or(code),
+            _ => code,
+        };
+        std::iter::once(Input::Key(key)).chain(typed).collect()
+    }
+}
+
+fn key_for_char(c: char) -> Option<KeyCode> {
+    match c {
+        'a'..='z' => Some(LETTERS[(c as u8 - b'a') as usize]),
+        '0'..='9' => Some(DIGITS[(c as u8 - b'0') as usize]),
+        _ => None,
+    }
+}
+
+// Every input bound to each action; any of them triggers it. Actions left out of the bindings
+// file keep their defaults, and an empty list unbinds one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Bindings(BTreeMap<Action, Vec<Input>>);
+
+impl Bindings {
+    pub fn primary() -> Self {
+        use {Action::*, KeyCode::*};
+        Self::from_pairs(&[
+            (MoveForward, &[Input::Key(KeyW)]), (MoveBack, &[Input::Key(KeyS)]), (MoveLeft, &[Input::Key(KeyA)]), (MoveRight, &[Input::Key(KeyD)]),
+            (Jump, &[Input::Key(Space)]), (Sprint, &[Input::Key(ShiftLeft)]), (Crouch, &[Input::Key(ControlLeft)]),
+            (Grapple, &[Input::Mouse(Mouse::Left)]), (Interact, &[Input::Key(KeyE)]), (Zoom, &[Input::Mouse(Mouse::Right)]),
+        ])
+    }
+
+    // Second split-screen player when no gamepad is available.
+    pub fn secondary() -> Self {
+        use {Action::*, KeyCode::*};
+        Self::from_pairs(&[
+            (MoveForward, &[Input::Key(ArrowUp)]), (MoveBack, &[Input::Key(ArrowDown)]), (MoveLeft, &[Input::Key(ArrowLeft)]), (MoveRight, &[Input::Key(ArrowRight)]),
+            (Jump, &[Input::Key(ControlRight)]), (Sprint, &[Input::Key(ShiftRight)]), (Crouch, &[Input::Key(Numpad0)]),
+            (Grapple, &[Input::Key(Numpad7)]), (Interact, &[Input::Key(Numpad9)]), (Zoom, &[Input::Key(Numpad1)]),
+            (LookUp, &[Input::Key(Numpad8)]), (LookDown, &[Input::Key(Numpad5)]), (LookLeft, &[Input::Key(Numpad4)]), (LookRight, &[Input::Key(Numpad6)]),
+        ])
+    }
+
+    fn from_pairs(pairs: &[(Action, &[Input])]) -> Self {
+        Self(pairs.iter().map(|(action, inputs)| (*action, inputs.to_vec())).collect())
+    }
+
+    pub fn inputs(&self, action: Action) -> &[Input] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    // Actions `input` is bound to.
+    pub fn actions(&self, input: Input) -> impl Iterator<Item = Action> + '_ {
+        self.0.iter().filter(move |(_, inputs)| inputs.contains(&input)).map(|(action, _)| *action)
+    }
+
+    // `self` with whatever `file` sets laid over it.
+    fn merged(mut self, file: Bindings) -> Self {
+        self.0.extend(file.0.into_iter().map(|(action, inputs)| (action, inputs.into_iter().map(Input::normalized).collect())));
+        self
+    }
+}
+
+// Contents of the bindings file (paths.bindings), one table per split-screen player.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BindingsFile {
+    pub player_one: Bindings,
+    pub player_two: Bindings,
+}
+
+impl Default for BindingsFile {
+    fn default() -> Self {
+        Self { player_one: Bindings::primary(), player_two: Bindings::secondary() }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PartialFile {
+    player_one: Option<Bindings>,
+    player_two: Option<Bindings>,
+}
+
+impl BindingsFile {
+    // The defaults with the bindings file laid over them. A missing file is written out with
+    // the defaults so there's something to edit; a broken one is reported and ignored.
+    pub fn load() -> Self {
+        let path = &config_file::get().paths.bindings;
+        let Ok(text) = std::fs::read_to_string(path) else {
+            let defaults = Self::default();
+            defaults.save();
+            return defaults;
+        };
+        match toml::from_str::<PartialFile>(&text) {
+            Ok(file) => Self {
+                player_one: Bindings::primary().merged(file.player_one.unwrap_or_default()),
+                player_two: Bindings::secondary().merged(file.player_two.unwrap_or_default()),
+            },
+            Err(e) => {
+                log::warn!("Ignoring {}: {}", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let path = &config_file::get().paths.bindings;
+        let result = toml::to_string_pretty(self).map_err(|e| e.to_string())
+            .and_then(|text| std::fs::write(path, text).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => log::info!("Wrote key bindings to {}", path),
+            Err(e) => log::warn!("Couldn't save {}: {}", path, e),
+        }
+    }
+}
@@ -1,15 +1,28 @@
 // camera.rs
-use glam::{DMat4, DVec3, Mat4, Vec3};
+use glam::{DMat4, DQuat, DVec3, Mat4, Vec2, Vec3};
 use winit::event::*;
-use winit::keyboard::{KeyCode, PhysicalKey};
-use crate::config;
+use winit::keyboard::PhysicalKey;
+use crate::{bindings::{Action, Bindings, Input}, config, config_file};
 
-#[derive(Debug)]
+// How a camera maps view space to clip space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    // Straight-down map view showing `half_height` meters above and below the center.
+    Orthographic { half_height: f32 },
+}
+
+#[derive(Debug, Clone)]
 pub struct Camera {
     pub eye: DVec3,
     pub yaw: f32,
     pub pitch: f32,
+    // Bank around the view direction, used by the glider.
+    pub roll: f32,
     pub aspect: f32,
+    // Vertical field of view in degrees, for the perspective projection.
+    pub fov_y: f32,
+    pub projection: Projection,
 }
 
 impl Camera {
@@ -18,21 +31,65 @@ impl Camera {
             eye: DVec3::new(0.0, 50.0, 0.0),
             yaw: -90.0f32.to_radians(),
             pitch: 0.0,
+            roll: 0.0,
             aspect,
+            fov_y: config_file::get().graphics.fov,
+            projection: Projection::Perspective,
         }
     }
 
-    pub fn build_view_projection_matrix(&self) -> Mat4 {
+    // Overhead map camera centered on this one, north up.
+    pub fn map_view(&self) -> Camera {
+        Camera {
+            eye: DVec3::new(self.eye.x, config::MAP_VIEW_HEIGHT as f64, self.eye.z),
+            // Just short of straight down so the view matrix keeps a well-defined up axis.
+            yaw: -90.0f32.to_radians(),
+            pitch: -89.9f32.to_radians(),
+            roll: 0.0,
+            aspect: self.aspect,
+            fov_y: self.fov_y,
+            projection: Projection::Orthographic { half_height: config::MAP_VIEW_EXTENT },
+        }
+    }
+
+    pub fn forward(&self) -> DVec3 {
         let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
-        let target = DVec3::new(
+        DVec3::new(
             (cos_pitch * cos_yaw) as f64,
             sin_pitch as f64,
             (cos_pitch * sin_yaw) as f64,
-        ).normalize();
-        let view = DMat4::look_at_rh(self.eye, self.eye + target, DVec3::Y);
-        let proj = Mat4::perspective_rh(config::FOV_Y.to_radians(), self.aspect, config::Z_NEAR, config::Z_FAR);
-        proj * view.as_mat4()
+        ).normalize()
+    }
+
+    pub fn build_view_matrix(&self) -> Mat4 {
+        self.view_matrix_at(self.eye)
+    }
+
+    fn view_matrix_at(&self, eye: DVec3) -> Mat4 {
+        let forward = self.forward();
+        let up = DQuat::from_axis_angle(forward, self.roll as f64) * DVec3::Y;
+        DMat4::look_at_rh(eye, eye + forward, up).as_mat4()
+    }
+
+    pub fn build_projection_matrix(&self) -> Mat4 {
+        match self.projection {
+            Projection::Perspective => Mat4::perspective_rh(self.fov_y.to_radians(), self.aspect, config::Z_NEAR, config_file::far_plane()),
+            Projection::Orthographic { half_height } => {
+                let half_width = half_height * self.aspect;
+                Mat4::orthographic_rh(-half_width, half_width, -half_height, half_height, config::Z_NEAR, config::MAP_VIEW_HEIGHT - config::CHUNK_MIN_Y)
+            }
+        }
+    }
+
+    pub fn build_view_projection_matrix(&self) -> Mat4 {
+        self.build_projection_matrix() * self.build_view_matrix()
+    }
+
+    // Same as the view-projection, but for positions relative to the eye. Far from the origin
+    // f32 world positions lose the precision the transform needs; offsets from the camera don't.
+    pub fn build_relative_view_projection_matrix(&self) -> Mat4 {
+        self.build_projection_matrix() * self.view_matrix_at(DVec3::ZERO)
     }
 }
 
@@ -41,33 +98,114 @@ impl Camera {
 pub struct CameraUniform {
     pub view_proj: [[f32; 4]; 4],
     pub screen_size: [f32; 2],
+    // Distance over which geometry fully fades into the sky, hiding the draw distance.
     pub fog_dist: [f32; 2],
+    // w is the underwater murk density, 0 above the surface.
     pub camera_pos: [f32; 4],
+    // xyz toward the sun, w the daylight factor (0 night, 1 day).
+    pub sun_dir: [f32; 4],
+    // Horizon color, also the fog tint; w the street wetness (0 dry, 1 soaked).
+    pub sky_color: [f32; 4],
+    pub zenith_color: [f32; 4],
+    // Maps clip space back to world space for the sky pass.
+    pub inv_view_proj: [[f32; 4]; 4],
+    // Pixel rect (x, y, w, h) of the view, for passes working in screen space.
+    pub viewport: [f32; 4],
+    // Height fog density at the base height, its falloff per meter above it, the base
+    // height, and the aerial perspective density (0 disables).
+    pub fog: [f32; 4],
+    // view_proj for camera-relative positions, see Camera::build_relative_view_projection_matrix.
+    pub relative_view_proj: [[f32; 4]; 4],
 }
 
+// Held actions of one player, set through its bindings, a gamepad or a replay.
 pub struct CameraController {
     pub move_fwd: bool, pub move_back: bool, pub move_left: bool, pub move_right: bool, pub jump: bool,
+    pub sprint: bool, pub crouch: bool, pub grapple: bool, pub interact: bool, pub zoom: bool,
+    // Analog input from a gamepad, in -1..1 per axis.
+    pub move_axis: Vec2, pub look_axis: Vec2,
+    look_keys: [bool; 4],
+    bindings: Bindings,
+    // Inputs currently down, each with the key or button it came from, so an action stays held
+    // while any of its inputs is and a release lets go of whatever its press stood for.
+    held: Vec<(Input, Input)>,
 }
 
 impl CameraController {
-    pub fn new() -> Self {
-        Self { move_fwd: false, move_back: false, move_left: false, move_right: false, jump: false }
+    pub fn new(bindings: Bindings) -> Self {
+        Self {
+            move_fwd: false, move_back: false, move_left: false, move_right: false, jump: false, sprint: false, crouch: false, grapple: false, interact: false, zoom: false,
+            move_axis: Vec2::ZERO, look_axis: Vec2::ZERO, look_keys: [false; 4], bindings, held: Vec::new(),
+        }
+    }
+    // Lets go of every held input, for when key releases won't arrive.
+    pub fn release_all(&mut self) {
+        *self = Self::new(std::mem::take(&mut self.bindings));
+    }
+
+    pub fn set_bindings(&mut self, bindings: Bindings) {
+        self.bindings = bindings;
+        self.release_all();
     }
+
     pub fn process_events(&mut self, event: &WindowEvent) -> bool {
-        match event {
-            WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(key), state, .. }, .. } => {
-                let pressed = *state == ElementState::Pressed;
-                match key {
-                    KeyCode::KeyW => { self.move_fwd = pressed; true }
-                    KeyCode::KeyS => { self.move_back = pressed; true }
-                    KeyCode::KeyA => { self.move_left = pressed; true }
-                    KeyCode::KeyD => { self.move_right = pressed; true }
-                    KeyCode::Space => { self.jump = pressed; true }
-                    _ => false,
-                }
-            }
-            _ => false,
+        let Some((source, inputs, pressed)) = controller_input(event) else { return false };
+        self.apply(source, &inputs, pressed)
+    }
+
+    // A press or release of `source`; a press stands for `inputs`. Returns whether any bound
+    // action changed.
+    pub fn apply(&mut self, source: Input, inputs: &[Input], pressed: bool) -> bool {
+        // A release may type something else than its press did (Shift or AltGr changed in between).
+        let inputs: Vec<Input> = if pressed { inputs.to_vec() } else { self.held.iter().filter(|(s, _)| *s == source).map(|&(_, i)| i).collect() };
+        let actions: Vec<Action> = inputs.iter().flat_map(|&i| self.bindings.actions(i)).collect();
+        if actions.is_empty() { return false; }
+        self.held.retain(|&(s, _)| s != source);
+        if pressed { self.held.extend(inputs.iter().map(|&i| (source, i))); }
+        for action in actions {
+            let down = self.bindings.inputs(action).iter().any(|i| self.held.iter().any(|(_, h)| h == i));
+            *self.action_mut(action) = down;
+        }
+        true
+    }
+
+    fn action_mut(&mut self, action: Action) -> &mut bool {
+        match action {
+            Action::MoveForward => &mut self.move_fwd,
+            Action::MoveBack => &mut self.move_back,
+            Action::MoveLeft => &mut self.move_left,
+            Action::MoveRight => &mut self.move_right,
+            Action::Jump => &mut self.jump,
+            Action::Sprint => &mut self.sprint,
+            Action::Crouch => &mut self.crouch,
+            Action::Grapple => &mut self.grapple,
+            Action::Interact => &mut self.interact,
+            Action::Zoom => &mut self.zoom,
+            Action::LookUp => &mut self.look_keys[0],
+            Action::LookDown => &mut self.look_keys[1],
+            Action::LookLeft => &mut self.look_keys[2],
+            Action::LookRight => &mut self.look_keys[3],
+        }
+    }
+    // Combined look rate from look keys and the gamepad stick (x = yaw, y = pitch).
+    pub fn look_rate(&self) -> Vec2 {
+        let [up, down, left, right] = self.look_keys.map(|b| if b { 1.0 } else { 0.0 });
+        self.look_axis + Vec2::new(right - left, up - down)
+    }
+}
+
+// The key or mouse button behind a window event, what a press of it stands for (see
+// KeyLayout::inputs), and whether it was pressed.
+pub fn controller_input(event: &WindowEvent) -> Option<(Input, Vec<Input>, bool)> {
+    match event {
+        WindowEvent::KeyboardInput { event: key_event @ KeyEvent { physical_key: PhysicalKey::Code(key), state, .. }, .. } => {
+            Some((Input::Key(*key), config_file::get().controls.key_layout.inputs(key_event), *state == ElementState::Pressed))
+        }
+        WindowEvent::MouseInput { state, button, .. } => {
+            let input = Input::mouse(*button)?;
+            Some((input, vec![input], *state == ElementState::Pressed))
         }
+        _ => None,
     }
 }
 
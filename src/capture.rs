@@ -0,0 +1,198 @@
+// capture.rs
+use std::collections::VecDeque;
+use glam::{DVec2, Vec2};
+use serde::{Deserialize, Serialize};
+use crate::{bindings::Input, camera::CameraController, config_file, player::Player, settings::MouseSettings};
+
+// Something that reached the players' controllers between two frames.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CaptureEvent {
+    // A bound key or mouse button; `inputs` is what a press stood for (empty on release).
+    Input { source: Input, inputs: Vec<Input>, pressed: bool },
+    // Mouse wheel notches, which set the flying speed or the orbit distance.
+    Wheel(f64),
+    // Every held input let go, as when a menu opens.
+    ReleaseAll,
+}
+
+// Player two's controller as a gamepad left it, which doesn't go through events.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PadState {
+    move_axis: [f32; 2],
+    look_axis: [f32; 2],
+    // jump, sprint, crouch, grapple, interact, zoom
+    buttons: [bool; 6],
+}
+
+impl PadState {
+    #[cfg(feature = "gamepad")]
+    pub fn read(controller: &CameraController) -> Self {
+        let c = controller;
+        Self {
+            move_axis: c.move_axis.to_array(), look_axis: c.look_axis.to_array(),
+            buttons: [c.jump, c.sprint, c.crouch, c.grapple, c.interact, c.zoom],
+        }
+    }
+
+    pub fn apply(&self, controller: &mut CameraController) {
+        let c = controller;
+        (c.move_axis, c.look_axis) = (Vec2::from(self.move_axis), Vec2::from(self.look_axis));
+        [c.jump, c.sprint, c.crouch, c.grapple, c.interact, c.zoom] = self.buttons;
+    }
+}
+
+// One update: how long it was, the mouse movement looked with, and what came in before it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureFrame {
+    pub dt: f64,
+    pub look: [f64; 2],
+    pub events: Vec<CaptureEvent>,
+    pub pad: Option<PadState>,
+}
+
+// Where each player stood and how the simulation stood when the capture began.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureStart {
+    // eye, yaw and pitch per player
+    players: Vec<([f64; 3], f32, f32)>,
+    physics_accumulator: f64,
+    world_clock: f64,
+    mouse: MouseSettings,
+}
+
+impl CaptureStart {
+    pub fn new(players: &[Player], physics_accumulator: f64, world_clock: f64, mouse: MouseSettings) -> Self {
+        let players = players.iter().map(|p| (p.camera.eye.to_array(), p.camera.yaw, p.camera.pitch)).collect();
+        Self { players, physics_accumulator, world_clock, mouse }
+    }
+
+    // Puts the players back where they were, at rest and holding nothing. Returns the physics
+    // accumulator and world clock to continue from.
+    pub fn restore(&self, players: &mut [Player]) -> (f64, f64) {
+        for (player, &(eye, yaw, pitch)) in players.iter_mut().zip(&self.players) {
+            player.teleport(eye.into());
+            (player.camera.yaw, player.camera.pitch) = (yaw, pitch);
+            player.controller.release_all();
+        }
+        (self.physics_accumulator, self.world_clock)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CaptureFile {
+    start: CaptureStart,
+    frames: Vec<CaptureFrame>,
+}
+
+enum CaptureState {
+    Idle,
+    Recording { file: CaptureFile, pending: Vec<CaptureEvent> },
+    Playing { start: CaptureStart, frames: VecDeque<CaptureFrame>, played: usize, quit_at_end: bool },
+}
+
+// Records every input that drives the players, frame by frame with each frame's length, into
+// the capture file (paths.capture), and plays one back in place of live input. Frames replay
+// with their recorded lengths through the same fixed-step physics, so a run that starts from
+// the same loaded chunks ends in the same place: for bug reports and automated soak tests
+// (--capture and --playback).
+pub struct InputCapture {
+    state: CaptureState,
+}
+
+impl InputCapture {
+    pub fn new() -> Self {
+        Self { state: CaptureState::Idle }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self.state, CaptureState::Recording { .. })
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(self.state, CaptureState::Playing { .. })
+    }
+
+    pub fn start_recording(&mut self, start: CaptureStart) {
+        self.state = CaptureState::Recording { file: CaptureFile { start, frames: Vec::new() }, pending: Vec::new() };
+        log::info!("Capturing input to {}", config_file::get().paths.capture);
+    }
+
+    // Stops recording and writes the capture out; returns what happened, for the console.
+    pub fn stop_recording(&mut self) -> String {
+        let CaptureState::Recording { file, .. } = std::mem::replace(&mut self.state, CaptureState::Idle) else { return "Not capturing".to_string() };
+        let path = &config_file::get().paths.capture;
+        let seconds: f64 = file.frames.iter().map(|f| f.dt).sum();
+        let result = serde_json::to_string(&file).map_err(|e| e.to_string())
+            .and_then(|text| std::fs::write(path, text).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => format!("Saved {} frames ({:.1} s) of input to {}", file.frames.len(), seconds, path),
+            Err(e) => format!("Couldn't save {}: {}", path, e),
+        }
+    }
+
+    // Loads `path` for playback; the caller restores the returned start. With `quit_at_end`
+    // the game exits once the last frame has played.
+    pub fn start_playback(&mut self, path: &str, quit_at_end: bool) -> Result<CaptureStart, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Couldn't read {}: {}", path, e))?;
+        let file: CaptureFile = serde_json::from_str(&text).map_err(|e| format!("{} isn't an input capture: {}", path, e))?;
+        log::info!("Playing back {} frames of input from {}", file.frames.len(), path);
+        self.state = CaptureState::Playing { start: file.start.clone(), frames: file.frames.into(), played: 0, quit_at_end };
+        Ok(file.start)
+    }
+
+    pub fn stop_playback(&mut self) {
+        if self.is_playing() { self.state = CaptureState::Idle; }
+    }
+
+    // Adds an input that just reached the controllers to the frame being recorded.
+    pub fn record(&mut self, event: CaptureEvent) {
+        if let CaptureState::Recording { pending, .. } = &mut self.state { pending.push(event); }
+    }
+
+    // Closes the frame being recorded.
+    pub fn record_frame(&mut self, dt: f64, look: DVec2, pad: Option<PadState>) {
+        if let CaptureState::Recording { file, pending } = &mut self.state {
+            file.frames.push(CaptureFrame { dt, look: look.to_array(), events: std::mem::take(pending), pad });
+        }
+    }
+
+    // The next frame to play, or None when not playing. The last one ends playback; the bool
+    // says whether the game should quit then.
+    pub fn next_frame(&mut self) -> Option<(CaptureFrame, bool)> {
+        let CaptureState::Playing { frames, played, quit_at_end, .. } = &mut self.state else { return None };
+        let frame = frames.pop_front()?;
+        *played += 1;
+        let last = frames.is_empty();
+        let quit = last && *quit_at_end;
+        if last { self.state = CaptureState::Idle; }
+        Some((frame, quit))
+    }
+
+    // Mouse settings to look with: the recorded ones while playing.
+    pub fn mouse(&self) -> Option<MouseSettings> {
+        match &self.state {
+            CaptureState::Playing { start, .. } => Some(start.mouse),
+            _ => None,
+        }
+    }
+
+    pub fn status(&self) -> Option<String> {
+        match &self.state {
+            CaptureState::Idle => None,
+            CaptureState::Recording { file, .. } => Some(format!("Capturing input: {} frames", file.frames.len())),
+            CaptureState::Playing { frames, played, .. } => Some(format!("Playing input: {} / {}", played, played + frames.len())),
+        }
+    }
+}
+
+// Applies a played-back event to the active players' controllers as live input would be.
+// Wheel events are the caller's.
+pub fn apply(event: &CaptureEvent, players: &mut [Player]) {
+    match event {
+        CaptureEvent::Input { source, inputs, pressed } => {
+            players.iter_mut().any(|p| p.controller.apply(*source, inputs, *pressed));
+        }
+        CaptureEvent::ReleaseAll => for player in players { player.controller.release_all(); },
+        CaptureEvent::Wheel(_) => {}
+    }
+}
@@ -0,0 +1,76 @@
+// chunk_fade.rs
+use std::collections::HashMap;
+use std::time::Instant;
+use crate::config;
+
+// Fades chunks in from the haze instead of popping: once when a view's draw radius first
+// reaches them, and again whenever their mesh finishes streaming in. Each chunk draw's fade
+// (0 hidden, 1 fully shown) is read by the scene shader as a per-instance attribute, next to
+// the chunk's origin relative to the camera; the indirect draws set first_instance to the
+// draw's position, so values go in draw order.
+pub struct ChunkFades {
+    // Per view: when each chunk inside the draw radius entered it, and the last frame it was there.
+    entered: Vec<HashMap<(i32, i32), (Instant, u64)>>,
+    frame: u64,
+    now: Instant,
+    values: Vec<[f32; 4]>,
+    buffer: wgpu::Buffer,
+    capacity: usize,
+}
+
+impl ChunkFades {
+    pub fn new(device: &wgpu::Device, view_count: usize) -> Self {
+        let capacity = 1024;
+        Self {
+            entered: vec![HashMap::new(); view_count], frame: 0, now: Instant::now(),
+            values: Vec::new(), buffer: Self::create_buffer(device, capacity), capacity,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Chunk Fades"), size: capacity as u64 * 16,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+        })
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+        self.now = Instant::now();
+        self.values.clear();
+    }
+
+    // Fade of a chunk within `view`'s draw radius whose mesh was uploaded at `uploaded`.
+    pub fn in_range(&mut self, view: usize, coord: (i32, i32), uploaded: Instant) -> f32 {
+        let frame = self.frame;
+        let entry = self.entered[view].entry(coord).or_insert((self.now, frame));
+        entry.1 = frame;
+        let since = self.now.duration_since(entry.0.max(uploaded)).as_secs_f32();
+        if config::CHUNK_FADE_TIME > 0.0 { (since / config::CHUNK_FADE_TIME).min(1.0) } else { 1.0 }
+    }
+
+    // Adds the next chunk draw: its mesh origin minus the view's eye, and its fade.
+    pub fn push(&mut self, relative_origin: glam::Vec3, fade: f32) {
+        self.values.push(relative_origin.extend(fade).to_array());
+    }
+
+    // Forgets chunks that left the draw radius so they fade in again on return, then uploads
+    // this frame's values; call before the scene pass.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let frame = self.frame;
+        for view in &mut self.entered {
+            view.retain(|_, (_, seen)| *seen == frame);
+        }
+        if self.values.is_empty() { return; }
+        if self.values.len() > self.capacity {
+            self.capacity = self.values.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.values));
+    }
+
+    // Bind at vertex slot 1 before drawing chunks.
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
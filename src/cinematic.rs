@@ -0,0 +1,147 @@
+// cinematic.rs
+use glam::DVec3;
+use serde::{Deserialize, Serialize};
+use crate::{camera::Camera, config, config_file};
+
+// One stop on a camera flight: where the camera was and where it looked.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub eye: [f64; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+// Keyframes dropped while roaming, flown through along a Catmull-Rom spline. Each stretch
+// between two keyframes takes the same share of the duration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+    // Seconds for the whole flight.
+    pub duration: f64,
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        Self { keyframes: Vec::new(), duration: config::CINEMATIC_DURATION }
+    }
+}
+
+impl CameraPath {
+    // Eye and (yaw, pitch, 0) `t` (0..1) of the way along the flight. Needs two keyframes.
+    fn sample(&self, t: f64) -> (DVec3, DVec3) {
+        // Yaw unwrapped so the camera turns the short way between keyframes.
+        let mut yaw = self.keyframes[0].yaw as f64;
+        let mut previous = yaw;
+        let points: Vec<(DVec3, DVec3)> = self.keyframes.iter().map(|k| {
+            yaw += (k.yaw as f64 - previous + std::f64::consts::PI).rem_euclid(std::f64::consts::TAU) - std::f64::consts::PI;
+            previous = k.yaw as f64;
+            (DVec3::from(k.eye), DVec3::new(yaw, k.pitch as f64, 0.0))
+        }).collect();
+
+        let last = points.len() - 1;
+        let x = t.clamp(0.0, 1.0) * last as f64;
+        let i = (x.floor() as usize).min(last - 1);
+        let at = |j: isize| points[j.clamp(0, last as isize) as usize];
+        let [p0, p1, p2, p3] = [-1, 0, 1, 2].map(|o| at(i as isize + o));
+        let local = x - i as f64;
+        (catmull_rom([p0.0, p1.0, p2.0, p3.0], local), catmull_rom([p0.1, p1.1, p2.1, p3.1], local))
+    }
+}
+
+// Uniform Catmull-Rom between p[1] and p[2].
+fn catmull_rom(p: [DVec3; 4], t: f64) -> DVec3 {
+    let (t2, t3) = (t * t, t * t * t);
+    0.5 * (2.0 * p[1] + (p[2] - p[0]) * t + (2.0 * p[0] - 5.0 * p[1] + 4.0 * p[2] - p[3]) * t2 + (3.0 * p[1] - p[0] - 3.0 * p[2] + p[3]) * t3)
+}
+
+// Records a camera path for flythrough videos and plays it back in place of player one's
+// view. The path is saved to and loaded from CINEMATIC_PATH_FILE.
+pub struct Cinematic {
+    pub path: CameraPath,
+    // Named flights shipped with the map package.
+    pub tours: Vec<(String, CameraPath)>,
+    // Seconds into the flight while playing.
+    playing: Option<f64>,
+}
+
+impl Cinematic {
+    // Starts with the saved path, if there is one.
+    pub fn load() -> Self {
+        let path = std::fs::read_to_string(&config_file::get().paths.camera_path).ok()
+            .and_then(|text| serde_json::from_str(&text).map_err(|e| log::warn!("Ignoring {}: {}", config_file::get().paths.camera_path, e)).ok())
+            .unwrap_or_default();
+        Self { path, tours: Vec::new(), playing: None }
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(&self.path).map_err(|e| e.to_string())
+            .and_then(|text| std::fs::write(&config_file::get().paths.camera_path, text).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => log::info!("Saved {} keyframes to {}", self.path.keyframes.len(), config_file::get().paths.camera_path),
+            Err(e) => log::warn!("Couldn't save {}: {}", config_file::get().paths.camera_path, e),
+        }
+    }
+
+    pub fn drop_keyframe(&mut self, camera: &Camera) {
+        self.path.keyframes.push(Keyframe { eye: camera.eye.to_array(), yaw: camera.yaw, pitch: camera.pitch });
+        log::info!("Keyframe {} dropped", self.path.keyframes.len());
+    }
+
+    pub fn clear(&mut self) {
+        self.path.keyframes.clear();
+        self.playing = None;
+        log::info!("Camera path cleared");
+    }
+
+    pub fn toggle_playback(&mut self) {
+        if self.playing.is_some() {
+            self.playing = None;
+        } else if self.path.keyframes.len() < 2 {
+            log::info!("A camera path needs at least two keyframes (K drops one)");
+        } else {
+            self.playing = Some(0.0);
+        }
+    }
+
+    // Lengthens or shortens the flight by CINEMATIC_DURATION_STEP per step.
+    pub fn adjust_duration(&mut self, steps: f64) {
+        self.path.duration = (self.path.duration + steps * config::CINEMATIC_DURATION_STEP).max(config::CINEMATIC_DURATION_STEP);
+        log::info!("Camera path duration {:.0} s", self.path.duration);
+    }
+
+    // Advances playback, stopping at the end of the flight.
+    pub fn update(&mut self, dt: f64) {
+        let Some(time) = &mut self.playing else { return };
+        *time += dt;
+        if *time >= self.path.duration { self.playing = None; }
+    }
+
+    // Flies `path` in place of the recorded one, which it replaces until the next load.
+    pub fn play(&mut self, path: CameraPath) {
+        self.path = path;
+        self.playing = Some(0.0);
+    }
+
+    // Flies tour `number`, counting from 1.
+    pub fn play_tour(&mut self, number: usize) -> Result<String, String> {
+        let (name, tour) = number.checked_sub(1).and_then(|i| self.tours.get(i)).ok_or_else(|| format!("No tour {} (tour lists them)", number))?;
+        let reply = format!("Flying tour {}", name);
+        self.play(tour.clone());
+        Ok(reply)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.is_some()
+    }
+
+    pub fn hides_ui(&self) -> bool {
+        self.playing.is_some() && config::CINEMATIC_HIDE_UI
+    }
+
+    // `base` moved along the flight while playing.
+    pub fn camera(&self, base: &Camera) -> Option<Camera> {
+        let time = self.playing?;
+        let (eye, angles) = self.path.sample(time / self.path.duration);
+        Some(Camera { eye, yaw: angles.x as f32, pitch: angles.y as f32, roll: 0.0, ..base.clone() })
+    }
+}
@@ -0,0 +1,95 @@
+// cli.rs
+use clap::Parser;
+use crate::{spawn::SpawnPoint, waypoints::{Waypoint, Waypoints}};
+
+// Command line options. Most are shorthands for config values and go through
+// config_file::load, so they win over skyroam.toml and are checked the same way.
+#[derive(Debug, Parser)]
+#[command(name = "skyroam", version, about = "Roam a city built from OpenStreetMap data.")]
+pub struct Cli {
+    #[arg(long, value_name = "PATH", help = "Config file to read instead of skyroam.toml")]
+    pub config: Option<String>,
+    #[arg(long, value_name = "PATH", help = "OpenStreetMap .pbf file to load, skipping the map package menu")]
+    pub map: Option<String>,
+    #[arg(long, value_name = "LAT,LON", value_parser = parse_coords, help = "Coordinates at the center of the world, for --map or the built-in map")]
+    pub origin: Option<(f64, f64)>,
+    #[arg(long, value_name = "LAT,LON|PLACE", value_parser = parse_spawn, help = "Where to start; wins over the map package's and config's spawn")]
+    pub spawn: Option<SpawnPoint>,
+    #[arg(long, value_name = "METERS", help = "Start this high above the street")]
+    pub spawn_height: Option<f64>,
+    #[arg(long, conflicts_with = "fullscreen", help = "Run in a window")]
+    pub windowed: bool,
+    #[arg(long, help = "Take over the display (exclusive fullscreen)")]
+    pub fullscreen: bool,
+    #[arg(long, value_name = "INDEX|NAME", help = "Monitor to go fullscreen on, by its number (from 0) or (part of) its name")]
+    pub monitor: Option<String>,
+    #[arg(long, value_name = "on|off", value_parser = clap::builder::BoolishValueParser::new(), hide_possible_values = true, help = "Cap the frame rate at the display's refresh rate")]
+    pub vsync: Option<bool>,
+    #[arg(long, value_name = "DEGREES", help = "Vertical field of view")]
+    pub fov: Option<f32>,
+    #[arg(long, value_parser = ["low", "medium", "high", "ultra"], help = "Graphics preset; graphics values set in the config file or with --set still win")]
+    pub preset: Option<String>,
+    #[arg(long, value_name = "FPS", help = "Frame rate cap")]
+    pub frame_limit: Option<u32>,
+    #[arg(long, value_name = "NAME=LAT,LON", value_parser = parse_waypoint, help = "Add a named waypoint (repeatable)")]
+    pub waypoint: Vec<Waypoint>,
+    #[arg(long, value_name = "PATH", help = "Record every input into PATH once the map has loaded, saved on quitting")]
+    pub capture: Option<String>,
+    #[arg(long, value_name = "PATH", conflicts_with = "capture", help = "Play back an input capture once the map has loaded, then quit")]
+    pub playback: Option<String>,
+    #[arg(long, value_name = "REPORT", num_args = 0..=1, default_missing_value = crate::config::BENCHMARK_REPORT, conflicts_with_all = ["capture", "playback"], help = "Fly a fixed loop over the loaded map, write a performance report to REPORT (JSON, and a per-frame CSV beside it), then quit")]
+    pub benchmark: Option<String>,
+    #[arg(long, value_name = "PATH", help = "Write GPU and CPU frame timings to PATH as Chrome tracing JSON")]
+    pub trace: Option<String>,
+    #[arg(long, value_name = "SECTION.KEY=VALUE", help = "Set any skyroam.toml value, e.g. physics.gravity=40 (repeatable)")]
+    pub set: Vec<String>,
+}
+
+impl Cli {
+    // The options that stand for config values, as (SECTION.KEY, value) in the order they apply.
+    // `--set` comes last so it can still change anything.
+    pub fn config_overrides(&self) -> Vec<(String, toml::Value)> {
+        let mut overrides: Vec<(String, toml::Value)> = Vec::new();
+        let mut push = |key: &str, value: toml::Value| overrides.push((key.to_string(), value));
+        if let Some(map) = &self.map { push("map.file", map.clone().into()); }
+        if let Some((lat, lon)) = self.origin {
+            push("map.origin_lat", lat.into());
+            push("map.origin_lon", lon.into());
+        }
+        if let Some(height) = self.spawn_height { push("map.spawn_height", height.into()); }
+        if self.windowed { push("graphics.window_mode", "windowed".into()); }
+        if self.fullscreen { push("graphics.window_mode", "exclusive".into()); }
+        if let Some(monitor) = &self.monitor {
+            let choice = monitor.parse::<i64>().map_or_else(|_| monitor.clone().into(), toml::Value::from);
+            push("graphics.monitor", choice);
+        }
+        if let Some(vsync) = self.vsync { push("graphics.present_mode", if vsync { "vsync" } else { "no_tearing" }.into()); }
+        if let Some(fov) = self.fov { push("graphics.fov", (fov as f64).into()); }
+        if let Some(preset) = &self.preset { push("graphics.preset", preset.clone().into()); }
+        if let Some(path) = &self.capture { push("paths.capture", path.clone().into()); }
+        if let Some(path) = &self.trace { push("paths.trace", path.clone().into()); }
+        if let Some(fps) = self.frame_limit { push("graphics.frame_limit", (fps as i64).into()); }
+        for assignment in &self.set {
+            match assignment.split_once('=') {
+                Some((key, value)) => overrides.push((key.trim().to_string(), crate::config_file::parse_value(value.trim()))),
+                None => log::warn!("Ignoring --set '{}'; expected SECTION.KEY=VALUE", assignment),
+            }
+        }
+        overrides
+    }
+}
+
+fn parse_coords(value: &str) -> Result<(f64, f64), String> {
+    match SpawnPoint::parse(value) {
+        SpawnPoint::Coordinates { lat, lon } => Ok((lat, lon)),
+        _ => Err("expected LAT,LON in degrees".to_string()),
+    }
+}
+
+fn parse_spawn(value: &str) -> Result<SpawnPoint, String> {
+    Ok(SpawnPoint::parse(value))
+}
+
+fn parse_waypoint(value: &str) -> Result<Waypoint, String> {
+    Waypoints::parse(value).ok_or_else(|| "expected NAME=LAT,LON".to_string())
+}
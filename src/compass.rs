@@ -0,0 +1,51 @@
+// compass.rs
+use crate::{config, hud::HudRenderer, text::TextRenderer};
+
+const CARDINALS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+
+// Compass bearing of a ground direction (x east, z south) in degrees clockwise from north.
+pub fn bearing(x: f64, z: f64) -> f64 {
+    x.atan2(-z).to_degrees().rem_euclid(360.0)
+}
+
+// Heading strip across the top of `viewport` with a tick every 5 degrees, numbers every 15 and
+// the cardinal directions, and under it the bearing and the position in latitude and longitude.
+pub fn draw(hud: &mut HudRenderer, text: &mut TextRenderer, viewport: [f32; 4], bearing: f64, (lat, lon): (f64, f64)) {
+    let [x, y, w, _] = viewport;
+    let (width, height) = (config::COMPASS_WIDTH.min(w - 24.0), 32.0);
+    let center = x + w * 0.5;
+    let (x0, y0) = (center - width * 0.5, y + 10.0);
+    hud.rect([x0, y0], [x0 + width, y0 + height], [0.0, 0.0, 0.0, 0.45]);
+
+    let half_span = config::COMPASS_SPAN * 0.5;
+    let px_per_degree = width as f64 / config::COMPASS_SPAN;
+    let first = ((bearing - half_span) / 5.0).ceil() as i32;
+    let last = ((bearing + half_span) / 5.0).floor() as i32;
+    for step in first..=last {
+        let degrees = step * 5;
+        let tick_x = center + ((degrees as f64 - bearing) * px_per_degree) as f32;
+        let wrapped = degrees.rem_euclid(360);
+        let (tick, label) = if wrapped % 45 == 0 {
+            (12.0, Some(CARDINALS[(wrapped / 45) as usize].to_string()))
+        } else if wrapped % 15 == 0 {
+            (8.0, Some(wrapped.to_string()))
+        } else {
+            (4.0, None)
+        };
+        hud.rect([tick_x - 0.5, y0 + height - tick], [tick_x + 0.5, y0 + height], [1.0, 1.0, 1.0, 0.8]);
+        if let Some(label) = label {
+            let (size, color) = match wrapped {
+                0 => (14.0, [1.0, 0.3, 0.25, 1.0]),
+                _ if wrapped % 45 == 0 => (14.0, [1.0, 1.0, 1.0, 1.0]),
+                _ => (11.0, [0.8, 0.8, 0.8, 0.9]),
+            };
+            text.text_centered(&label, tick_x, y0 + 2.0, size, color);
+        }
+    }
+    hud.rect([center - 1.0, y0], [center + 1.0, y0 + height], [1.0, 0.85, 0.3, 1.0]);
+
+    let north_south = if lat >= 0.0 { 'N' } else { 'S' };
+    let east_west = if lon >= 0.0 { 'E' } else { 'W' };
+    let readout = format!("{:03.0}°   {:.5}° {}, {:.5}° {}", bearing.round() % 360.0, lat.abs(), north_south, lon.abs(), east_west);
+    text.text_centered(&readout, center, y0 + height + 4.0, 14.0, [1.0, 1.0, 1.0, 0.9]);
+}
@@ -1,41 +1,512 @@
 // config.rs
+// Defaults for the values skyroam.toml can change are picked up by config_file.rs; read those
+// through config_file::get() so the file and command line overrides apply.
 
 pub const WINDOW_TITLE: &str = "SkyRoam";
 
+// Display (F11 or Alt+Enter toggles between WINDOW_MODE and windowed)
+pub const WINDOW_MODE: crate::display::WindowMode = crate::display::WindowMode::Borderless;
+pub const WINDOW_SIZE: (u32, u32) = (1600, 900); // inner size when windowed
+pub const REMEMBER_WINDOW: bool = true; // reopen on the last monitor, at the last windowed size and position
+pub const WINDOW_STATE_FILE: &str = "window.json";
+pub const FULLSCREEN_RESOLUTION: Option<(u32, u32)> = None; // exclusive only; None = native
+pub const FULLSCREEN_REFRESH_HZ: Option<u32> = None; // exclusive only; None = highest
+pub const PRESENT_MODE: crate::display::PresentPreference = crate::display::PresentPreference::NoTearing;
+pub const FRAME_LIMIT: Option<u32> = None; // ignored under VSync when >= the refresh rate
+pub const FRAME_LIMIT_STEPS: [u32; 7] = [30, 60, 90, 120, 144, 165, 240]; // settings menu choices after Off
+pub const LOW_LATENCY_MODE: bool = false; // wait for the GPU each frame before sampling input
+pub const BACKGROUND_FPS: f64 = 5.0; // redraw rate while the window is unfocused
+pub const HIDDEN_WAKE_MS: u64 = 100; // how often a minimized or covered window still takes in loaded chunks
+
 // World Generation
 pub const MAP_FILE_PATH: &str = "nyc.pbf"; 
+pub const MAPS_DIR: &str = "maps"; // map packages (directories or zips with a map.json)
+pub const CONFIG_WATCH_INTERVAL: f64 = 1.0; // seconds between checks for skyroam.toml changing on disk
 
-// NYC Coordinates
+// NYC Coordinates (map packages can override these)
 pub const ORIGIN_LAT: f64 = 40.7580;
 pub const ORIGIN_LON: f64 = -73.9855;
 
+// Spawn ("lat,lon" or a place name; --spawn and map packages override it, "" is the map origin)
+pub const SPAWN: &str = "";
+pub const SPAWN_SEARCH_RADIUS: f32 = 100.0; // how far from a spawn inside a building to look for open ground
+pub const SPAWN_SEARCH_STEP: f32 = 5.0;
+
+// Teleport (1-4 jump to a slot, Shift+1-4 saves the current spot into it)
+pub const TELEPORT_FADE_TIME: f64 = 0.4; // seconds to fade out, and again to fade back in
+pub const TELEPORT_SLOTS: [Option<(f64, f64)>; 4] = [None; 4]; // (lat, lon) for slots nothing was saved into
+
 // Performance
 // 12x12 grid = 144 MegaChunks. 
 // This creates a perfect balance between culling and draw call reduction.
-pub const WORLD_SIZE: f32 = 12000.0;
+pub const WORLD_SIZE: f32 = 12000.0; // chunks are config_file::chunk_size() = world size / CHUNK_GRID_AXIS across
 pub const CHUNK_GRID_AXIS: usize = 12; 
-pub const CHUNK_SIZE: f32 = WORLD_SIZE / CHUNK_GRID_AXIS as f32;
 
 // Physics
 pub const PHYSICS_GRID_CELL_SIZE: f32 = 50.0;
 pub const PLAYER_RADIUS: f64 = 0.5;
+pub const EYE_HEIGHT: f64 = 1.8;
+pub const HEAD_CLEARANCE: f64 = 0.15; // top of the body above the eye
 pub const WALL_THICKNESS: f64 = 0.2; 
+pub const WALL_MERGE_TOLERANCE: f32 = 0.01; // meters a corner may sit off a straight run of walls and still be merged away
+pub const STEP_HEIGHT: f64 = 0.5; // tallest ledge walked onto without jumping
+
+// Height Inference (buildings without height/levels tags)
+pub const HEIGHT_SEED: u64 = 0x5EED_C17E;
+pub const LEVEL_HEIGHT: f32 = 3.2;
+pub const ROOF_ALLOWANCE: f32 = 1.0;
+pub const HEIGHT_JITTER: f32 = 1.5;
+pub const HEIGHT_MIN_NEIGHBOURS: usize = 3;
+pub const HEIGHT_NEIGHBOUR_RADIUS: f32 = 150.0; // meters around a building whose known heights its guess leans toward
+
+// Barriers (barrier=wall/fence/hedge) without a height tag
+pub const BARRIER_WALL_HEIGHT: f32 = 2.5;
+pub const BARRIER_FENCE_HEIGHT: f32 = 1.6;
+pub const BARRIER_HEDGE_HEIGHT: f32 = 1.3;
+
+// Subway Tunnels (railway=subway with layer<0, entered via railway=subway_entrance)
+pub const TUNNEL_LAYER_DEPTH: f32 = 12.0; // floor depth per negative layer
+pub const TUNNEL_WIDTH: f32 = 9.0;
+pub const TUNNEL_HEIGHT: f32 = 5.0;
+pub const TUNNEL_MAX_SPAN: f32 = 400.0; // long segments are split so chunk lookups find them
+pub const ENTRANCE_WIDTH: f32 = 3.5;
+pub const ENTRANCE_RAMP_LENGTH: f32 = 30.0;
+pub const ENTRANCE_MAX_DISTANCE: f32 = 150.0;
+pub const ENTRANCE_RAILING_HEIGHT: f32 = 1.0; // ramp walls poke above the street
+pub const TUNNEL_STEP_TOLERANCE: f32 = 0.6;
+pub const UNDERGROUND_THRESHOLD: f32 = -0.5;
+
+// Roads (drivable highway=* ways at street level)
+pub const ROAD_LANE_WIDTH: f32 = 3.5; // used with the lanes tag
+pub const ROAD_MAX_SPAN: f32 = 200.0; // long segments are split so chunk lookups find them
+pub const ROAD_COLOR: [f32; 3] = [0.09, 0.09, 0.10];
 
-// Movement
-pub const MOVE_SPEED: f64 = 60.0; // Fast dev speed
+// Key Bindings (movement, grapple, interact and zoom per player; the reset_bindings console command restores them)
+pub const BINDINGS_FILE: &str = "bindings.toml";
+pub const KEY_LAYOUT: crate::bindings::KeyLayout = crate::bindings::KeyLayout::Physical; // Logical matches KeyW etc. by the printed letter
+
+// Mouse (defaults; the F10 settings menu changes them and saves to SETTINGS_FILE)
+pub const SETTINGS_FILE: &str = "settings.json";
+pub const MOUSE_SENSITIVITY: f64 = 0.003; // radians per pixel
+pub const MOUSE_SENSITIVITY_RANGE: (f64, f64) = (0.0005, 0.02);
+pub const MOUSE_SENSITIVITY_STEP: f64 = 1.1; // multiplier per menu step
+pub const MOUSE_INVERT_Y: bool = false;
+pub const MOUSE_SMOOTHING: f64 = 0.0; // seconds to catch up with the mouse, 0 is raw
+pub const MOUSE_SMOOTHING_STEP: f64 = 0.01;
+pub const MOUSE_SMOOTHING_MAX: f64 = 0.1;
+pub const MOUSE_ACCELERATION: f64 = 0.0; // extra gain per 1000 pixels/s of mouse speed
+pub const MOUSE_ACCELERATION_STEP: f64 = 0.1;
+pub const MOUSE_ACCELERATION_MAX: f64 = 2.0;
+pub const MOUSE_ACCELERATION_MAX_GAIN: f64 = 4.0;
+
+// Movement (Shift sprints, Ctrl crouches)
+pub const WALK_SPEED: f64 = 20.0;
+pub const SPRINT_SPEED: f64 = 60.0;
+pub const CROUCH_SPEED: f64 = 6.0;
+pub const MOVE_ACCELERATION: f64 = 10.0; // per second, times the target speed
+pub const AIR_ACCELERATION: f64 = 2.0; // same, but only steers: momentum carries on without input
+pub const GROUND_FRICTION: f64 = 6.0; // share of the speed lost per second on the ground
+pub const FRICTION_STOP_SPEED: f64 = 5.0; // slower than this, friction acts as if at this speed
+pub const SLIDE_MIN_SPEED: f64 = 15.0; // crouching faster than this slides
+pub const SLIDE_FRICTION: f64 = 0.8; // replaces GROUND_FRICTION while sliding
+pub const CROUCH_EYE_HEIGHT: f64 = 1.0;
+pub const CROUCH_TRANSITION_SPEED: f64 = 6.0; // m/s the eye moves when crouching or standing up
 pub const GRAVITY: f64 = 70.0;
 pub const JUMP_FORCE: f64 = 25.0;
+pub const COYOTE_TIME: f64 = 0.1; // seconds after running off an edge that a jump still works
+pub const JUMP_BUFFER_TIME: f64 = 0.12; // a jump pressed this long before landing still happens
+pub const JUMP_CUT: f64 = 0.5; // share of the upward speed kept when jump is released early
 pub const TERMINAL_VELOCITY: f64 = -120.0;
+pub const PHYSICS_TICK_RATE: f64 = 120.0; // fixed simulation steps per second, independent of the framerate
 pub const PHYSICS_STEP_SIZE: f64 = 0.005; 
 pub const MAX_PHYSICS_STEPS: i32 = 3;
 
-// Rendering
+// Elevators (platforms riding up building walls)
+pub const ELEVATOR_MIN_HEIGHT: f32 = 60.0; // buildings this tall may get one
+pub const ELEVATOR_PERCENT: i64 = 15; // share of those that do
+pub const ELEVATOR_SNAP_DISTANCE: f32 = 15.0; // highway=elevator nodes farther from any wall are skipped
+pub const ELEVATOR_SIZE: f32 = 3.0;
+pub const ELEVATOR_THICKNESS: f32 = 0.3;
+pub const ELEVATOR_WALL_GAP: f32 = 0.3;
+pub const ELEVATOR_SPEED: f32 = 4.0; // m/s
+pub const ELEVATOR_PAUSE: f32 = 4.0; // seconds waiting at each end
+pub const ELEVATOR_COLOR: [f32; 3] = [0.35, 0.36, 0.4];
+
+// Grappling Hook (hold the left mouse button; jump lets go)
+pub const GRAPPLE_RANGE: f32 = 250.0;
+pub const GRAPPLE_REEL_SPEED: f64 = 15.0; // m/s the rope shortens while held
+pub const GRAPPLE_MIN_LENGTH: f64 = 2.0;
+pub const GRAPPLE_STIFFNESS: f64 = 40.0; // spring pull per meter of stretch, m/s²
+pub const ROPE_COLOR: [f32; 4] = [0.9, 0.8, 0.6, 1.0];
+
+// Glider (hold Space while falling; A/D steer)
+pub const GLIDE_FALL_SPEED: f64 = 5.0; // fastest sink while gliding
+pub const GLIDE_SPEED_RANGE: (f64, f64) = (12.0, 90.0); // forward airspeed
+pub const GLIDE_DRAG: f64 = 0.15; // fraction of airspeed lost per second
+pub const GLIDE_TURN_SPEED: f32 = 1.2; // radians per second of steering
+pub const GLIDE_BANK: f32 = 0.3; // roll in radians per radian/s of turning
+pub const GLIDE_MAX_BANK: f32 = 0.6;
+pub const GLIDE_BANK_SPEED: f32 = 3.0; // radians per second the roll follows the turn
+
+// Parkour (run along walls in the air, climb ledges within reach)
+pub const WALL_RUN_MIN_SPEED: f64 = 12.0; // speed along the wall needed to start
+pub const WALL_RUN_TIME: f64 = 1.5; // longest run before sliding off
+pub const WALL_RUN_GRAVITY: f64 = 12.0; // replaces GRAVITY while running
+pub const WALL_RUN_REACH: f64 = 0.3; // how far the wall is probed for each update
+pub const WALL_RUN_TILT: f32 = 0.25; // camera roll away from the wall, radians
+pub const WALL_JUMP_PUSH: f64 = 15.0; // m/s away from the wall when jumping off
+pub const MANTLE_REACH: f64 = 3.0; // highest ledge above the feet that can be grabbed
+pub const MANTLE_SPEED: f64 = 6.0; // m/s climbed while pulling up
+pub const MANTLE_PUSH: f64 = 5.0; // m/s onto the ledge once over it
+
+// Ladders (fire escapes on some walk-up and industrial buildings; look at one and walk into it)
+pub const LADDER_PERCENT: i64 = 20; // share of those buildings that get one
+pub const LADDER_HEIGHT_RANGE: (f32, f32) = (6.0, 40.0);
+pub const LADDER_WIDTH: f32 = 0.8;
+pub const LADDER_STANDOFF: f32 = 0.25; // gap between the wall and the rungs
+pub const LADDER_RUNG_SPACING: f32 = 0.35;
+pub const LADDER_REACH: f32 = 0.5; // farthest in front of the rungs the body can grab them
+pub const LADDER_COLOR: [f32; 3] = [0.22, 0.12, 0.08];
+pub const CLIMB_SPEED: f64 = 3.0;
+pub const CLIMB_PUSH_OFF: f64 = 4.0; // m/s away from the wall when jumping off
+
+// Cars (E gets in and out; W/S drive and brake, A/D steer)
+pub const CAR_SPAWN_RADIUS: f32 = 150.0; // a car is parked on a road this close to each player's spawn
+pub const CAR_ENTER_DISTANCE: f64 = 5.0;
+pub const CAR_LENGTH: f32 = 4.5;
+pub const CAR_WIDTH: f32 = 2.0;
+pub const CAR_HEIGHT: f32 = 1.5;
+pub const CAR_MAX_SPEED: f64 = 45.0; // m/s
+pub const CAR_REVERSE_SPEED: f64 = 10.0;
+pub const CAR_ACCELERATION: f64 = 12.0; // m/s²
+pub const CAR_BRAKING: f64 = 30.0;
+pub const CAR_ROLLING_DRAG: f64 = 3.0; // m/s² lost with no throttle
+pub const CAR_STEER_RATE: f32 = 1.6; // radians per second at full lock
+pub const CAR_FULL_STEER_SPEED: f64 = 8.0; // slower than this steers less
+pub const CAR_CAMERA_DISTANCE: f64 = 7.0; // behind the car at rest
+pub const CAR_CAMERA_STRETCH: f64 = 0.15; // extra meters per m/s of speed
+pub const CAR_CAMERA_HEIGHT: f64 = 2.5;
+pub const CAR_CAMERA_FOLLOW: f32 = 3.0; // how fast the view swings back behind the car
+
+// Noclip (F toggles flying, mouse wheel sets its speed, Space/Ctrl rise and sink)
+pub const FLY_SPEED: f64 = 80.0;
+pub const FLY_SPEED_RANGE: (f64, f64) = (5.0, 1500.0);
+pub const FLY_SPEED_STEP: f64 = 1.25; // speed multiplier per wheel notch
+
+// Third Person (V toggles player one's chase camera)
+pub const THIRD_PERSON_DISTANCE: f64 = 4.0; // behind the eye
+pub const THIRD_PERSON_SHOULDER: f64 = 0.8; // to the right of the eye
+pub const THIRD_PERSON_HEIGHT: f64 = 0.4; // above the eye
+pub const THIRD_PERSON_WALL_GAP: f32 = 0.3; // kept between the camera and anything behind it
+pub const AVATAR_HEAD_SIZE: f32 = 0.35;
+pub const AVATAR_HEAD_COLOR: [f32; 3] = [0.85, 0.7, 0.58];
+
+// Field of View ([ and ] adjust player one's; hold the right mouse button to zoom)
+pub const FOV_RANGE: (f32, f32) = (50.0, 110.0); // degrees, FOV_Y is the default
+pub const FOV_STEP: f32 = 5.0;
+pub const SPRINT_FOV_KICK: f32 = 8.0; // degrees added at full sprint speed
+pub const ZOOM_FOV: f32 = 20.0;
+pub const FOV_EASE: f32 = 8.0; // how quickly the view follows, per second
+
+// Cinematic Camera (K drops a keyframe, Shift+K clears them, P plays or stops, Shift+P saves, -/= change the duration)
+pub const CINEMATIC_PATH_FILE: &str = "camera_path.json";
+pub const CINEMATIC_DURATION: f64 = 20.0; // seconds for a new path's whole flight
+pub const CINEMATIC_DURATION_STEP: f64 = 5.0;
+pub const CINEMATIC_HIDE_UI: bool = true; // overlays and labels stay hidden during playback
+
+// Benchmark (--benchmark flies a loop around the map origin once every chunk has loaded, then quits)
+pub const BENCHMARK_REPORT: &str = "benchmark.json"; // a per-frame .csv is written next to it
+pub const BENCHMARK_DURATION: f64 = 90.0; // seconds for the whole flight
+pub const BENCHMARK_RADIUS: f64 = 1500.0; // meters; smaller worlds get 40% of their size
+pub const BENCHMARK_ALTITUDES: [f64; 2] = [60.0, 220.0]; // keyframes alternate between rooftop and overview height
+pub const BENCHMARK_KEYFRAMES: usize = 12; // around the loop
+pub const BENCHMARK_STALL_MS: f64 = 4.0; // chunk uploads taking longer than this in a frame count as a stall
+
+// Orbit Camera (O circles what the crosshair is on; drag with the left mouse button, wheel zooms)
+pub const ORBIT_PICK_DISTANCE: f32 = 3000.0;
+pub const ORBIT_DISTANCE_RANGE: (f64, f64) = (10.0, 2000.0);
+pub const ORBIT_FIT: f32 = 1.5; // starting distance per meter of the building's largest side
+pub const ORBIT_DRAG_SPEED: f32 = 0.005; // radians per pixel dragged
+pub const ORBIT_ZOOM_STEP: f64 = 1.2; // distance divisor per wheel notch
+
+// View Effects (F9 toggles; each amount at 0 turns that effect off)
+pub const VIEW_EFFECTS: bool = true;
+pub const HEAD_BOB: f64 = 0.05; // meters up and down at walking speed
+pub const HEAD_BOB_STRIDE: f64 = 2.2; // meters walked per bob
+pub const LANDING_DIP: f64 = 0.02; // meters per m/s of landing speed past LANDING_DIP_MIN_SPEED
+pub const LANDING_DIP_MIN_SPEED: f64 = 15.0;
+pub const LANDING_DIP_MAX: f64 = 0.6;
+pub const LANDING_RECOVERY: f64 = 6.0; // how quickly the view comes back up, per second
+pub const STRAFE_TILT: f32 = 0.03; // roll toward the side moved to, radians at walking speed
+
+// Hard Landings (the view shakes and a red vignette flashes; both can be turned off in the F10 settings menu)
+pub const SCREEN_SHAKE: bool = true;
+pub const DAMAGE_VIGNETTE: bool = true;
+pub const HARD_LANDING_SPEED: (f64, f64) = (22.0, 60.0); // m/s of fall speed where the feedback starts and peaks
+pub const SHAKE_ANGLE: f64 = 0.025; // radians at full strength
+pub const SHAKE_FREQUENCY: f64 = 14.0; // Hz
+pub const SHAKE_RECOVERY: f64 = 5.0; // how quickly the shake dies down, per second
+pub const HURT_RECOVERY: f64 = 0.8; // vignette strength lost per second
+pub const VIGNETTE_COLOR: [f32; 3] = [0.75, 0.02, 0.02];
+
+// Photo Mode (F12 freezes the world and frees the camera; wheel focuses, R autofocuses, ,/. blur, -/= exposure)
+pub const PHOTO_SPEED: f64 = 3.0;
+pub const PHOTO_FAST_SPEED: f64 = 20.0; // while holding sprint
+pub const PHOTO_LOOK_SCALE: f32 = 0.4; // mouse look multiplier
+pub const PHOTO_FOCUS: f32 = 30.0;
+pub const PHOTO_FOCUS_RANGE: (f32, f32) = (1.0, 5000.0);
+pub const PHOTO_FOCUS_STEP: f32 = 1.15; // focus distance multiplier per wheel notch
+pub const PHOTO_BLUR_STEP: f32 = 2.0; // pixels
+pub const PHOTO_MAX_BLUR: f32 = 24.0;
+pub const PHOTO_EXPOSURE_STEP: f32 = 0.25; // stops
+pub const PHOTO_EXPOSURE_RANGE: f32 = 4.0;
+
+// Replays (F5 starts and stops recording player one, Shift+F5 plays the saved replay back)
+pub const REPLAY_FILE: &str = "replay.skyreplay";
+pub const REPLAY_SAMPLE_RATE: f64 = 30.0; // camera and input samples per second
+
+// Input Capture (the capture and playback console commands, or --capture and --playback)
+pub const CAPTURE_FILE: &str = "capture.json";
+
+// Split Screen (F2 toggles at runtime)
+pub const SPLIT_SCREEN: bool = false;
+pub const PLAYER_TWO_SPAWN_OFFSET: glam::DVec3 = glam::DVec3::new(4.0, 0.0, 0.0);
+pub const PLAYER_COLORS: [[f32; 4]; 2] = [[0.2, 0.55, 1.0, 1.0], [1.0, 0.55, 0.15, 1.0]];
+pub const STICK_LOOK_SPEED: f32 = 2.5; // radians per second at full deflection
+#[cfg(feature = "gamepad")]
+pub const STICK_DEADZONE: f32 = 0.15;
+
+// Game Modes (F4 cycles, Tab shows the scoreboard)
+pub const TAG_DISTANCE: f64 = 2.5;
+pub const TAG_COOLDOWN: f64 = 3.0;
+pub const HIDE_TIME: f64 = 30.0;
+pub const SEEK_TIME: f64 = 180.0;
+pub const FIND_DISTANCE: f64 = 4.0;
+pub const FIND_SCORE: f64 = 30.0;
+pub const REVEAL_INTERVAL: f64 = 45.0;
+pub const REVEAL_DURATION: f64 = 3.0;
+pub const ROUND_OVER_TIME: f64 = 6.0;
+
+// Rendering (graphics presets fill in the values marked "preset"; High uses these)
+pub const GRAPHICS_PRESET: crate::preset::GraphicsPreset = crate::preset::GraphicsPreset::High;
 pub const FOV_Y: f32 = 65.0;
 pub const Z_NEAR: f32 = 0.5;
-pub const Z_FAR: f32 = 25000.0;
-pub const DRAW_DISTANCE: f32 = 15000.0; 
-pub const FOG_START: f32 = 10000.0;
-pub const FOG_END: f32 = 14000.0;       
+pub const FAR_PLANE_MARGIN: f32 = 2000.0; // the far plane sits this far past the world's diagonal
+pub const DRAW_DISTANCE: f32 = 15000.0; // preset
+pub const FOG_START: f32 = 10000.0; // preset; geometry fades fully into the sky between these
+pub const FOG_END: f32 = 14000.0; // preset
+pub const FOG_DENSITY: f32 = 0.00015; // height fog extinction per meter at FOG_BASE_HEIGHT
+pub const FOG_HEIGHT_FALLOFF: f32 = 0.004; // fog thins by e every 1 / falloff meters of height
+pub const FOG_BASE_HEIGHT: f32 = 0.0;
+pub const AERIAL_PERSPECTIVE: bool = true; // uniform haze tinting distant buildings toward the sky
+pub const AERIAL_DENSITY: f32 = 0.00006;
+pub const LOD_DISTANCE: f32 = 1500.0; // preset; chunks farther than this draw their simplified mesh
+pub const LOD_MIN_WALL_LENGTH: f32 = 6.0; // shorter walls are left out of the LOD mesh
+pub const FOOTPRINT_SIMPLIFY_EPSILON: f32 = 0.25; // meters a footprint corner may sit off the simplified outline
+pub const NODE_INDEX_SPILL: usize = 8_000_000; // map nodes held in RAM before they are sorted and spilled to a temp file (128 MB)
+pub const NODE_INDEX_PAGE: usize = 4096; // nodes read back from the on-disk index at a time
+pub const NODE_INDEX_CACHE_PAGES: usize = 4096; // on-disk index pages kept in memory (256 MB)
+pub const OCCLUSION_CULLING: bool = true; // skip chunks hidden behind nearer buildings (F7 toggles)
+pub const CHUNK_FADE_TIME: f32 = 1.5; // seconds newly drawn or streamed-in chunks take to emerge from the haze
+pub const MESH_PAGE_VERTICES: u32 = 1 << 20; // chunk meshes share vertex/index buffers of this size
+pub const MESH_PAGE_INDICES: u32 = 1 << 22;
+pub const CHUNK_UPLOAD_BYTES: u64 = 8 * 1024 * 1024; // chunk mesh bytes copied to the GPU per frame; the rest waits
+pub const CHUNK_UPLOAD_COUNT: usize = 16; // and at most this many chunk meshes
+pub const CHUNK_UNLOAD_MARGIN: f32 = 2000.0; // chunks this far past the draw distance are unloaded, and streamed again once back inside it
+
+// Street Lights (clustered forward lighting, on at night)
+pub const STREET_LIGHTS_ENABLED: bool = true;
+pub const STREET_LAMP_HEIGHT: f32 = 6.0;
+pub const STREET_LAMP_RADIUS: f32 = 25.0; // light reaches zero at this distance
+pub const STREET_LAMP_COLOR: [f32; 3] = [1.0, 0.62, 0.28]; // sodium orange
+pub const STREET_LAMP_INTENSITY: f32 = 3.0;
+pub const MAX_LIGHTS: u32 = 4096; // nearest lamps uploaded per view
+pub const MAX_LIGHTS_PER_CLUSTER: u32 = 64;
+pub const LIGHT_CLUSTER_FAR: f32 = 1500.0; // lamps farther than this are ignored
+pub const LIGHT_CLUSTER_GRID: [u32; 3] = [16, 9, 24]; // screen tiles x, y and depth slices
+
+// Water
+pub const WATER_LEVEL: f32 = -0.05; // just above the ground plane
+pub const WATER_COLOR: [f32; 3] = [0.02, 0.07, 0.09];
+pub const WATER_WAVE_STRENGTH: f32 = 0.06; // tilt of the animated surface normal
+pub const WATER_WAVE_SPEED: f32 = 1.0;
+pub const WATER_DEPTH: f32 = 4.0; // basin below the surface
+pub const WATER_BED_COLOR: [f32; 3] = [0.06, 0.07, 0.05];
+
+// Swimming
+pub const SWIM_SPEED: f64 = 5.0;
+pub const SWIM_DEPTH: f64 = 1.0; // feet this far below the surface start swimming
+pub const SWIM_FLOAT_HEIGHT: f64 = 0.3; // the eye bobs this far above the surface
+pub const BUOYANCY: f64 = 12.0; // m/s² per meter below the float height
+pub const SWIM_STROKE_ACCELERATION: f64 = 25.0; // up or down while jump or crouch is held
+pub const WATER_DRAG: f64 = 2.5; // fraction of velocity lost per second
+pub const UNDERWATER_FOG_DENSITY: f32 = 0.12;
+pub const SSR_ENABLED: bool = true; // preset; screen-space reflections of the skyline; the sky is reflected either way
+pub const SSR_STEPS: u32 = 32;
+pub const SSR_MAX_DISTANCE: f32 = 1500.0; // meters a reflected ray travels before falling back to the sky
+
+// Building Highlight (the building under the crosshair glows)
+pub const BUILDING_HIGHLIGHT: bool = true;
+pub const HIGHLIGHT_DISTANCE: f32 = 400.0; // farthest building the crosshair picks, meters
+pub const HIGHLIGHT_COLOR: [f32; 3] = [1.0, 0.75, 0.35];
+pub const HIGHLIGHT_STRENGTH: f32 = 0.8;
+
+// Chunk Streaming (progress panel and shimmering walls around chunks still loading)
+pub const STREAMING_COLOR: [f32; 3] = [0.35, 0.7, 1.0];
+pub const STREAMING_STRENGTH: f32 = 0.6;
+pub const STREAMING_WALL_HEIGHT: f32 = 40.0; // meters
+
+// Crosshair (changes color over a building, and grows within grapple range)
+pub const CROSSHAIR_STYLE: crate::crosshair::CrosshairStyle = crate::crosshair::CrosshairStyle::Dot; // until changed in the settings menu (F10)
+pub const CROSSHAIR_SIZE: f32 = 6.0; // pixels across
+pub const CROSSHAIR_THICKNESS: f32 = 2.0; // line width of the cross and ring, pixels
+pub const CROSSHAIR_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const CROSSHAIR_BUILDING_COLOR: [f32; 4] = [1.0, 0.75, 0.35, 1.0];
+pub const CROSSHAIR_GRAPPLE_COLOR: [f32; 4] = [0.45, 0.9, 1.0, 1.0];
+pub const CROSSHAIR_GRAPPLE_SCALE: f32 = 1.8; // size multiplier while the grapple would catch
+
+// Weather (F8 cycles clear, rain and snow)
+pub const START_WEATHER: crate::weather::WeatherKind = crate::weather::WeatherKind::Clear;
+pub const WEATHER_TRANSITION: f32 = 4.0; // seconds for precipitation and fog to fade in
+pub const WET_STREETS_TIME: f32 = 30.0; // seconds of rain until the streets are soaked
+pub const DRY_STREETS_TIME: f32 = 120.0;
+pub const WEATHER_BOX_SIZE: f32 = 60.0; // edge of the particle volume following the camera, meters
+pub const RAIN_PARTICLES: u32 = 20000;
+pub const RAIN_FALL_SPEED: f32 = 14.0; // m/s
+pub const RAIN_DROP_WIDTH: f32 = 0.02;
+pub const RAIN_DROP_LENGTH: f32 = 0.6;
+pub const RAIN_COLOR: [f32; 4] = [0.7, 0.75, 0.8, 0.35]; // alpha is the streak opacity
+pub const RAIN_FOG_SCALE: f32 = 2.5; // multiplies fog and haze density
+pub const SNOW_PARTICLES: u32 = 12000;
+pub const SNOW_FALL_SPEED: f32 = 1.2;
+pub const SNOW_FLAKE_SIZE: f32 = 0.08;
+pub const SNOW_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+pub const SNOW_FOG_SCALE: f32 = 4.0;
+
+// Map View (hold M)
+pub const MAP_VIEW_HEIGHT: f32 = 1500.0; // above the tallest roofs
+pub const MAP_VIEW_EXTENT: f32 = 600.0; // meters from the player to the top edge of the view
+
+// Minimap (N toggles; turns with player one's heading)
+pub const MINIMAP: bool = true;
+pub const MINIMAP_RADIUS: f32 = 90.0; // pixels
+pub const MINIMAP_RANGE: f32 = 250.0; // meters from the player to the rim
+pub const MINIMAP_TEXELS: u32 = 512;
+pub const MINIMAP_TEXTURE_EXTENT: f32 = 1024.0; // meters across the footprint texture, re-centered as the player nears its edge
+pub const MINIMAP_BACKGROUND: [f32; 4] = [0.08, 0.08, 0.09, 0.85];
+pub const MINIMAP_ROAD_COLOR: [f32; 4] = [0.45, 0.45, 0.42, 0.9];
+pub const MINIMAP_BUILDING_COLOR: [f32; 4] = [0.75, 0.72, 0.65, 0.95];
+pub const MINIMAP_WATER_COLOR: [f32; 4] = [0.15, 0.3, 0.5, 0.9];
+
+// Landmark Labels (L toggles; names of the map's named buildings)
+pub const LANDMARK_LABELS: bool = true;
+pub const LABEL_RADIUS: f32 = 600.0; // meters; labels fade out toward here
+pub const LABEL_FADE_START: f32 = 350.0;
+pub const LABEL_HEIGHT: f32 = 6.0; // meters above the roof
+pub const LABEL_MAX: usize = 24; // per view
+pub const LABEL_SIZE: (f32, f32) = (18.0, 12.0); // pixels, up close and at LABEL_RADIUS
+
+// Waypoints (B drops one where you stand; listed on the pause menu)
+pub const WAYPOINTS_FILE: &str = "waypoints.json";
+pub const WAYPOINT_BEACON_HEIGHT: f64 = 120.0; // meters
+pub const WAYPOINT_BEACON_WIDTH: f32 = 3.0; // meters, held between 1 and 12 pixels on screen
+pub const WAYPOINT_COLOR: [f32; 3] = [0.3, 1.0, 0.6];
+
+// Speedometer (H toggles; speed, climb rate and height above ground in each view)
+pub const SPEEDOMETER: bool = true;
+pub const SPEEDOMETER_SMOOTHING: f64 = 0.25; // seconds the readout takes to settle
+pub const SPEEDOMETER_MAX_JUMP: f64 = 50.0; // meters moved in one frame that count as a teleport, not speed
+
+// Console (~ opens it; type help for the commands)
+pub const CONSOLE_HISTORY: usize = 50; // commands kept for Up/Down
+pub const CONSOLE_SCROLLBACK: usize = 200; // output lines kept
+
+// Notifications (toasts in the top-left corner)
+pub const NOTIFICATION_DURATION: f64 = 3.0; // seconds fully shown
+pub const NOTIFICATION_FADE: f64 = 0.3; // seconds to fade in, and again to fade out
+pub const NOTIFICATION_MAX: usize = 5;
+pub const DISTRICT_RADIUS: f32 = 200.0; // meters from a park or neighbourhood node that count as inside it
+
+// Compass (heading strip and latitude/longitude at the top of each view)
+pub const COMPASS: bool = true;
+pub const COMPASS_WIDTH: f32 = 360.0; // pixels
+pub const COMPASS_SPAN: f64 = 120.0; // degrees of heading across the strip
+
+// Stats Overlay (F3 toggles FPS, frame times, draw calls, chunks, memory and position)
+pub const STATS_OVERLAY: bool = false;
+pub const STATS_GRAPH_FRAMES: usize = 150;
+pub const STATS_GRAPH_MAX_MS: f32 = 50.0; // frame time at the top of the graph
+pub const PROFILER_SMOOTHING: f32 = 0.05; // weight of the newest frame in the GPU and CPU timings
+pub const PROFILER_READBACKS: usize = 3; // frames of GPU timestamps in flight at once
+
+// Developer Panels (F1, built with the "egui" feature)
+#[cfg(feature = "egui")]
+pub const DEV_UI_MAX_FOG: f32 = 10.0; // top of the fog multiplier slider
+
+// Debug Views (Shift+F3 cycles: wireframe, chunk bounds, collision, normals)
+pub const DEBUG_COLLISION_RADIUS: f32 = 150.0; // collision walls are drawn within this distance
+
+// Anti-Aliasing
+pub const ANTI_ALIASING: crate::post::AntiAliasing = crate::post::AntiAliasing::Msaa; // preset
+pub const TAA_BLEND: f32 = 0.1; // weight of the new frame in the history; lower is smoother but ghosts more
+pub const TAA_JITTER_FRAMES: u32 = 8; // length of the sub-pixel jitter sequence
+
+// Dynamic Resolution (scene renders below window size when frames run slow)
+pub const DYNAMIC_RESOLUTION: bool = true;
+pub const TARGET_FPS: f32 = 60.0;
+pub const RENDER_SCALE_RANGE: (f32, f32) = (0.5, 1.0); // fraction of the window size per axis
+pub const RENDER_SCALE_STEP: f32 = 0.1;
+pub const RENDER_SCALE_HEADROOM: f32 = 0.75; // scale back up once frames take less than this share of the target
+pub const RENDER_SCALE_COOLDOWN: f64 = 1.0; // seconds between changes
+
+// Shadows (F6 toggles)
+pub const SHADOWS_ENABLED: bool = true; // preset
+pub const SHADOW_MAP_SIZE: u32 = 2048; // preset
+pub const SHADOW_CASCADE_SPLITS: [f32; 3] = [120.0, 500.0, 1800.0]; // far distance of each cascade
+pub const SHADOW_CASTER_DEPTH: f32 = 600.0; // how far toward the sun casters are captured
+
+// Ambient Occlusion (post-process)
+pub const SSAO_ENABLED: bool = true; // preset
+pub const SSAO_STRENGTH: f32 = 0.8; // 0 = off, 1 = occluded corners lose all ambient light
+pub const SSAO_RADIUS: f32 = 1.5; // meters
+pub const SSAO_SAMPLES: u32 = 12;
+pub const SSAO_MAX_DISTANCE: f32 = 400.0; // fades out beyond this
+
+// Exposure & Tonemapping
+pub const TONEMAPPER: crate::post::Tonemapper = crate::post::Tonemapper::Aces;
+pub const AUTO_EXPOSURE: bool = true;
+pub const EXPOSURE: f32 = 1.0; // used when AUTO_EXPOSURE is off
+pub const EXPOSURE_KEY: f32 = 0.3; // average scene luminance auto-exposure aims for
+pub const EXPOSURE_RANGE: (f32, f32) = (0.3, 4.0); // keeps nights dark and noon from going grey
+pub const EXPOSURE_ADAPT_SPEED: f32 = 1.5; // higher adapts faster
+
+// Bloom
+pub const BLOOM_ENABLED: bool = true; // preset
+pub const BLOOM_INTENSITY: f32 = 0.6; // 0 = off
+pub const BLOOM_THRESHOLD: f32 = 1.0; // exposed brightness where glow starts
+pub const BLOOM_KNEE: f32 = 0.5; // fraction of the threshold over which glow fades in
+pub const BLOOM_LEVELS: u32 = 6; // blur mip levels; more spreads the glow further
+
+// Day/Night Cycle
+pub const DAY_LENGTH_SECONDS: f64 = 1200.0; // real seconds per 24 in-game hours
+pub const START_TIME_OF_DAY: f64 = 17.5; // hours since midnight
+pub const FIXED_TIME_OF_DAY: Option<f64> = None; // Some(hours) stops the clock
+
+// Building Facades
+pub const FACADE_TEXTURE_SIZE: u32 = 64; // texels per window bay tile
+pub const FACADE_BAY_WIDTH: f32 = 3.5;
+
+// GPU Memory Budget (None = pick by adapter type)
+pub const GPU_BUDGET_MB: Option<u64> = None;
+pub const GPU_BUDGET_DISCRETE_MB: u64 = 3072;
+pub const GPU_BUDGET_INTEGRATED_MB: u64 = 1024;
+pub const GPU_BUDGET_HIGH_WATER: f64 = 0.90;
+pub const GPU_BUDGET_LOW_WATER: f64 = 0.75;
+pub const GPU_BUDGET_PROTECT_RADIUS: f32 = 2000.0; // never evict chunks this close
+pub const GPU_BUDGET_LOD_SCALE: f32 = 0.5; // LOD distance multiplier once memory runs short
+pub const GPU_BUDGET_SHADOW_MIN: u32 = 512; // shadow cascades never shrink below this
+pub const GPU_BUDGET_SCALE_STEP: f32 = 0.25; // render scale cap lowered per step
+pub const MESH_CACHE_MB: u64 = 1024; // RAM for chunk meshes kept to re-upload after eviction; the rest spill to disk
 
 pub const CHUNK_MIN_Y: f32 = -50.0;
 pub const CHUNK_MAX_Y: f32 = 1200.0;
\ No newline at end of file
@@ -0,0 +1,405 @@
+// config_file.rs
+use std::{fmt::Display, sync::{Arc, OnceLock, RwLock}, time::SystemTime};
+use serde::{Deserialize, Serialize};
+use crate::{bindings::KeyLayout, cli::Cli, config, display::{MonitorChoice, PresentPreference, WindowMode}, post::{AntiAliasing, Tonemapper}, preset::GraphicsPreset};
+
+pub const DEFAULT_PATH: &str = "skyroam.toml";
+
+static CONFIG: RwLock<Option<Arc<ConfigFile>>> = RwLock::new(None);
+static SOURCE: OnceLock<Source> = OnceLock::new();
+// The graphics preset picked in the settings menu; it wins over graphics.preset wherever that was set.
+static CHOSEN_PRESET: RwLock<Option<GraphicsPreset>> = RwLock::new(None);
+
+// The config.rs values worth changing without a rebuild, read from skyroam.toml (or
+// `--config PATH`) at startup. Missing keys keep the config.rs value; command line options
+// (see cli.rs) win over the file. ConfigWatcher reloads it when it changes on disk.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub map: MapConfig,
+    pub graphics: GraphicsConfig,
+    pub physics: PhysicsConfig,
+    pub controls: ControlsConfig,
+    pub paths: PathsConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MapConfig {
+    pub file: String,
+    pub maps_dir: String,
+    pub origin_lat: f64,
+    pub origin_lon: f64,
+    pub spawn: String,
+    // Meters above the street the players start at; they fall (or glide) from there.
+    pub spawn_height: f64,
+    // Side of the square the chunk grid covers, meters.
+    pub world_size: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GraphicsConfig {
+    pub window_mode: WindowMode,
+    // Fullscreen on this monitor; None is the one the window was last on, or the primary one.
+    pub monitor: Option<MonitorChoice>,
+    // Inner size when windowed.
+    pub window_size: (u32, u32),
+    // Reopen on the last monitor, and windowed at the last size and position (see paths.window).
+    pub remember_window: bool,
+    // Exclusive fullscreen only; None picks the native resolution and highest refresh rate.
+    pub resolution: Option<(u32, u32)>,
+    pub refresh_hz: Option<u32>,
+    pub present_mode: PresentPreference,
+    pub frame_limit: Option<u32>,
+    pub fov: f32,
+    pub tonemapper: Tonemapper,
+    // Fills in the values below that are left out (see preset.rs).
+    pub preset: GraphicsPreset,
+    pub anti_aliasing: AntiAliasing,
+    pub fog_start: f32,
+    pub fog_end: f32,
+    pub draw_distance: f32,
+    pub shadows: bool,
+    pub shadow_map_size: u32,
+    // Chunks farther than this draw their simplified mesh, meters.
+    pub lod_distance: f32,
+    pub ssao: bool,
+    pub bloom: bool,
+    // Screen-space reflections on water.
+    pub reflections: bool,
+    // VRAM to keep chunk meshes within, MB; None picks by adapter type.
+    pub gpu_budget_mb: Option<u64>,
+    // RAM for evicted chunk meshes waiting to be re-uploaded, MB; beyond it they go to paths.mesh_cache.
+    pub mesh_cache_mb: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhysicsConfig {
+    pub gravity: f64,
+    pub jump_force: f64,
+    pub walk_speed: f64,
+    pub sprint_speed: f64,
+    pub crouch_speed: f64,
+    pub terminal_velocity: f64,
+}
+
+// Defaults for the settings menu; whatever it saved to the settings file still wins.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlsConfig {
+    pub mouse_sensitivity: f64,
+    pub invert_y: bool,
+    // Whether letter and digit key bindings follow the key's position or its printed character.
+    pub key_layout: KeyLayout,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    pub settings: String,
+    pub bindings: String,
+    pub waypoints: String,
+    pub replay: String,
+    pub camera_path: String,
+    pub window: String,
+    pub capture: String,
+    // Evicted chunk meshes beyond graphics.mesh_cache_mb; the platform cache directory when unset.
+    pub mesh_cache: Option<String>,
+    // Chrome tracing JSON of the profiler's GPU and CPU timings, written while running; off when unset.
+    pub trace: Option<String>,
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        Self {
+            file: config::MAP_FILE_PATH.into(), maps_dir: config::MAPS_DIR.into(),
+            origin_lat: config::ORIGIN_LAT, origin_lon: config::ORIGIN_LON, spawn: config::SPAWN.into(), spawn_height: 0.0, world_size: config::WORLD_SIZE,
+        }
+    }
+}
+
+impl Default for GraphicsConfig {
+    fn default() -> Self {
+        let preset = config::GRAPHICS_PRESET.values();
+        Self {
+            window_mode: config::WINDOW_MODE, monitor: None, window_size: config::WINDOW_SIZE, remember_window: config::REMEMBER_WINDOW,
+            resolution: config::FULLSCREEN_RESOLUTION, refresh_hz: config::FULLSCREEN_REFRESH_HZ, present_mode: config::PRESENT_MODE, frame_limit: config::FRAME_LIMIT,
+            fov: config::FOV_Y, tonemapper: config::TONEMAPPER, preset: config::GRAPHICS_PRESET, anti_aliasing: preset.anti_aliasing,
+            fog_start: preset.fog_start, fog_end: preset.fog_end, draw_distance: preset.draw_distance, shadows: preset.shadows, shadow_map_size: preset.shadow_map_size,
+            lod_distance: preset.lod_distance, ssao: preset.ssao, bloom: preset.bloom, reflections: preset.reflections,
+            gpu_budget_mb: config::GPU_BUDGET_MB, mesh_cache_mb: config::MESH_CACHE_MB,
+        }
+    }
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            gravity: config::GRAVITY, jump_force: config::JUMP_FORCE, walk_speed: config::WALK_SPEED,
+            sprint_speed: config::SPRINT_SPEED, crouch_speed: config::CROUCH_SPEED, terminal_velocity: config::TERMINAL_VELOCITY,
+        }
+    }
+}
+
+impl Default for ControlsConfig {
+    fn default() -> Self {
+        Self { mouse_sensitivity: config::MOUSE_SENSITIVITY, invert_y: config::MOUSE_INVERT_Y, key_layout: config::KEY_LAYOUT }
+    }
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        Self {
+            settings: config::SETTINGS_FILE.into(), bindings: config::BINDINGS_FILE.into(), waypoints: config::WAYPOINTS_FILE.into(),
+            replay: config::REPLAY_FILE.into(), camera_path: config::CINEMATIC_PATH_FILE.into(), window: config::WINDOW_STATE_FILE.into(),
+            capture: config::CAPTURE_FILE.into(), mesh_cache: None, trace: None,
+        }
+    }
+}
+
+// The current config; the config.rs defaults until `load` runs (as in tests). Hold on to it
+// only briefly: a reload swaps in a new one.
+pub fn get() -> Arc<ConfigFile> {
+    if let Some(config) = CONFIG.read().unwrap().as_ref() { return config.clone(); }
+    CONFIG.write().unwrap().get_or_insert_with(Arc::default).clone()
+}
+
+pub fn world_size() -> f32 {
+    get().map.world_size
+}
+
+pub fn chunk_size() -> f32 {
+    world_size() / config::CHUNK_GRID_AXIS as f32
+}
+
+// Just past the far corner of the world seen from the opposite one, so the depth buffer's
+// precision isn't spread over distances nothing is drawn at.
+pub fn far_plane() -> f32 {
+    world_size() * std::f32::consts::SQRT_2 + config::FAR_PLANE_MARGIN
+}
+
+// Reads the config file and command line overrides. Call once, before anything reads the config.
+// A broken file is reported and ignored as a whole; a bad value falls back to its default.
+pub fn load(cli: &Cli) {
+    let source = SOURCE.get_or_init(|| Source { path: cli.config.clone(), overrides: cli.config_overrides() });
+    let loaded = read(source).unwrap_or_else(|e| {
+        log::error!("{}", e);
+        ConfigFile::default()
+    });
+    *CONFIG.write().unwrap() = Some(Arc::new(loaded));
+}
+
+// Startup: applies the preset the settings menu saved last time. Call right after `load`,
+// before anything reads the graphics config.
+pub fn restore_preset(preset: GraphicsPreset) {
+    *CHOSEN_PRESET.write().unwrap() = Some(preset);
+    let Some(source) = SOURCE.get() else { return };
+    match read(source) {
+        Ok(loaded) => *CONFIG.write().unwrap() = Some(Arc::new(loaded)),
+        Err(e) => log::error!("{}", e),
+    }
+}
+
+// The settings menu picked another preset: applies the parts of it that can change while
+// running, like a reload of the file does. Values the file or command line set stay as they are.
+pub fn choose_preset(preset: GraphicsPreset) -> Option<Result<Reload, String>> {
+    *CHOSEN_PRESET.write().unwrap() = Some(preset);
+    let source = SOURCE.get()?;
+    Some(read(source).map(reload))
+}
+
+// Whether SECTION.KEY was set on the command line.
+pub fn from_command_line(key: &str) -> bool {
+    SOURCE.get().is_some_and(|source| source.overrides.iter().any(|(k, _)| k == key))
+}
+
+// Where the config came from, to read it the same way again on a reload.
+struct Source {
+    // None is DEFAULT_PATH, which may be missing.
+    path: Option<String>,
+    overrides: Vec<(String, toml::Value)>,
+}
+
+impl Source {
+    fn path(&self) -> &str {
+        self.path.as_deref().unwrap_or(DEFAULT_PATH)
+    }
+}
+
+fn read(source: &Source) -> Result<ConfigFile, String> {
+    let mut table = match std::fs::read_to_string(source.path()) {
+        Ok(text) => text.parse::<toml::Table>().map_err(|e| format!("Ignoring {}: {}", source.path(), e))?,
+        Err(e) if source.path.is_some() => return Err(format!("Couldn't read {}: {}", source.path(), e)),
+        Err(_) => toml::Table::new(),
+    };
+    for (key, value) in &source.overrides { set(&mut table, key, value.clone()); }
+    if let Some(preset) = *CHOSEN_PRESET.read().unwrap() && let Ok(name) = toml::Value::try_from(preset) {
+        set(&mut table, "graphics.preset", name);
+    }
+    fill_preset(&mut table);
+    let config = ConfigFile::deserialize(table).map_err(|e| format!("Ignoring {}: {}", source.path(), e))?;
+    Ok(config.validated())
+}
+
+// Puts the preset's graphics values into `table` where it has none of its own.
+fn fill_preset(table: &mut toml::Table) {
+    let section = table.entry("graphics").or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    let Some(section) = section.as_table_mut() else { return };
+    let preset = match section.get("preset") {
+        // A bad name is reported when the whole config is deserialized.
+        Some(name) => match GraphicsPreset::deserialize(name.clone()) {
+            Ok(preset) => preset,
+            Err(_) => return,
+        },
+        None => config::GRAPHICS_PRESET,
+    };
+    let Ok(toml::Value::Table(values)) = toml::Value::try_from(preset.values()) else { return };
+    for (key, value) in values { section.entry(key).or_insert(value); }
+}
+
+// Values a reload applies straight away (whole sections end in a dot). The rest only take
+// effect on the next start: they size GPU resources, the world grid or the window, or name files.
+const LIVE: [&str; 12] = [
+    "graphics.fov", "graphics.preset", "graphics.fog_start", "graphics.fog_end", "graphics.draw_distance", "graphics.shadows", "graphics.lod_distance",
+    "graphics.ssao", "graphics.bloom", "graphics.reflections", "physics.", "controls.",
+];
+
+// What a reload changed, by SECTION.KEY.
+#[derive(Debug, Default)]
+pub struct Reload {
+    pub applied: Vec<String>,
+    pub needs_restart: Vec<String>,
+}
+
+// Notices the config file changing on disk by its modification time, checked every
+// CONFIG_WATCH_INTERVAL, and reloads it.
+pub struct ConfigWatcher {
+    modified: Option<SystemTime>,
+    since_check: f64,
+}
+
+impl ConfigWatcher {
+    pub fn new() -> Self {
+        Self { modified: modified_time(), since_check: 0.0 }
+    }
+
+    // Reloads when the file changed since the last check. Err is a message for a file that
+    // no longer reads; the running config is kept then.
+    pub fn poll(&mut self, dt: f64) -> Option<Result<Reload, String>> {
+        self.since_check += dt;
+        if self.since_check < config::CONFIG_WATCH_INTERVAL { return None; }
+        self.since_check = 0.0;
+        let modified = modified_time();
+        if modified == self.modified { return None; }
+        self.modified = modified;
+        let source = SOURCE.get()?;
+        Some(read(source).map(reload))
+    }
+}
+
+fn modified_time() -> Option<SystemTime> {
+    let path = SOURCE.get().map_or(DEFAULT_PATH, Source::path);
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Swaps in the live values of `new`, keeping the rest as they are.
+fn reload(new: ConfigFile) -> Reload {
+    let current = get();
+    let (old_values, new_values) = (flatten(&current), flatten(&new));
+    let mut merged = toml::Table::new();
+    let mut result = Reload::default();
+    let keys: std::collections::BTreeSet<&String> = old_values.keys().chain(new_values.keys()).collect();
+    for key in keys {
+        let live = LIVE.iter().any(|l| if l.ends_with('.') { key.starts_with(l) } else { key == l });
+        let (old, new) = (old_values.get(key), new_values.get(key));
+        if old != new {
+            if live { result.applied.push(key.clone()); } else { result.needs_restart.push(key.clone()); }
+        }
+        if let Some(value) = if live { new } else { old } { set(&mut merged, key, value.clone()); }
+    }
+    match ConfigFile::deserialize(merged) {
+        Ok(merged) => *CONFIG.write().unwrap() = Some(Arc::new(merged)),
+        Err(e) => log::error!("Couldn't apply the reloaded config: {}", e),
+    }
+    result
+}
+
+// Every value as SECTION.KEY; unset options are left out.
+fn flatten(config: &ConfigFile) -> std::collections::BTreeMap<String, toml::Value> {
+    let table = toml::Table::try_from(config).unwrap_or_default();
+    table.into_iter()
+        .filter_map(|(section, values)| if let toml::Value::Table(values) = values { Some((section, values)) } else { None })
+        .flat_map(|(section, values)| values.into_iter().map(move |(key, value)| (format!("{}.{}", section, key), value)))
+        .collect()
+}
+
+// A TOML value as written in the file; anything that doesn't parse is taken as a bare string.
+pub fn parse_value(text: &str) -> toml::Value {
+    format!("value = {}", text).parse::<toml::Table>().ok()
+        .and_then(|mut t| t.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(text.to_string()))
+}
+
+fn set(table: &mut toml::Table, key: &str, value: toml::Value) {
+    let Some((section, name)) = key.split_once('.') else {
+        log::warn!("Ignoring override '{}'; expected SECTION.KEY", key);
+        return;
+    };
+    let entry = table.entry(section).or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    match entry.as_table_mut() {
+        Some(section) => { section.insert(name.to_string(), value); }
+        None => log::warn!("Ignoring override '{}'; {} isn't a section", key, section),
+    }
+}
+
+// Puts back the default of `value` when it fails `valid`.
+fn check<T: Display + Copy>(name: &str, value: &mut T, default: T, valid: impl Fn(T) -> bool) {
+    if !valid(*value) {
+        log::warn!("Config {} = {} is out of range; using {}", name, value, default);
+        *value = default;
+    }
+}
+
+impl ConfigFile {
+    fn validated(mut self) -> Self {
+        let (map, graphics, physics, controls) = (MapConfig::default(), GraphicsConfig::default(), PhysicsConfig::default(), ControlsConfig::default());
+        check("map.origin_lat", &mut self.map.origin_lat, map.origin_lat, |v| (-90.0..=90.0).contains(&v));
+        check("map.origin_lon", &mut self.map.origin_lon, map.origin_lon, |v| (-180.0..=180.0).contains(&v));
+        check("map.spawn_height", &mut self.map.spawn_height, map.spawn_height, |v| (0.0..=10000.0).contains(&v));
+        check("map.world_size", &mut self.map.world_size, map.world_size, |v| v >= 1000.0 && v.is_finite());
+        if self.graphics.frame_limit == Some(0) {
+            log::warn!("Config graphics.frame_limit = 0 is out of range; leaving the frame rate unlimited");
+            self.graphics.frame_limit = None;
+        }
+        let (width, height) = self.graphics.window_size;
+        if width < 320 || height < 240 {
+            log::warn!("Config graphics.window_size = [{}, {}] is too small; using the default", width, height);
+            self.graphics.window_size = graphics.window_size;
+        }
+        let (min_fov, max_fov) = config::FOV_RANGE;
+        check("graphics.fov", &mut self.graphics.fov, graphics.fov, |v| (min_fov..=max_fov).contains(&v));
+        check("graphics.fog_start", &mut self.graphics.fog_start, graphics.fog_start, |v| v >= 0.0);
+        let fog_start = self.graphics.fog_start;
+        check("graphics.fog_end", &mut self.graphics.fog_end, graphics.fog_end.max(fog_start + 1.0), |v| v > fog_start);
+        check("graphics.draw_distance", &mut self.graphics.draw_distance, graphics.draw_distance, |v| v > 0.0);
+        check("graphics.lod_distance", &mut self.graphics.lod_distance, graphics.lod_distance, |v| v > 0.0);
+        if self.graphics.gpu_budget_mb.is_some_and(|mb| mb < 256) {
+            log::warn!("Config graphics.gpu_budget_mb is below 256; picking the budget by adapter type");
+            self.graphics.gpu_budget_mb = None;
+        }
+        check("graphics.shadow_map_size", &mut self.graphics.shadow_map_size, graphics.shadow_map_size, |v| v.is_power_of_two() && (256..=8192).contains(&v));
+        check("physics.gravity", &mut self.physics.gravity, physics.gravity, |v| v > 0.0);
+        check("physics.jump_force", &mut self.physics.jump_force, physics.jump_force, |v| v >= 0.0);
+        check("physics.walk_speed", &mut self.physics.walk_speed, physics.walk_speed, |v| v > 0.0);
+        // Sprinting keeps its default lead over whatever walking is set to.
+        let walk = self.physics.walk_speed;
+        check("physics.sprint_speed", &mut self.physics.sprint_speed, walk * physics.sprint_speed / physics.walk_speed, |v| v > walk);
+        check("physics.crouch_speed", &mut self.physics.crouch_speed, physics.crouch_speed, |v| v > 0.0);
+        check("physics.terminal_velocity", &mut self.physics.terminal_velocity, physics.terminal_velocity, |v| v < 0.0);
+        let (min_sensitivity, max_sensitivity) = config::MOUSE_SENSITIVITY_RANGE;
+        check("controls.mouse_sensitivity", &mut self.controls.mouse_sensitivity, controls.mouse_sensitivity, |v| (min_sensitivity..=max_sensitivity).contains(&v));
+        self
+    }
+}
@@ -0,0 +1,172 @@
+// console.rs
+use winit::{event::KeyEvent, keyboard::{KeyCode, PhysicalKey}};
+use crate::{config, hud::HudRenderer, spawn::SpawnPoint, text::TextRenderer};
+
+// Every command, with its arguments and what it does, for `help` and tab completion.
+const COMMANDS: [(&str, &str, &str); 13] = [
+    ("tp", "LAT,LON | PLACE", "teleport to coordinates, a place or a waypoint"),
+    ("time", "[HOURS]", "show or set the time of day"),
+    ("fog", "[MULTIPLIER]", "show or set the fog density multiplier"),
+    ("speed", "[M/S]", "show or set the noclip flying speed"),
+    ("noclip", "", "toggle flying"),
+    ("reload_chunks", "", "stream every chunk from the map file again"),
+    ("reset_bindings", "", "restore the default key bindings and rewrite the bindings file"),
+    ("stats", "", "print frame stats and toggle the overlay"),
+    ("capture", "", "start or stop recording every input into the capture file"),
+    ("playback", "[PATH]", "play back an input capture, or stop playing one"),
+    ("tour", "[N]", "fly one of the map package's tours, or list them"),
+    ("clear", "", "clear the console"),
+    ("help", "", "list the commands"),
+];
+
+#[derive(Debug)]
+pub enum Command {
+    Teleport(SpawnPoint),
+    Time(Option<f64>),
+    Fog(Option<f32>),
+    Speed(Option<f64>),
+    Noclip,
+    ReloadChunks,
+    ResetBindings,
+    Stats,
+    Capture,
+    Playback(Option<String>),
+    Tour(Option<usize>),
+    Clear,
+    Help,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let (name, args) = line.trim().split_once(char::is_whitespace).map_or((line.trim(), ""), |(n, a)| (n, a.trim()));
+        let number = |what: &str| -> Result<Option<f64>, String> {
+            if args.is_empty() { return Ok(None); }
+            args.parse::<f64>().map(Some).map_err(|_| format!("{}: expected {}, got '{}'", name, what, args))
+        };
+        Ok(match name {
+            "tp" if args.is_empty() => return Err("tp: expected LAT,LON or a place name".to_string()),
+            // "lat lon" works as well as "lat,lon".
+            "tp" => Self::Teleport(SpawnPoint::parse(&match args.split_whitespace().collect::<Vec<_>>()[..] {
+                [lat, lon] if lat.parse::<f64>().is_ok() && lon.parse::<f64>().is_ok() => format!("{},{}", lat, lon),
+                _ => args.to_string(),
+            })),
+            "time" => Self::Time(number("hours")?),
+            "fog" => Self::Fog(number("a multiplier")?.map(|f| f as f32)),
+            "speed" => Self::Speed(number("meters per second")?),
+            "noclip" => Self::Noclip,
+            "reload_chunks" => Self::ReloadChunks,
+            "reset_bindings" => Self::ResetBindings,
+            "stats" => Self::Stats,
+            "capture" => Self::Capture,
+            "playback" => Self::Playback((!args.is_empty()).then(|| args.to_string())),
+            "tour" => Self::Tour(number("a tour number")?.map(|n| n as usize)),
+            "clear" => Self::Clear,
+            "help" => Self::Help,
+            _ => return Err(format!("Unknown command '{}' (try help)", name)),
+        })
+    }
+
+    pub fn help() -> impl Iterator<Item = String> {
+        COMMANDS.iter().map(|(name, args, about)| if args.is_empty() { format!("{} - {}", name, about) } else { format!("{} {} - {}", name, args, about) })
+    }
+}
+
+// Drop-down console (the ~ key). Takes the keyboard while open; Enter runs the line through
+// GameState::run_command, Up/Down walk the history and Tab completes command names.
+pub struct Console {
+    pub open: bool,
+    input: String,
+    output: Vec<String>,
+    history: Vec<String>,
+    // Entry of `history` shown in the input line, while browsing it.
+    browsing: Option<usize>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self { open: false, input: String::new(), output: vec!["Type help for a list of commands".to_string()], history: Vec::new(), browsing: None }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.output.push(line.into());
+        if self.output.len() > config::CONSOLE_SCROLLBACK {
+            self.output.drain(..self.output.len() - config::CONSOLE_SCROLLBACK);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.output.clear();
+    }
+
+    // Edits the input line; returns a submitted line to run.
+    pub fn key(&mut self, event: &KeyEvent) -> Option<String> {
+        let PhysicalKey::Code(key) = event.physical_key else { return None };
+        match key {
+            KeyCode::Enter | KeyCode::NumpadEnter => {
+                let line = std::mem::take(&mut self.input);
+                self.browsing = None;
+                if line.trim().is_empty() { return None; }
+                self.print(format!("> {}", line));
+                if self.history.last() != Some(&line) { self.history.push(line.clone()); }
+                if self.history.len() > config::CONSOLE_HISTORY { self.history.remove(0); }
+                return Some(line);
+            }
+            KeyCode::Backspace => { self.input.pop(); }
+            KeyCode::ArrowUp | KeyCode::ArrowDown => self.browse(key == KeyCode::ArrowUp),
+            KeyCode::Tab => self.complete(),
+            _ => if let Some(text) = &event.text {
+                self.input.extend(text.chars().filter(|c| !c.is_control()));
+            },
+        }
+        None
+    }
+
+    fn browse(&mut self, older: bool) {
+        if self.history.is_empty() { return; }
+        let last = self.history.len() - 1;
+        self.browsing = match (self.browsing, older) {
+            (None, true) => Some(last),
+            (None, false) => None,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i < last => Some(i + 1),
+            (Some(_), false) => None,
+        };
+        self.input = self.browsing.map_or_else(String::new, |i| self.history[i].clone());
+    }
+
+    // Completes the command name being typed, or as far as the matches agree and lists them.
+    fn complete(&mut self) {
+        if self.input.contains(char::is_whitespace) { return; }
+        let matches: Vec<&str> = COMMANDS.iter().map(|c| c.0).filter(|name| name.starts_with(self.input.as_str())).collect();
+        match matches[..] {
+            [] => {}
+            [only] => self.input = format!("{} ", only),
+            _ => {
+                let common = matches.iter().skip(1).fold(matches[0], |prefix, name| {
+                    let len = prefix.chars().zip(name.chars()).take_while(|(a, b)| a == b).count();
+                    &prefix[..len]
+                });
+                self.input = common.to_string();
+                self.print(matches.join("  "));
+            }
+        }
+    }
+
+    pub fn draw(&self, hud: &mut HudRenderer, text: &mut TextRenderer, screen: [f32; 2]) {
+        let (size, line) = (16.0, 20.0);
+        let height = (screen[1] * 0.4).round();
+        hud.rect([0.0, 0.0], [screen[0], height], [0.02, 0.02, 0.04, 0.88]);
+        hud.rect([0.0, height - line - 10.0], [screen[0], height - line - 9.0], [1.0, 1.0, 1.0, 0.2]);
+        text.text(&format!("> {}_", self.input), [12.0, height - line - 4.0], size, [1.0, 1.0, 1.0, 1.0]);
+        let rows = ((height - line - 20.0) / line).max(0.0) as usize;
+        let shown = &self.output[self.output.len().saturating_sub(rows)..];
+        for (i, l) in shown.iter().enumerate() {
+            let y = height - line - 14.0 - (shown.len() - i) as f32 * line;
+            text.text(l, [12.0, y], size, [0.85, 0.85, 0.85, 1.0]);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// crosshair.rs
+use serde::{Deserialize, Serialize};
+use crate::{camera::Camera, config, shader, shader_cache, world::World};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CrosshairStyle {
+    Dot,
+    Cross,
+    Ring,
+}
+
+impl Default for CrosshairStyle {
+    fn default() -> Self {
+        config::CROSSHAIR_STYLE
+    }
+}
+
+impl CrosshairStyle {
+    const ALL: [Self; 3] = [Self::Dot, Self::Cross, Self::Ring];
+
+    // The style `steps` away in the settings menu, wrapping around.
+    pub fn step(self, steps: i32) -> Self {
+        let index = Self::ALL.iter().position(|&s| s == self).unwrap_or(0) as i32;
+        Self::ALL[(index + steps).rem_euclid(Self::ALL.len() as i32) as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Dot => "Dot",
+            Self::Cross => "Cross",
+            Self::Ring => "Ring",
+        }
+    }
+}
+
+// What's under a view's crosshair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reticle {
+    Idle,
+    // A building within highlight range but too far to grapple.
+    Building,
+    // Anything the grapple would catch.
+    Grapple,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CrosshairInstance {
+    color: [f32; 4],
+    // x: size in pixels, y: style, z: line thickness in pixels, w: scene pixels per window pixel.
+    params: [f32; 4],
+}
+
+// Draws each view's crosshair in the composite pass, styled from config and changing color
+// (and size, when the grapple would catch) with whatever the view is aimed at.
+pub struct Crosshairs {
+    pipeline: wgpu::RenderPipeline,
+    instance_buffer: wgpu::Buffer,
+    reticles: Vec<Reticle>,
+}
+
+impl Crosshairs {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, camera_layout: &wgpu::BindGroupLayout, view_count: usize) -> Self {
+        let module = shader_cache::module(device, "UI Shader", shader::UI_SHADER);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[camera_layout], push_constant_ranges: &[] });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("UI Pipeline"), layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module, entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<CrosshairInstance>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &[
+                        wgpu::VertexAttribute { offset: 0,  shader_location: 0, format: wgpu::VertexFormat::Float32x4 },
+                        wgpu::VertexAttribute { offset: 16, shader_location: 1, format: wgpu::VertexFormat::Float32x4 },
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module, entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState { format, blend: Some(wgpu::BlendState::ALPHA_BLENDING), write_mask: wgpu::ColorWrites::ALL })],
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleStrip, ..Default::default() },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Crosshair Instances"), size: (view_count * std::mem::size_of::<CrosshairInstance>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+        });
+        Self { pipeline, instance_buffer, reticles: vec![Reticle::Idle; view_count] }
+    }
+
+    // Casts the view's aim ray out to the farther of the grapple and highlight ranges.
+    pub fn update(&mut self, view: usize, camera: &Camera, world: &World) {
+        let range = config::GRAPPLE_RANGE.max(config::HIGHLIGHT_DISTANCE);
+        self.reticles[view] = match world.raycast(camera.eye.as_vec3(), camera.forward().as_vec3(), range) {
+            Some(hit) if hit.distance <= config::GRAPPLE_RANGE => Reticle::Grapple,
+            Some(hit) if hit.building.is_some() && hit.distance <= config::HIGHLIGHT_DISTANCE => Reticle::Building,
+            _ => Reticle::Idle,
+        };
+    }
+
+    // `scale` is the render scale; the camera uniform's screen size is in scene pixels.
+    pub fn prepare(&self, queue: &wgpu::Queue, style: CrosshairStyle, scale: f32) {
+        let style = match style {
+            CrosshairStyle::Dot => 0.0,
+            CrosshairStyle::Cross => 1.0,
+            CrosshairStyle::Ring => 2.0,
+        };
+        let instances: Vec<CrosshairInstance> = self.reticles.iter().map(|reticle| {
+            let (color, grow) = match reticle {
+                Reticle::Idle => (config::CROSSHAIR_COLOR, 1.0),
+                Reticle::Building => (config::CROSSHAIR_BUILDING_COLOR, 1.0),
+                Reticle::Grapple => (config::CROSSHAIR_GRAPPLE_COLOR, config::CROSSHAIR_GRAPPLE_SCALE),
+            };
+            CrosshairInstance { color, params: [config::CROSSHAIR_SIZE * grow, style, config::CROSSHAIR_THICKNESS, scale] }
+        }).collect();
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+    }
+
+    // Expects the view's camera bind group at group 0 and its viewport set.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, view: usize) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        let instance = view as u32;
+        pass.draw(0..4, instance..instance + 1);
+    }
+}
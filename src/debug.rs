@@ -0,0 +1,147 @@
+// debug.rs
+use glam::Vec3;
+use crate::{post, shader, shader_cache};
+
+// F3 cycles through these. Wireframe and Normals swap the chunk pipeline, the others
+// draw colored lines over the normal scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugMode {
+    Off,
+    Wireframe,
+    ChunkBounds,
+    Collision,
+    Normals,
+}
+
+impl DebugMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Wireframe,
+            Self::Wireframe => Self::ChunkBounds,
+            Self::ChunkBounds => Self::Collision,
+            Self::Collision => Self::Normals,
+            Self::Normals => Self::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Wireframe => "Wireframe",
+            Self::ChunkBounds => "Chunk Bounds",
+            Self::Collision => "Collision",
+            Self::Normals => "Normals",
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+// Line list rebuilt every frame for the debug views and grapple ropes, drawn depth-tested
+// in the scene pass.
+pub struct DebugLines {
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    vertices: Vec<DebugVertex>,
+    count: u32,
+}
+
+impl DebugLines {
+    pub fn new(device: &wgpu::Device, camera_layout: &wgpu::BindGroupLayout) -> Self {
+        let module = shader_cache::module(device, "Debug Line Shader", shader::DEBUG_LINE_SHADER);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[camera_layout], push_constant_ranges: &[] });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug Line Pipeline"), layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module, entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<DebugVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &[
+                        wgpu::VertexAttribute { offset: 0,  shader_location: 0, format: wgpu::VertexFormat::Float32x3 },
+                        wgpu::VertexAttribute { offset: 12, shader_location: 1, format: wgpu::VertexFormat::Float32x4 },
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module, entry_point: "fs_main",
+                // Lines leave the normal target alone so SSAO still sees the surfaces behind them.
+                targets: &[
+                    Some(wgpu::ColorTargetState { format: post::HDR_FORMAT, blend: None, write_mask: wgpu::ColorWrites::ALL }),
+                    Some(wgpu::ColorTargetState { format: post::NORMAL_FORMAT, blend: None, write_mask: wgpu::ColorWrites::empty() }),
+                ],
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::LineList, ..Default::default() },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float, depth_write_enabled: false, depth_compare: wgpu::CompareFunction::LessEqual, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState { count: post::scene_samples(), mask: !0, alpha_to_coverage_enabled: false },
+            multiview: None,
+        });
+
+        let capacity = 4096;
+        Self { pipeline, buffer: Self::create_buffer(device, capacity), capacity, vertices: Vec::new(), count: 0 }
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug Lines"), size: (capacity * std::mem::size_of::<DebugVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: [f32; 4]) {
+        self.vertices.push(DebugVertex { position: a.to_array(), color });
+        self.vertices.push(DebugVertex { position: b.to_array(), color });
+    }
+
+    // Connected segments through `points`.
+    pub fn strip(&mut self, points: &[Vec3], color: [f32; 4]) {
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], color);
+        }
+    }
+
+    // The 12 edges of an axis-aligned box.
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: [f32; 4]) {
+        let corner = |i: u32| Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        );
+        for i in 0..8u32 {
+            for axis in [1, 2, 4] {
+                if i & axis == 0 { self.line(corner(i), corner(i | axis), color); }
+            }
+        }
+    }
+
+    // Uploads the lines added since the last clear; call before the scene pass.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.count = self.vertices.len() as u32;
+        if self.vertices.is_empty() { return; }
+        if self.vertices.len() > self.capacity {
+            self.capacity = self.vertices.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.vertices));
+    }
+
+    // Expects the camera bind group at group 0.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        if self.count == 0 { return; }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_vertex_buffer(0, self.buffer.slice(..));
+        pass.draw(0..self.count, 0..1);
+    }
+}
@@ -0,0 +1,69 @@
+// dev_ui.rs
+use std::sync::Arc;
+use winit::{event::WindowEvent, window::Window};
+
+// Optional egui layer (feature "egui", F1) drawn over the finished frame. GameState builds the
+// settings and developer panels each frame; this owns the egui context, its winit input state
+// and the wgpu renderer.
+pub struct DevUi {
+    pub open: bool,
+    ctx: egui::Context,
+    input: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    window: Arc<Window>,
+}
+
+impl DevUi {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, window: Arc<Window>) -> Self {
+        let ctx = egui::Context::default();
+        let max_texture_side = device.limits().max_texture_dimension_2d as usize;
+        let input = egui_winit::State::new(ctx.clone(), egui::ViewportId::ROOT, &*window, Some(window.scale_factor() as f32), Some(max_texture_side));
+        let renderer = egui_wgpu::Renderer::new(device, format, None, 1);
+        Self { open: false, ctx, input, renderer, window }
+    }
+
+    // Opens or closes the panels. Returns whether they're open.
+    pub fn toggle(&mut self) -> bool {
+        self.open = !self.open;
+        self.open
+    }
+
+    // Feeds a window event to egui; true if a panel used it. Clicks never reach the game while
+    // the panels are open, so missing a panel doesn't fire the grapple.
+    pub fn on_window_event(&mut self, event: &WindowEvent) -> bool {
+        if !self.open { return false; }
+        self.input.on_window_event(&self.window, event).consumed || matches!(event, WindowEvent::MouseInput { .. })
+    }
+
+    // Runs one egui frame with `panels` building the UI.
+    pub fn run(&mut self, panels: impl FnMut(&egui::Context)) -> egui::FullOutput {
+        let raw = self.input.take_egui_input(&self.window);
+        let mut output = self.ctx.run(raw, panels);
+        self.input.handle_platform_output(&self.window, std::mem::take(&mut output.platform_output));
+        output
+    }
+
+    // Draws the frame from run() on top of `target`.
+    pub fn paint(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, size: [u32; 2], output: egui::FullOutput) {
+        let jobs = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+        let screen = egui_wgpu::ScreenDescriptor { size_in_pixels: size, pixels_per_point: output.pixels_per_point };
+        for (id, delta) in &output.textures_delta.set {
+            self.renderer.update_texture(device, queue, *id, delta);
+        }
+        // Only paint callbacks record extra command buffers, and the panels use none.
+        self.renderer.update_buffers(device, queue, encoder, &jobs, &screen);
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Dev UI Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target, resolve_target: None, ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: None, timestamp_writes: None, occlusion_query_set: None,
+            });
+            self.renderer.render(&mut pass, &jobs, &screen);
+        }
+        for id in &output.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
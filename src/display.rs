@@ -0,0 +1,284 @@
+// display.rs
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use winit::{dpi::{PhysicalPosition, PhysicalSize}, event_loop::{ControlFlow, EventLoopWindowTarget}, monitor::{MonitorHandle, VideoMode}, window::{Fullscreen, Window}};
+use crate::{config, config_file};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMode {
+    Windowed,
+    Borderless,
+    // Takes over the display at the configured resolution and refresh rate.
+    Exclusive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresentPreference {
+    // Immediate if supported: lowest latency, may tear.
+    LowLatency,
+    // Mailbox if supported: no tearing, renders as fast as possible.
+    NoTearing,
+    // Fifo: capped at the display's refresh rate.
+    #[serde(rename = "vsync")]
+    VSync,
+}
+
+impl PresentPreference {
+    const ALL: [Self; 3] = [Self::VSync, Self::NoTearing, Self::LowLatency];
+
+    // Next or previous preference, for the settings menu.
+    pub fn step(self, steps: i32) -> Self {
+        let i = Self::ALL.iter().position(|&p| p == self).unwrap_or(0) as i32;
+        Self::ALL[(i + steps).clamp(0, Self::ALL.len() as i32 - 1) as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::VSync => "VSync (Fifo)",
+            Self::NoTearing => "No tearing (Mailbox)",
+            Self::LowLatency => "Low latency (Immediate)",
+        }
+    }
+}
+
+// The monitor to go fullscreen on: its index in the platform's list, or its name or part of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MonitorChoice {
+    Index(usize),
+    Name(String),
+}
+
+// The configured monitor, else the one the window was last on (with graphics.remember_window),
+// else the primary one.
+pub fn pick_monitor(available: Vec<MonitorHandle>, primary: Option<MonitorHandle>, memory: &WindowMemory) -> Option<MonitorHandle> {
+    let name_matches = |m: &MonitorHandle, name: &str| m.name().is_some_and(|n| n.to_lowercase().contains(&name.to_lowercase()));
+    if let Some(choice) = &config_file::get().graphics.monitor {
+        let found = match choice {
+            MonitorChoice::Index(i) => available.get(*i).cloned(),
+            MonitorChoice::Name(name) => available.iter().find(|m| name_matches(m, name)).cloned(),
+        };
+        if found.is_some() { return found; }
+        let names: Vec<String> = available.iter().enumerate().map(|(i, m)| format!("{}: {}", i, m.name().unwrap_or_default())).collect();
+        log::warn!("No monitor {:?}; using the primary one. Monitors: {}", choice, names.join(", "));
+    } else if let Some(name) = &memory.monitor && let Some(monitor) = available.iter().find(|m| m.name().as_deref() == Some(name)) {
+        return Some(monitor.clone());
+    }
+    primary.or_else(|| available.into_iter().next())
+}
+
+// Where the window was when the game last closed, in the window file (paths.window). Only
+// read and written with graphics.remember_window.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowMemory {
+    pub monitor: Option<String>,
+    // Outer position and inner size the last time it closed windowed.
+    pub position: Option<(i32, i32)>,
+    pub size: Option<(u32, u32)>,
+}
+
+impl WindowMemory {
+    pub fn load() -> Self {
+        if !config_file::get().graphics.remember_window { return Self::default(); }
+        let path = &config_file::get().paths.window;
+        std::fs::read_to_string(path).ok()
+            .and_then(|text| serde_json::from_str(&text).map_err(|e| log::warn!("Ignoring {}: {}", path, e)).ok())
+            .unwrap_or_default()
+    }
+
+    // Records the window's monitor, and its geometry when windowed; a fullscreen window keeps
+    // the geometry it had the last time it closed windowed.
+    pub fn save(window: &Window) {
+        if !config_file::get().graphics.remember_window { return; }
+        let mut memory = Self::load();
+        memory.monitor = window.current_monitor().and_then(|m| m.name());
+        // A minimized window reports no size worth reopening at.
+        let size = window.inner_size();
+        if window.fullscreen().is_none() && size.width >= 320 && size.height >= 240 {
+            memory.position = window.outer_position().ok().map(|p| (p.x, p.y));
+            memory.size = Some((size.width, size.height));
+        }
+        let path = &config_file::get().paths.window;
+        let result = serde_json::to_string_pretty(&memory).map_err(|e| e.to_string())
+            .and_then(|text| std::fs::write(path, text).map_err(|e| e.to_string()));
+        if let Err(e) = result { log::warn!("Couldn't save {}: {}", path, e); }
+    }
+
+    // Size and position for a new window on `monitor`: the remembered ones, unless a monitor
+    // was picked in the config, which centers the window on it instead.
+    pub fn geometry(&self, monitor: Option<&MonitorHandle>) -> (PhysicalSize<u32>, Option<PhysicalPosition<i32>>) {
+        let (width, height) = self.size.unwrap_or(config_file::get().graphics.window_size);
+        let size = PhysicalSize::new(width, height);
+        let position = match (&config_file::get().graphics.monitor, monitor) {
+            (Some(_), Some(monitor)) => {
+                let (origin, area) = (monitor.position(), monitor.size());
+                Some(PhysicalPosition::new(
+                    origin.x + (area.width.saturating_sub(width) / 2) as i32, origin.y + (area.height.saturating_sub(height) / 2) as i32,
+                ))
+            }
+            _ => self.position.map(|(x, y)| PhysicalPosition::new(x, y)),
+        };
+        (size, position)
+    }
+}
+
+pub fn pick_present_mode(preference: PresentPreference, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+    let order: &[wgpu::PresentMode] = match preference {
+        PresentPreference::LowLatency => &[wgpu::PresentMode::Immediate, wgpu::PresentMode::Mailbox],
+        PresentPreference::NoTearing => &[wgpu::PresentMode::Mailbox],
+        PresentPreference::VSync => &[],
+    };
+    order.iter().copied().find(|m| supported.contains(m)).unwrap_or(wgpu::PresentMode::Fifo)
+}
+
+// The monitor's video mode closest to the configured resolution and refresh rate.
+// Unset options prefer the largest resolution and then the highest refresh rate.
+pub fn select_video_mode(monitor: &MonitorHandle) -> Option<VideoMode> {
+    let modes: Vec<VideoMode> = monitor.video_modes().collect();
+    let graphics = &config_file::get().graphics;
+    let size = graphics.resolution
+        .map(|(w, h)| winit::dpi::PhysicalSize::new(w, h))
+        .filter(|s| modes.iter().any(|m| m.size() == *s))
+        .or_else(|| modes.iter().map(|m| m.size()).max_by_key(|s| s.width as u64 * s.height as u64))?;
+
+    let mut candidates: Vec<VideoMode> = modes.into_iter().filter(|m| m.size() == size).collect();
+    candidates.sort_by_key(|m| (m.refresh_rate_millihertz(), m.bit_depth()));
+    match graphics.refresh_hz {
+        Some(hz) => candidates.into_iter().min_by_key(|m| (m.refresh_rate_millihertz() as i64 - hz as i64 * 1000).abs()),
+        None => candidates.pop(),
+    }
+}
+
+// Fullscreen setting for a window mode. Exclusive falls back to borderless when the
+// platform reports no video modes (e.g. Wayland).
+pub fn fullscreen_for(mode: WindowMode, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+    match mode {
+        WindowMode::Windowed => None,
+        WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        WindowMode::Exclusive => match monitor.as_ref().and_then(select_video_mode) {
+            Some(video_mode) => {
+                log::info!("Exclusive fullscreen: {}x{} @ {:.2} Hz", video_mode.size().width, video_mode.size().height, video_mode.refresh_rate_millihertz() as f64 / 1000.0);
+                Some(Fullscreen::Exclusive(video_mode))
+            }
+            None => {
+                log::warn!("No exclusive video modes available, using borderless fullscreen");
+                Some(Fullscreen::Borderless(monitor))
+            }
+        },
+    }
+}
+
+// F11 / Alt+Enter: between windowed (at the configured size) and the configured fullscreen
+// mode, which is borderless when the config asks for a window to start with.
+pub fn toggle_fullscreen(window: &Window) {
+    let graphics = &config_file::get().graphics;
+    if window.fullscreen().is_some() {
+        window.set_fullscreen(None);
+        let (width, height) = graphics.window_size;
+        let _ = window.request_inner_size(winit::dpi::PhysicalSize::new(width, height));
+    } else {
+        let mode = if graphics.window_mode == WindowMode::Windowed { WindowMode::Borderless } else { graphics.window_mode };
+        window.set_fullscreen(fullscreen_for(mode, window.current_monitor()));
+    }
+}
+
+// Refresh rate the window is currently presented at, in Hz.
+pub fn refresh_rate(window: &Window) -> Option<f64> {
+    let millihertz = match window.fullscreen() {
+        Some(Fullscreen::Exclusive(mode)) => mode.refresh_rate_millihertz(),
+        _ => window.current_monitor()?.refresh_rate_millihertz()?,
+    };
+    Some(millihertz as f64 / 1000.0)
+}
+
+// Sleeps out the rest of each frame to hold the configured frame rate.
+pub struct FrameLimiter {
+    frame_time: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl FrameLimiter {
+    // With Fifo the swapchain already paces frames, so a cap at or above the refresh rate
+    // would only add latency and is dropped.
+    pub fn new(present_mode: wgpu::PresentMode, refresh_hz: Option<f64>, frame_limit: Option<u32>) -> Self {
+        let limit = frame_limit.map(|fps| fps as f64).filter(|&fps| {
+            fps > 0.0 && !(present_mode == wgpu::PresentMode::Fifo && refresh_hz.is_some_and(|hz| fps >= hz))
+        });
+        log::info!(
+            "Present mode {:?} at {} Hz, frame limit {}", present_mode,
+            refresh_hz.map_or("unknown".to_string(), |hz| format!("{:.0}", hz)),
+            limit.map_or("off".to_string(), |fps| format!("{:.0} FPS", fps)),
+        );
+        Self { frame_time: limit.map(|fps| Duration::from_secs_f64(1.0 / fps)), next_frame: Instant::now() }
+    }
+
+    pub fn wait(&mut self) {
+        let Some(frame_time) = self.frame_time else { return };
+        let now = Instant::now();
+        if self.next_frame > now {
+            // Sleep most of the gap and spin the last stretch; OS sleeps overshoot.
+            let remaining = self.next_frame - now;
+            if remaining > Duration::from_millis(2) { std::thread::sleep(remaining - Duration::from_millis(2)); }
+            while Instant::now() < self.next_frame { std::hint::spin_loop(); }
+            self.next_frame += frame_time;
+        } else {
+            // Running behind: restart the schedule instead of bursting to catch up.
+            self.next_frame = now + frame_time;
+        }
+    }
+}
+
+// Frame pacing for a window nobody is looking at: unfocused it redraws at BACKGROUND_FPS,
+// minimized or covered not at all, and the loop sleeps in between instead of polling.
+pub struct Visibility {
+    focused: bool,
+    occluded: bool,
+    minimized: bool,
+    next_frame: Instant,
+}
+
+impl Visibility {
+    pub fn new() -> Self {
+        Self { focused: true, occluded: false, minimized: false, next_frame: Instant::now() }
+    }
+
+    // Returns true when focus was lost, so the cursor can be let go.
+    pub fn set_focused(&mut self, focused: bool) -> bool {
+        let lost = self.focused && !focused;
+        if self.focused != focused { log::info!("Window {}", if focused { "focused; full frame rate" } else { "unfocused; redrawing at the background rate" }); }
+        self.focused = focused;
+        lost
+    }
+
+    pub fn set_occluded(&mut self, occluded: bool) {
+        self.occluded = occluded;
+    }
+
+    pub fn resized(&mut self, size: PhysicalSize<u32>) {
+        self.minimized = size.width == 0 || size.height == 0;
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.occluded || self.minimized
+    }
+
+    // Picks how long the loop may sleep; true when a frame is due now.
+    pub fn pace(&mut self, elwt: &EventLoopWindowTarget<()>) -> bool {
+        let now = Instant::now();
+        if self.is_hidden() {
+            elwt.set_control_flow(ControlFlow::WaitUntil(now + Duration::from_millis(config::HIDDEN_WAKE_MS)));
+            return false;
+        }
+        if self.focused {
+            elwt.set_control_flow(ControlFlow::Poll);
+            return true;
+        }
+        let due = now >= self.next_frame;
+        if due { self.next_frame = now + Duration::from_secs_f64(1.0 / config::BACKGROUND_FPS); }
+        elwt.set_control_flow(ControlFlow::WaitUntil(self.next_frame));
+        due
+    }
+}
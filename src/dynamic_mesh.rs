@@ -0,0 +1,100 @@
+// dynamic_mesh.rs
+use glam::{DVec3, Vec2, Vec3};
+use wgpu::util::DeviceExt;
+use crate::vertex::{PackedVertex, UNTEXTURED, Vertex};
+
+// Boxes for things that move every frame (elevator platforms, cars, player avatars), rebuilt
+// each frame and drawn with the chunk pipeline in the scene pass.
+pub struct DynamicMeshes {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    // The chunk pipeline reads a per-instance origin relative to the camera and a fade. Boxes
+    // are in world space, so each view's instance is minus its eye, always fully shown.
+    view_buffer: wgpu::Buffer,
+    capacity: usize,
+    vertices: Vec<PackedVertex>,
+    index_count: u32,
+}
+
+impl DynamicMeshes {
+    pub fn new(device: &wgpu::Device, view_count: usize) -> Self {
+        let capacity = 64;
+        let (vertex_buffer, index_buffer) = Self::create_buffers(device, capacity);
+        let view_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Dynamic Mesh Views"), contents: bytemuck::cast_slice(&vec![[0.0f32, 0.0, 0.0, 1.0]; view_count]),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        Self { vertex_buffer, index_buffer, view_buffer, capacity, vertices: Vec::new(), index_count: 0 }
+    }
+
+    // Room for `capacity` boxes; the index buffer never changes, so it's filled here.
+    fn create_buffers(device: &wgpu::Device, capacity: usize) -> (wgpu::Buffer, wgpu::Buffer) {
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Dynamic Mesh Vertices"), size: (capacity * 24 * std::mem::size_of::<PackedVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+        });
+        let indices: Vec<u32> = (0..capacity as u32 * 6).flat_map(|face| {
+            let base = face * 4;
+            [base, base + 1, base + 2, base, base + 2, base + 3]
+        }).collect();
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Dynamic Mesh Indices"), contents: bytemuck::cast_slice(&indices), usage: wgpu::BufferUsages::INDEX,
+        });
+        (vertex_buffer, index_buffer)
+    }
+
+    // Starts this frame's boxes.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    // Boxes pushed so far this frame, for marking ranges to hide from a view.
+    pub fn box_count(&self) -> usize {
+        self.vertices.len() / 24
+    }
+
+    // Box around `center` from `low` to `high`, `half.x` along the unit vector `axis` and
+    // `half.y` across it, six faces of four vertices each.
+    pub fn push_box(&mut self, center: Vec2, axis: Vec2, half: Vec2, low: f32, high: f32, color: [f32; 3]) {
+        let (u, v) = (axis * half.x, axis.perp() * half.y);
+        let corners = [center - u - v, center + u - v, center + u + v, center - u + v];
+        let mut face = |points: [Vec3; 4], normal: Vec3| {
+            self.vertices.extend(points.iter().map(|p| PackedVertex::from(Vertex { position: p.to_array(), normal: normal.to_array(), color, facade: UNTEXTURED })));
+        };
+        let at = |p: Vec2, y: f32| Vec3::new(p.x, y, p.y);
+        face(corners.map(|p| at(p, high)), Vec3::Y);
+        face(corners.map(|p| at(p, low)), Vec3::NEG_Y);
+        for i in 0..4 {
+            let (a, b) = (corners[i], corners[(i + 1) % 4]);
+            let out = (a + b) * 0.5 - center;
+            face([at(a, low), at(b, low), at(b, high), at(a, high)], Vec3::new(out.x, 0.0, out.y).normalize_or_zero());
+        }
+    }
+
+    // Uploads this frame's boxes and each view's eye; call before the scene pass.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, eyes: &[DVec3]) {
+        let views: Vec<[f32; 4]> = eyes.iter().map(|eye| (-*eye).as_vec3().extend(1.0).to_array()).collect();
+        queue.write_buffer(&self.view_buffer, 0, bytemuck::cast_slice(&views));
+        let boxes = self.vertices.len() / 24;
+        self.index_count = (boxes * 36) as u32;
+        if boxes == 0 { return; }
+        if boxes > self.capacity {
+            self.capacity = boxes.next_power_of_two();
+            (self.vertex_buffer, self.index_buffer) = Self::create_buffers(device, self.capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+    }
+
+    // Expects the chunk pipeline and `view`'s bind groups to be set; rebinds vertex slots 0 and
+    // 1. The boxes in `hidden` are left out, e.g. a first-person player's own avatar.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, view: usize, hidden: std::ops::Range<usize>) {
+        if self.index_count == 0 { return; }
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, self.view_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        let (start, end) = (hidden.start as u32 * 36, hidden.end as u32 * 36);
+        let instance = view as u32..view as u32 + 1;
+        if start > 0 { pass.draw_indexed(0..start.min(self.index_count), 0, instance.clone()); }
+        if end < self.index_count { pass.draw_indexed(end.max(start)..self.index_count, 0, instance); }
+    }
+}
@@ -0,0 +1,69 @@
+// elevator.rs
+use glam::Vec2;
+use crate::{config, dynamic_mesh::DynamicMeshes};
+
+// Platform riding up and down the outside of a building wall, from just above the ground to
+// the roof. It pauses at both ends and moves at ELEVATOR_SPEED in between, timed from the
+// world clock so every client of the physics sees the same height.
+#[derive(Debug, Clone, Copy)]
+pub struct Elevator {
+    pub center: Vec2,
+    // Unit vector along the wall the platform is attached to.
+    pub axis: Vec2,
+    pub bottom: f32,
+    pub top: f32,
+    // Seconds added to the clock so neighbouring elevators don't move in step.
+    phase: f32,
+}
+
+impl Elevator {
+    // Elevator against the wall from `start` to `end`, `along` (0..1) of the way, on the side
+    // `outward` points to, rising to `top`.
+    pub fn on_wall(start: Vec2, end: Vec2, outward: Vec2, along: f32, top: f32, phase: f32) -> Self {
+        let half = config::ELEVATOR_SIZE * 0.5;
+        let center = start.lerp(end, along) + outward * (half + config::ELEVATOR_WALL_GAP);
+        Self { center, axis: (end - start).try_normalize().unwrap_or(Vec2::X), bottom: config::ELEVATOR_THICKNESS, top, phase }
+    }
+
+    fn travel_time(&self) -> f32 {
+        (self.top - self.bottom) / config::ELEVATOR_SPEED
+    }
+
+    // Seconds into the current up-and-down cycle.
+    fn cycle_time(&self, time: f64) -> f32 {
+        let cycle = 2.0 * (self.travel_time() + config::ELEVATOR_PAUSE);
+        (time + self.phase as f64).rem_euclid(cycle as f64) as f32
+    }
+
+    // Height of the platform's top surface at `time` on the world clock.
+    pub fn height(&self, time: f64) -> f32 {
+        let (travel, pause) = (self.travel_time(), config::ELEVATOR_PAUSE);
+        let t = self.cycle_time(time);
+        if t < pause { self.bottom }
+        else if t < pause + travel { self.bottom + (t - pause) * config::ELEVATOR_SPEED }
+        else if t < 2.0 * pause + travel { self.top }
+        else { self.top - (t - 2.0 * pause - travel) * config::ELEVATOR_SPEED }
+    }
+
+    // Vertical speed of the platform at `time`.
+    pub fn velocity(&self, time: f64) -> f32 {
+        let (travel, pause) = (self.travel_time(), config::ELEVATOR_PAUSE);
+        let t = self.cycle_time(time);
+        if t < pause || (pause + travel..2.0 * pause + travel).contains(&t) { 0.0 }
+        else if t < pause + travel { config::ELEVATOR_SPEED }
+        else { -config::ELEVATOR_SPEED }
+    }
+
+    pub fn contains(&self, p: Vec2) -> bool {
+        let local = p - self.center;
+        let half = config::ELEVATOR_SIZE * 0.5;
+        local.dot(self.axis).abs() <= half && local.perp_dot(self.axis).abs() <= half
+    }
+
+    // Adds the platform at `time` on the world clock.
+    pub fn push_mesh(&self, meshes: &mut DynamicMeshes, time: f64) {
+        let height = self.height(time);
+        let half = Vec2::splat(config::ELEVATOR_SIZE * 0.5);
+        meshes.push_box(self.center, self.axis, half, height - config::ELEVATOR_THICKNESS, height, config::ELEVATOR_COLOR);
+    }
+}
@@ -0,0 +1,189 @@
+// facade.rs
+use std::collections::HashMap;
+use wgpu::util::DeviceExt;
+use crate::{config, height::BuildingKind};
+
+// One layer of the facade texture array. Each texel tile covers one window bay of one level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FacadeStyle {
+    Glass,
+    Office,
+    Brick,
+    Concrete,
+    Industrial,
+}
+
+impl FacadeStyle {
+    pub const ALL: [Self; 5] = [Self::Glass, Self::Office, Self::Brick, Self::Concrete, Self::Industrial];
+
+    pub fn for_kind(kind: BuildingKind, levels: f32) -> Self {
+        match kind {
+            BuildingKind::Tower => Self::Glass,
+            BuildingKind::Office | BuildingKind::Commercial if levels >= 10.0 => Self::Glass,
+            BuildingKind::Office | BuildingKind::Commercial => Self::Office,
+            BuildingKind::House | BuildingKind::Residential | BuildingKind::Apartments | BuildingKind::Religious => Self::Brick,
+            BuildingKind::Industrial | BuildingKind::Garage | BuildingKind::Shed => Self::Industrial,
+            BuildingKind::Retail | BuildingKind::Civic | BuildingKind::Unknown => Self::Concrete,
+        }
+    }
+
+    // As map package manifests name the styles.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "glass" => Some(Self::Glass),
+            "office" => Some(Self::Office),
+            "brick" => Some(Self::Brick),
+            "concrete" => Some(Self::Concrete),
+            "industrial" => Some(Self::Industrial),
+            _ => None,
+        }
+    }
+
+    pub fn layer(self) -> f32 {
+        Self::ALL.iter().position(|&s| s == self).unwrap_or(0) as f32
+    }
+
+    // Window rectangle (u0, v0, u1, v1) inside the tile and the wall tint around it.
+    fn window_and_tint(self) -> ([f32; 4], [f32; 3]) {
+        match self {
+            Self::Glass => ([0.06, 0.08, 0.94, 0.96], [0.75, 0.85, 1.0]),
+            Self::Office => ([0.15, 0.25, 0.85, 0.85], [1.0, 1.0, 1.0]),
+            Self::Brick => ([0.30, 0.30, 0.70, 0.80], [1.35, 0.80, 0.62]),
+            Self::Concrete => ([0.20, 0.30, 0.80, 0.80], [1.1, 1.05, 0.95]),
+            Self::Industrial => ([0.10, 0.65, 0.90, 0.80], [0.95, 0.95, 0.9]),
+        }
+    }
+
+    // RGB is the wall tint at half scale (the shader doubles it), alpha the window mask.
+    fn generate(self) -> Vec<u8> {
+        let size = config::FACADE_TEXTURE_SIZE;
+        let ([u0, v0, u1, v1], tint) = self.window_and_tint();
+        let mut texels = Vec::with_capacity((size * size * 4) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                let u = (x as f32 + 0.5) / size as f32;
+                // Texture rows run top-down, levels bottom-up.
+                let v = 1.0 - (y as f32 + 0.5) / size as f32;
+                let window = u > u0 && u < u1 && v > v0 && v < v1;
+                let mortar = self == Self::Brick && (y % 6 == 0 || (x + (y / 6) % 2 * 6) % 12 == 0);
+                let shade = if mortar { 0.8 } else { 1.0 };
+                texels.extend(tint.map(|t| (t * shade * 0.5 * 255.0).min(255.0) as u8));
+                texels.push(if window { 255 } else { 0 });
+            }
+        }
+        texels
+    }
+}
+
+// Reads a map package's facade texture: an RGBA PAM image (Netpbm P7, as GIMP and ImageMagick
+// write it) FACADE_TEXTURE_SIZE texels square, its color the wall and its alpha the window mask.
+// Returns the texels as generate() lays them out.
+pub fn read_override(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let mut rest = bytes.strip_prefix(b"P7\n").ok_or("not a PAM image")?;
+    let mut header = HashMap::new();
+    loop {
+        let end = rest.iter().position(|&b| b == b'\n').ok_or("the header has no ENDHDR")?;
+        let line = std::str::from_utf8(&rest[..end]).map_err(|_| "the header isn't text")?.trim();
+        rest = &rest[end + 1..];
+        if line == "ENDHDR" { break; }
+        if let Some((key, value)) = line.split_once(char::is_whitespace) && !line.starts_with('#') { header.insert(key, value.trim()); }
+    }
+    let size = config::FACADE_TEXTURE_SIZE.to_string();
+    for (key, wanted) in [("WIDTH", size.as_str()), ("HEIGHT", size.as_str()), ("DEPTH", "4"), ("MAXVAL", "255")] {
+        let found = header.get(key).copied();
+        if found != Some(wanted) { return Err(format!("{} is {}, needs to be {}", key, found.unwrap_or("missing"), wanted)); }
+    }
+    let len = (config::FACADE_TEXTURE_SIZE * config::FACADE_TEXTURE_SIZE * 4) as usize;
+    if rest.len() < len { return Err("the image data is cut short".into()); }
+    Ok(rest[..len].chunks_exact(4).flat_map(|t| [t[0] / 2, t[1] / 2, t[2] / 2, t[3]]).collect())
+}
+
+// Box-filters an RGBA8 image down to half size.
+fn downsample(texels: &[u8], size: u32) -> Vec<u8> {
+    let half = size / 2;
+    let mut out = Vec::with_capacity((half * half * 4) as usize);
+    for y in 0..half {
+        for x in 0..half {
+            for c in 0..4 {
+                let at = |dx: u32, dy: u32| texels[(((y * 2 + dy) * size + x * 2 + dx) * 4 + c) as usize] as u32;
+                out.push(((at(0, 0) + at(1, 0) + at(0, 1) + at(1, 1)) / 4) as u8);
+            }
+        }
+    }
+    out
+}
+
+// Procedural window-grid array texture sampled by the scene shader at bind group 1. A map
+// package can replace any style's layer with its own texture.
+pub struct FacadeTextures {
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl FacadeTextures {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let bind_group_layout = Self::layout(device);
+        let bind_group = Self::create_bind_group(device, queue, &bind_group_layout, &[]);
+        Self { bind_group_layout, bind_group }
+    }
+
+    // Also built on the shader worker for the scene pipelines; wgpu treats identical layouts as one.
+    pub fn layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Float { filterable: true }, view_dimension: wgpu::TextureViewDimension::D2Array, multisampled: false }, count: None,
+                },
+                wgpu::BindGroupLayoutEntry { binding: 1, visibility: wgpu::ShaderStages::FRAGMENT, ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering), count: None },
+            ], label: Some("Facade Layout"),
+        })
+    }
+
+    // Rebuilds the texture with `overrides` (texels from read_override) in place of the
+    // generated layers of their styles.
+    pub fn set_overrides(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, overrides: &[(FacadeStyle, Vec<u8>)]) {
+        self.bind_group = Self::create_bind_group(device, queue, &self.bind_group_layout, overrides);
+    }
+
+    fn create_bind_group(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, overrides: &[(FacadeStyle, Vec<u8>)]) -> wgpu::BindGroup {
+        let size = config::FACADE_TEXTURE_SIZE;
+        let mip_level_count = size.ilog2() + 1;
+
+        // Mips are laid out per layer, which is the order create_texture_with_data expects.
+        let mut data = Vec::new();
+        for style in FacadeStyle::ALL {
+            let mut level = overrides.iter().find(|(s, _)| *s == style).map_or_else(|| style.generate(), |(_, texels)| texels.clone());
+            let mut level_size = size;
+            for _ in 0..mip_level_count {
+                data.extend_from_slice(&level);
+                if level_size > 1 {
+                    level = downsample(&level, level_size);
+                    level_size /= 2;
+                }
+            }
+        }
+
+        let texture = device.create_texture_with_data(queue, &wgpu::TextureDescriptor {
+            label: Some("Facade Textures"),
+            size: wgpu::Extent3d { width: size, height: size, depth_or_array_layers: FacadeStyle::ALL.len() as u32 },
+            mip_level_count, sample_count: 1, dimension: wgpu::TextureDimension::D2, format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST, view_formats: &[],
+        }, wgpu::util::TextureDataOrder::LayerMajor, &data);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor { dimension: Some(wgpu::TextureViewDimension::D2Array), ..Default::default() });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Facade Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat, address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear, min_filter: wgpu::FilterMode::Linear, mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 8, ..Default::default()
+        });
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ], label: Some("Facade Bind Group"),
+        })
+    }
+}
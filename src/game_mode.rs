@@ -0,0 +1,215 @@
+// game_mode.rs
+use crate::{config, hud::HudRenderer, player::Player, text::TextRenderer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeKind {
+    FreeRoam,
+    Tag,
+    HideAndSeek,
+}
+
+impl ModeKind {
+    pub fn next(self) -> Self {
+        match self {
+            Self::FreeRoam => Self::Tag,
+            Self::Tag => Self::HideAndSeek,
+            Self::HideAndSeek => Self::FreeRoam,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::FreeRoam => "Free Roam",
+            Self::Tag => "Tag",
+            Self::HideAndSeek => "Hide and Seek",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Playing,
+    Hiding { remaining: f64 },
+    Seeking { remaining: f64 },
+    RoundOver { remaining: f64 },
+}
+
+// Rules for the local players. All state changes happen here, so the host stays the
+// single authority on tags, finds and scores.
+pub struct GameMode {
+    pub kind: ModeKind,
+    pub phase: Phase,
+    // "It" in tag, the seeker in hide and seek.
+    pub hunter: usize,
+    pub scores: Vec<f64>,
+    found: Vec<bool>,
+    tag_cooldown: f64,
+    reveal_timer: f64,
+    pub revealing: bool,
+}
+
+impl GameMode {
+    pub fn new(kind: ModeKind, player_count: usize) -> Self {
+        let phase = match kind {
+            ModeKind::HideAndSeek => Phase::Hiding { remaining: config::HIDE_TIME },
+            _ => Phase::Playing,
+        };
+        Self {
+            kind, phase, hunter: 0,
+            scores: vec![0.0; player_count], found: vec![false; player_count],
+            tag_cooldown: config::TAG_COOLDOWN, reveal_timer: config::REVEAL_INTERVAL, revealing: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.kind != ModeKind::FreeRoam
+    }
+
+    // The seeker may not move while the others hide.
+    pub fn is_frozen(&self, player: usize) -> bool {
+        matches!(self.phase, Phase::Hiding { .. }) && player == self.hunter
+    }
+
+    pub fn update(&mut self, players: &[Player], dt: f64) {
+        if players.len() < 2 { return; }
+        match self.kind {
+            ModeKind::FreeRoam => {}
+            ModeKind::Tag => self.update_tag(players, dt),
+            ModeKind::HideAndSeek => self.update_hide_and_seek(players, dt),
+        }
+    }
+
+    fn update_tag(&mut self, players: &[Player], dt: f64) {
+        self.tag_cooldown -= dt;
+        for i in (0..players.len()).filter(|&i| i != self.hunter) {
+            self.scores[i] += dt;
+        }
+        if self.tag_cooldown > 0.0 { return; }
+
+        let hunter_pos = players[self.hunter].camera.eye;
+        let tagged = (0..players.len())
+            .filter(|&i| i != self.hunter)
+            .find(|&i| players[i].camera.eye.distance(hunter_pos) < config::TAG_DISTANCE);
+        if let Some(i) = tagged {
+            log::info!("Player {} tagged player {}", self.hunter + 1, i + 1);
+            self.hunter = i;
+            self.tag_cooldown = config::TAG_COOLDOWN;
+        }
+    }
+
+    fn update_hide_and_seek(&mut self, players: &[Player], dt: f64) {
+        self.phase = match self.phase {
+            Phase::Hiding { remaining } if remaining - dt <= 0.0 => {
+                self.reveal_timer = config::REVEAL_INTERVAL;
+                Phase::Seeking { remaining: config::SEEK_TIME }
+            }
+            Phase::Hiding { remaining } => Phase::Hiding { remaining: remaining - dt },
+            Phase::Seeking { remaining } => {
+                let seeker_pos = players[self.hunter].camera.eye;
+                for (i, player) in players.iter().enumerate() {
+                    if i == self.hunter || self.found[i] { continue; }
+                    self.scores[i] += dt;
+                    if player.camera.eye.distance(seeker_pos) < config::FIND_DISTANCE {
+                        log::info!("Seeker (player {}) found player {}", self.hunter + 1, i + 1);
+                        self.found[i] = true;
+                        self.scores[self.hunter] += config::FIND_SCORE;
+                    }
+                }
+
+                // Hiders are periodically revealed so rounds can't stall.
+                self.reveal_timer -= dt;
+                self.revealing = self.reveal_timer <= 0.0;
+                if self.reveal_timer <= -config::REVEAL_DURATION { self.reveal_timer = config::REVEAL_INTERVAL; }
+
+                let all_found = (0..players.len()).all(|i| i == self.hunter || self.found[i]);
+                if all_found || remaining - dt <= 0.0 {
+                    self.revealing = false;
+                    log::info!("Round over: scores {:?}", self.scores.iter().map(|s| s.round()).collect::<Vec<_>>());
+                    Phase::RoundOver { remaining: config::ROUND_OVER_TIME }
+                } else {
+                    Phase::Seeking { remaining: remaining - dt }
+                }
+            }
+            Phase::RoundOver { remaining } if remaining - dt <= 0.0 => {
+                self.hunter = (self.hunter + 1) % players.len();
+                self.found.iter_mut().for_each(|f| *f = false);
+                Phase::Hiding { remaining: config::HIDE_TIME }
+            }
+            Phase::RoundOver { remaining } => Phase::RoundOver { remaining: remaining - dt },
+            Phase::Playing => Phase::Hiding { remaining: config::HIDE_TIME },
+        };
+    }
+
+    // Fraction of the current timed phase still left, if the phase is timed.
+    fn phase_fraction(&self) -> Option<f32> {
+        match self.phase {
+            Phase::Playing => None,
+            Phase::Hiding { remaining } => Some((remaining / config::HIDE_TIME) as f32),
+            Phase::Seeking { remaining } => Some((remaining / config::SEEK_TIME) as f32),
+            Phase::RoundOver { remaining } => Some((remaining / config::ROUND_OVER_TIME) as f32),
+        }
+    }
+
+    fn phase_label(&self) -> Option<String> {
+        match self.phase {
+            Phase::Playing => None,
+            Phase::Hiding { remaining } => Some(format!("Hide! {:.0}s", remaining.ceil())),
+            Phase::Seeking { remaining } => Some(format!("Seek {:.0}s", remaining.ceil())),
+            Phase::RoundOver { .. } => Some("Round over".to_string()),
+        }
+    }
+
+    // Role markers per viewport, the phase timer, and the scoreboard when requested.
+    pub fn draw_overlay(&self, hud: &mut HudRenderer, text: &mut TextRenderer, viewports: &[[f32; 4]], screen: [f32; 2], show_scoreboard: bool) {
+        if !self.is_active() || viewports.len() < 2 { return; }
+
+        for (i, &[x, y, w, h]) in viewports.iter().enumerate() {
+            if i == self.hunter {
+                if self.is_frozen(i) {
+                    hud.rect([x, y], [x + w, y + h], [0.0, 0.0, 0.0, 0.92]);
+                }
+                hud.outline([x, y], [x + w, y + h], 6.0, [0.9, 0.15, 0.1, 0.9]);
+            } else if self.revealing && !self.found[i] {
+                hud.outline([x, y], [x + w, y + h], 6.0, [1.0, 0.85, 0.1, 0.9]);
+            }
+        }
+
+        if let Some(fraction) = self.phase_fraction() {
+            let bar_w = 300.0;
+            let x0 = (screen[0] - bar_w) * 0.5;
+            hud.rect([x0, 12.0], [x0 + bar_w, 18.0], [0.1, 0.1, 0.1, 0.8]);
+            hud.rect([x0, 12.0], [x0 + bar_w * fraction.clamp(0.0, 1.0), 18.0], [1.0, 1.0, 1.0, 0.9]);
+        }
+        if let Some(label) = self.phase_label() {
+            text.text_centered(&label, screen[0] * 0.5, 22.0, 18.0, [1.0, 1.0, 1.0, 0.9]);
+        }
+
+        if show_scoreboard || matches!(self.phase, Phase::RoundOver { .. }) {
+            self.draw_scoreboard(hud, text, screen);
+        }
+    }
+
+    fn draw_scoreboard(&self, hud: &mut HudRenderer, text: &mut TextRenderer, screen: [f32; 2]) {
+        let row_h = 28.0;
+        let panel_w = 360.0;
+        let panel_h = 24.0 + row_h * self.scores.len() as f32;
+        let x0 = (screen[0] - panel_w) * 0.5;
+        let y0 = (screen[1] - panel_h) * 0.5;
+        hud.rect([x0, y0], [x0 + panel_w, y0 + panel_h], [0.0, 0.0, 0.0, 0.75]);
+
+        let best = self.scores.iter().cloned().fold(1.0, f64::max);
+        for (i, score) in self.scores.iter().enumerate() {
+            let y = y0 + 12.0 + row_h * i as f32;
+            let color = config::PLAYER_COLORS[i % config::PLAYER_COLORS.len()];
+            hud.rect([x0 + 12.0, y + 4.0], [x0 + 32.0, y + row_h - 4.0], color);
+            if i == self.hunter {
+                hud.outline([x0 + 8.0, y], [x0 + 36.0, y + row_h], 2.0, [0.9, 0.15, 0.1, 1.0]);
+            }
+            let bar_max = panel_w - 112.0;
+            let bar_w = bar_max * (*score / best) as f32;
+            hud.rect([x0 + 44.0, y + 8.0], [x0 + 44.0 + bar_max, y + row_h - 8.0], [0.15, 0.15, 0.15, 1.0]);
+            hud.rect([x0 + 44.0, y + 8.0], [x0 + 44.0 + bar_w, y + row_h - 8.0], color);
+            text.text(&format!("{:.0}", score), [x0 + 56.0 + bar_max, y + 5.0], 18.0, [1.0, 1.0, 1.0, 1.0]);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// gamepad.rs
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+use glam::Vec2;
+use crate::{camera::CameraController, config};
+
+// Feeds the first connected gamepad into a player's controller.
+pub struct GamepadInput {
+    gilrs: Gilrs,
+    active: Option<GamepadId>,
+}
+
+impl GamepadInput {
+    pub fn new() -> Option<Self> {
+        let gilrs = Gilrs::new().map_err(|e| log::warn!("Gamepad support unavailable: {}", e)).ok()?;
+        let active = gilrs.gamepads().next().map(|(id, _)| id);
+        Some(Self { gilrs, active })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.active.is_some()
+    }
+
+    // Drains pending events and returns true if a gamepad was newly connected.
+    pub fn poll(&mut self, controller: &mut CameraController) -> bool {
+        let mut connected = false;
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::Connected => {
+                    if self.active.is_none() { self.active = Some(event.id); }
+                    connected = true;
+                }
+                EventType::Disconnected if self.active == Some(event.id) => {
+                    self.active = None;
+                    controller.move_axis = Vec2::ZERO;
+                    controller.look_axis = Vec2::ZERO;
+                    controller.jump = false;
+                    controller.sprint = false;
+                    controller.crouch = false;
+                    controller.grapple = false;
+                    controller.interact = false;
+                    controller.zoom = false;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(id) = self.active {
+            let pad = self.gilrs.gamepad(id);
+            controller.move_axis = deadzone(Vec2::new(pad.value(Axis::LeftStickX), pad.value(Axis::LeftStickY)));
+            controller.look_axis = deadzone(Vec2::new(pad.value(Axis::RightStickX), pad.value(Axis::RightStickY)));
+            controller.jump = pad.is_pressed(Button::South);
+            controller.sprint = pad.is_pressed(Button::LeftThumb);
+            controller.crouch = pad.is_pressed(Button::East);
+            controller.grapple = pad.is_pressed(Button::RightTrigger2);
+            controller.interact = pad.is_pressed(Button::North);
+            controller.zoom = pad.is_pressed(Button::LeftTrigger2);
+        }
+        connected
+    }
+}
+
+fn deadzone(v: Vec2) -> Vec2 {
+    let len = v.length();
+    if len < config::STICK_DEADZONE { return Vec2::ZERO; }
+    // Rescale so output starts at 0 right outside the deadzone.
+    v / len * ((len - config::STICK_DEADZONE) / (1.0 - config::STICK_DEADZONE)).min(1.0)
+}
@@ -0,0 +1,79 @@
+// gpu_budget.rs
+use crate::{config, config_file};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Allocation {
+    ChunkMeshes,
+    RenderTargets,
+    ShadowMaps,
+}
+
+// Quality given up, in this order, while memory stays over the high water mark with no chunk
+// meshes left to evict, or when the surface reports it's out of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Degradation {
+    // Draw simplified meshes from closer in.
+    LodDistance,
+    // Halve the shadow cascades' resolution.
+    ShadowSize,
+    // Lower the scene targets' resolution cap, which the water reflections trace as well.
+    SceneScale,
+}
+
+const LADDER: [Degradation; 6] = [
+    Degradation::LodDistance, Degradation::ShadowSize, Degradation::SceneScale,
+    Degradation::ShadowSize, Degradation::SceneScale, Degradation::ShadowSize,
+];
+
+// Estimated VRAM usage against a budget derived from the adapter. wgpu can't report
+// free video memory, so the budget is a per-device-type guess that graphics.gpu_budget_mb can
+// override.
+pub struct GpuBudget {
+    pub budget: u64,
+    chunk_bytes: u64,
+    target_bytes: u64,
+    shadow_bytes: u64,
+    // Steps of LADDER taken so far.
+    steps: usize,
+    // Multiplies graphics.lod_distance.
+    pub lod_scale: f32,
+}
+
+impl GpuBudget {
+    pub fn from_adapter(info: &wgpu::AdapterInfo) -> Self {
+        let mb = config_file::get().graphics.gpu_budget_mb.unwrap_or(match info.device_type {
+            wgpu::DeviceType::DiscreteGpu => config::GPU_BUDGET_DISCRETE_MB,
+            _ => config::GPU_BUDGET_INTEGRATED_MB,
+        });
+        log::info!("GPU memory budget: {} MB ({:?} \"{}\")", mb, info.device_type, info.name);
+        Self { budget: mb * 1024 * 1024, chunk_bytes: 0, target_bytes: 0, shadow_bytes: 0, steps: 0, lod_scale: 1.0 }
+    }
+
+    // The next quality step to give up, or None once all of them have been.
+    pub fn next_degradation(&mut self) -> Option<Degradation> {
+        let step = LADDER.get(self.steps).copied();
+        self.steps += 1;
+        step
+    }
+
+    pub fn set(&mut self, kind: Allocation, bytes: u64) {
+        match kind {
+            Allocation::ChunkMeshes => self.chunk_bytes = bytes,
+            Allocation::RenderTargets => self.target_bytes = bytes,
+            Allocation::ShadowMaps => self.shadow_bytes = bytes,
+        }
+    }
+
+    pub fn used(&self) -> u64 {
+        self.chunk_bytes + self.target_bytes + self.shadow_bytes
+    }
+
+    pub fn is_over_high_water(&self) -> bool {
+        self.used() as f64 > self.budget as f64 * config::GPU_BUDGET_HIGH_WATER
+    }
+
+    // Degradation stops once usage drops below this.
+    pub fn low_water_bytes(&self) -> u64 {
+        (self.budget as f64 * config::GPU_BUDGET_LOW_WATER) as u64
+    }
+}
@@ -0,0 +1,192 @@
+// height.rs
+use std::collections::HashMap;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use crate::config;
+
+// Coarse classification of the OSM `building=*` value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildingKind {
+    Shed,
+    Garage,
+    House,
+    Residential,
+    Apartments,
+    Retail,
+    Commercial,
+    Office,
+    Industrial,
+    Civic,
+    Religious,
+    Tower,
+    Unknown,
+}
+
+impl BuildingKind {
+    pub fn from_tag(value: &str) -> Self {
+        match value {
+            "shed" | "hut" | "cabin" | "kiosk" | "roof" | "carport" | "greenhouse" => Self::Shed,
+            "garage" | "garages" => Self::Garage,
+            "house" | "detached" | "semidetached_house" | "bungalow" | "farm" | "static_caravan" => Self::House,
+            "residential" | "terrace" | "townhouse" => Self::Residential,
+            "apartments" | "dormitory" | "hotel" => Self::Apartments,
+            "retail" | "supermarket" | "store" => Self::Retail,
+            "commercial" => Self::Commercial,
+            "office" => Self::Office,
+            "industrial" | "warehouse" | "factory" | "manufacture" => Self::Industrial,
+            "school" | "university" | "college" | "hospital" | "public" | "civic" | "government" | "train_station" | "transportation" => Self::Civic,
+            "church" | "cathedral" | "chapel" | "mosque" | "synagogue" | "temple" => Self::Religious,
+            "skyscraper" | "tower" => Self::Tower,
+            _ => Self::Unknown,
+        }
+    }
+
+    // Typical level range (min, max) for this kind of building.
+    fn level_range(self) -> (f32, f32) {
+        match self {
+            Self::Shed => (1.0, 1.0),
+            Self::Garage => (1.0, 1.0),
+            Self::House => (1.0, 3.0),
+            Self::Residential => (2.0, 5.0),
+            Self::Apartments => (4.0, 12.0),
+            Self::Retail => (1.0, 3.0),
+            Self::Commercial => (2.0, 8.0),
+            Self::Office => (5.0, 20.0),
+            Self::Industrial => (1.0, 3.0),
+            Self::Civic => (2.0, 6.0),
+            Self::Religious => (2.0, 5.0),
+            Self::Tower => (20.0, 50.0),
+            Self::Unknown => (1.0, 6.0),
+        }
+    }
+}
+
+// Parses OSM height values such as "12", "12.5 m" or "40'" into meters.
+pub fn parse_height(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let end = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let number = value[..end].parse::<f32>().ok()?;
+    let unit = value[end..].trim();
+    let meters = if unit.starts_with('\'') || unit.starts_with("ft") { number * 0.3048 } else { number };
+    (meters > 0.0).then_some(meters)
+}
+
+pub fn parse_levels(value: &str) -> Option<f32> {
+    let levels = value.trim().split([';', ',']).next()?.trim().parse::<f32>().ok()?;
+    (levels > 0.0).then_some(levels)
+}
+
+pub fn levels_to_height(levels: f32) -> f32 {
+    levels * config::LEVEL_HEIGHT + config::ROOF_ALLOWANCE
+}
+
+// Shoelace area of a closed footprint in square meters.
+pub fn footprint_area(points: &[glam::Vec2]) -> f32 {
+    let mut sum = 0.0;
+    for i in 0..points.len() {
+        let p1 = points[i];
+        let p2 = points[(i + 1) % points.len()];
+        sum += p1.x * p2.y - p2.x * p1.y;
+    }
+    (sum * 0.5).abs()
+}
+
+// Height statistics of the buildings around one building that carry real height data.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NeighbourhoodStats {
+    pub median: Option<f32>,
+    pub sample_count: usize,
+}
+
+impl NeighbourhoodStats {
+    pub fn from_known(mut heights: Vec<f32>) -> Self {
+        if heights.is_empty() { return Self::default(); }
+        heights.sort_unstable_by(|a, b| a.total_cmp(b));
+        Self { median: Some(heights[heights.len() / 2]), sample_count: heights.len() }
+    }
+}
+
+// Guesses a plausible height for buildings without `height` or `building:levels` tags.
+// Heights a map package lists by way id win over both the tags and the guess.
+pub struct HeightEstimator {
+    seed: u64,
+    overrides: HashMap<i64, f32>,
+}
+
+impl HeightEstimator {
+    pub fn new(seed: u64, overrides: HashMap<i64, f32>) -> Self {
+        Self { seed, overrides }
+    }
+
+    pub fn override_for(&self, id: i64) -> Option<f32> {
+        self.overrides.get(&id).copied()
+    }
+
+    // Seeded per building so the result doesn't depend on parse order.
+    pub fn estimate(&self, id: i64, kind: BuildingKind, area: f32, stats: &NeighbourhoodStats) -> f32 {
+        let mut rng = StdRng::seed_from_u64(self.seed ^ (id as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        let (mut min_levels, mut max_levels) = kind.level_range();
+
+        // Footprint area narrows the range: tiny footprints are never towers,
+        // huge footprints of unknown type are usually warehouses or malls.
+        if area < 40.0 {
+            max_levels = max_levels.min(1.0);
+        } else if area < 150.0 {
+            max_levels = max_levels.min(3.0);
+        } else if kind == BuildingKind::Unknown && area > 5000.0 {
+            min_levels = 1.0;
+            max_levels = 3.0;
+        } else if kind == BuildingKind::Unknown && area > 800.0 {
+            min_levels = min_levels.max(3.0);
+        }
+        min_levels = min_levels.min(max_levels);
+
+        let levels = rng.gen_range(min_levels..=max_levels).round();
+        let mut height = levels_to_height(levels);
+
+        // Pull untyped buildings toward their neighbours so blocks read as consistent.
+        if let Some(median) = stats.median && stats.sample_count >= config::HEIGHT_MIN_NEIGHBOURS {
+            let weight = if kind == BuildingKind::Unknown { 0.6 } else { 0.25 };
+            height += (median - height) * weight;
+        }
+
+        let jitter = rng.gen_range(-config::HEIGHT_JITTER..=config::HEIGHT_JITTER);
+        (height + jitter).max(config::LEVEL_HEIGHT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_heights_in_meters_and_feet() {
+        assert_eq!(parse_height("12"), Some(12.0));
+        assert_eq!(parse_height(" 12.5 m"), Some(12.5));
+        assert_eq!(parse_height("12.5m"), Some(12.5));
+        assert!((parse_height("40'").unwrap() - 12.192).abs() < 1e-4);
+        assert!((parse_height("40 ft").unwrap() - 12.192).abs() < 1e-4);
+        assert_eq!(parse_height("tall"), None);
+        assert_eq!(parse_height(""), None);
+        assert_eq!(parse_height("0"), None);
+    }
+
+    #[test]
+    fn parses_levels() {
+        assert_eq!(parse_levels("4"), Some(4.0));
+        assert_eq!(parse_levels(" 3;5 "), Some(3.0));
+        assert_eq!(parse_levels("2,4"), Some(2.0));
+        assert_eq!(parse_levels("0"), None);
+        assert_eq!(parse_levels("many"), None);
+    }
+
+    #[test]
+    fn same_seed_guesses_the_same() {
+        let stats = NeighbourhoodStats::from_known(vec![12.0, 15.0, 20.0, 30.0]);
+        let (a, b) = (HeightEstimator::new(7, HashMap::new()), HeightEstimator::new(7, HashMap::new()));
+        for (id, kind, area) in [(1, BuildingKind::House, 120.0), (2, BuildingKind::Office, 900.0), (3, BuildingKind::Unknown, 6000.0)] {
+            let height = a.estimate(id, kind, area, &stats);
+            assert_eq!(height, b.estimate(id, kind, area, &stats));
+            assert!(height >= config::LEVEL_HEIGHT);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// highlight.rs
+use std::time::Instant;
+use wgpu::util::DeviceExt;
+use crate::{camera::Camera, config, mesh_arena::MeshArena, post, shader, shader_cache, vertex::PackedVertex, world::World};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct HighlightUniform {
+    // rgb: tint, a: strength.
+    color: [f32; 4],
+    // x: seconds since start.
+    params: [f32; 4],
+}
+
+// Picks the building under each player's crosshair and draws its roof and walls again in
+// the scene pass as an additive glow. Depth is tested but not written, so only the visible
+// faces light up.
+pub struct BuildingHighlight {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform: HighlightUniform,
+    uniform_buffer: wgpu::Buffer,
+    start: Instant,
+    // Per view: (page, first index, index count, base vertex) of the picked building.
+    targets: Vec<Option<(usize, u32, u32, i32)>>,
+    // Per view: origin of the picked building's chunk mesh, uploaded relative to the view's eye.
+    origins: Vec<glam::DVec3>,
+    origin_buffer: wgpu::Buffer,
+}
+
+impl BuildingHighlight {
+    pub fn new(device: &wgpu::Device, camera_layout: &wgpu::BindGroupLayout, view_count: usize) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None,
+            }],
+            label: Some("Highlight Layout"),
+        });
+        let module = shader_cache::module(device, "Highlight Shader", shader::HIGHLIGHT_SHADER);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[camera_layout, &layout], push_constant_ranges: &[] });
+        let additive = wgpu::BlendComponent { src_factor: wgpu::BlendFactor::One, dst_factor: wgpu::BlendFactor::One, operation: wgpu::BlendOperation::Add };
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Highlight Pipeline"), layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module, entry_point: "vs_main",
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<PackedVertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &[
+                            wgpu::VertexAttribute { offset: 0,  shader_location: 0, format: wgpu::VertexFormat::Float32x3 },
+                            wgpu::VertexAttribute { offset: 12, shader_location: 1, format: wgpu::VertexFormat::Snorm8x4 },
+                        ],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: 16, step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &[wgpu::VertexAttribute { offset: 0, shader_location: 2, format: wgpu::VertexFormat::Float32x3 }],
+                    },
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module, entry_point: "fs_main",
+                // The normal target keeps the building's own normals for SSAO.
+                targets: &[
+                    Some(wgpu::ColorTargetState { format: post::HDR_FORMAT, blend: Some(wgpu::BlendState { color: additive, alpha: additive }), write_mask: wgpu::ColorWrites::ALL }),
+                    Some(wgpu::ColorTargetState { format: post::NORMAL_FORMAT, blend: None, write_mask: wgpu::ColorWrites::empty() }),
+                ],
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, cull_mode: None, ..Default::default() },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float, depth_write_enabled: false, depth_compare: wgpu::CompareFunction::LessEqual, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState { count: post::scene_samples(), mask: !0, alpha_to_coverage_enabled: false },
+            multiview: None,
+        });
+
+        let [r, g, b] = config::HIGHLIGHT_COLOR;
+        let uniform = HighlightUniform { color: [r, g, b, config::HIGHLIGHT_STRENGTH], params: [0.0; 4] };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Highlight Uniform"), contents: bytemuck::cast_slice(&[uniform]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout, entries: &[wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() }], label: None,
+        });
+        let origin_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Highlight Origins"), size: view_count as u64 * 16, usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+        });
+        Self { pipeline, bind_group, uniform, uniform_buffer, start: Instant::now(), targets: vec![None; view_count], origins: vec![glam::DVec3::ZERO; view_count], origin_buffer }
+    }
+
+    // Picks the building the view's camera looks at. Buildings whose chunk mesh is evicted
+    // can still be hit but aren't drawn.
+    pub fn update(&mut self, view: usize, camera: &Camera, world: &World) {
+        self.targets[view] = None;
+        if !config::BUILDING_HIGHLIGHT { return; }
+        let Some(building) = world.raycast(camera.eye.as_vec3(), camera.forward().as_vec3(), config::HIGHLIGHT_DISTANCE).and_then(|hit| hit.building) else { return };
+        let chunk = &world.chunks[&building.coord];
+        if let Some(mesh) = &chunk.mesh {
+            self.targets[view] = Some(mesh.draw(chunk.buildings[building.index].indices.clone()));
+            self.origins[view] = chunk.origin();
+        }
+    }
+
+    pub fn clear(&mut self, view: usize) {
+        self.targets[view] = None;
+    }
+
+    // Uploads the pulse time and origins; call before the scene pass. `eyes` are the views'
+    // camera positions this frame.
+    pub fn prepare(&mut self, queue: &wgpu::Queue, eyes: &[glam::DVec3]) {
+        if self.targets.iter().all(Option::is_none) { return; }
+        self.uniform.params[0] = self.start.elapsed().as_secs_f32();
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+        let origins: Vec<[f32; 4]> = self.origins.iter().zip(eyes).map(|(origin, eye)| (*origin - *eye).as_vec3().extend(0.0).to_array()).collect();
+        queue.write_buffer(&self.origin_buffer, 0, bytemuck::cast_slice(&origins));
+    }
+
+    // Expects the view's camera bind group at group 0; rebinds group 1.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, arena: &'a MeshArena, view: usize) {
+        let Some((page, first_index, index_count, base_vertex)) = self.targets[view] else { return };
+        let page = arena.page(page);
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(1, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, page.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, self.origin_buffer.slice(..));
+        pass.set_index_buffer(page.index_buffer.slice(..), page.index_format);
+        let instance = view as u32;
+        pass.draw_indexed(first_index..first_index + index_count, base_vertex, instance..instance + 1);
+    }
+}
@@ -0,0 +1,107 @@
+// hud.rs
+use wgpu::util::DeviceExt;
+use crate::{shader, shader_cache};
+
+// Screen-space rectangle in pixels, drawn as one instanced quad.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct HudRect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    pub color: [f32; 4],
+}
+
+// Collects flat-colored rects each frame and draws them on top of the scene.
+pub struct HudRenderer {
+    pipeline: wgpu::RenderPipeline,
+    screen_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    instance_buffer: wgpu::Buffer,
+    capacity: usize,
+    rects: Vec<HudRect>,
+    drawn: u32,
+}
+
+impl HudRenderer {
+    // `depth_format` must match the depth attachment of the pass drawing the HUD, if it has one.
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, depth_format: Option<wgpu::TextureFormat>) -> Self {
+        let shader = shader_cache::module(device, "HUD Shader", shader::HUD_SHADER);
+        let screen_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("HUD Screen"), contents: bytemuck::cast_slice(&[[1.0f32; 4]]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry { binding: 0, visibility: wgpu::ShaderStages::VERTEX, ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None }], label: None,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor { layout: &bind_group_layout, entries: &[wgpu::BindGroupEntry { binding: 0, resource: screen_buffer.as_entire_binding() }], label: None });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[&bind_group_layout], push_constant_ranges: &[] });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("HUD Pipeline"), layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader, entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<HudRect>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &[
+                        wgpu::VertexAttribute { offset: 0,  shader_location: 0, format: wgpu::VertexFormat::Float32x2 },
+                        wgpu::VertexAttribute { offset: 8,  shader_location: 1, format: wgpu::VertexFormat::Float32x2 },
+                        wgpu::VertexAttribute { offset: 16, shader_location: 2, format: wgpu::VertexFormat::Float32x4 },
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader, entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState { format, blend: Some(wgpu::BlendState::ALPHA_BLENDING), write_mask: wgpu::ColorWrites::ALL })],
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleStrip, ..Default::default() },
+            depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                format, depth_write_enabled: false, depth_compare: wgpu::CompareFunction::Always, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState { count: sample_count, mask: !0, alpha_to_coverage_enabled: false },
+            multiview: None,
+        });
+        let capacity = 256;
+        let instance_buffer = Self::create_instance_buffer(device, capacity);
+        Self { pipeline, screen_buffer, bind_group, instance_buffer, capacity, rects: Vec::new(), drawn: 0 }
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HUD Rects"), size: (capacity * std::mem::size_of::<HudRect>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+        })
+    }
+
+    pub fn rect(&mut self, min: [f32; 2], max: [f32; 2], color: [f32; 4]) {
+        self.rects.push(HudRect { min, max, color });
+    }
+
+    // Hollow rectangle of the given border thickness.
+    pub fn outline(&mut self, min: [f32; 2], max: [f32; 2], thickness: f32, color: [f32; 4]) {
+        self.rect(min, [max[0], min[1] + thickness], color);
+        self.rect([min[0], max[1] - thickness], max, color);
+        self.rect([min[0], min[1] + thickness], [min[0] + thickness, max[1] - thickness], color);
+        self.rect([max[0] - thickness, min[1] + thickness], [max[0], max[1] - thickness], color);
+    }
+
+    // Uploads this frame's rects; call before the render pass that draws them.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, screen_size: [f32; 2]) {
+        if self.rects.len() > self.capacity {
+            self.capacity = self.rects.len().next_power_of_two();
+            self.instance_buffer = Self::create_instance_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&[[screen_size[0], screen_size[1], 0.0, 0.0]]));
+        if !self.rects.is_empty() {
+            queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.rects));
+        }
+        self.drawn = self.rects.len() as u32;
+        self.rects.clear();
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        if self.drawn == 0 { return; }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        pass.draw(0..4, 0..self.drawn);
+    }
+}
@@ -0,0 +1,46 @@
+// labels.rs
+use glam::{Vec2, Vec3};
+use crate::{camera::Camera, config, hud::HudRenderer, poi::PoiIndex, text::TextRenderer, world::World};
+
+// Names of landmark buildings around the camera, drawn facing the screen above their roofs and
+// fading out toward LABEL_RADIUS. Nearer names go first and a label overlapping one already
+// placed is dropped, so dense blocks show a few readable names instead of a pile; names hidden
+// behind other buildings aren't drawn.
+pub fn draw(hud: &mut HudRenderer, text: &mut TextRenderer, places: &PoiIndex, world: &World, camera: &Camera, viewport: [f32; 4]) {
+    let eye = camera.eye.as_vec3();
+    let mut candidates: Vec<(f32, &str, Vec3)> = places.buildings_near(Vec2::new(eye.x, eye.z), config::LABEL_RADIUS)
+        .filter(|(_, c)| world.chunks.contains_key(&World::chunk_coord_at(c.x, c.y)))
+        .map(|(name, c)| {
+            // Centroids of L-shaped footprints can miss the roof.
+            let roof = world.roof_at(c.x, c.y, f32::INFINITY).unwrap_or(config::LEVEL_HEIGHT);
+            let anchor = Vec3::new(c.x, roof + config::LABEL_HEIGHT, c.y);
+            (anchor.distance(eye), name, anchor)
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let view_proj = camera.build_relative_view_projection_matrix();
+    let [x, y, w, h] = viewport;
+    let (near_size, far_size) = config::LABEL_SIZE;
+    let mut placed: Vec<[f32; 4]> = Vec::new();
+    for (distance, name, anchor) in candidates {
+        if placed.len() >= config::LABEL_MAX { break; }
+        let clip = view_proj * (anchor.as_dvec3() - camera.eye).as_vec3().extend(1.0);
+        if clip.w <= 0.0 { continue; }
+        let ndc = clip.truncate() / clip.w;
+        if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 { continue; }
+
+        let size = near_size + (far_size - near_size) * (distance / config::LABEL_RADIUS).min(1.0);
+        let half_width = text.measure(name, size) * 0.5 + 4.0;
+        let [cx, cy] = [x + (ndc.x * 0.5 + 0.5) * w, y + (0.5 - ndc.y * 0.5) * h];
+        let rect = [cx - half_width, cy - size - 6.0, cx + half_width, cy];
+        if placed.iter().any(|r| rect[0] < r[2] && rect[2] > r[0] && rect[1] < r[3] && rect[3] > r[1]) { continue; }
+        if distance > 1.0 && world.raycast(eye, (anchor - eye) / distance, distance - 1.0).is_some() { continue; }
+        placed.push(rect);
+
+        let fade = (distance - config::LABEL_FADE_START) / (config::LABEL_RADIUS - config::LABEL_FADE_START);
+        let alpha = 1.0 - fade.clamp(0.0, 1.0);
+        hud.rect([rect[0], rect[1]], [rect[2], rect[3]], [0.0, 0.0, 0.0, 0.45 * alpha]);
+        text.text_centered(name, cx, rect[1] + 3.0, size, [1.0, 1.0, 1.0, alpha]);
+    }
+}
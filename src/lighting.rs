@@ -0,0 +1,160 @@
+// lighting.rs
+use glam::Vec3;
+use wgpu::util::DeviceExt;
+use crate::{camera::Camera, config, config_file, shader, shader_cache, world::World};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PointLight {
+    // xyz: world position, w: radius.
+    position: [f32; 4],
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClusterUniform {
+    view: [[f32; 4]; 4],
+    inv_proj: [[f32; 4]; 4],
+    // x: near, y: far, z: light count, w: max lights per cluster.
+    params: [f32; 4],
+    grid: [u32; 4],
+}
+
+// Lights and cluster lists of one player's viewport, bound at group 3 of the scene pipeline.
+struct ClusterView {
+    uniform: ClusterUniform,
+    uniform_buffer: wgpu::Buffer,
+    light_buffer: wgpu::Buffer,
+    compute_bind_group: wgpu::BindGroup,
+    bind_group: wgpu::BindGroup,
+}
+
+// Clustered forward lighting for street lamps. Each view's frustum is split into a grid of
+// froxels (screen tiles times exponential depth slices); a compute pass lists the lights
+// touching each froxel, and the scene shader only loops over its own froxel's list.
+pub struct ClusteredLights {
+    pipeline: wgpu::ComputePipeline,
+    views: Vec<ClusterView>,
+    bytes: u64,
+}
+
+impl ClusteredLights {
+    fn cluster_count() -> u32 {
+        let [x, y, z] = config::LIGHT_CLUSTER_GRID;
+        x * y * z
+    }
+
+    // What the scene shader reads the light clusters through.
+    pub fn layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding, visibility: wgpu::ShaderStages::FRAGMENT, count: None,
+            ty: wgpu::BindingType::Buffer { ty: if binding == 0 { wgpu::BufferBindingType::Uniform } else { wgpu::BufferBindingType::Storage { read_only: true } }, has_dynamic_offset: false, min_binding_size: None },
+        };
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { entries: &[entry(0), entry(1), entry(2), entry(3)], label: Some("Light Cluster Layout") })
+    }
+
+    pub fn new(device: &wgpu::Device, view_count: usize) -> Self {
+        let entry = |binding, visibility, ty| wgpu::BindGroupLayoutEntry { binding, visibility, ty, count: None };
+        let uniform = wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None };
+        let storage = |read_only| wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only }, has_dynamic_offset: false, min_binding_size: None };
+        let bind_group_layout = Self::layout(device);
+        let compute_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                entry(0, wgpu::ShaderStages::COMPUTE, uniform),
+                entry(1, wgpu::ShaderStages::COMPUTE, storage(true)),
+                entry(2, wgpu::ShaderStages::COMPUTE, storage(false)),
+                entry(3, wgpu::ShaderStages::COMPUTE, storage(false)),
+            ], label: Some("Light Binning Layout"),
+        });
+
+        let clusters = Self::cluster_count() as u64;
+        let per_cluster = config::MAX_LIGHTS_PER_CLUSTER as u64;
+        let light_bytes = config::MAX_LIGHTS as u64 * std::mem::size_of::<PointLight>() as u64;
+        let buffer = |label, size, usage| device.create_buffer(&wgpu::BufferDescriptor { label: Some(label), size, usage, mapped_at_creation: false });
+        let views = (0..view_count).map(|_| {
+            let uniform = ClusterUniform {
+                view: glam::Mat4::IDENTITY.to_cols_array_2d(), inv_proj: glam::Mat4::IDENTITY.to_cols_array_2d(),
+                params: [config::Z_NEAR, config::LIGHT_CLUSTER_FAR, 0.0, per_cluster as f32],
+                grid: [config::LIGHT_CLUSTER_GRID[0], config::LIGHT_CLUSTER_GRID[1], config::LIGHT_CLUSTER_GRID[2], 0],
+            };
+            let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Cluster Uniform"), contents: bytemuck::cast_slice(&[uniform]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+            let light_buffer = buffer("Point Lights", light_bytes, wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
+            let counts = buffer("Cluster Light Counts", clusters * 4, wgpu::BufferUsages::STORAGE);
+            let indices = buffer("Cluster Light Indices", clusters * per_cluster * 4, wgpu::BufferUsages::STORAGE);
+            let entries = [
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: light_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: counts.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: indices.as_entire_binding() },
+            ];
+            let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor { layout: &compute_layout, entries: &entries, label: None });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor { layout: &bind_group_layout, entries: &entries, label: None });
+            ClusterView { uniform, uniform_buffer, light_buffer, compute_bind_group, bind_group }
+        }).collect();
+
+        let module = shader_cache::module(device, "Cluster Shader", shader::CLUSTER_SHADER);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[&compute_layout], push_constant_ranges: &[] });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Light Binning Pipeline"), layout: Some(&pipeline_layout), module: &module, entry_point: "cs_main",
+        });
+
+        let bytes = view_count as u64 * (light_bytes + clusters * (per_cluster + 1) * 4);
+        Self { pipeline, views, bytes }
+    }
+
+    pub fn gpu_bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn bind_group(&self, view: usize) -> &wgpu::BindGroup {
+        &self.views[view].bind_group
+    }
+
+    // Uploads the lamps nearest to the camera. Lamps switch on as daylight fades.
+    pub fn update(&mut self, queue: &wgpu::Queue, view: usize, camera: &Camera, world: &World, daylight: f32) {
+        let cluster_view = &mut self.views[view];
+        let strength = (1.0 - daylight).clamp(0.0, 1.0) * config::STREET_LAMP_INTENSITY;
+        let mut lights = Vec::new();
+        if config::STREET_LIGHTS_ENABLED && strength > 0.0 {
+            let eye = camera.eye.as_vec3();
+            let far = config::LIGHT_CLUSTER_FAR + config::STREET_LAMP_RADIUS;
+            let chunk_radius = (config_file::chunk_size() * config_file::chunk_size() * 2.0).sqrt() * 0.5;
+            let mut nearby: Vec<(f32, Vec3)> = world.chunks.values()
+                .filter(|c| c.center().distance(glam::Vec2::new(eye.x, eye.z)) <= far + chunk_radius)
+                .flat_map(|c| c.lights.iter().map(|&p| (p.distance_squared(eye), p)))
+                .filter(|(dist_sq, _)| *dist_sq <= far * far)
+                .collect();
+            if nearby.len() > config::MAX_LIGHTS as usize {
+                nearby.select_nth_unstable_by(config::MAX_LIGHTS as usize, |a, b| a.0.total_cmp(&b.0));
+                nearby.truncate(config::MAX_LIGHTS as usize);
+            }
+            let [r, g, b] = config::STREET_LAMP_COLOR;
+            lights.extend(nearby.into_iter().map(|(_, p)| PointLight {
+                position: [p.x, p.y, p.z, config::STREET_LAMP_RADIUS], color: [r * strength, g * strength, b * strength, 0.0],
+            }));
+        }
+
+        cluster_view.uniform.view = camera.build_view_matrix().to_cols_array_2d();
+        cluster_view.uniform.inv_proj = camera.build_projection_matrix().inverse().to_cols_array_2d();
+        cluster_view.uniform.params[2] = lights.len() as f32;
+        if !lights.is_empty() {
+            queue.write_buffer(&cluster_view.light_buffer, 0, bytemuck::cast_slice(&lights));
+        }
+        queue.write_buffer(&cluster_view.uniform_buffer, 0, bytemuck::cast_slice(&[cluster_view.uniform]));
+    }
+
+    // Bins the lights of the first `view_count` views into their clusters; call before the scene pass.
+    pub fn compute(&self, encoder: &mut wgpu::CommandEncoder, view_count: usize) {
+        let views = &self.views[..view_count];
+        if views.iter().all(|v| v.uniform.params[2] == 0.0) { return; }
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Light Binning"), timestamp_writes: None });
+        pass.set_pipeline(&self.pipeline);
+        for view in views.iter().filter(|v| v.uniform.params[2] > 0.0) {
+            pass.set_bind_group(0, &view.compute_bind_group, &[]);
+            pass.dispatch_workgroups(Self::cluster_count().div_ceil(64), 1, 1);
+        }
+    }
+}
@@ -1,45 +1,122 @@
 // main.rs
 use winit::{
-    event::*, event_loop::{ControlFlow, EventLoop}, window::{WindowBuilder, CursorGrabMode, Fullscreen, Window},
+    event::*, event_loop::{ControlFlow, EventLoop}, window::{WindowBuilder, CursorGrabMode, Window},
     keyboard::{KeyCode, PhysicalKey},
 };
 use wgpu::util::DeviceExt;
-use std::time::Instant;
 use std::sync::mpsc;
 use std::thread;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+mod cli;
 mod config;
+mod config_file;
+mod display;
 mod shader;
+mod shader_cache;
 mod vertex;
+mod bindings;
 mod camera;
+mod capture;
+mod benchmark;
+mod cinematic;
+mod compass;
+mod console;
+mod crosshair;
+mod vignette;
+mod facade;
 mod world;
+mod chunk_fade;
+mod mesh_arena;
+mod mesh_cache;
 mod map_loader;
+mod node_index;
+mod map_package;
+mod menu;
+mod height;
+mod time_of_day;
+mod pause;
+mod player;
+mod hud;
+mod labels;
+mod minimap;
+mod text;
+mod shadow;
+mod post;
+mod preset;
+mod render_scale;
+mod occlusion;
+mod debug;
+mod water;
+mod weather;
+mod highlight;
+mod dynamic_mesh;
+mod elevator;
+mod vehicle;
+mod orbit;
+mod photo;
+mod poi;
+mod notifications;
+mod waypoints;
+mod replay;
+mod settings;
+mod speedometer;
+mod stats_overlay;
+mod streaming;
+mod spawn;
+mod lighting;
+mod game_mode;
+mod gpu_budget;
+mod profiler;
+mod upload;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+#[cfg(feature = "egui")]
+mod dev_ui;
 mod state;
 
+use clap::Parser;
+use cli::Cli;
+use map_package::MapPackage;
+use menu::{MapMenu, MenuAction};
 use state::{GameState, GpuContext};
+use text::TextRenderer;
+use poi::PoiIndex;
+use spawn::SpawnPoint;
+use waypoints::Waypoint;
 use world::LoaderMessage;
 
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct LoadingUniforms {
-    screen_size: [f32; 2], progress: f32, _pad: f32,
+    screen_size: [f32; 2], progress: f32, failed: f32,
+}
+
+// One loader status ("Reading Nodes...", "Meshing...") and how long it has run.
+struct LoadPhase {
+    name: String,
+    started: Instant,
+    // None while it's still the current phase.
+    took: Option<Duration>,
 }
 
 struct LoadingScreen {
     pipeline: wgpu::RenderPipeline,
     uniform_buffer: wgpu::Buffer,
     bind_group: wgpu::BindGroup,
+    text: TextRenderer,
     pub current_progress: f32,
-    pub status_text: String,
+    started: Instant,
+    phases: Vec<LoadPhase>,
+    // Set when the loader reports an error; renders the error screen instead.
+    error: Option<String>,
 }
 
 impl LoadingScreen {
     fn new(ctx: &GpuContext) -> Self {
-        let shader = ctx.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Loading"), source: wgpu::ShaderSource::Wgsl(shader::LOADING_SHADER.into()),
-        });
-        let uniforms = LoadingUniforms { screen_size: [ctx.config.width as f32, ctx.config.height as f32], progress: 0.0, _pad: 0.0 };
+        let shader = shader_cache::module(&ctx.device, "Loading", shader::LOADING_SHADER);
+        let uniforms = LoadingUniforms { screen_size: [ctx.config.width as f32, ctx.config.height as f32], progress: 0.0, failed: 0.0 };
         let uniform_buffer = ctx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None, contents: bytemuck::cast_slice(&[uniforms]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
@@ -54,16 +131,65 @@ impl LoadingScreen {
             fragment: Some(wgpu::FragmentState { module: &shader, entry_point: "fs_main", targets: &[Some(wgpu::ColorTargetState { format: ctx.config.format, blend: Some(wgpu::BlendState::REPLACE), write_mask: wgpu::ColorWrites::ALL })] }),
             primitive: wgpu::PrimitiveState::default(), depth_stencil: None, multisample: wgpu::MultisampleState::default(), multiview: None,
         });
-        Self { pipeline, uniform_buffer, bind_group, current_progress: 0.0, status_text: "Initializing".into() }
+        let text = TextRenderer::new(&ctx.device, ctx.config.format, 1, None);
+        let started = Instant::now();
+        let phases = vec![LoadPhase { name: "Initializing".into(), started, took: None }];
+        Self { pipeline, uniform_buffer, bind_group, text, current_progress: 0.0, started, phases, error: None }
+    }
+
+    // The loader repeats its status while a phase runs; a new one ends the current phase.
+    fn set_status(&mut self, status: String) {
+        if self.phases.last().is_some_and(|p| p.name == status) { return; }
+        self.finish();
+        self.phases.push(LoadPhase { name: status, started: Instant::now(), took: None });
+    }
+
+    // Stops the clock on the current phase.
+    fn finish(&mut self) {
+        if let Some(phase) = self.phases.last_mut() && phase.took.is_none() { phase.took = Some(phase.started.elapsed()); }
+    }
+
+    fn fail(&mut self, error: String) {
+        self.finish();
+        self.error = Some(error);
     }
     
-    fn render(&self, ctx: &mut GpuContext) {
+    fn render(&mut self, ctx: &mut GpuContext) {
         let Ok(output) = ctx.surface.get_current_texture() else { return };
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
         let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
         
-        let uniforms = LoadingUniforms { screen_size: [ctx.config.width as f32, ctx.config.height as f32], progress: self.current_progress, _pad: 0.0 };
+        let failed = self.error.is_some();
+        let uniforms = LoadingUniforms { screen_size: [ctx.config.width as f32, ctx.config.height as f32], progress: self.current_progress, failed: if failed { 1.0 } else { 0.0 } };
         ctx.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+
+        // Headline above the bar; below it the loader status (or the error) with the time so
+        // far, then each phase with how long it took.
+        let [cx, cy] = [uniforms.screen_size[0] * 0.5, uniforms.screen_size[1] * 0.5];
+        let (headline, color) = if failed {
+            ("Load Error".to_string(), [1.0, 0.3, 0.3, 1.0])
+        } else {
+            (format!("Loading {}%", (self.current_progress.clamp(0.0, 1.0) * 100.0) as u32), [1.0, 1.0, 1.0, 1.0])
+        };
+        self.text.text_centered(&headline, cx, cy - 40.0, 24.0, color);
+        let status = match &self.error {
+            Some(error) => error.clone(),
+            None => format!("{}  {:.1} s", self.phases.last().map_or("", |p| p.name.as_str()), self.started.elapsed().as_secs_f32()),
+        };
+        self.text.text_centered(&status, cx, cy + 14.0, 16.0, [0.6, 0.6, 0.6, 1.0]);
+        let (left, right) = (cx - 150.0, cx + 150.0);
+        for (i, phase) in self.phases.iter().enumerate() {
+            let y = cy + 48.0 + i as f32 * 20.0;
+            let color = if phase.took.is_some() { [0.45, 0.45, 0.45, 1.0] } else { [0.9, 0.9, 0.9, 1.0] };
+            let time = format!("{:.2} s", phase.took.unwrap_or_else(|| phase.started.elapsed()).as_secs_f32());
+            self.text.text(phase.name.trim_end_matches("..."), [left, y], 14.0, color);
+            self.text.text(&time, [right - self.text.measure(&time, 14.0), y], 14.0, color);
+        }
+        if let Some((done, total)) = shader_cache::progress() {
+            let y = cy + 48.0 + self.phases.len() as f32 * 20.0 + 10.0;
+            self.text.text(&format!("Compiling shaders and pipelines {} / {}", done, total), [left, y], 14.0, [0.6, 0.6, 0.6, 1.0]);
+        }
+        self.text.prepare(&ctx.device, &ctx.queue, uniforms.screen_size);
         
         {
             let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -73,6 +199,7 @@ impl LoadingScreen {
             pass.set_pipeline(&self.pipeline);
             pass.set_bind_group(0, &self.bind_group, &[]);
             pass.draw(0..4, 0..1);
+            self.text.draw(&mut pass);
         }
         ctx.queue.submit(std::iter::once(encoder.finish()));
         output.present();
@@ -84,28 +211,23 @@ fn set_cursor_grab(window: &Window, grabbed: bool) {
     else { let _ = window.set_cursor_grab(CursorGrabMode::None); window.set_cursor_visible(true); }
 }
 
-fn main() {
-    env_logger::init();
-    let event_loop = EventLoop::new().unwrap();
-    event_loop.set_control_flow(ControlFlow::Poll);
-    
-    let builder = WindowBuilder::new().with_title(config::WINDOW_TITLE);
-    let monitor = event_loop.primary_monitor();
-    let window = Arc::new(builder.with_fullscreen(Some(Fullscreen::Borderless(monitor))).build(&event_loop).unwrap());
-    
-    let mut gpu_ctx_opt = Some(pollster::block_on(GpuContext::new(window.clone())));
-    let mut loading_screen = LoadingScreen::new(gpu_ctx_opt.as_ref().unwrap());
-
-    // Threading setup
-    let (tx, rx) = mpsc::channel();
-    // Clone for the thread
-    let tx_thread = tx.clone();
-    
+// Returns where to send the chunks the game wants meshed again after unloading them; the
+// loader thread serves those once the map is in, until the sender is dropped.
+fn spawn_loader(package: &MapPackage, tx: mpsc::Sender<LoaderMessage>) -> mpsc::Sender<Vec<(i32, i32)>> {
+    log::info!("Loading map package {}", package.summary());
+    let path = package.map_path().to_string_lossy().into_owned();
+    let origin = package.origin();
+    let height_overrides = package.height_overrides();
+    let (restream, requests) = mpsc::channel::<Vec<(i32, i32)>>();
     thread::spawn(move || {
         // Clone for the callback closure inside the thread
-        let tx_callback = tx_thread.clone();
+        let tx_callback = tx.clone();
         
-        map_loader::load_chunks_from_osm_stream(config::MAP_FILE_PATH, move |chunk_batch_opt, progress, status| {
+        let tx_places = tx.clone();
+        let on_places = move |places| { tx_places.send(LoaderMessage::Places(places)).ok(); };
+        let tx_queued = tx.clone();
+        let on_queued = move |coords| { tx_queued.send(LoaderMessage::Queued(coords)).ok(); };
+        let result = map_loader::load_chunks_from_osm_stream(&path, origin, height_overrides, on_places, on_queued, move |chunk_batch_opt, progress, status| {
              if let Some(batch) = chunk_batch_opt {
                  tx_callback.send(LoaderMessage::BatchLoaded(batch)).ok();
              }
@@ -116,27 +238,111 @@ fn main() {
         });
         
         // Use the thread's copy of tx for the final signal
-        tx_thread.send(LoaderMessage::Done).ok();
+        match result {
+            Ok(mut source) => {
+                tx.send(LoaderMessage::Done).ok();
+                for coords in requests {
+                    let batch: Vec<_> = coords.into_iter().filter_map(|coord| source.build(coord)).collect();
+                    if tx.send(LoaderMessage::BatchLoaded(batch)).is_err() { break; }
+                }
+            }
+            Err(err) => { tx.send(LoaderMessage::Failed(err)).ok(); }
+        }
     });
+    restream
+}
+
+// `spawn` and `waypoints` come from the command line; the spawn wins over the package's and config's.
+fn start_game(ctx: GpuContext, package: &MapPackage, spawn: Option<&SpawnPoint>, waypoints: &[Waypoint], places: Option<PoiIndex>) -> GameState {
+    let mut state = GameState::new(ctx);
+    state.map_origin = package.origin();
+    state.cinematic.tours = package.tours();
+    if let Some(hours) = package.manifest.config.start_time_of_day { state.time_of_day.hours = hours; }
+    let spawn = spawn.cloned()
+        .or_else(|| package.manifest.config.spawn.as_deref().map(SpawnPoint::parse))
+        .unwrap_or_else(|| SpawnPoint::parse(&config_file::get().map.spawn));
+    if let Some(target) = spawn.resolve(package.origin(), places.as_ref()) { state.spawn_at(target); }
+    if let Some(places) = places { state.places = places; }
+    for waypoint in waypoints { state.waypoints.add(waypoint.clone()); }
+    let textures = package.facade_textures();
+    if !textures.is_empty() { state.set_facade_textures(&textures); }
+    for (name, commands) in package.plugins() {
+        log::info!("Running plugin {} ({} commands)", name, commands.len());
+        for command in commands { state.run_command(&command); }
+    }
+    state
+}
+
+fn main() {
+    env_logger::init();
+    let cli = Cli::parse();
+    config_file::load(&cli);
+    settings::restore_graphics_preset();
+    let event_loop = EventLoop::new().unwrap();
+    event_loop.set_control_flow(ControlFlow::Poll);
+    
+    let memory = display::WindowMemory::load();
+    let monitor = display::pick_monitor(event_loop.available_monitors().collect(), event_loop.primary_monitor(), &memory);
+    let (size, position) = memory.geometry(monitor.as_ref());
+    let mut builder = WindowBuilder::new().with_title(config::WINDOW_TITLE).with_inner_size(size);
+    if let Some(position) = position { builder = builder.with_position(position); }
+    let fullscreen = display::fullscreen_for(config_file::get().graphics.window_mode, monitor);
+    let window = Arc::new(builder.with_fullscreen(fullscreen).build(&event_loop).unwrap());
+    
+    let mut gpu_ctx_opt = Some(pollster::block_on(GpuContext::new(window.clone())));
+    shader_cache::precompile(gpu_ctx_opt.as_ref().unwrap().device.clone());
+    let mut loading_screen = LoadingScreen::new(gpu_ctx_opt.as_ref().unwrap());
+    let present_mode = gpu_ctx_opt.as_ref().unwrap().config.present_mode;
+    let mut frame_limiter = display::FrameLimiter::new(present_mode, display::refresh_rate(&window), config_file::get().graphics.frame_limit);
+
+    // Threading setup
+    let (tx, rx) = mpsc::channel();
+    let (spawn, waypoints) = (cli.spawn, cli.waypoint);
+    // Input capture and playback wait for every chunk, so a run sees the same world each time.
+    let (mut capture, mut playback, mut benchmark) = (cli.capture.is_some(), cli.playback, cli.benchmark);
+    let mut places = None;
+    // Chunks announced before the game state exists to track them.
+    let mut queued = None;
+
+    // With packages installed, pick one from the menu before loading; otherwise load the built-in map,
+    // which is also what --map picks.
+    let mut packages = map_package::discover();
+    if cli.map.is_some() { packages.truncate(1); }
+    let mut package = packages[0].clone();
+    let mut menu = None;
+    let mut restream = None;
+    if packages.len() > 1 {
+        let map_menu = MapMenu::new(gpu_ctx_opt.as_ref().unwrap(), std::mem::take(&mut packages));
+        window.set_title(&map_menu.title());
+        menu = Some(map_menu);
+    } else {
+        restream = Some(spawn_loader(&package, tx.clone()));
+    }
 
     let mut state: Option<GameState> = None;
     let mut is_loading_phase = true;
-    let mut last_fps_print = Instant::now();
-    let mut frames = 0;
     
     set_cursor_grab(&window, false);
 
+    // For Alt+Enter; the game state tracks its own modifiers.
+    let mut alt_held = false;
+    let mut visibility = display::Visibility::new();
+
     event_loop.run(move |event, elwt| {
+        if let Event::WindowEvent { event: WindowEvent::ModifiersChanged(modifiers), .. } = &event { alt_held = modifiers.state().alt_key(); }
         match event {
             Event::WindowEvent { ref event, window_id } if window_id == window.id() => {
                 match event {
                     WindowEvent::CloseRequested => elwt.exit(),
                     WindowEvent::Resized(size) => {
+                        visibility.resized(*size);
                         if let Some(s) = &mut state { s.resize(*size); }
                         else if let Some(ctx) = &mut gpu_ctx_opt { ctx.resize(*size); }
                     },
                     WindowEvent::RedrawRequested => {
-                        if is_loading_phase {
+                        if let Some(m) = &mut menu {
+                            if let Some(ctx) = &gpu_ctx_opt { m.render(ctx); }
+                        } else if is_loading_phase {
                             if let Some(s) = &mut state {
                                 loading_screen.render(&mut s.ctx);
                             } else if let Some(ctx) = &mut gpu_ctx_opt {
@@ -144,94 +350,165 @@ fn main() {
                             }
                         } else if let Some(s) = &mut state {
                             s.update();
+                            if s.quit_requested { elwt.exit(); }
+                            if std::mem::take(&mut s.reload_requested) { restream = Some(spawn_loader(&package, tx.clone())); }
+                            if !s.restream_requests.is_empty() && let Some(restream) = &restream { restream.send(std::mem::take(&mut s.restream_requests)).ok(); }
+                            if std::mem::take(&mut s.display_changed) {
+                                frame_limiter = display::FrameLimiter::new(s.ctx.config.present_mode, display::refresh_rate(&window), s.frame_limit);
+                            }
                             match s.render() {
                                 Ok(_) => {}
-                                Err(wgpu::SurfaceError::Lost) => s.resize(s.ctx.size),
-                                Err(wgpu::SurfaceError::OutOfMemory) => elwt.exit(),
+                                // Switching display modes can leave the surface behind the window.
+                                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => s.resize(window.inner_size()),
+                                // Integrated GPUs share memory with everything else; free some and try the next frame.
+                                Err(wgpu::SurfaceError::OutOfMemory) => if !s.recover_from_out_of_memory() {
+                                    log::error!("Out of GPU memory with nothing left to free");
+                                    elwt.exit();
+                                },
                                 Err(e) => eprintln!("Render Error: {:?}", e),
                             }
+                            frame_limiter.wait();
                         }
                     },
+                    WindowEvent::Focused(focused) => {
+                        // Tabbing away lets go of the cursor; a click takes it back.
+                        if visibility.set_focused(*focused) {
+                            if let Some(s) = &mut state { s.mouse_captured = false; }
+                            set_cursor_grab(&window, false);
+                        }
+                    },
+                    WindowEvent::Occluded(occluded) => visibility.set_occluded(*occluded),
                     WindowEvent::MouseInput { state: element_state, button: MouseButton::Left, .. } if !is_loading_phase => {
-                        if *element_state == ElementState::Pressed { 
+                        // Once captured, the button fires the grapple.
+                        if let Some(s) = &mut state && (s.mouse_captured || s.ui_has_pointer()) {
+                            s.input(event);
+                        } else if *element_state == ElementState::Pressed { 
                             if let Some(s) = &mut state { s.mouse_captured = true; }
                             set_cursor_grab(&window, true); 
                         }
                     },
-                    WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(KeyCode::Escape), state: element_state, .. }, .. } => {
-                        if *element_state == ElementState::Pressed { 
-                             if let Some(s) = &mut state { s.mouse_captured = false; }
-                             set_cursor_grab(&window, false); 
+                    WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(key @ (KeyCode::F11 | KeyCode::Enter)), state: ElementState::Pressed, repeat: false, .. }, .. }
+                        if *key == KeyCode::F11 || alt_held => {
+                        display::toggle_fullscreen(&window);
+                        // The new size arrives as a Resized event, which rebuilds the surface and render targets.
+                        // The refresh rate may have changed with the display mode.
+                        frame_limiter = match &state {
+                            Some(s) => display::FrameLimiter::new(s.ctx.config.present_mode, display::refresh_rate(&window), s.frame_limit),
+                            None => display::FrameLimiter::new(present_mode, display::refresh_rate(&window), config_file::get().graphics.frame_limit),
+                        };
+                    },
+                    WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(key), state: ElementState::Pressed, .. }, .. } if menu.is_some() => {
+                        let Some(m) = &mut menu else { return };
+                        match m.key(*key) {
+                            MenuAction::None => {}
+                            MenuAction::Moved => window.set_title(&m.title()),
+                            MenuAction::Selected => {
+                                window.set_title(config::WINDOW_TITLE);
+                                package = m.selected_package().clone();
+                                restream = Some(spawn_loader(&package, tx.clone()));
+                                menu = None;
+                            }
                         }
+                        window.request_redraw();
+                    },
+                    WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(KeyCode::Escape), state: ElementState::Pressed, repeat: false, .. }, .. } => {
+                        // In game Escape pauses and resumes; while loading it just frees the mouse.
+                        let captured = match &mut state { Some(s) if !is_loading_phase => s.escape(), _ => false };
+                        set_cursor_grab(&window, captured);
                     },
                     _ => {
-                        if !is_loading_phase {
-                            if let Some(s) = &mut state { s.input(event); }
+                        if !is_loading_phase && let Some(s) = &mut state {
+                            // Menus and panels can capture or free the mouse.
+                            let captured = s.mouse_captured;
+                            s.input(event);
+                            if s.mouse_captured != captured { set_cursor_grab(&window, s.mouse_captured); }
+                            if s.quit_requested { elwt.exit(); }
                         }
                     }
                 }
             },
-            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
-                if !is_loading_phase {
-                      if let Some(s) = &mut state { s.update_camera_rotation(delta); }
-                }
+            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } if !is_loading_phase => {
+                if let Some(s) = &mut state { s.accumulate_mouse_delta(delta); }
             },
             Event::AboutToWait => {
                 let mut chunk_loaded = false;
                 while let Ok(msg) = rx.try_recv() {
                     match msg {
                         LoaderMessage::Status(s) => {
-                            loading_screen.status_text = s;
+                            loading_screen.set_status(s);
                             window.request_redraw(); 
                         },
                         LoaderMessage::Progress(p) => {
                             loading_screen.current_progress = p;
                             window.request_redraw();
                         },
+                        LoaderMessage::Places(index) => match &mut state {
+                            Some(s) => s.places = index,
+                            None => places = Some(index),
+                        },
+                        LoaderMessage::Queued(coords) => match &mut state {
+                            Some(s) => s.streaming.queue(coords),
+                            None => queued = Some(coords),
+                        },
                         LoaderMessage::BatchLoaded(batch) => {
                             // Init State on first chunk batch
                             if state.is_none() {
                                 if let Some(ctx) = gpu_ctx_opt.take() {
-                                    state = Some(GameState::new(ctx));
+                                    state = Some(start_game(ctx, &package, spawn.as_ref(), &waypoints, places.take()));
+                                    #[cfg(feature = "egui")]
+                                    if let Some(s) = &mut state { s.attach_dev_ui(window.clone()); }
                                 }
                                 is_loading_phase = false;
                                 set_cursor_grab(&window, true);
                             }
                             if let Some(s) = &mut state {
-                                for chunk in batch {
-                                    s.world.insert_chunk(&s.ctx.device, chunk);
-                                }
+                                if let Some(coords) = queued.take() { s.streaming.queue(coords); }
+                                for chunk in batch { s.insert_chunk(chunk); }
                             }
                             chunk_loaded = true;
                         },
+                        LoaderMessage::Failed(err) => {
+                            log::error!("Map loading failed: {}", err);
+                            window.set_title(&format!("{} | {}", config::WINDOW_TITLE, err));
+                            if state.is_none() {
+                                loading_screen.fail(err.to_string());
+                                window.request_redraw();
+                            }
+                        },
                         LoaderMessage::Done => {
                             loading_screen.current_progress = 1.0;
-                            loading_screen.status_text = "Done".into();
-                            if state.is_none() {
-                                if let Some(ctx) = gpu_ctx_opt.take() { state = Some(GameState::new(ctx)); }
+                            loading_screen.finish();
+                            if state.is_none() && let Some(ctx) = gpu_ctx_opt.take() { state = Some(start_game(ctx, &package, spawn.as_ref(), &waypoints, places.take())); }
+                            // Every chunk is in; a spawn in an empty one can't wait any longer.
+                            if let Some(s) = &mut state {
+                                s.place_pending_spawn(true);
+                                s.notifications.push("Chunk streaming complete");
+                                if std::mem::take(&mut capture) { s.start_capture(); }
+                                if let Some(report) = benchmark.take() { s.start_benchmark(report, package.map_path().display().to_string()); }
+                                if let Some(path) = playback.take() && let Err(e) = s.start_playback(&path, true) {
+                                    log::error!("{}", e);
+                                    elwt.exit();
+                                }
                             }
                             is_loading_phase = false;
                         }
                     }
                 }
                 
-                if chunk_loaded || !is_loading_phase {
+                // Chunks keep arriving while the window is in the background, but frames don't
+                // need to be drawn at full speed, or at all while it's hidden.
+                if visibility.pace(elwt) && (chunk_loaded || !is_loading_phase) {
                      window.request_redraw();
                 }
 
-                if !is_loading_phase {
-                    frames += 1;
-                    if last_fps_print.elapsed().as_secs_f32() >= 1.0 {
-                        let chunk_count = state.as_ref().map(|s| s.world.chunks.len()).unwrap_or(0);
-                        let cam_y = state.as_ref().map(|s| s.camera.eye.y).unwrap_or(0.0);
-                        window.set_title(&format!("{} | FPS: {} | Chunks: {} | Y: {:.1}", config::WINDOW_TITLE, frames, chunk_count, cam_y));
-                        frames = 0;
-                        last_fps_print = Instant::now();
-                    }
-                } else {
+                if is_loading_phase {
                     thread::sleep(std::time::Duration::from_millis(5));
                 }
             },
+            Event::LoopExiting => {
+                display::WindowMemory::save(&window);
+                if let Some(s) = &mut state { s.finish_capture(); }
+            }
             _ => {}
         }
     }).unwrap();
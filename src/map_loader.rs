@@ -1,21 +1,14 @@
 // map_loader.rs
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, SeekFrom};
 use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
 use std::thread;
 use std::time::Duration;
-use osmpbf::{ElementReader, Element};
+use osmpbf::{BlobDecode, BlobReader, ByteOffset, ElementReader, Element, Mmap, MmapBlob};
 use glam::Vec2;
 use rayon::prelude::*;
-use crate::{config, vertex::Vertex, world::{ChunkData, WallCollider}};
-
-// 12 bytes per node.
-#[derive(Clone, Copy)]
-struct CompactNode {
-    id: i64,
-    x: f32,
-    y: f32
-}
+use crate::{config, config_file, elevator::Elevator, facade::FacadeStyle, node_index::{CompactNode, NodeCollector, NodeIndex}, poi::{PlaceKind, PoiIndex}, height::{self, BuildingKind, HeightEstimator, NeighbourhoodStats}, vertex::{UNTEXTURED, Vertex}, world::{self, BuildingInfo, ChunkData, Ladder, LocalCollisionGrid, RoadSegment, RoofTriangle, TunnelSpan, WallCollider}};
 
 // Wraps a file reader and increments an atomic counter on every read.
 struct ProgressReader {
@@ -32,34 +25,483 @@ impl Read for ProgressReader {
 }
 
 #[inline(always)]
-fn coords_to_local(lat: f64, lon: f64) -> (f32, f32) {
-    let lat_rad = config::ORIGIN_LAT.to_radians();
+pub fn coords_to_local(lat: f64, lon: f64, (origin_lat, origin_lon): (f64, f64)) -> (f32, f32) {
+    let lat_rad = origin_lat.to_radians();
     const METERS_LAT: f64 = 111132.0;
     let meters_lon = 111319.5 * lat_rad.cos();
 
-    let x = (lon - config::ORIGIN_LON) * meters_lon;
-    let z = -(lat - config::ORIGIN_LAT) * METERS_LAT;
+    let x = (lon - origin_lon) * meters_lon;
+    let z = -(lat - origin_lat) * METERS_LAT;
     (x as f32, z as f32)
 }
 
+// Latitude and longitude of a local position, the inverse of coords_to_local.
+pub fn local_to_coords(x: f64, z: f64, (origin_lat, origin_lon): (f64, f64)) -> (f64, f64) {
+    const METERS_LAT: f64 = 111132.0;
+    let meters_lon = 111319.5 * origin_lat.to_radians().cos();
+    (origin_lat - z / METERS_LAT, origin_lon + x / meters_lon)
+}
+
 struct RawBuilding {
+    id: i64,
     points: Vec<Vec2>,
+    // None until inferred for buildings without height/levels tags.
+    height: Option<f32>,
+    levels: Option<f32>,
+    kind: BuildingKind,
+    color: [f32; 3],
+}
+
+// One straight piece of a `barrier=*` way.
+struct BarrierSegment {
+    start: Vec2,
+    end: Vec2,
     height: f32,
     color: [f32; 3],
 }
 
-pub fn load_chunks_from_osm_stream<F>(path: &str, on_update: F) 
-where F: Fn(Option<Vec<ChunkData>>, f32, &str) + Send + Sync + 'static 
+#[derive(Default)]
+struct ChunkBucket {
+    buildings: Vec<RawBuilding>,
+    barriers: Vec<BarrierSegment>,
+    tunnels: Vec<TunnelSpan>,
+    roads: Vec<RoadSegment>,
+    // Water areas clipped to the chunk square.
+    water: Vec<Vec<Vec2>>,
+    // Footprints of entrance ramps clipped to the chunk square, cut out of its ground. A ramp's
+    // own geometry goes to the chunk its middle lies in.
+    ramps: Vec<Vec<Vec2>>,
+    lamps: Vec<Vec2>,
+    // `highway=elevator` nodes, each placed against the nearest building wall.
+    elevators: Vec<Vec2>,
+}
+
+impl ChunkBucket {
+    fn is_empty(&self) -> bool {
+        self.buildings.is_empty() && self.barriers.is_empty() && self.tunnels.is_empty() && self.roads.is_empty() && self.water.is_empty() && self.lamps.is_empty()
+    }
+}
+
+// Closed ways drawn as water surfaces. Lakes mapped as multipolygon relations are skipped.
+fn is_water_area((k, v): (&str, &str)) -> bool {
+    matches!((k, v), ("natural", "water") | ("waterway", "riverbank" | "dock") | ("landuse", "reservoir" | "basin"))
+}
+
+// Sutherland-Hodgman clip of a polygon to an axis-aligned rectangle.
+fn clip_to_rect(points: &[Vec2], min: Vec2, max: Vec2) -> Vec<Vec2> {
+    let mut out = points.to_vec();
+    for (axis, bound, upper) in [(0, min.x, false), (0, max.x, true), (1, min.y, false), (1, max.y, true)] {
+        let inside = |p: Vec2| if upper { p[axis] <= bound } else { p[axis] >= bound };
+        let input = std::mem::take(&mut out);
+        for (i, &p) in input.iter().enumerate() {
+            let prev = input[(i + input.len() - 1) % input.len()];
+            let cross = prev.lerp(p, (bound - prev[axis]) / (p[axis] - prev[axis]));
+            match (inside(prev), inside(p)) {
+                (true, true) => out.push(p),
+                (true, false) => out.push(cross),
+                (false, true) => { out.push(cross); out.push(p); }
+                (false, false) => {}
+            }
+        }
+    }
+    out
+}
+
+// Calls `f` with the bucket index and clipped piece of every chunk the polygon overlaps.
+fn clip_to_chunks(points: &[Vec2], mut f: impl FnMut(usize, Vec<Vec2>)) {
+    let (min, max) = points.iter().fold((Vec2::MAX, Vec2::MIN), |(lo, hi), p| (lo.min(*p), hi.max(*p)));
+    let axis = config::CHUNK_GRID_AXIS;
+    let cell = |v: f32| (((v + config_file::world_size() / 2.0) / config_file::chunk_size()).floor() as i32).clamp(0, axis as i32 - 1) as usize;
+    for gz in cell(min.y)..=cell(max.y) {
+        for gx in cell(min.x)..=cell(max.x) {
+            let origin = world::chunk_origin((gx as i32, gz as i32));
+            let piece = clip_to_rect(points, origin, origin + Vec2::splat(config_file::chunk_size()));
+            if piece.len() >= 3 { f(gz * axis + gx, piece); }
+        }
+    }
+}
+
+// Douglas-Peucker on a closed footprint: drops the corners within `epsilon` of the outline
+// without them, which OSM traces leave many of along straight walls, so runs of coplanar wall
+// quads become one. Repeated nodes (including the closing one) go too. A footprint that would
+// collapse below a triangle keeps its corners.
+fn simplify_footprint(points: &[Vec2], epsilon: f32) -> Vec<Vec2> {
+    let mut ring = points.to_vec();
+    ring.dedup_by(|a, b| a.distance(*b) < 0.001);
+    while ring.len() > 1 && ring[0].distance(ring[ring.len() - 1]) < 0.001 { ring.pop(); }
+    if ring.len() < 4 { return ring; }
+
+    // Split the ring at its first corner and the one farthest from it, and simplify each half.
+    let far = (1..ring.len()).max_by(|&a, &b| ring[a].distance_squared(ring[0]).total_cmp(&ring[b].distance_squared(ring[0]))).unwrap();
+    let closed: Vec<Vec2> = ring.iter().copied().chain(std::iter::once(ring[0])).collect();
+    let mut keep = vec![false; ring.len()];
+    (keep[0], keep[far]) = (true, true);
+    douglas_peucker(&closed, 0, far, epsilon, &mut keep);
+    douglas_peucker(&closed, far, ring.len(), epsilon, &mut keep);
+    let simplified: Vec<Vec2> = ring.iter().zip(&keep).filter(|&(_, &k)| k).map(|(&p, _)| p).collect();
+    if simplified.len() >= 3 { simplified } else { ring }
+}
+
+// Marks in `keep` the points strictly between `first` and `last` that the simplified line needs.
+fn douglas_peucker(points: &[Vec2], first: usize, last: usize, epsilon: f32, keep: &mut [bool]) {
+    if last <= first + 1 { return; }
+    let (a, b) = (points[first], points[last]);
+    let off_line = |p: Vec2| {
+        let ab = b - a;
+        let t = ((p - a).dot(ab) / ab.length_squared().max(1e-12)).clamp(0.0, 1.0);
+        p.distance(a + ab * t)
+    };
+    let (index, distance) = (first + 1..last).map(|i| (i, off_line(points[i]))).max_by(|x, y| x.1.total_cmp(&y.1)).unwrap();
+    if distance <= epsilon { return; }
+    keep[index] = true;
+    douglas_peucker(points, first, index, epsilon, keep);
+    douglas_peucker(points, index, last, epsilon, keep);
+}
+
+fn is_subway_entrance((k, v): (&str, &str)) -> bool {
+    k == "railway" && v == "subway_entrance"
+}
+
+fn is_street_lamp((k, v): (&str, &str)) -> bool {
+    k == "highway" && v == "street_lamp"
+}
+
+fn is_elevator((k, v): (&str, &str)) -> bool {
+    k == "highway" && v == "elevator"
+}
+
+// Name and kind of a node worth indexing as a point of interest.
+fn poi_name<'a>(tags: impl Iterator<Item = (&'a str, &'a str)>) -> Option<(&'a str, PlaceKind)> {
+    let (mut name, mut kind) = (None, None);
+    for (k, v) in tags {
+        if k == "name" {
+            name = Some(v);
+        } else if matches!(k, "place" | "leisure") {
+            kind = Some(PlaceKind::District);
+        } else if PoiIndex::is_poi_key(k) {
+            kind = kind.or(Some(PlaceKind::Point));
+        }
+    }
+    name.zip(kind)
+}
+
+// Floor height of an underground `railway=subway` way, or None if it isn't one.
+fn subway_tunnel_floor<'a>(tags: impl Iterator<Item = (&'a str, &'a str)>) -> Option<f32> {
+    let (mut subway, mut tunnel, mut layer) = (false, false, None);
+    for (k, v) in tags {
+        match k {
+            "railway" => subway = v == "subway",
+            "tunnel" => tunnel = v != "no",
+            "layer" => layer = v.trim().parse::<i32>().ok(),
+            _ => {}
+        }
+    }
+    let layer = match layer {
+        Some(l) if l < 0 => l,
+        None if tunnel => -1,
+        _ => return None,
+    };
+    subway.then_some(layer as f32 * config::TUNNEL_LAYER_DEPTH)
+}
+
+// Width of a street-level `highway=*` way cars can drive on, or None if it isn't one. The
+// lanes tag overrides the default width of the road class.
+fn drivable_road_width<'a>(tags: impl Iterator<Item = (&'a str, &'a str)>) -> Option<f32> {
+    let (mut width, mut lanes, mut elevated) = (None, None, false);
+    for (k, v) in tags {
+        match k {
+            "highway" => width = match v {
+                "motorway" | "trunk" => Some(14.0),
+                "primary" => Some(12.0),
+                "secondary" => Some(10.0),
+                "tertiary" => Some(8.0),
+                "residential" | "unclassified" | "living_street" => Some(7.0),
+                "motorway_link" | "trunk_link" | "primary_link" | "secondary_link" | "tertiary_link" => Some(6.0),
+                "service" => Some(4.5),
+                _ => None,
+            },
+            "lanes" => lanes = v.trim().parse::<f32>().ok().filter(|l| *l >= 1.0),
+            "tunnel" | "bridge" => elevated |= v != "no",
+            _ => {}
+        }
+    }
+    if elevated { return None; }
+    width.map(|w| lanes.map_or(w, |l| l * config::ROAD_LANE_WIDTH))
+}
+
+// Splits tunnels into chunk-sized spans and links each entrance to its nearest tunnel
+// with a ramp down from the street and a flat connector corridor.
+fn build_tunnel_spans(segments: &[(Vec2, Vec2, f32)], entrances: &[Vec2]) -> Vec<TunnelSpan> {
+    let mut spans = Vec::new();
+    let half_width = config::TUNNEL_WIDTH * 0.5;
+    for &(a, b, floor) in segments {
+        let len = a.distance(b);
+        if len < 0.1 { continue; }
+        let pieces = (len / config::TUNNEL_MAX_SPAN).ceil() as usize;
+        for i in 0..pieces {
+            let start = a.lerp(b, i as f32 / pieces as f32);
+            let end = a.lerp(b, (i + 1) as f32 / pieces as f32);
+            spans.push(TunnelSpan { start, end, half_width, floor_start: floor, floor_end: floor, open_top: false });
+        }
+    }
+
+    for &entrance in entrances {
+        let nearest = segments.iter().map(|&(a, b, floor)| {
+            let ab = b - a;
+            let t = ((entrance - a).dot(ab) / ab.length_squared().max(1e-6)).clamp(0.0, 1.0);
+            let p = a + ab * t;
+            (p, floor, p.distance(entrance))
+        }).min_by(|x, y| x.2.total_cmp(&y.2));
+        let Some((target, floor, dist)) = nearest else { break };
+        if !(1.0..=config::ENTRANCE_MAX_DISTANCE).contains(&dist) { continue; }
+
+        let dir = (target - entrance) / dist;
+        let ramp_len = config::ENTRANCE_RAMP_LENGTH.min(dist);
+        let ramp_end = entrance + dir * ramp_len;
+        let half_width = config::ENTRANCE_WIDTH * 0.5;
+        spans.push(TunnelSpan { start: entrance, end: ramp_end, half_width, floor_start: 0.0, floor_end: floor, open_top: true });
+        if dist - ramp_len > 0.5 {
+            spans.push(TunnelSpan { start: ramp_end, end: target, half_width, floor_start: floor, floor_end: floor, open_top: false });
+        }
+    }
+    spans
+}
+
+fn push_quad(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, corners: [[f32; 3]; 4], normal: [f32; 3], color: [f32; 3]) {
+    let base = vertices.len() as u32;
+    vertices.extend(corners.iter().map(|&position| Vertex { position, normal, color, facade: UNTEXTURED }));
+    indices.extend_from_slice(&[base, base+1, base+2, base, base+2, base+3]);
+}
+
+// Floor, side walls and (unless it's a ramp) ceiling of a tunnel span, facing inward.
+fn push_tunnel_geometry(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, span: &TunnelSpan) {
+    let dir = (span.end - span.start).normalize_or_zero();
+    let side = Vec2::new(-dir.y, dir.x) * span.half_width;
+    let (sl, sr, el, er) = (span.start + side, span.start - side, span.end + side, span.end - side);
+    let (fs, fe) = (span.floor_start, span.floor_end);
+    let (ts, te) = if span.open_top { (config::ENTRANCE_RAILING_HEIGHT, config::ENTRANCE_RAILING_HEIGHT) } else { (fs + config::TUNNEL_HEIGHT, fe + config::TUNNEL_HEIGHT) };
+    let (floor_color, wall_color) = if span.open_top { (ENTRANCE_FLOOR_COLOR, ENTRANCE_WALL_COLOR) } else { (TUNNEL_FLOOR_COLOR, TUNNEL_WALL_COLOR) };
+
+    push_quad(vertices, indices, [[sl.x, fs, sl.y], [el.x, fe, el.y], [er.x, fe, er.y], [sr.x, fs, sr.y]], [0.0, 1.0, 0.0], floor_color);
+    if !span.open_top {
+        push_quad(vertices, indices, [[sl.x, ts, sl.y], [el.x, te, el.y], [er.x, te, er.y], [sr.x, ts, sr.y]], [0.0, -1.0, 0.0], wall_color);
+    }
+    let n = side.normalize_or_zero();
+    push_quad(vertices, indices, [[sl.x, fs, sl.y], [el.x, fe, el.y], [el.x, te, el.y], [sl.x, ts, sl.y]], [-n.x, 0.0, -n.y], wall_color);
+    push_quad(vertices, indices, [[sr.x, fs, sr.y], [er.x, fe, er.y], [er.x, te, er.y], [sr.x, ts, sr.y]], [n.x, 0.0, n.y], wall_color);
+}
+
+const TUNNEL_FLOOR_COLOR: [f32; 3] = [0.12, 0.12, 0.13];
+const TUNNEL_WALL_COLOR: [f32; 3] = [0.32, 0.31, 0.28];
+const ENTRANCE_FLOOR_COLOR: [f32; 3] = [0.20, 0.20, 0.20];
+const ENTRANCE_WALL_COLOR: [f32; 3] = [0.10, 0.35, 0.15];
+
+// Default height and color for the barrier types we turn into walls.
+fn barrier_style(value: &str) -> Option<(f32, [f32; 3])> {
+    match value {
+        "wall" | "city_wall" | "retaining_wall" => Some((config::BARRIER_WALL_HEIGHT, [0.30, 0.27, 0.24])),
+        "fence" | "guard_rail" => Some((config::BARRIER_FENCE_HEIGHT, [0.18, 0.18, 0.20])),
+        "hedge" => Some((config::BARRIER_HEDGE_HEIGHT, [0.08, 0.22, 0.08])),
+        _ => None,
+    }
+}
+
+// Resolves way node refs to local positions, or None if any node is missing.
+fn way_points(refs: impl Iterator<Item = i64>, node_store: &NodeIndex) -> Option<Vec<Vec2>> {
+    refs.map(|id| node_store.get(id)).collect()
+}
+
+fn chunk_index(p: Vec2) -> Option<usize> {
+    let gx = ((p.x + config_file::world_size() / 2.0) / config_file::chunk_size()).floor() as i32;
+    let gz = ((p.y + config_file::world_size() / 2.0) / config_file::chunk_size()).floor() as i32;
+    let axis = config::CHUNK_GRID_AXIS as i32;
+    (gx >= 0 && gx < axis && gz >= 0 && gz < axis).then(|| (gz as usize) * config::CHUNK_GRID_AXIS + (gx as usize))
+}
+
+// Last element seen before a decode failure, to help locate corrupt blocks.
+#[derive(Debug, Clone, Copy)]
+pub enum ElementContext {
+    Node(i64),
+    Way(i64),
+    Relation(i64),
+}
+
+impl ElementContext {
+    fn of(element: &Element) -> Self {
+        match element {
+            Element::Node(n) => Self::Node(n.id()),
+            Element::DenseNode(n) => Self::Node(n.id),
+            Element::Way(w) => Self::Way(w.id()),
+            Element::Relation(r) => Self::Relation(r.id()),
+        }
+    }
+}
+
+impl std::fmt::Display for ElementContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Node(id) => write!(f, "node {}", id),
+            Self::Way(id) => write!(f, "way {}", id),
+            Self::Relation(id) => write!(f, "relation {}", id),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoaderError {
+    Open { path: String, source: std::io::Error },
+    Decode { pass: &'static str, byte_offset: u64, total_bytes: u64, last_element: Option<ElementContext>, source: osmpbf::Error },
+    // The file parsed, but nothing landed inside the world bounds.
+    Empty { path: String, nodes: usize },
+    // Sorting the nodes spilled to disk failed.
+    NodeIndex(std::io::Error),
+}
+
+impl std::fmt::Display for LoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Open { path, source } => write!(f, "Cannot open map '{}': {}", path, source),
+            Self::Decode { pass, byte_offset, total_bytes, last_element, source } => {
+                write!(f, "Corrupt map data while {} near byte {} of {}: {}", pass, byte_offset, total_bytes, source)?;
+                match last_element {
+                    Some(e) => write!(f, " (last good element: {})", e),
+                    None => write!(f, " (no elements decoded)"),
+                }
+            }
+            Self::Empty { path, nodes } => write!(f, "No buildings from '{}' fall inside the world ({} nodes read); check the map origin", path, nodes),
+            Self::NodeIndex(e) => write!(f, "Cannot index map nodes on disk: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoaderError {}
+
+// Chunks meshed before they're sent on together.
+pub const CHUNK_BATCH: usize = 4;
+
+// Stops the progress monitor thread however the loader exits.
+struct MonitorStop(Arc<std::sync::atomic::AtomicU8>);
+
+impl Drop for MonitorStop {
+    fn drop(&mut self) {
+        self.0.store(99, Ordering::Relaxed);
+    }
+}
+
+// What the nodes pass collects: from the whole file, or from one block when blocks are
+// decoded in parallel and joined in file order.
+#[derive(Default)]
+struct NodePass {
+    nodes: NodeCollector,
+    entrances: Vec<Vec2>,
+    lamps: Vec<Vec2>,
+    elevators: Vec<Vec2>,
+    places: PoiIndex,
+    last_element: Option<ElementContext>,
+}
+
+impl NodePass {
+    fn add(&mut self, element: &Element, origin: (f64, f64)) {
+        self.last_element = Some(ElementContext::of(element));
+        match element {
+            Element::DenseNode(n) => self.add_node(n.id, coords_to_local(n.lat(), n.lon(), origin), || n.tags()),
+            Element::Node(n) => self.add_node(n.id(), coords_to_local(n.lat(), n.lon(), origin), || n.tags()),
+            _ => {}
+        }
+    }
+
+    fn add_node<'a, T: Iterator<Item = (&'a str, &'a str)>>(&mut self, id: i64, (x, y): (f32, f32), tags: impl Fn() -> T) {
+        self.nodes.push(CompactNode { id, x, y });
+        let p = Vec2::new(x, y);
+        if tags().any(is_subway_entrance) { self.entrances.push(p); }
+        if tags().any(is_street_lamp) { self.lamps.push(p); }
+        if tags().any(is_elevator) { self.elevators.push(p); }
+        if let Some((name, kind)) = poi_name(tags()) { self.places.push(name, p, kind); }
+    }
+
+    fn append(&mut self, other: NodePass) {
+        self.nodes.append(other.nodes);
+        self.entrances.extend(other.entrances);
+        self.lamps.extend(other.lamps);
+        self.elevators.extend(other.elevators);
+        self.places.append(other.places);
+        if other.last_element.is_some() { self.last_element = other.last_element; }
+    }
+}
+
+// The nodes pass over a memory-mapped file, decoding its blocks on the rayon pool.
+fn read_nodes_mapped(mmap: &Mmap, file: &File, origin: (f64, f64), bytes_read: &AtomicU64, total_bytes: u64) -> Result<NodePass, LoaderError> {
+    let decode_error = |byte_offset, last_element, source| LoaderError::Decode { pass: "reading nodes", byte_offset, total_bytes, last_element, source };
+    // A blob that won't read starts where the last good one ends.
+    let failed_at = |last: Option<&MmapBlob>| last.map_or(0, |b| blob_end(file, b.offset().0).unwrap_or(b.offset().0));
+    let mut blobs = Vec::new();
+    for blob in mmap.blob_iter() {
+        blobs.push(blob.map_err(|source| decode_error(failed_at(blobs.last()), None, source))?);
+    }
+    let ends: Vec<u64> = blobs.iter().skip(1).map(|b| b.offset().0).chain(std::iter::once(total_bytes)).collect();
+
+    // Blocks are decoded a window at a time so the collector can spill between windows
+    // rather than every block's nodes being held at once.
+    let mut pass = NodePass::default();
+    let window = rayon::current_num_threads() * 8;
+    for (blobs, ends) in blobs.chunks(window).zip(ends.chunks(window)) {
+        let parts: Vec<Result<NodePass, osmpbf::Error>> = blobs.par_iter().zip(ends).map(|(blob, &end)| {
+            let mut part = NodePass::default();
+            if let BlobDecode::OsmData(block) = blob.decode()? { block.for_each_element(|element| part.add(&element, origin)); }
+            bytes_read.fetch_add(end - blob.offset().0, Ordering::Relaxed);
+            Ok(part)
+        }).collect();
+        for (part, blob) in parts.into_iter().zip(blobs) {
+            pass.append(part.map_err(|source| decode_error(blob.offset().0, pass.last_element, source))?);
+        }
+    }
+    Ok(pass)
+}
+
+// Where the blob starting at byte `start` ends; the memory-mapped reader doesn't say.
+fn blob_end(file: &File, start: u64) -> Option<u64> {
+    let mut reader = BlobReader::new_seekable(BufReader::new(file.try_clone().ok()?)).ok()?;
+    reader.blob_from_offset(ByteOffset(start)).ok()?;
+    reader.seek_raw(SeekFrom::Current(0)).ok()
+}
+
+// The nodes pass reading the file front to back.
+fn read_nodes_streamed(file: File, origin: (f64, f64), bytes_read: &Arc<AtomicU64>, total_bytes: u64) -> Result<NodePass, LoaderError> {
+    let reader = ProgressReader {
+        inner: BufReader::with_capacity(1024 * 1024, file), // 1MB Buffer
+        counter: bytes_read.clone(),
+    };
+    let mut pass = NodePass { nodes: NodeCollector::with_capacity(8_000_000), ..NodePass::default() };
+    ElementReader::new(reader).for_each(|element| pass.add(&element, origin)).map_err(|source| LoaderError::Decode {
+        pass: "reading nodes", byte_offset: bytes_read.load(Ordering::Relaxed), total_bytes, last_element: pass.last_element, source,
+    })?;
+    Ok(pass)
+}
+
+// What every non-empty chunk was meshed from, kept once the map has loaded so chunks the game
+// unloads for distance can be meshed again without another pass over the map file.
+pub struct ChunkSource {
+    buckets: HashMap<(i32, i32), ChunkBucket>,
+    scratch: MeshScratch,
+}
+
+impl ChunkSource {
+    pub fn build(&mut self, coord: (i32, i32)) -> Option<ChunkData> {
+        Some(build_chunk_geometry(self.buckets.get(&coord)?, coord, &mut self.scratch))
+    }
+}
+
+// `on_places` gets the points of interest and named buildings once the ways are read, before any chunk.
+// `on_queued` gets the coordinates of every chunk about to be meshed, before the first batch.
+pub fn load_chunks_from_osm_stream<F, P, Q>(path: &str, origin: (f64, f64), height_overrides: HashMap<i64, f32>, on_places: P, on_queued: Q, on_update: F) -> Result<ChunkSource, LoaderError>
+where F: Fn(Option<Vec<ChunkData>>, f32, &str) + Send + Sync + 'static, P: FnOnce(PoiIndex), Q: FnOnce(Vec<(i32, i32)>)
 {
     let path_str = path.to_string();
+    let open = |p: &str| File::open(p).map_err(|source| LoaderError::Open { path: p.to_string(), source });
+    let file = open(&path_str)?;
     
     // Get File Size for progress calc
-    let file_meta = match File::open(&path_str) {
-        Ok(f) => f.metadata().ok(),
-        Err(_) => None,
-    };
-    
-    let total_bytes = if let Some(meta) = file_meta { meta.len() } else { 1 };
+    let total_bytes = file.metadata().map(|m| m.len()).unwrap_or(1).max(1);
     
     // Shared Atomic Counter
     let bytes_read = Arc::new(AtomicU64::new(0));
@@ -70,8 +512,9 @@ where F: Fn(Option<Vec<ChunkData>>, f32, &str) + Send + Sync + 'static
     
     let phase = Arc::new(std::sync::atomic::AtomicU8::new(0));
     let phase_monitor = phase.clone();
+    let _monitor_stop = MonitorStop(phase.clone());
 
-    let monitor_handle = thread::spawn(move || {
+    let _monitor_handle = thread::spawn(move || {
         loop {
             let p_val = phase_monitor.load(Ordering::Relaxed);
             if p_val == 99 { break; } // Exit signal
@@ -97,44 +540,26 @@ where F: Fn(Option<Vec<ChunkData>>, f32, &str) + Send + Sync + 'static
         }
     });
 
-    let file = match File::open(&path_str) {
-        Ok(f) => f,
-        Err(_) => {
-            callback_ref(None, 1.0, "Error: File Not Found");
-            return;
+    // Memory-mapped with blocks decoded in parallel where the file can be mapped, otherwise
+    // streamed through a buffered reader one element at a time.
+    // SAFETY: the map file isn't written to while it's being loaded.
+    let nodes = match unsafe { Mmap::from_file(&file) } {
+        Ok(mmap) => read_nodes_mapped(&mmap, &file, origin, &bytes_read, total_bytes)?,
+        Err(e) => {
+            log::warn!("Couldn't memory-map {} ({}); reading it as a stream", path_str, e);
+            read_nodes_streamed(file, origin, &bytes_read, total_bytes)?
         }
     };
-    
-    let reader = ProgressReader {
-        inner: BufReader::with_capacity(1024 * 1024, file), // 1MB Buffer
-        counter: bytes_read.clone(),
-    };
-    
-    let mut node_store: Vec<CompactNode> = Vec::with_capacity(8_000_000);
-    let pbf_reader = ElementReader::new(reader);
-    
-    let _ = pbf_reader.for_each(|element| {
-        match element {
-            Element::DenseNode(n) => {
-                let (x, y) = coords_to_local(n.lat(), n.lon());
-                node_store.push(CompactNode { id: n.id, x, y });
-            }
-            Element::Node(n) => {
-                let (x, y) = coords_to_local(n.lat(), n.lon());
-                node_store.push(CompactNode { id: n.id(), x, y });
-            }
-            _ => {}
-        }
-    });
+    let NodePass { nodes, entrances, lamps, elevators, mut places, .. } = nodes;
 
     phase.store(1, Ordering::Relaxed);
-    node_store.par_sort_unstable_by_key(|n| n.id);
+    let node_store = nodes.finish().map_err(LoaderError::NodeIndex)?;
 
     phase.store(2, Ordering::Relaxed);
     // Reset byte counter for the second pass so progress math works
     bytes_read.store(0, Ordering::Relaxed);
     
-    let file2 = File::open(&path_str).unwrap();
+    let file2 = open(&path_str)?;
     let reader2 = ProgressReader {
         inner: BufReader::with_capacity(1024 * 1024, file2),
         counter: bytes_read.clone(),
@@ -142,87 +567,142 @@ where F: Fn(Option<Vec<ChunkData>>, f32, &str) + Send + Sync + 'static
     let pbf_reader2 = ElementReader::new(reader2);
     
     let grid_size = config::CHUNK_GRID_AXIS * config::CHUNK_GRID_AXIS;
-    let mut chunk_buckets: Vec<Vec<RawBuilding>> = (0..grid_size).map(|_| Vec::new()).collect();
+    let mut chunk_buckets: Vec<ChunkBucket> = (0..grid_size).map(|_| ChunkBucket::default()).collect();
     
-    let _ = pbf_reader2.for_each(|element| {
-        if let Element::Way(way) = element {
-            if way.tags().any(|(k, _)| k == "building") {
-                let mut height = 20.0;
-                if let Some(h_str) = way.tags().find(|(k, _)| *k == "height").map(|(_, v)| v) {
-                    if let Ok(h) = h_str.trim_matches(|c: char| !c.is_numeric() && c != '.').parse::<f32>() {
-                        height = h;
-                    }
-                }
-                
-                let seed = (way.id() % 100) as f32 / 100.0;
-                let grey = 0.15 + (seed * 0.20);
-                let color = [grey, grey, grey];
-
-                let mut points = Vec::new();
-                let mut valid = true;
-                let mut cx = 0.0; let mut cy = 0.0;
-
-                for id in way.refs() {
-                    if let Ok(idx) = node_store.binary_search_by_key(&id, |n| n.id) {
-                        let n = node_store[idx];
-                        points.push(Vec2::new(n.x, n.y));
-                        cx += n.x; cy += n.y;
-                    } else {
-                        valid = false;
-                        break;
-                    }
+    let estimator = HeightEstimator::new(config::HEIGHT_SEED, height_overrides);
+    let mut tunnel_segments: Vec<(Vec2, Vec2, f32)> = Vec::new();
+    let mut last_element = None;
+    pbf_reader2.for_each(|element| {
+        last_element = Some(ElementContext::of(&element));
+        let Element::Way(way) = element else { return };
+        if let Some(building) = way.tags().find(|(k, _)| *k == "building").map(|(_, v)| v) {
+            let kind = BuildingKind::from_tag(building);
+            let mut tagged_height = None;
+            let mut levels = None;
+            let mut name = None;
+            for (k, v) in way.tags() {
+                match k {
+                    "height" => tagged_height = height::parse_height(v),
+                    "building:levels" => levels = height::parse_levels(v),
+                    "name" => name = Some(v),
+                    _ => {}
                 }
+            }
+            let height = estimator.override_for(way.id()).or(tagged_height).or(levels.map(height::levels_to_height));
+            
+            let seed = (way.id() % 100) as f32 / 100.0;
+            let grey = 0.15 + (seed * 0.20);
+            let color = [grey, grey, grey];
 
-                if valid && points.len() >= 3 {
-                    // Winding
-                    let mut sum = 0.0;
-                    for i in 0..points.len() {
-                        let p1 = points[i];
-                        let p2 = points[(i+1)%points.len()];
-                        sum += (p2.x - p1.x)*(p2.y + p1.y);
-                    }
-                    if sum > 0.0 { points.reverse(); }
+            let Some(points) = way_points(way.refs(), &node_store) else { return };
+            let mut points = simplify_footprint(&points, config::FOOTPRINT_SIMPLIFY_EPSILON);
+            if points.len() < 3 { return; }
 
-                    cx /= points.len() as f32;
-                    cy /= points.len() as f32;
+            // Winding
+            let mut sum = 0.0;
+            for i in 0..points.len() {
+                let p1 = points[i];
+                let p2 = points[(i+1)%points.len()];
+                sum += (p2.x - p1.x)*(p2.y + p1.y);
+            }
+            if sum > 0.0 { points.reverse(); }
 
-                    let off_x = cx + (config::WORLD_SIZE / 2.0);
-                    let off_z = cy + (config::WORLD_SIZE / 2.0);
-                    let gx = (off_x / config::CHUNK_SIZE).floor() as i32;
-                    let gz = (off_z / config::CHUNK_SIZE).floor() as i32;
+            let centroid = points.iter().copied().sum::<Vec2>() / points.len() as f32;
+            if let Some(idx) = chunk_index(centroid) {
+                if let Some(name) = name { places.push(name, centroid, PlaceKind::Building); }
+                chunk_buckets[idx].buildings.push(RawBuilding { id: way.id(), points, height, levels, kind, color });
+            }
+        } else if let Some((default_height, color)) = way.tags().find(|(k, _)| *k == "barrier").and_then(|(_, v)| barrier_style(v)) {
+            let height = way.tags().find(|(k, _)| *k == "height").and_then(|(_, v)| height::parse_height(v)).unwrap_or(default_height);
+            let Some(points) = way_points(way.refs(), &node_store) else { return };
 
-                    if gx >= 0 && gx < config::CHUNK_GRID_AXIS as i32 && gz >= 0 && gz < config::CHUNK_GRID_AXIS as i32 {
-                        let idx = (gz as usize) * config::CHUNK_GRID_AXIS + (gx as usize);
-                        chunk_buckets[idx].push(RawBuilding { points, height, color });
+            // Bucket per segment so long fences collide in every chunk they cross.
+            for pair in points.windows(2) {
+                let (start, end) = (pair[0], pair[1]);
+                if start.distance_squared(end) < 1e-4 { continue; }
+                if let Some(idx) = chunk_index((start + end) * 0.5) {
+                    chunk_buckets[idx].barriers.push(BarrierSegment { start, end, height, color });
+                }
+            }
+        } else if let Some(floor) = subway_tunnel_floor(way.tags()) {
+            let Some(points) = way_points(way.refs(), &node_store) else { return };
+            tunnel_segments.extend(points.windows(2).map(|pair| (pair[0], pair[1], floor)));
+        } else if let Some(width) = drivable_road_width(way.tags()) {
+            let Some(points) = way_points(way.refs(), &node_store) else { return };
+            for pair in points.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                let pieces = (a.distance(b) / config::ROAD_MAX_SPAN).ceil() as usize;
+                for i in 0..pieces {
+                    let start = a.lerp(b, i as f32 / pieces as f32);
+                    let end = a.lerp(b, (i + 1) as f32 / pieces as f32);
+                    if let Some(idx) = chunk_index((start + end) * 0.5) {
+                        chunk_buckets[idx].roads.push(RoadSegment { start, end, half_width: width * 0.5 });
                     }
                 }
             }
+        } else if way.tags().any(is_water_area) {
+            let Some(mut points) = way_points(way.refs(), &node_store) else { return };
+            if points.len() < 4 || points.first() != points.last() { return; }
+            points.pop();
+
+            // Lakes can span many chunks, so each chunk gets its own clipped piece.
+            clip_to_chunks(&points, |idx, piece| chunk_buckets[idx].water.push(piece));
         }
-    });
+    }).map_err(|source| LoaderError::Decode {
+        pass: "parsing ways", byte_offset: bytes_read.load(Ordering::Relaxed), total_bytes, last_element, source,
+    })?;
+
+    log::info!("Indexed {} points of interest", places.len());
+    on_places(places);
+
+    let node_count = node_store.len();
 
     drop(node_store); // Free RAM
     phase.store(99, Ordering::Relaxed); // Stop monitor thread
 
+    for span in build_tunnel_spans(&tunnel_segments, &entrances) {
+        if span.open_top {
+            clip_to_chunks(&span.footprint(), |idx, piece| chunk_buckets[idx].ramps.push(piece));
+        }
+        if let Some(idx) = chunk_index((span.start + span.end) * 0.5) {
+            chunk_buckets[idx].tunnels.push(span);
+        }
+    }
+
+    for lamp in lamps {
+        if let Some(idx) = chunk_index(lamp) { chunk_buckets[idx].lamps.push(lamp); }
+    }
+    for elevator in elevators {
+        if let Some(idx) = chunk_index(elevator) { chunk_buckets[idx].elevators.push(elevator); }
+    }
+
+    if chunk_buckets.iter().all(|b| b.is_empty()) {
+        return Err(LoaderError::Empty { path: path_str, nodes: node_count });
+    }
+
+    infer_missing_heights(&mut chunk_buckets, &estimator);
+
     callback_ref(None, 0.95, "Meshing...");
 
-    let numbered_chunks: Vec<(usize, Vec<RawBuilding>)> = chunk_buckets.into_iter().enumerate().collect();
+    let numbered_chunks: Vec<(usize, ChunkBucket)> = chunk_buckets.into_iter().enumerate().collect();
     let total_chunks = numbered_chunks.len();
-    let mut batch = Vec::new();
+    let bucket_coord = |idx: usize| ((idx % config::CHUNK_GRID_AXIS) as i32, (idx / config::CHUNK_GRID_AXIS) as i32);
+    on_queued(numbered_chunks.iter().filter(|(_, b)| !b.is_empty()).map(|(idx, _)| bucket_coord(*idx)).collect());
+    let mut batch = Vec::with_capacity(CHUNK_BATCH);
+    let mut scratch = MeshScratch::default();
+    let mut buckets = HashMap::new();
 
-    for (i, (idx, buildings)) in numbered_chunks.into_iter().enumerate() {
-        if buildings.is_empty() { continue; }
-        
-        let gz = idx / config::CHUNK_GRID_AXIS;
-        let gx = idx % config::CHUNK_GRID_AXIS;
-        let coord = (gx as i32, gz as i32);
+    for (i, (idx, bucket)) in numbered_chunks.into_iter().enumerate() {
+        if bucket.is_empty() { continue; }
 
-        let chunk = build_chunk_geometry(buildings, coord);
+        let chunk = build_chunk_geometry(&bucket, bucket_coord(idx), &mut scratch);
+        buckets.insert(bucket_coord(idx), bucket);
         batch.push(chunk);
 
-        if batch.len() >= 4 {
+        // Handed over whole; the meshes in it are far too big to copy.
+        if batch.len() >= CHUNK_BATCH {
             let p = 0.95 + (i as f32 / total_chunks as f32) * 0.05;
-            callback_ref(Some(batch.clone()), p, "Streaming...");
-            batch.clear();
+            callback_ref(Some(std::mem::replace(&mut batch, Vec::with_capacity(CHUNK_BATCH))), p, "Streaming...");
         }
     }
     
@@ -231,56 +711,271 @@ where F: Fn(Option<Vec<ChunkData>>, f32, &str) + Send + Sync + 'static
     } else {
         callback_ref(None, 1.0, "Done");
     }
+    Ok(ChunkSource { buckets, scratch })
 }
 
-fn build_chunk_geometry(buildings: Vec<RawBuilding>, coord: (i32, i32)) -> ChunkData {
-    let mut vertices = Vec::with_capacity(buildings.len() * 24);
-    let mut indices = Vec::with_capacity(buildings.len() * 36);
-    let mut walls = Vec::with_capacity(buildings.len() * 4);
+// Each guess is pulled toward the known heights within HEIGHT_NEIGHBOUR_RADIUS of the building,
+// across chunk borders too; they're binned in cells that size so only nine cells are searched.
+fn infer_missing_heights(buckets: &mut [ChunkBucket], estimator: &HeightEstimator) {
+    let centroid = |points: &[Vec2]| points.iter().copied().sum::<Vec2>() / points.len() as f32;
+    let cell = |p: Vec2| ((p.x / config::HEIGHT_NEIGHBOUR_RADIUS).floor() as i32, (p.y / config::HEIGHT_NEIGHBOUR_RADIUS).floor() as i32);
+    let mut known: HashMap<(i32, i32), Vec<(Vec2, f32)>> = HashMap::new();
+    for b in buckets.iter().flat_map(|bucket| &bucket.buildings) {
+        let Some(height) = b.height else { continue };
+        let center = centroid(&b.points);
+        known.entry(cell(center)).or_default().push((center, height));
+    }
+
+    buckets.par_iter_mut().for_each(|bucket| {
+        for b in bucket.buildings.iter_mut().filter(|b| b.height.is_none()) {
+            let center = centroid(&b.points);
+            let (cx, cz) = cell(center);
+            let nearby = (-1..=1).flat_map(|x| (-1..=1).map(move |z| (cx + x, cz + z)))
+                .filter_map(|c| known.get(&c))
+                .flatten()
+                .filter(|(p, _)| p.distance(center) <= config::HEIGHT_NEIGHBOUR_RADIUS)
+                .map(|&(_, height)| height)
+                .collect();
+            let stats = NeighbourhoodStats::from_known(nearby);
+            b.height = Some(estimator.estimate(b.id, b.kind, height::footprint_area(&b.points), &stats));
+        }
+    });
+}
+
+// Elevator platforms: one against the wall nearest each `highway=elevator` node, and one on
+// the longest wall of a share of the tall buildings, picked by OSM id so it's stable.
+fn place_elevators(buildings: &[RawBuilding], nodes: &[Vec2]) -> Vec<Elevator> {
+    // (start, end, outward normal) of each wall.
+    fn walls(b: &RawBuilding) -> impl Iterator<Item = (Vec2, Vec2, Vec2)> {
+        // Positive for counter-clockwise outlines, whose outside is right of each edge.
+        let winding = (0..b.points.len()).map(|i| b.points[i].perp_dot(b.points[(i + 1) % b.points.len()])).sum::<f32>().signum();
+        (0..b.points.len()).map(move |i| {
+            let (start, end) = (b.points[i], b.points[(i + 1) % b.points.len()]);
+            (start, end, -(end - start).perp().normalize_or_zero() * winding)
+        })
+    }
+    let phase = |b: &RawBuilding| b.id.rem_euclid(97) as f32;
+    let mut elevators = Vec::new();
+
+    for &node in nodes {
+        let nearest = buildings.iter().flat_map(|b| walls(b).map(move |wall| (b, wall))).map(|(b, (start, end, outward))| {
+            let edge = end - start;
+            let along = ((node - start).dot(edge) / edge.length_squared().max(1e-6)).clamp(0.0, 1.0);
+            (node.distance(start + edge * along), b, start, end, outward, along)
+        }).min_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some((distance, b, start, end, outward, along)) = nearest && distance <= config::ELEVATOR_SNAP_DISTANCE {
+            elevators.push(Elevator::on_wall(start, end, outward, along, b.height.unwrap_or(config::LEVEL_HEIGHT), phase(b)));
+        }
+    }
+
+    for b in buildings {
+        let height = b.height.unwrap_or(config::LEVEL_HEIGHT);
+        if height < config::ELEVATOR_MIN_HEIGHT || b.id.rem_euclid(100) >= config::ELEVATOR_PERCENT { continue; }
+        let longest = walls(b).max_by(|a, c| a.0.distance_squared(a.1).total_cmp(&c.0.distance_squared(c.1)));
+        if let Some((start, end, outward)) = longest {
+            elevators.push(Elevator::on_wall(start, end, outward, 0.5, height, phase(b)));
+        }
+    }
+    elevators
+}
+
+// Fire escape ladder up the middle of the shortest wall of a share of the walk-up residential
+// and industrial buildings, picked by OSM id so it's stable.
+fn place_ladder(b: &RawBuilding, height: f32) -> Option<Ladder> {
+    let (min, max) = config::LADDER_HEIGHT_RANGE;
+    let walk_up = matches!(b.kind, BuildingKind::Residential | BuildingKind::Apartments | BuildingKind::Industrial);
+    if !walk_up || !(min..=max).contains(&height) || b.id.rem_euclid(100) >= config::LADDER_PERCENT { return None; }
+    let n = b.points.len();
+    let (start, end) = (0..n).map(|i| (b.points[i], b.points[(i + 1) % n]))
+        .filter(|(a, c)| a.distance(*c) >= config::LADDER_WIDTH * 2.0)
+        .min_by(|x, y| x.0.distance_squared(x.1).total_cmp(&y.0.distance_squared(y.1)))?;
+    let edge = end - start;
+    // Same outward side as the wall's mesh normal.
+    Some(Ladder { center: start.lerp(end, 0.5), normal: Vec2::new(edge.y, -edge.x).normalize(), top: height })
+}
 
-    let cx = coord.0 as f32 * config::CHUNK_SIZE - (config::WORLD_SIZE/2.0);
-    let cz = coord.1 as f32 * config::CHUNK_SIZE - (config::WORLD_SIZE/2.0);
-    let s = config::CHUNK_SIZE;
+// Two rails and the rungs between them, LADDER_STANDOFF out from the wall.
+fn push_ladder_geometry(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, ladder: &Ladder) {
+    let across = ladder.normal.perp();
+    let (side, rail) = (across * config::LADDER_WIDTH * 0.5, across * 0.05);
+    let front = ladder.center + ladder.normal * config::LADDER_STANDOFF;
+    let normal = [ladder.normal.x, 0.0, ladder.normal.y];
+    let quad = |a: Vec2, b: Vec2, low: f32, high: f32| [[a.x, low, a.y], [b.x, low, b.y], [b.x, high, b.y], [a.x, high, a.y]];
+    for rail_center in [front - side, front + side] {
+        push_quad(vertices, indices, quad(rail_center - rail, rail_center + rail, 0.0, ladder.top), normal, config::LADDER_COLOR);
+    }
+    let rungs = (ladder.top / config::LADDER_RUNG_SPACING) as usize;
+    for i in 1..=rungs {
+        let y = i as f32 * config::LADDER_RUNG_SPACING;
+        push_quad(vertices, indices, quad(front - side, front + side, y - 0.03, y + 0.03), normal, config::LADDER_COLOR);
+    }
+}
+
+// Working buffers of build_chunk_geometry that don't end up in the chunk, kept from one
+// chunk to the next so meshing doesn't allocate them again each time.
+#[derive(Default)]
+struct MeshScratch {
+    // Outline coordinates for earcut.
+    flat: Vec<f64>,
+    ground: Vec<f64>,
+    holes: Vec<usize>,
+    walls: Vec<WallCollider>,
+    roofs: Vec<RoofTriangle>,
+    water_triangles: Vec<RoofTriangle>,
+}
+
+fn build_chunk_geometry(bucket: &ChunkBucket, coord: (i32, i32), scratch: &mut MeshScratch) -> ChunkData {
+    let ChunkBucket { buildings, barriers, tunnels, roads, water, ramps, lamps, elevators } = bucket;
+    let elevators = place_elevators(buildings, elevators);
+    let MeshScratch { flat, ground, holes, walls, roofs, water_triangles } = scratch;
+    ground.clear();
+    holes.clear();
+    walls.clear();
+    roofs.clear();
+    water_triangles.clear();
+    let mut vertices = Vec::with_capacity(buildings.len() * 24 + barriers.len() * 4);
+    let mut indices = Vec::with_capacity(buildings.len() * 36 + barriers.len() * 6);
+    // Distant LOD: a flat ground quad, every roof and only the longer walls. Barriers,
+    // tunnels and short wall segments are too small to see from LOD_DISTANCE.
+    let mut lod_indices = Vec::with_capacity(buildings.len() * 12);
+    let mut building_info = Vec::with_capacity(buildings.len());
+    let mut ladders = Vec::new();
+
+    let origin = world::chunk_origin(coord);
+    let (cx, cz) = (origin.x, origin.y);
+    let s = config_file::chunk_size();
     
-    let base = 0;
-    vertices.push(Vertex{ position: [cx, -0.1, cz], normal:[0.0,1.0,0.0], color:[0.05,0.05,0.05] });
-    vertices.push(Vertex{ position: [cx+s, -0.1, cz], normal:[0.0,1.0,0.0], color:[0.05,0.05,0.05] });
-    vertices.push(Vertex{ position: [cx+s, -0.1, cz+s], normal:[0.0,1.0,0.0], color:[0.05,0.05,0.05] });
-    vertices.push(Vertex{ position: [cx, -0.1, cz+s], normal:[0.0,1.0,0.0], color:[0.05,0.05,0.05] });
-    indices.extend_from_slice(&[base, base+1, base+2, base, base+2, base+3]);
+    // Ground, with holes over water basins and the part of every entrance ramp in this chunk.
+    // Pieces are pulled in from the chunk edge so every hole stays inside.
+    ground.extend_from_slice(&[cx as f64, cz as f64, (cx+s) as f64, cz as f64, (cx+s) as f64, (cz+s) as f64, cx as f64, (cz+s) as f64]);
+    for area in water.iter().chain(ramps) {
+        let inset = clip_to_rect(area, origin + Vec2::splat(0.01), origin + Vec2::splat(s - 0.01));
+        if inset.len() < 3 { continue; }
+        holes.push(ground.len() / 2);
+        ground.extend(inset.iter().flat_map(|c| [c.x as f64, c.y as f64]));
+    }
+    match earcutr::earcut(ground, holes, 2) {
+        Ok(tris) if !tris.is_empty() => {
+            for p in ground.chunks(2) {
+                vertices.push(Vertex { position: [p[0] as f32, -0.1, p[1] as f32], normal: [0.0, 1.0, 0.0], color: [0.05, 0.05, 0.05], facade: UNTEXTURED });
+            }
+            indices.extend(tris.into_iter().map(|i| i as u32));
+        }
+        _ => push_quad(&mut vertices, &mut indices, [[cx, -0.1, cz], [cx+s, -0.1, cz], [cx+s, -0.1, cz+s], [cx, -0.1, cz+s]], [0.0, 1.0, 0.0], [0.05, 0.05, 0.05]),
+    }
+    push_quad(&mut vertices, &mut lod_indices, [[cx, -0.1, cz], [cx+s, -0.1, cz], [cx+s, -0.1, cz+s], [cx, -0.1, cz+s]], [0.0, 1.0, 0.0], [0.05, 0.05, 0.05]);
+
+    // Roads just above the ground, each strip run past its ends by the half width so the
+    // joints of a bending road are covered.
+    for road in roads {
+        let dir = (road.end - road.start).normalize_or_zero();
+        let (start, end) = (road.start - dir * road.half_width, road.end + dir * road.half_width);
+        let side = dir.perp() * road.half_width;
+        let corners = [start + side, end + side, end - side, start - side].map(|p| [p.x, -0.05, p.y]);
+        push_quad(&mut vertices, &mut indices, corners, [0.0, 1.0, 0.0], config::ROAD_COLOR);
+    }
 
     for b in buildings {
-        let flat_poly: Vec<f64> = b.points.iter().flat_map(|v| vec![v.x as f64, v.y as f64]).collect();
-        if let Ok(tris) = earcutr::earcut(&flat_poly, &[], 2) {
+        let height = b.height.unwrap_or(config::LEVEL_HEIGHT);
+        let first = indices.len() as u32;
+        flat.clear();
+        flat.extend(b.points.iter().flat_map(|v| [v.x as f64, v.y as f64]));
+        if let Ok(tris) = earcutr::earcut(flat, &[], 2) {
             let base_idx = vertices.len() as u32;
             for p in &b.points {
-                vertices.push(Vertex { position: [p.x, b.height, p.y], normal: [0.0, 1.0, 0.0], color: b.color });
+                vertices.push(Vertex { position: [p.x, height, p.y], normal: [0.0, 1.0, 0.0], color: b.color, facade: UNTEXTURED });
             }
-            for idx in tris { indices.push(base_idx + idx as u32); }
+            let roof = indices.len();
+            for idx in &tris { indices.push(base_idx + *idx as u32); }
+            let building = Some(building_info.len() as u32);
+            roofs.extend(tris.chunks_exact(3).map(|t| RoofTriangle { corners: [b.points[t[0]], b.points[t[1]], b.points[t[2]]], height, building }));
+            lod_indices.extend_from_slice(&indices[roof..]);
         }
 
+        // Window bays run continuously around the footprint; the per-building offset keeps
+        // neighbours from lighting the same windows.
+        let levels = b.levels.unwrap_or(((height - config::ROOF_ALLOWANCE) / config::LEVEL_HEIGHT).round()).max(1.0);
+        let level_height = ((height - config::ROOF_ALLOWANCE) / levels).max(1.0);
+        let layer = FacadeStyle::for_kind(b.kind, levels).layer();
+        let top = height / level_height;
+        let mut bay = (b.id.rem_euclid(97) * 13) as f32;
+
         for j in 0..b.points.len() {
             let p1 = b.points[j];
             let p2 = b.points[(j + 1) % b.points.len()];
             if (p1.x-p2.x).abs() < 0.01 && (p1.y-p2.y).abs() < 0.01 { continue; }
             let edge = p2 - p1;
             let normal = glam::Vec3::new(edge.y, 0.0, -edge.x).normalize().to_array();
+            let next_bay = bay + (edge.length() / config::FACADE_BAY_WIDTH).round().max(1.0);
             
             let base = vertices.len() as u32;
-            vertices.push(Vertex { position: [p1.x, 0.0, p1.y], normal, color: b.color });
-            vertices.push(Vertex { position: [p2.x, 0.0, p2.y], normal, color: b.color });
-            vertices.push(Vertex { position: [p2.x, b.height, p2.y], normal, color: b.color });
-            vertices.push(Vertex { position: [p1.x, b.height, p1.y], normal, color: b.color });
+            vertices.push(Vertex { position: [p1.x, 0.0, p1.y], normal, color: b.color, facade: [bay, 0.0, levels, layer] });
+            vertices.push(Vertex { position: [p2.x, 0.0, p2.y], normal, color: b.color, facade: [next_bay, 0.0, levels, layer] });
+            vertices.push(Vertex { position: [p2.x, height, p2.y], normal, color: b.color, facade: [next_bay, top, levels, layer] });
+            vertices.push(Vertex { position: [p1.x, height, p1.y], normal, color: b.color, facade: [bay, top, levels, layer] });
+            // Restarted between walls so bays stay whole numbers in the half floats meshes are
+            // packed with; the window pattern just shifts.
+            bay = next_bay.rem_euclid(1024.0);
             indices.extend_from_slice(&[base, base+1, base+2, base, base+2, base+3]);
+            if edge.length() >= config::LOD_MIN_WALL_LENGTH {
+                lod_indices.extend_from_slice(&[base, base+1, base+2, base, base+2, base+3]);
+            }
 
-            walls.push(WallCollider {
-                start: p1, end: p2, height: b.height,
-                min_x: p1.x.min(p2.x) - config::WALL_THICKNESS as f32,
-                max_x: p1.x.max(p2.x) + config::WALL_THICKNESS as f32,
-                min_z: p1.y.min(p2.y) - config::WALL_THICKNESS as f32,
-                max_z: p1.y.max(p2.y) + config::WALL_THICKNESS as f32,
-            });
+            walls.push(WallCollider::new(p1, p2, height).of_building(building_info.len()));
+        }
+        building_info.push(BuildingInfo { indices: first..indices.len() as u32 });
+        if let Some(ladder) = place_ladder(b, height) {
+            push_ladder_geometry(&mut vertices, &mut indices, &ladder);
+            ladders.push(ladder);
         }
     }
-    ChunkData { vertices, indices, walls, coord }
+
+    // Barriers are single double-sided strips; the scene shader lights both faces.
+    for seg in barriers {
+        let edge = seg.end - seg.start;
+        let normal = glam::Vec3::new(edge.y, 0.0, -edge.x).normalize().to_array();
+        let base = vertices.len() as u32;
+        vertices.push(Vertex { position: [seg.start.x, 0.0, seg.start.y], normal, color: seg.color, facade: UNTEXTURED });
+        vertices.push(Vertex { position: [seg.end.x, 0.0, seg.end.y], normal, color: seg.color, facade: UNTEXTURED });
+        vertices.push(Vertex { position: [seg.end.x, seg.height, seg.end.y], normal, color: seg.color, facade: UNTEXTURED });
+        vertices.push(Vertex { position: [seg.start.x, seg.height, seg.start.y], normal, color: seg.color, facade: UNTEXTURED });
+        indices.extend_from_slice(&[base, base+1, base+2, base, base+2, base+3]);
+
+        walls.push(WallCollider::new(seg.start, seg.end, seg.height));
+    }
+    for span in tunnels {
+        push_tunnel_geometry(&mut vertices, &mut indices, span);
+    }
+
+    // Water is drawn in its own transparent pass, so it gets a separate mesh. Below it, the
+    // basin bed and its shore walls go in the chunk mesh for swimmers to see.
+    let (mut water_vertices, mut water_indices) = (Vec::new(), Vec::new());
+    let bed = config::WATER_LEVEL - config::WATER_DEPTH;
+    for area in water {
+        flat.clear();
+        flat.extend(area.iter().flat_map(|p| [p.x as f64, p.y as f64]));
+        let Ok(tris) = earcutr::earcut(flat, &[], 2) else { continue };
+        let base = water_vertices.len() as u32;
+        water_vertices.extend(area.iter().map(|p| Vertex { position: [p.x, config::WATER_LEVEL, p.y], normal: [0.0, 1.0, 0.0], color: config::WATER_COLOR, facade: UNTEXTURED }));
+        water_indices.extend(tris.iter().map(|&i| base + i as u32));
+        water_triangles.extend(tris.chunks_exact(3).map(|t| RoofTriangle { corners: [area[t[0]], area[t[1]], area[t[2]]], height: config::WATER_LEVEL, building: None }));
+
+        let base = vertices.len() as u32;
+        vertices.extend(area.iter().map(|p| Vertex { position: [p.x, bed, p.y], normal: [0.0, 1.0, 0.0], color: config::WATER_BED_COLOR, facade: UNTEXTURED }));
+        indices.extend(tris.iter().map(|&i| base + i as u32));
+        // Edges along the chunk border continue into the neighbour's basin, so they get no wall.
+        let on_border = |p: Vec2| p.x <= cx + 0.01 || p.x >= cx + s - 0.01 || p.y <= cz + 0.01 || p.y >= cz + s - 0.01;
+        // Walls face into the water whichever way the outline winds.
+        let winding = (0..area.len()).map(|i| area[i].perp_dot(area[(i + 1) % area.len()])).sum::<f32>().signum();
+        for i in 0..area.len() {
+            let (a, b) = (area[i], area[(i + 1) % area.len()]);
+            if on_border(a) && on_border(b) { continue; }
+            let normal = (b - a).perp().normalize_or_zero() * winding;
+            push_quad(&mut vertices, &mut indices, [[a.x, bed, a.y], [b.x, bed, b.y], [b.x, -0.1, b.y], [a.x, -0.1, a.y]], [normal.x, 0.0, normal.y], config::WATER_BED_COLOR);
+        }
+    }
+
+    let collision = Arc::new(LocalCollisionGrid::new(walls, roofs, water_triangles, tunnels.clone(), roads.clone(), ladders, origin));
+    let lights = lamps.iter().map(|p| glam::Vec3::new(p.x, config::STREET_LAMP_HEIGHT, p.y)).collect();
+    ChunkData { vertices, indices, lod_indices, water_vertices, water_indices, lights, buildings: building_info, elevators, collision, coord }
 }
\ No newline at end of file
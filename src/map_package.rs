@@ -0,0 +1,433 @@
+// map_package.rs
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::{cinematic::CameraPath, config_file, console::Command, facade::{self, FacadeStyle}};
+
+pub const MANIFEST_NAME: &str = "map.json";
+
+// Settings a package may override. Anything left out keeps the value from skyroam.toml (or config.rs).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ConfigOverrides {
+    pub origin_lat: Option<f64>,
+    pub origin_lon: Option<f64>,
+    pub start_time_of_day: Option<f64>,
+    // "lat,lon" or a place name, like --spawn.
+    pub spawn: Option<String>,
+}
+
+// Data tables that correct what the map file says.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DataOverrides {
+    // CSV of `way_id,height_m` lines, winning over the way's tags and the height estimate.
+    pub building_heights: Option<String>,
+}
+
+// Contents of `map.json`. Paths are relative to the package root.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub map: String,
+    #[serde(default)]
+    pub config: ConfigOverrides,
+    // Camera paths in the format K/P record (see cinematic.rs), flown with the `tour` command.
+    #[serde(default)]
+    pub tours: Vec<String>,
+    #[serde(default)]
+    pub overrides: DataOverrides,
+    // Facade textures replacing the generated ones, by style: glass, office, brick, concrete or
+    // industrial. See facade::read_override for the format.
+    #[serde(default)]
+    pub textures: HashMap<String, String>,
+    // Console scripts run when the map starts, one command per line as typed into the console.
+    // Blank lines and lines starting with `#` are skipped.
+    #[serde(default)]
+    pub plugins: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum PackageError {
+    Io { path: PathBuf, source: io::Error },
+    Manifest { path: PathBuf, source: serde_json::Error },
+    Zip { path: PathBuf, reason: String },
+    MissingFile { path: PathBuf },
+    Invalid { path: PathBuf, reason: String },
+}
+
+impl std::fmt::Display for PackageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "Cannot read map package '{}': {}", path.display(), source),
+            Self::Manifest { path, source } => write!(f, "Invalid manifest '{}': {}", path.display(), source),
+            Self::Zip { path, reason } => write!(f, "Cannot unpack '{}': {}", path.display(), reason),
+            Self::MissingFile { path } => write!(f, "Map package references missing file '{}'", path.display()),
+            Self::Invalid { path, reason } => write!(f, "Invalid package file '{}': {}", path.display(), reason),
+        }
+    }
+}
+
+impl std::error::Error for PackageError {}
+
+// A ready-to-roam city: a map file plus everything shipped alongside it, loaded as one unit.
+#[derive(Debug, Clone)]
+pub struct MapPackage {
+    pub manifest: Manifest,
+    // Directory the manifest paths resolve against. Zips are unpacked into the cache first.
+    pub root: PathBuf,
+}
+
+impl MapPackage {
+    // The map configured in skyroam.toml, for running without any packages installed.
+    pub fn builtin() -> Self {
+        Self {
+            manifest: Manifest {
+                name: "Default".into(), description: String::new(), map: config_file::get().map.file.clone(),
+                config: ConfigOverrides::default(), tours: Vec::new(), overrides: DataOverrides::default(),
+                textures: HashMap::new(), plugins: Vec::new(),
+            },
+            root: PathBuf::from("."),
+        }
+    }
+
+    pub fn open(path: &Path) -> Result<Self, PackageError> {
+        let root = if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip")) {
+            let stem = path.file_stem().unwrap_or_default();
+            let dest = Path::new(&config_file::get().map.maps_dir).join(".cache").join(stem);
+            unpack_zip(path, &dest)?;
+            dest
+        } else {
+            path.to_path_buf()
+        };
+
+        let manifest_path = root.join(MANIFEST_NAME);
+        let text = fs::read_to_string(&manifest_path).map_err(|source| PackageError::Io { path: manifest_path.clone(), source })?;
+        let manifest: Manifest = serde_json::from_str(&text).map_err(|source| PackageError::Manifest { path: manifest_path, source })?;
+        let package = Self { manifest, root };
+
+        let mut referenced = vec![package.manifest.map.as_str()];
+        referenced.extend(package.manifest.tours.iter().map(String::as_str));
+        referenced.extend(package.manifest.overrides.building_heights.as_deref());
+        referenced.extend(package.manifest.textures.values().map(String::as_str));
+        referenced.extend(package.manifest.plugins.iter().map(String::as_str));
+        for file in referenced {
+            let resolved = package.resolve(file);
+            if !resolved.exists() { return Err(PackageError::MissingFile { path: resolved }); }
+        }
+
+        for (style, file) in &package.manifest.textures {
+            let path = package.resolve(file);
+            if FacadeStyle::from_name(style).is_none() { return Err(PackageError::Invalid { path, reason: format!("'{}' isn't a facade style", style) }); }
+            let bytes = fs::read(&path).map_err(|source| PackageError::Io { path: path.clone(), source })?;
+            facade::read_override(&bytes).map_err(|reason| PackageError::Invalid { path, reason })?;
+        }
+        for file in &package.manifest.plugins {
+            let path = package.resolve(file);
+            let text = fs::read_to_string(&path).map_err(|source| PackageError::Io { path: path.clone(), source })?;
+            for (number, line) in script_lines(&text) {
+                Command::parse(line).map_err(|e| PackageError::Invalid { path: path.clone(), reason: format!("line {}: {}", number, e) })?;
+            }
+        }
+        Ok(package)
+    }
+
+    pub fn resolve(&self, relative: &str) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    pub fn map_path(&self) -> PathBuf {
+        self.resolve(&self.manifest.map)
+    }
+
+    pub fn origin(&self) -> (f64, f64) {
+        let overrides = &self.manifest.config;
+        (overrides.origin_lat.unwrap_or(config_file::get().map.origin_lat), overrides.origin_lon.unwrap_or(config_file::get().map.origin_lon))
+    }
+
+    pub fn summary(&self) -> String {
+        let m = &self.manifest;
+        format!(
+            "{} ({} tours, {} facade textures, {} plugins{})",
+            m.name, m.tours.len(), m.textures.len(), m.plugins.len(), if m.overrides.building_heights.is_some() { ", building heights" } else { "" },
+        )
+    }
+
+    // The tours that parse, named after their files. Broken ones are logged and left out.
+    pub fn tours(&self) -> Vec<(String, CameraPath)> {
+        self.manifest.tours.iter().filter_map(|file| {
+            let path = self.resolve(file);
+            let tour = fs::read_to_string(&path).map_err(|e| e.to_string())
+                .and_then(|text| serde_json::from_str::<CameraPath>(&text).map_err(|e| e.to_string()));
+            match tour {
+                Ok(tour) if tour.keyframes.len() >= 2 => {
+                    let name = path.file_stem().map_or_else(|| file.clone(), |s| s.to_string_lossy().into_owned());
+                    Some((name, tour))
+                }
+                Ok(_) => {
+                    log::warn!("Skipping tour '{}': it needs at least two keyframes", path.display());
+                    None
+                }
+                Err(e) => {
+                    log::warn!("Skipping tour '{}': {}", path.display(), e);
+                    None
+                }
+            }
+        }).collect()
+    }
+
+    // The facade textures by style. They were checked when the package was opened; any that
+    // no longer read are logged and left out.
+    pub fn facade_textures(&self) -> Vec<(FacadeStyle, Vec<u8>)> {
+        self.manifest.textures.iter().filter_map(|(style, file)| {
+            let path = self.resolve(file);
+            let texels = fs::read(&path).map_err(|e| e.to_string()).and_then(|bytes| facade::read_override(&bytes));
+            match texels {
+                Ok(texels) => Some((FacadeStyle::from_name(style)?, texels)),
+                Err(e) => {
+                    log::warn!("Skipping facade texture '{}': {}", path.display(), e);
+                    None
+                }
+            }
+        }).collect()
+    }
+
+    // Each plugin's commands, named after its file, in manifest order.
+    pub fn plugins(&self) -> Vec<(String, Vec<String>)> {
+        self.manifest.plugins.iter().filter_map(|file| {
+            let path = self.resolve(file);
+            match fs::read_to_string(&path) {
+                Ok(text) => {
+                    let name = path.file_stem().map_or_else(|| file.clone(), |s| s.to_string_lossy().into_owned());
+                    Some((name, script_lines(&text).map(|(_, line)| line.to_string()).collect()))
+                }
+                Err(e) => {
+                    log::warn!("Skipping plugin '{}': {}", path.display(), e);
+                    None
+                }
+            }
+        }).collect()
+    }
+
+    // Heights by way id from overrides.building_heights. Lines that aren't `id,height` (a
+    // header, comments) are skipped.
+    pub fn height_overrides(&self) -> HashMap<i64, f32> {
+        let Some(file) = &self.manifest.overrides.building_heights else { return HashMap::new() };
+        let path = self.resolve(file);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                log::warn!("Ignoring building heights '{}': {}", path.display(), e);
+                return HashMap::new();
+            }
+        };
+        let heights: HashMap<i64, f32> = text.lines().filter_map(|line| {
+            let (id, height) = line.split_once(',')?;
+            Some((id.trim().parse().ok()?, height.trim().parse::<f32>().ok().filter(|h| *h > 0.0)?))
+        }).collect();
+        log::info!("Loaded {} building heights from {}", heights.len(), path.display());
+        heights
+    }
+}
+
+// Every package directory and zip in MAPS_DIR, sorted by name, after the built-in map.
+// Broken packages are logged and left out of the list.
+pub fn discover() -> Vec<MapPackage> {
+    let mut packages = Vec::new();
+    if let Ok(entries) = fs::read_dir(&config_file::get().map.maps_dir) {
+        let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| !p.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')))
+            .filter(|p| p.join(MANIFEST_NAME).is_file() || p.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip")))
+            .collect();
+        paths.sort();
+        for path in paths {
+            match MapPackage::open(&path) {
+                Ok(package) => packages.push(package),
+                Err(err) => log::warn!("Skipping map package: {}", err),
+            }
+        }
+    }
+    packages.insert(0, MapPackage::builtin());
+    packages
+}
+
+// A plugin's commands with their line numbers.
+fn script_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())).filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+fn read_u16(buf: &[u8], at: usize) -> u16 { u16::from_le_bytes([buf[at], buf[at + 1]]) }
+fn read_u32(buf: &[u8], at: usize) -> u32 { u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]]) }
+
+// Minimal zip reader for stored and deflated entries, enough for packages made by any
+// common archiver. Skips unpacking when the cache is newer than the archive.
+fn unpack_zip(archive: &Path, dest: &Path) -> Result<(), PackageError> {
+    let io_err = |source| PackageError::Io { path: archive.to_path_buf(), source };
+    let zip_err = |reason: &str| PackageError::Zip { path: archive.to_path_buf(), reason: reason.to_string() };
+
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    if let (Some(zip_time), Some(cache_time)) = (modified(archive), modified(&dest.join(MANIFEST_NAME)))
+        && cache_time >= zip_time {
+        return Ok(());
+    }
+
+    let mut file = File::open(archive).map_err(io_err)?;
+    let len = file.metadata().map_err(io_err)?.len();
+    // The end-of-central-directory record sits in the last 22 bytes plus up to 64 KiB of comment.
+    let tail_len = len.min(22 + 65535);
+    let mut tail = vec![0; tail_len as usize];
+    file.seek(SeekFrom::Start(len - tail_len)).map_err(io_err)?;
+    file.read_exact(&mut tail).map_err(io_err)?;
+    let eocd = (0..tail.len().saturating_sub(21)).rev().find(|&i| read_u32(&tail, i) == 0x0605_4b50).ok_or_else(|| zip_err("no end of central directory"))?;
+    let entry_count = read_u16(&tail, eocd + 10) as usize;
+    let dir_size = read_u32(&tail, eocd + 12) as usize;
+    let dir_offset = read_u32(&tail, eocd + 16) as u64;
+    if dir_offset + dir_size as u64 > len { return Err(zip_err("central directory runs past the end of the file")); }
+
+    let mut directory = vec![0; dir_size];
+    file.seek(SeekFrom::Start(dir_offset)).map_err(io_err)?;
+    file.read_exact(&mut directory).map_err(io_err)?;
+
+    let mut at = 0;
+    for _ in 0..entry_count {
+        if at + 46 > directory.len() || read_u32(&directory, at) != 0x0201_4b50 { return Err(zip_err("corrupt central directory")); }
+        let method = read_u16(&directory, at + 10);
+        let compressed = read_u32(&directory, at + 20) as u64;
+        let name_len = read_u16(&directory, at + 28) as usize;
+        let extra_len = read_u16(&directory, at + 30) as usize;
+        let comment_len = read_u16(&directory, at + 32) as usize;
+        let local_offset = read_u32(&directory, at + 42) as u64;
+        if at + 46 + name_len + extra_len + comment_len > directory.len() { return Err(zip_err("corrupt central directory")); }
+        let name = String::from_utf8_lossy(&directory[at + 46..at + 46 + name_len]).replace('\\', "/");
+        at += 46 + name_len + extra_len + comment_len;
+
+        // Refuse entries that would escape the cache directory.
+        if name.split('/').any(|part| part == "..") || name.starts_with('/') { return Err(zip_err("entry path escapes the package")); }
+        let out_path = dest.join(&name);
+        if name.ends_with('/') {
+            fs::create_dir_all(&out_path).map_err(io_err)?;
+            continue;
+        }
+        if let Some(parent) = out_path.parent() { fs::create_dir_all(parent).map_err(io_err)?; }
+
+        let mut local = [0u8; 30];
+        file.seek(SeekFrom::Start(local_offset)).map_err(io_err)?;
+        file.read_exact(&mut local).map_err(io_err)?;
+        let data_start = local_offset + 30 + read_u16(&local, 26) as u64 + read_u16(&local, 28) as u64;
+        if data_start + compressed > len { return Err(zip_err(&format!("'{}' runs past the end of the file", name))); }
+        file.seek(SeekFrom::Start(data_start)).map_err(io_err)?;
+        let data = (&mut file).take(compressed);
+
+        let mut out = File::create(&out_path).map_err(io_err)?;
+        match method {
+            0 => io::copy(&mut { data }, &mut out),
+            8 => io::copy(&mut flate2::read::DeflateDecoder::new(data), &mut out),
+            _ => return Err(zip_err(&format!("'{}' uses unsupported compression method {}", name, method))),
+        }.map_err(io_err)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    // A zip of `entries` (name, contents, deflated), laid out as archivers write them. CRCs are
+    // left at zero, as the reader doesn't check them.
+    fn zip(entries: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let (mut out, mut directory) = (Vec::new(), Vec::new());
+        for &(name, contents, deflate) in entries {
+            let data = if deflate {
+                let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(contents).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                contents.to_vec()
+            };
+            let method: u16 = if deflate { 8 } else { 0 };
+            let offset = out.len() as u32;
+            out.extend(0x0403_4b50u32.to_le_bytes());
+            out.extend([0; 4]);
+            out.extend(method.to_le_bytes());
+            out.extend([0; 8]);
+            out.extend((data.len() as u32).to_le_bytes());
+            out.extend((contents.len() as u32).to_le_bytes());
+            out.extend((name.len() as u16).to_le_bytes());
+            out.extend([0; 2]);
+            out.extend(name.as_bytes());
+            out.extend(&data);
+
+            directory.extend(0x0201_4b50u32.to_le_bytes());
+            directory.extend([0; 6]);
+            directory.extend(method.to_le_bytes());
+            directory.extend([0; 8]);
+            directory.extend((data.len() as u32).to_le_bytes());
+            directory.extend((contents.len() as u32).to_le_bytes());
+            directory.extend((name.len() as u16).to_le_bytes());
+            directory.extend([0; 12]);
+            directory.extend(offset.to_le_bytes());
+            directory.extend(name.as_bytes());
+        }
+        let dir_offset = out.len() as u32;
+        out.extend(&directory);
+        out.extend(0x0605_4b50u32.to_le_bytes());
+        out.extend([0; 4]);
+        out.extend((entries.len() as u16).to_le_bytes());
+        out.extend((entries.len() as u16).to_le_bytes());
+        out.extend((directory.len() as u32).to_le_bytes());
+        out.extend(dir_offset.to_le_bytes());
+        out.extend([0; 2]);
+        out
+    }
+
+    // Writes `bytes` as an archive and unpacks it into a fresh directory next to it.
+    fn unpack(test: &str, bytes: &[u8]) -> (Result<(), PackageError>, PathBuf) {
+        let base = std::env::temp_dir().join(format!("skyroam-zip-{}-{}", test, std::process::id()));
+        fs::remove_dir_all(&base).ok();
+        fs::create_dir_all(&base).unwrap();
+        let archive = base.join("package.zip");
+        fs::write(&archive, bytes).unwrap();
+        let dest = base.join("unpacked");
+        (unpack_zip(&archive, &dest), dest)
+    }
+
+    #[test]
+    fn unpacks_stored_and_deflated_entries() {
+        let tour = "keyframes ".repeat(50);
+        let bytes = zip(&[("map.json", b"{}", false), ("tours/", b"", false), ("tours/loop.json", tour.as_bytes(), true)]);
+        let (result, dest) = unpack("entries", &bytes);
+        result.unwrap();
+        assert_eq!(fs::read(dest.join("map.json")).unwrap(), b"{}");
+        assert_eq!(fs::read_to_string(dest.join("tours/loop.json")).unwrap(), tour);
+    }
+
+    #[test]
+    fn rejects_entries_outside_the_package() {
+        for name in ["../escape.txt", "tours/../../escape.txt", "/escape.txt"] {
+            let (result, _) = unpack("escape", &zip(&[(name, b"out", false)]));
+            assert!(matches!(result, Err(PackageError::Zip { .. })), "{} was unpacked", name);
+        }
+    }
+
+    #[test]
+    fn rejects_a_truncated_directory() {
+        let bytes = zip(&[("map.json", b"{}", false)]);
+        // Claims the entry's name runs well past the end of the directory.
+        let mut long_name = bytes.clone();
+        let entry = long_name.len() - 22 - (46 + "map.json".len());
+        long_name[entry + 28..entry + 30].copy_from_slice(&500u16.to_le_bytes());
+        let (result, _) = unpack("long-name", &long_name);
+        assert!(matches!(result, Err(PackageError::Zip { .. })));
+
+        // Cuts the file off partway through the directory, keeping the end record.
+        let mut cut = bytes[..bytes.len() - 22 - 30].to_vec();
+        cut.extend(&bytes[bytes.len() - 22..]);
+        let (result, _) = unpack("cut", &cut);
+        assert!(matches!(result, Err(PackageError::Zip { .. })));
+    }
+}
@@ -0,0 +1,100 @@
+// menu.rs
+use winit::keyboard::KeyCode;
+use crate::{config, hud::HudRenderer, map_package::MapPackage, state::GpuContext, text::TextRenderer};
+
+pub enum MenuAction {
+    None,
+    Moved,
+    Selected,
+}
+
+// Map selection shown before loading when packages are installed. Each row names a map
+// and its description; the window title repeats the highlighted one.
+pub struct MapMenu {
+    packages: Vec<MapPackage>,
+    pub selected: usize,
+    hud: HudRenderer,
+    text: TextRenderer,
+}
+
+impl MapMenu {
+    pub fn new(ctx: &GpuContext, packages: Vec<MapPackage>) -> Self {
+        Self {
+            packages, selected: 0,
+            hud: HudRenderer::new(&ctx.device, ctx.config.format, 4, None),
+            text: TextRenderer::new(&ctx.device, ctx.config.format, 4, None),
+        }
+    }
+
+    pub fn selected_package(&self) -> &MapPackage {
+        &self.packages[self.selected]
+    }
+
+    pub fn title(&self) -> String {
+        let package = &self.packages[self.selected];
+        let description = if package.manifest.description.is_empty() { String::new() } else { format!(" - {}", package.manifest.description) };
+        format!(
+            "{} | Select map ({}/{}): {}{} | Up/Down, Enter", config::WINDOW_TITLE,
+            self.selected + 1, self.packages.len(), package.summary(), description,
+        )
+    }
+
+    pub fn key(&mut self, key: KeyCode) -> MenuAction {
+        let count = self.packages.len();
+        match key {
+            KeyCode::ArrowUp | KeyCode::KeyW => self.selected = (self.selected + count - 1) % count,
+            KeyCode::ArrowDown | KeyCode::KeyS => self.selected = (self.selected + 1) % count,
+            KeyCode::Enter | KeyCode::NumpadEnter | KeyCode::Space => return MenuAction::Selected,
+            _ => {
+                let digit = match key {
+                    KeyCode::Digit1 => 1, KeyCode::Digit2 => 2, KeyCode::Digit3 => 3, KeyCode::Digit4 => 4, KeyCode::Digit5 => 5,
+                    KeyCode::Digit6 => 6, KeyCode::Digit7 => 7, KeyCode::Digit8 => 8, KeyCode::Digit9 => 9,
+                    _ => return MenuAction::None,
+                };
+                if digit > count { return MenuAction::None; }
+                self.selected = digit - 1;
+            }
+        }
+        MenuAction::Moved
+    }
+
+    pub fn render(&mut self, ctx: &GpuContext) {
+        let Ok(output) = ctx.surface.get_current_texture() else { return };
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+        let screen = [ctx.config.width as f32, ctx.config.height as f32];
+        let labels: Vec<String> = self.packages.iter().enumerate().map(|(i, p)| format!("{}. {}", i + 1, p.summary())).collect();
+        let widest = labels.iter().map(|l| self.text.measure(l, 18.0)).fold(0.0, f32::max);
+        let (row_w, row_h, gap) = (widest.max(420.0) + 24.0, 48.0, 10.0);
+        let total_h = self.packages.len() as f32 * (row_h + gap) - gap;
+        let x0 = (screen[0] - row_w) * 0.5;
+        let y0 = (screen[1] - total_h) * 0.5;
+        for (i, (label, package)) in labels.iter().zip(&self.packages).enumerate() {
+            let y = y0 + i as f32 * (row_h + gap);
+            let fill = if i == self.selected { [0.85, 0.85, 0.85, 1.0] } else { [0.15, 0.15, 0.15, 1.0] };
+            self.hud.rect([x0, y], [x0 + row_w, y + row_h], fill);
+            let (ink, dim) = if i == self.selected { ([0.05, 0.05, 0.05, 1.0], [0.3, 0.3, 0.3, 1.0]) } else { ([0.95, 0.95, 0.95, 1.0], [0.6, 0.6, 0.6, 1.0]) };
+            self.text.text(label, [x0 + 12.0, y + 5.0], 18.0, ink);
+            self.text.text(&package.manifest.description, [x0 + 12.0, y + 27.0], 14.0, dim);
+        }
+        self.hud.prepare(&ctx.device, &ctx.queue, screen);
+        self.text.prepare(&ctx.device, &ctx.queue, screen);
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Map Menu"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &ctx.msaa_texture, resolve_target: Some(&view),
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None, occlusion_query_set: None,
+            });
+            self.hud.draw(&mut pass);
+            self.text.draw(&mut pass);
+        }
+        ctx.queue.submit(std::iter::once(encoder.finish()));
+        output.present();
+    }
+}
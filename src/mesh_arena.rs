@@ -0,0 +1,328 @@
+// mesh_arena.rs
+use std::ops::Range;
+use std::sync::{Arc, atomic::{AtomicU8, Ordering}};
+use wgpu::util::DrawIndexedIndirectArgs;
+use crate::{config, upload::Staging, vertex::PackedVertex};
+
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+// Free ranges of a page buffer in elements, sorted and coalesced.
+struct FreeList(Vec<Range<u32>>);
+
+impl FreeList {
+    fn new(capacity: u32) -> Self {
+        Self(std::iter::once(0..capacity).collect())
+    }
+
+    fn alloc(&mut self, len: u32) -> Option<Range<u32>> {
+        let slot = self.0.iter().position(|r| r.len() as u32 >= len)?;
+        let start = self.0[slot].start;
+        self.0[slot].start += len;
+        if self.0[slot].is_empty() { self.0.remove(slot); }
+        Some(start..start + len)
+    }
+
+    fn free(&mut self, range: Range<u32>) {
+        let at = self.0.partition_point(|r| r.start < range.start);
+        self.0.insert(at, range);
+        if at + 1 < self.0.len() && self.0[at].end == self.0[at + 1].start {
+            self.0[at].end = self.0.remove(at + 1).end;
+        }
+        if at > 0 && self.0[at - 1].end == self.0[at].start {
+            self.0[at - 1].end = self.0.remove(at).end;
+        }
+    }
+}
+
+// One shared vertex and index buffer holding the meshes of many chunks.
+pub struct MeshPage {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    // Every mesh in the page uses this; see index_format().
+    pub index_format: wgpu::IndexFormat,
+    vertex_free: FreeList,
+    index_free: FreeList,
+    live: usize,
+}
+
+// Where a chunk's mesh lives: its page and element ranges within that page's buffers.
+#[derive(Debug, Clone)]
+pub struct MeshAllocation {
+    pub page: usize,
+    pub vertices: Range<u32>,
+    pub indices: Range<u32>,
+}
+
+// 16-bit indices for meshes they can address, halving their size; 32-bit for larger ones.
+pub fn index_format(vertex_count: usize) -> wgpu::IndexFormat {
+    if vertex_count <= 1 << 16 { wgpu::IndexFormat::Uint16 } else { wgpu::IndexFormat::Uint32 }
+}
+
+pub fn index_size(format: wgpu::IndexFormat) -> u64 {
+    match format {
+        wgpu::IndexFormat::Uint16 => 2,
+        wgpu::IndexFormat::Uint32 => 4,
+    }
+}
+
+// Packs chunk meshes into a few large buffers so chunks sharing a page can be drawn with
+// one bind and a single indirect call. Pages are released once their last chunk is freed.
+pub struct MeshArena {
+    pages: Vec<Option<MeshPage>>,
+}
+
+impl MeshArena {
+    pub fn new() -> Self {
+        Self { pages: Vec::new() }
+    }
+
+    pub fn page(&self, index: usize) -> &MeshPage {
+        self.pages[index].as_ref().expect("allocation refers to a released page")
+    }
+
+    // Size of every page's buffers, used or not; freeing a mesh only gives VRAM back once
+    // its page empties.
+    pub fn gpu_bytes(&self) -> u64 {
+        (0..self.pages.len()).map(|index| self.page_bytes(index)).sum()
+    }
+
+    // What releasing page `index` gives back, or 0 if it's already gone.
+    pub fn page_bytes(&self, index: usize) -> u64 {
+        self.pages[index].as_ref().map_or(0, |page| page.vertex_buffer.size() + page.index_buffer.size())
+    }
+
+    // Meshes go in pages of their index format. 16-bit index ranges are kept to even lengths
+    // so every copy stays 4-byte aligned; the padding is never drawn.
+    pub fn upload(&mut self, staging: &mut Staging, vertices: &[PackedVertex], indices: &[u32]) -> MeshAllocation {
+        let format = index_format(vertices.len());
+        let vertex_len = vertices.len() as u32;
+        let index_len = if format == wgpu::IndexFormat::Uint16 { indices.len().next_multiple_of(2) } else { indices.len() } as u32;
+        let found = self.pages.iter_mut().enumerate().find_map(|(i, page)| {
+            let page = page.as_mut().filter(|page| page.index_format == format)?;
+            let vertices = page.vertex_free.alloc(vertex_len)?;
+            let Some(indices) = page.index_free.alloc(index_len) else {
+                page.vertex_free.free(vertices);
+                return None;
+            };
+            Some(MeshAllocation { page: i, vertices, indices })
+        });
+        let alloc = found.unwrap_or_else(|| {
+            // Oversized chunks get a page of their own.
+            let page = Self::create_page(staging.device, vertex_len.max(config::MESH_PAGE_VERTICES), index_len.max(config::MESH_PAGE_INDICES), format);
+            let slot = self.pages.iter().position(Option::is_none).unwrap_or(self.pages.len());
+            if slot == self.pages.len() { self.pages.push(None); }
+            let page = self.pages[slot].insert(page);
+            MeshAllocation { page: slot, vertices: page.vertex_free.alloc(vertex_len).unwrap(), indices: page.index_free.alloc(index_len).unwrap() }
+        });
+
+        let page = self.pages[alloc.page].as_mut().unwrap();
+        page.live += 1;
+        let vertex_size = std::mem::size_of::<PackedVertex>() as u64;
+        staging.write(&page.vertex_buffer, alloc.vertices.start as u64 * vertex_size, bytemuck::cast_slice(vertices));
+        let offset = alloc.indices.start as u64 * index_size(format);
+        match format {
+            wgpu::IndexFormat::Uint16 => {
+                let mut short: Vec<u16> = indices.iter().map(|&i| i as u16).collect();
+                short.resize(index_len as usize, 0);
+                staging.write(&page.index_buffer, offset, bytemuck::cast_slice(&short));
+            }
+            wgpu::IndexFormat::Uint32 => staging.write(&page.index_buffer, offset, bytemuck::cast_slice(indices)),
+        }
+        alloc
+    }
+
+    pub fn free(&mut self, alloc: &MeshAllocation) {
+        let Some(page) = self.pages[alloc.page].as_mut() else { return };
+        page.vertex_free.free(alloc.vertices.clone());
+        page.index_free.free(alloc.indices.clone());
+        page.live -= 1;
+        if page.live == 0 && let Some(page) = self.pages[alloc.page].take() {
+            page.vertex_buffer.destroy();
+            page.index_buffer.destroy();
+        }
+    }
+
+    // Copies meshes back out of their pages, each as its vertices and its first `index count`
+    // indices, for the mesh cache to keep once they're evicted. The copy is mapped without
+    // waiting on the GPU; see MeshReadback. None if there's nothing to copy.
+    pub fn read_back(&self, device: &wgpu::Device, queue: &wgpu::Queue, meshes: &[(&MeshAllocation, u32)]) -> Option<MeshReadback> {
+        let vertex_size = std::mem::size_of::<PackedVertex>() as u64;
+        // Each mesh's vertices then indices, one mesh after another; the sizes keep every copy 4-byte aligned.
+        let mut size = 0;
+        let spans: Vec<ReadbackSpan> = meshes.iter().map(|(alloc, index_count)| {
+            let index_format = self.page(alloc.page).index_format;
+            let vertex_bytes = alloc.vertices.len() as u64 * vertex_size;
+            let index_bytes = alloc.indices.len() as u64 * index_size(index_format);
+            size += vertex_bytes + index_bytes;
+            ReadbackSpan { offset: size - vertex_bytes - index_bytes, vertex_bytes, index_bytes, index_format, index_count: *index_count }
+        }).collect();
+        if size == 0 { return None; }
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Mesh Read Back"), size, usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Mesh Read Back") });
+        for ((alloc, _), span) in meshes.iter().zip(&spans) {
+            let page = self.page(alloc.page);
+            encoder.copy_buffer_to_buffer(&page.vertex_buffer, alloc.vertices.start as u64 * vertex_size, &buffer, span.offset, span.vertex_bytes);
+            encoder.copy_buffer_to_buffer(&page.index_buffer, alloc.indices.start as u64 * index_size(page.index_format), &buffer, span.offset + span.vertex_bytes, span.index_bytes);
+        }
+        // Pages freed after this keep their buffers until the copy has run.
+        queue.submit(Some(encoder.finish()));
+
+        let map_state = Arc::new(AtomicU8::new(MAP_PENDING));
+        let state = map_state.clone();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            if let Err(e) = &result { log::warn!("Couldn't read chunk meshes back from the GPU: {}", e); }
+            state.store(if result.is_ok() { MAP_DONE } else { MAP_FAILED }, Ordering::Release);
+        });
+        Some(MeshReadback { buffer, spans, map_state })
+    }
+
+    fn create_page(device: &wgpu::Device, vertex_capacity: u32, index_capacity: u32, index_format: wgpu::IndexFormat) -> MeshPage {
+        let buffer = |label, size, usage| device.create_buffer(&wgpu::BufferDescriptor { label: Some(label), size, usage, mapped_at_creation: false });
+        MeshPage {
+            vertex_buffer: buffer("Mesh Page V", vertex_capacity as u64 * std::mem::size_of::<PackedVertex>() as u64, wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC),
+            index_buffer: buffer("Mesh Page I", index_capacity as u64 * index_size(index_format), wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC),
+            index_format,
+            vertex_free: FreeList::new(vertex_capacity),
+            index_free: FreeList::new(index_capacity),
+            live: 0,
+        }
+    }
+}
+
+// Where one mesh sits in a readback buffer.
+struct ReadbackSpan {
+    offset: u64,
+    vertex_bytes: u64,
+    index_bytes: u64,
+    index_format: wgpu::IndexFormat,
+    // Indices to keep; the rest is padding or free space.
+    index_count: u32,
+}
+
+// Meshes on their way back from the GPU, mapped once the device gets to it. Poll the device,
+// then check is_ready() before taking the copies.
+pub struct MeshReadback {
+    buffer: wgpu::Buffer,
+    spans: Vec<ReadbackSpan>,
+    // Set by the map callback: MAP_DONE or MAP_FAILED.
+    map_state: Arc<AtomicU8>,
+}
+
+impl MeshReadback {
+    pub fn is_ready(&self) -> bool {
+        self.map_state.load(Ordering::Acquire) != MAP_PENDING
+    }
+
+    // The copies in the order they were asked for, or None if the mapping failed.
+    pub fn copies(&self) -> Option<Vec<(Vec<PackedVertex>, Vec<u32>)>> {
+        if self.map_state.load(Ordering::Acquire) != MAP_DONE { return None; }
+        let bytes = self.buffer.slice(..).get_mapped_range();
+        Some(self.spans.iter().map(|span| {
+            let (offset, vertex_bytes, index_bytes) = (span.offset as usize, span.vertex_bytes as usize, span.index_bytes as usize);
+            let vertices = elements(&bytes[offset..offset + vertex_bytes]);
+            let indices = &bytes[offset + vertex_bytes..offset + vertex_bytes + index_bytes];
+            let mut indices: Vec<u32> = match span.index_format {
+                wgpu::IndexFormat::Uint16 => elements::<u16>(indices).into_iter().map(u32::from).collect(),
+                wgpu::IndexFormat::Uint32 => elements(indices),
+            };
+            indices.truncate(span.index_count as usize);
+            (vertices, indices)
+        }).collect())
+    }
+}
+
+// Bytes mapped from the GPU or read from a file need not be aligned for T, so each element is copied out.
+pub fn elements<T: bytemuck::Pod>(bytes: &[u8]) -> Vec<T> {
+    bytes.chunks_exact(std::mem::size_of::<T>()).map(bytemuck::pod_read_unaligned).collect()
+}
+
+// Consecutive draw commands of one view that read from the same page.
+pub struct DrawBatch {
+    pub view: usize,
+    pub page: usize,
+    pub commands: Range<u32>,
+}
+
+// Per-frame chunk draw commands. Uses multi_draw_indexed_indirect where the adapter has it,
+// otherwise replays the same commands as plain draw_indexed calls. Each command's
+// first_instance is the draw's position in the list passed to build(), so per-draw data
+// uploaded in that order can be read through an instance-rate vertex buffer.
+pub struct IndirectDraws {
+    multi_draw: bool,
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    commands: Vec<DrawIndexedIndirectArgs>,
+    pub batches: Vec<DrawBatch>,
+}
+
+impl IndirectDraws {
+    const STRIDE: u64 = std::mem::size_of::<DrawIndexedIndirectArgs>() as u64;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let multi_draw = device.features().contains(wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE);
+        log::info!("Chunk draws: {}", if multi_draw { "multi-draw indirect" } else { "per-chunk draw_indexed fallback" });
+        let capacity = 1024;
+        Self { multi_draw, buffer: Self::create_buffer(device, capacity), capacity, commands: Vec::new(), batches: Vec::new() }
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Chunk Draw Commands"), size: capacity as u64 * Self::STRIDE,
+            usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+        })
+    }
+
+    // Builds this frame's commands from each view's visible (page, first index, index count, base vertex) draws,
+    // given nearest first. Draws are grouped by page, pages in order of their nearest draw, and
+    // stay nearest first within a page.
+    pub fn build(&mut self, views: Vec<Vec<(usize, u32, u32, i32)>>) {
+        self.commands.clear();
+        self.batches.clear();
+        let mut next_instance = 0;
+        for (view, draws) in views.into_iter().enumerate() {
+            let mut draws: Vec<(u32, (usize, u32, u32, i32))> = draws.into_iter().enumerate().map(|(i, d)| (next_instance + i as u32, d)).collect();
+            next_instance += draws.len() as u32;
+            let mut page_order: Vec<usize> = Vec::new();
+            for (_, d) in &draws {
+                if !page_order.contains(&d.0) { page_order.push(d.0); }
+            }
+            draws.sort_by_key(|(_, d)| page_order.iter().position(|&page| page == d.0));
+            for (first_instance, (page, first_index, index_count, base_vertex)) in draws {
+                let next = self.commands.len() as u32;
+                match self.batches.last_mut() {
+                    Some(batch) if batch.view == view && batch.page == page => batch.commands.end += 1,
+                    _ => self.batches.push(DrawBatch { view, page, commands: next..next + 1 }),
+                }
+                self.commands.push(DrawIndexedIndirectArgs { index_count, instance_count: 1, first_index, base_vertex, first_instance });
+            }
+        }
+    }
+
+    // Uploads the commands; call before the pass drawing them.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.multi_draw || self.commands.is_empty() { return; }
+        if self.commands.len() > self.capacity {
+            self.capacity = self.commands.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        let bytes: Vec<u8> = self.commands.iter().flat_map(|c| c.as_bytes().iter().copied()).collect();
+        queue.write_buffer(&self.buffer, 0, &bytes);
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, arena: &'a MeshArena, batch: &DrawBatch) {
+        let page = arena.page(batch.page);
+        pass.set_vertex_buffer(0, page.vertex_buffer.slice(..));
+        pass.set_index_buffer(page.index_buffer.slice(..), page.index_format);
+        if self.multi_draw {
+            pass.multi_draw_indexed_indirect(&self.buffer, batch.commands.start as u64 * Self::STRIDE, batch.commands.len() as u32);
+            return;
+        }
+        for c in &self.commands[batch.commands.start as usize..batch.commands.end as usize] {
+            pass.draw_indexed(c.first_index..c.first_index + c.index_count, c.base_vertex, c.first_instance..c.first_instance + 1);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+// mesh_cache.rs
+use std::{collections::{HashMap, VecDeque}, io, ops::Range, path::PathBuf};
+use crate::{config_file, mesh_arena::{elements, MeshReadback}, upload::PendingMesh, vertex::PackedVertex};
+
+// What the cache needs of an evicted mesh besides the vertices and indices read back.
+pub struct Evicted {
+    pub coord: (i32, i32),
+    pub index_count: u32,
+    pub lod_indices: Range<u32>,
+    pub gpu_bytes: u64,
+    // Whether a copy of its water follows the mesh's in the readback.
+    pub water: bool,
+}
+
+// CPU copies of the chunk meshes the GPU budget evicted, so they can be uploaded again when the
+// player comes back instead of reloading the map. Beyond graphics.mesh_cache_mb the least
+// recently evicted are written to the cache directory and read back from there.
+pub struct MeshCache {
+    // Each mesh with the stamp it was stored under.
+    memory: HashMap<(i32, i32), (PendingMesh, u64)>,
+    memory_bytes: u64,
+    // Stored meshes oldest first. Entries whose mesh has been taken or stored again since no
+    // longer match its stamp and are skipped.
+    order: VecDeque<((i32, i32), u64)>,
+    // Spilled meshes and their sizes; files from earlier runs are never read.
+    on_disk: HashMap<(i32, i32), u64>,
+    // Meshes evicted without a copy and their sizes; the loader builds them again.
+    dropped: HashMap<(i32, i32), u64>,
+    // Readbacks still in flight under their stamps, and the stamp each chunk awaits. Chunks
+    // discarded or evicted again since don't match and are skipped when their copy lands.
+    reading: Vec<(MeshReadback, Vec<Evicted>, u64)>,
+    awaited: HashMap<(i32, i32), u64>,
+    clock: u64,
+}
+
+impl MeshCache {
+    pub fn new() -> Self {
+        Self {
+            memory: HashMap::new(), memory_bytes: 0, order: VecDeque::new(), on_disk: HashMap::new(),
+            dropped: HashMap::new(), reading: Vec::new(), awaited: HashMap::new(), clock: 0,
+        }
+    }
+
+    // GPU bytes of the evicted mesh for `coord`, if it can be restored: from a copy, or from
+    // the loader if it was dropped. None while its copy is still being read back.
+    pub fn size(&self, coord: (i32, i32)) -> Option<u64> {
+        self.memory.get(&coord).map(|(mesh, _)| mesh.gpu_bytes())
+            .or_else(|| self.on_disk.get(&coord).copied())
+            .or_else(|| self.dropped.get(&coord).copied())
+    }
+
+    // Keeps the meshes in `readback` once the GPU has copied them; see collect().
+    pub fn read(&mut self, readback: MeshReadback, meshes: Vec<Evicted>) {
+        self.clock += 1;
+        for mesh in &meshes { self.discard(mesh.coord); }
+        self.awaited.extend(meshes.iter().map(|mesh| (mesh.coord, self.clock)));
+        self.reading.push((readback, meshes, self.clock));
+    }
+
+    // Notes a mesh evicted without a copy, `bytes` in size.
+    pub fn drop_mesh(&mut self, coord: (i32, i32), bytes: u64) {
+        self.discard(coord);
+        self.dropped.insert(coord, bytes);
+    }
+
+    // Stores the copies that have arrived. Ones that failed to map are dropped instead.
+    pub fn collect(&mut self, device: &wgpu::Device) {
+        if self.reading.is_empty() { return; }
+        device.poll(wgpu::Maintain::Poll);
+        let (ready, reading) = std::mem::take(&mut self.reading).into_iter().partition(|(readback, _, _)| readback.is_ready());
+        self.reading = reading;
+        for (readback, meshes, stamp) in ready {
+            let mut copies = readback.copies().map(Vec::into_iter);
+            for mesh in meshes {
+                let copy = copies.as_mut().and_then(Iterator::next);
+                let water = if mesh.water { copies.as_mut().and_then(Iterator::next) } else { None };
+                if self.awaited.get(&mesh.coord) != Some(&stamp) { continue; }
+                self.awaited.remove(&mesh.coord);
+                match copy {
+                    Some((vertices, indices)) => self.store(PendingMesh {
+                        coord: mesh.coord, vertices, indices, index_count: mesh.index_count, lod_indices: mesh.lod_indices, water,
+                    }),
+                    None => { self.dropped.insert(mesh.coord, mesh.gpu_bytes); }
+                }
+            }
+        }
+    }
+
+    pub fn store(&mut self, mesh: PendingMesh) {
+        self.discard(mesh.coord);
+        self.clock += 1;
+        self.memory_bytes += mesh.gpu_bytes();
+        self.order.push_back((mesh.coord, self.clock));
+        self.memory.insert(mesh.coord, (mesh, self.clock));
+        // Stale entries are dropped in one go once they outnumber the live ones.
+        if self.order.len() > 2 * self.memory.len() + 64 {
+            let memory = &self.memory;
+            self.order.retain(|(coord, stamp)| memory.get(coord).is_some_and(|(_, stored)| stored == stamp));
+        }
+
+        let budget = config_file::get().graphics.mesh_cache_mb * 1024 * 1024;
+        while self.memory_bytes > budget {
+            let Some((coord, stamp)) = self.order.pop_front() else { break };
+            if self.memory.get(&coord).is_none_or(|(_, stored)| *stored != stamp) { continue; }
+            let (mesh, _) = self.memory.remove(&coord).unwrap();
+            let bytes = mesh.gpu_bytes();
+            self.memory_bytes -= bytes;
+            match write(&mesh) {
+                Ok(()) => { self.on_disk.insert(coord, bytes); }
+                Err(e) => log::warn!("Couldn't write chunk {:?}'s mesh to {}: {}", coord, cache_dir().display(), e),
+            }
+        }
+    }
+
+    // Removes and returns the mesh for `coord`, from memory or disk. None if there's no copy
+    // to take, as for a dropped mesh.
+    pub fn take(&mut self, coord: (i32, i32)) -> Option<PendingMesh> {
+        if self.dropped.remove(&coord).is_some() { return None; }
+        if let Some((mesh, _)) = self.memory.remove(&coord) {
+            self.memory_bytes -= mesh.gpu_bytes();
+            return Some(mesh);
+        }
+        self.on_disk.remove(&coord)?;
+        let mesh = read(coord);
+        std::fs::remove_file(path(coord)).ok();
+        mesh.map_err(|e| log::warn!("Couldn't read chunk {:?}'s mesh back: {}", coord, e)).ok()
+    }
+
+    // Forgets the mesh for `coord`, once the loader has sent the chunk again.
+    pub fn discard(&mut self, coord: (i32, i32)) {
+        if let Some((mesh, _)) = self.memory.remove(&coord) { self.memory_bytes -= mesh.gpu_bytes(); }
+        if self.on_disk.remove(&coord).is_some() { std::fs::remove_file(path(coord)).ok(); }
+        self.dropped.remove(&coord);
+        self.awaited.remove(&coord);
+    }
+}
+
+impl Drop for MeshCache {
+    fn drop(&mut self) {
+        for coord in self.on_disk.keys() { std::fs::remove_file(path(*coord)).ok(); }
+    }
+}
+
+// paths.mesh_cache, or skyroam/meshes under the platform's cache directory: XDG_CACHE_HOME or
+// ~/.cache on Linux, ~/Library/Caches on macOS and %LOCALAPPDATA% on Windows.
+fn cache_dir() -> PathBuf {
+    if let Some(dir) = &config_file::get().paths.mesh_cache { return PathBuf::from(dir); }
+    let var = |name| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let base = if cfg!(windows) {
+        var("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Caches"))
+    } else {
+        var("XDG_CACHE_HOME").or_else(|| var("HOME").map(|home| home.join(".cache")))
+    };
+    base.unwrap_or_else(std::env::temp_dir).join("skyroam").join("meshes")
+}
+
+// Named by process as well, since every running copy of the game shares the directory.
+fn path(coord: (i32, i32)) -> PathBuf {
+    cache_dir().join(format!("{}_{}_{}.mesh", std::process::id(), coord.0, coord.1))
+}
+
+// Counts, then the vertices and indices, then the water's.
+fn write(mesh: &PendingMesh) -> io::Result<()> {
+    std::fs::create_dir_all(cache_dir())?;
+    let (water_vertices, water_indices) = mesh.water.as_ref().map_or((&[][..], &[][..]), |(v, i)| (&v[..], &i[..]));
+    let header = [
+        mesh.vertices.len() as u32, mesh.indices.len() as u32, mesh.index_count, mesh.lod_indices.start, mesh.lod_indices.end,
+        water_vertices.len() as u32, water_indices.len() as u32,
+    ];
+    let mut bytes = Vec::with_capacity(mesh.gpu_bytes() as usize + std::mem::size_of_val(&header));
+    bytes.extend_from_slice(bytemuck::cast_slice(&header));
+    bytes.extend_from_slice(bytemuck::cast_slice(&mesh.vertices));
+    bytes.extend_from_slice(bytemuck::cast_slice(&mesh.indices));
+    bytes.extend_from_slice(bytemuck::cast_slice(water_vertices));
+    bytes.extend_from_slice(bytemuck::cast_slice(water_indices));
+    std::fs::write(path(mesh.coord), bytes)
+}
+
+fn read(coord: (i32, i32)) -> io::Result<PendingMesh> {
+    let bytes = std::fs::read(path(coord))?;
+    let mut rest = &bytes[..];
+    let [vertices, indices, index_count, lod_start, lod_end, water_vertices, water_indices]: [u32; 7] = bytemuck::pod_read_unaligned(split(&mut rest, 28)?);
+    let vertex_size = std::mem::size_of::<PackedVertex>();
+    let vertices = elements(split(&mut rest, vertices as usize * vertex_size)?);
+    let indices = elements(split(&mut rest, indices as usize * 4)?);
+    let water = (water_indices > 0).then(|| -> io::Result<_> {
+        Ok((elements(split(&mut rest, water_vertices as usize * vertex_size)?), elements(split(&mut rest, water_indices as usize * 4)?)))
+    }).transpose()?;
+    Ok(PendingMesh { coord, vertices, indices, index_count, lod_indices: lod_start..lod_end, water })
+}
+
+fn split<'a>(rest: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if rest.len() < len { return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "mesh file is cut short")); }
+    let (head, tail) = rest.split_at(len);
+    *rest = tail;
+    Ok(head)
+}
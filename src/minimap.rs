@@ -0,0 +1,210 @@
+// minimap.rs
+use glam::Vec2;
+use wgpu::util::DeviceExt;
+use crate::{camera::Camera, config, shader, shader_cache, text::TextRenderer, world::{LocalCollisionGrid, RoadSegment, RoofTriangle, World}};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MinimapUniform {
+    // xy: disc center in pixels, z: radius in pixels.
+    disc: [f32; 4],
+    screen: [f32; 4],
+    // xy: the player's texture coordinate, z: texture units per pixel.
+    view: [f32; 4],
+    // xy: the player's heading on the ground (x, z).
+    heading: [f32; 4],
+    arrow_color: [f32; 4],
+}
+
+fn texel(color: [f32; 4]) -> [u8; 4] {
+    color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+// Triangles are binned into every collision cell they touch, so each copy comes with its
+// cell's bounds and only fills the texels inside them.
+fn binned_triangles<'a>(grid: &'a LocalCollisionGrid, cells: &'a [Vec<RoofTriangle>]) -> impl Iterator<Item = ([Vec2; 3], Vec2, Vec2)> + 'a {
+    cells.iter().enumerate().flat_map(move |(i, tris)| {
+        let min = grid.chunk_offset + Vec2::new((i % grid.grid_dim) as f32, (i / grid.grid_dim) as f32) * grid.cell_size;
+        tris.iter().map(move |t| (t.corners, min, min + Vec2::splat(grid.cell_size)))
+    })
+}
+
+// Corner map for player one. Building footprints, roads and water around the player are
+// rasterized on the CPU into a texture MINIMAP_TEXTURE_EXTENT meters across, redrawn when
+// chunks stream in or the player nears its edge; the disc samples it turned to the player's
+// heading and is drawn with the UI after the composite.
+pub struct Minimap {
+    pub visible: bool,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    texture: wgpu::Texture,
+    pixels: Vec<[u8; 4]>,
+    // World x, z at the middle of the texture, and how many chunks were loaded when it was drawn.
+    center: Option<Vec2>,
+    drawn_chunks: usize,
+}
+
+impl Minimap {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let size = config::MINIMAP_TEXELS;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Minimap"), size: wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+            mip_level_count: 1, sample_count: 1, dimension: wgpu::TextureDimension::D2, format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST, view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear, min_filter: wgpu::FilterMode::Linear, ..Default::default()
+        });
+        let shader = shader_cache::module(device, "Minimap Shader", shader::MINIMAP_SHADER);
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Minimap Uniform"), contents: bytemuck::cast_slice(&[<MinimapUniform as bytemuck::Zeroable>::zeroed()]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry { binding: 0, visibility: wgpu::ShaderStages::VERTEX_FRAGMENT, ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None },
+                wgpu::BindGroupLayoutEntry { binding: 1, visibility: wgpu::ShaderStages::FRAGMENT, ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Float { filterable: true }, view_dimension: wgpu::TextureViewDimension::D2, multisampled: false }, count: None },
+                wgpu::BindGroupLayoutEntry { binding: 2, visibility: wgpu::ShaderStages::FRAGMENT, ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering), count: None },
+            ], label: None,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ], label: None,
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[&bind_group_layout], push_constant_ranges: &[] });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Minimap Pipeline"), layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState { module: &shader, entry_point: "vs_main", buffers: &[] },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader, entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState { format, blend: Some(wgpu::BlendState::ALPHA_BLENDING), write_mask: wgpu::ColorWrites::ALL })],
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleStrip, ..Default::default() },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let pixels = vec![texel(config::MINIMAP_BACKGROUND); (size * size) as usize];
+        Self { visible: config::MINIMAP, pipeline, uniform_buffer, bind_group, texture, pixels, center: None, drawn_chunks: 0 }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    // Redraws the texture if needed and places the disc in the bottom-right corner of
+    // `viewport`, queueing the north marker on its rim.
+    pub fn prepare(&mut self, queue: &wgpu::Queue, world: &World, text: &mut TextRenderer, screen: [f32; 2], viewport: [f32; 4], camera: &Camera) {
+        let player = Vec2::new(camera.eye.x as f32, camera.eye.z as f32);
+        let margin = config::MINIMAP_TEXTURE_EXTENT * 0.5 - config::MINIMAP_RANGE;
+        let stale = self.center.is_none_or(|center| (player - center).abs().max_element() > margin);
+        if stale || world.chunks.len() != self.drawn_chunks {
+            let center = if stale { player } else { self.center.unwrap_or(player) };
+            self.redraw(queue, world, center);
+        }
+
+        let center = self.center.unwrap_or(player);
+        let uv = (player - center) / config::MINIMAP_TEXTURE_EXTENT + 0.5;
+        let forward = camera.forward();
+        let heading = Vec2::new(forward.x as f32, forward.z as f32).try_normalize().unwrap_or(Vec2::NEG_Y);
+        let radius = config::MINIMAP_RADIUS;
+        let [x, y, w, h] = viewport;
+        let disc = [x + w - radius - 16.0, y + h - radius - 16.0];
+        let uniform = MinimapUniform {
+            disc: [disc[0], disc[1], radius, 0.0],
+            screen: [screen[0], screen[1], 0.0, 0.0],
+            view: [uv.x, uv.y, config::MINIMAP_RANGE / radius / config::MINIMAP_TEXTURE_EXTENT, 0.0],
+            heading: [heading.x, heading.y, 0.0, 0.0],
+            arrow_color: config::PLAYER_COLORS[0],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        // Where north (-z) ends up on the turned disc.
+        let north = Vec2::new(-heading.x, heading.y) * (radius - 12.0);
+        text.text_centered("N", disc[0] + north.x, disc[1] + north.y - 8.0, 15.0, [1.0, 0.3, 0.25, 1.0]);
+    }
+
+    fn redraw(&mut self, queue: &wgpu::Queue, world: &World, center: Vec2) {
+        let size = config::MINIMAP_TEXELS;
+        let texel_size = config::MINIMAP_TEXTURE_EXTENT / size as f32;
+        let origin = center - Vec2::splat(config::MINIMAP_TEXTURE_EXTENT * 0.5);
+        let end = origin + Vec2::splat(config::MINIMAP_TEXTURE_EXTENT);
+        self.pixels.fill(texel(config::MINIMAP_BACKGROUND));
+
+        let (water, road, building) = (texel(config::MINIMAP_WATER_COLOR), texel(config::MINIMAP_ROAD_COLOR), texel(config::MINIMAP_BUILDING_COLOR));
+        let nearby = world.chunks.values().filter(|c| c.max.x > origin.x && c.min.x < end.x && c.max.y > origin.y && c.min.y < end.y);
+        for chunk in nearby {
+            let grid = &chunk.collision;
+            for (corners, min, max) in binned_triangles(grid, &grid.water_cells) {
+                self.fill_triangle(corners.map(|c| (c - origin) / texel_size), (min - origin) / texel_size, (max - origin) / texel_size, water);
+            }
+            for segment in &grid.roads {
+                self.fill_road(segment, origin, texel_size, road);
+            }
+            for (corners, min, max) in binned_triangles(grid, &grid.roof_cells) {
+                self.fill_triangle(corners.map(|c| (c - origin) / texel_size), (min - origin) / texel_size, (max - origin) / texel_size, building);
+            }
+        }
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture { texture: &self.texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+            bytemuck::cast_slice(&self.pixels),
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(size * 4), rows_per_image: Some(size) },
+            wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+        );
+        self.center = Some(center);
+        self.drawn_chunks = world.chunks.len();
+    }
+
+    // Texel range covering `min`..`max` in texel units, clamped to the texture.
+    fn texel_range(min: Vec2, max: Vec2) -> Option<([usize; 2], [usize; 2])> {
+        let size = config::MINIMAP_TEXELS as f32;
+        let (lo, hi) = (min.floor().max(Vec2::ZERO), max.ceil().min(Vec2::splat(size)));
+        (lo.x < hi.x && lo.y < hi.y).then_some(([lo.x as usize, lo.y as usize], [hi.x as usize, hi.y as usize]))
+    }
+
+    // Fills the texels whose centers lie inside the triangle and the clip box, all in texel units.
+    fn fill_triangle(&mut self, [a, b, c]: [Vec2; 3], clip_min: Vec2, clip_max: Vec2, color: [u8; 4]) {
+        let Some((lo, hi)) = Self::texel_range(a.min(b).min(c).max(clip_min), a.max(b).max(c).min(clip_max)) else { return };
+        let area = (b - a).perp_dot(c - a);
+        if area == 0.0 { return; }
+        let size = config::MINIMAP_TEXELS as usize;
+        for ty in lo[1]..hi[1] {
+            for tx in lo[0]..hi[0] {
+                let p = Vec2::new(tx as f32 + 0.5, ty as f32 + 0.5);
+                if p.cmplt(clip_min).any() || p.cmpge(clip_max).any() { continue; }
+                let (w0, w1, w2) = ((c - b).perp_dot(p - b), (a - c).perp_dot(p - c), (b - a).perp_dot(p - a));
+                let inside = if area > 0.0 { w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0 } else { w0 <= 0.0 && w1 <= 0.0 && w2 <= 0.0 };
+                if inside { self.pixels[ty * size + tx] = color; }
+            }
+        }
+    }
+
+    // Fills the texels within the road's half width of its centerline, at least one texel wide.
+    fn fill_road(&mut self, road: &RoadSegment, origin: Vec2, texel_size: f32, color: [u8; 4]) {
+        let (start, end) = ((road.start - origin) / texel_size, (road.end - origin) / texel_size);
+        let half_width = (road.half_width / texel_size).max(0.5);
+        let Some((lo, hi)) = Self::texel_range(start.min(end) - half_width, start.max(end) + half_width) else { return };
+        let size = config::MINIMAP_TEXELS as usize;
+        let along = end - start;
+        let length_sq = along.length_squared().max(1e-6);
+        for ty in lo[1]..hi[1] {
+            for tx in lo[0]..hi[0] {
+                let p = Vec2::new(tx as f32 + 0.5, ty as f32 + 0.5);
+                let t = ((p - start).dot(along) / length_sq).clamp(0.0, 1.0);
+                if p.distance_squared(start + along * t) <= half_width * half_width { self.pixels[ty * size + tx] = color; }
+            }
+        }
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..4, 0..1);
+    }
+}
@@ -0,0 +1,304 @@
+// node_index.rs
+use std::{cell::RefCell, cmp::Reverse, collections::{BinaryHeap, HashMap, VecDeque}, fs::File, io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write}, path::PathBuf};
+use bytemuck::{Pod, Zeroable};
+use glam::Vec2;
+use rayon::prelude::*;
+use crate::config;
+
+// 16 bytes per node.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct CompactNode {
+    pub id: i64,
+    pub x: f32,
+    pub y: f32,
+}
+
+const NODE_SIZE: u64 = std::mem::size_of::<CompactNode>() as u64;
+
+// A file in the temp directory that is deleted once dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn create(kind: &str) -> io::Result<(Self, File)> {
+        static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("skyroam-{}-{}-{}.bin", kind, std::process::id(), n));
+        let file = File::options().read(true).write(true).create_new(true).open(&path)?;
+        Ok((Self(path), file))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+// Sorted runs of nodes written one after another to a temp file.
+struct Runs {
+    temp: TempFile,
+    writer: BufWriter<File>,
+    // Node ranges of each run within the file.
+    bounds: Vec<(u64, u64)>,
+}
+
+// Collects the nodes pass's nodes. Past NODE_INDEX_SPILL nodes in memory they're sorted and
+// written to a temp file as a run, so country-sized extracts don't need every node in RAM;
+// finish() then merges the runs into an index read back through a page cache.
+pub struct NodeCollector {
+    nodes: Vec<CompactNode>,
+    runs: Option<Runs>,
+    spilled: usize,
+    // Nodes held before a run is written; NODE_INDEX_SPILL outside tests.
+    spill_at: usize,
+    // Once spilling fails, everything stays in memory.
+    failed: bool,
+}
+
+impl Default for NodeCollector {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl NodeCollector {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { nodes: Vec::with_capacity(capacity), runs: None, spilled: 0, spill_at: config::NODE_INDEX_SPILL, failed: false }
+    }
+
+    pub fn push(&mut self, node: CompactNode) {
+        self.nodes.push(node);
+        if self.nodes.len() >= self.spill_at { self.spill(); }
+    }
+
+    // Takes `other`'s nodes after these; for joining the per-block results of a parallel pass,
+    // which stay far below the spill size.
+    pub fn append(&mut self, mut other: NodeCollector) {
+        self.nodes.append(&mut other.nodes);
+        if self.nodes.len() >= self.spill_at { self.spill(); }
+    }
+
+    fn spill(&mut self) {
+        if self.failed { return; }
+        if let Err(e) = self.write_run() {
+            log::warn!("Couldn't write map nodes to a temp file ({}); keeping them all in memory", e);
+            self.failed = true;
+        }
+    }
+
+    fn write_run(&mut self) -> io::Result<()> {
+        if self.runs.is_none() {
+            let (temp, file) = TempFile::create("node-runs")?;
+            log::info!("Over {} map nodes; spilling them to {}", self.spill_at, temp.0.display());
+            self.runs = Some(Runs { temp, writer: BufWriter::new(file), bounds: Vec::new() });
+        }
+        let runs = self.runs.as_mut().unwrap();
+        self.nodes.par_sort_unstable_by_key(|n| n.id);
+        runs.writer.write_all(bytemuck::cast_slice(&self.nodes))?;
+        let start = runs.bounds.last().map_or(0, |b| b.1);
+        runs.bounds.push((start, start + self.nodes.len() as u64));
+        self.spilled += self.nodes.len();
+        self.nodes.clear();
+        Ok(())
+    }
+
+    // Sorts the nodes for lookups: in memory, or by merging the spilled runs into a file.
+    pub fn finish(mut self) -> io::Result<NodeIndex> {
+        if self.runs.is_none() {
+            self.nodes.par_sort_unstable_by_key(|n| n.id);
+            return Ok(NodeIndex::Memory(self.nodes));
+        }
+        if !self.nodes.is_empty() { self.write_run()?; }
+        let Runs { temp, mut writer, bounds } = self.runs.take().unwrap();
+        writer.flush()?;
+        drop(writer);
+        DiskNodes::merge(&temp, &bounds, self.spilled).map(NodeIndex::Disk)
+    }
+}
+
+// Node positions by id, for resolving way nodes.
+pub enum NodeIndex {
+    Memory(Vec<CompactNode>),
+    Disk(DiskNodes),
+}
+
+impl NodeIndex {
+    pub fn get(&self, id: i64) -> Option<Vec2> {
+        match self {
+            Self::Memory(nodes) => nodes.binary_search_by_key(&id, |n| n.id).ok().map(|i| Vec2::new(nodes[i].x, nodes[i].y)),
+            Self::Disk(disk) => disk.get(id),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Memory(nodes) => nodes.len(),
+            Self::Disk(disk) => disk.len,
+        }
+    }
+}
+
+// Sorted nodes in a temp file, found by binary search over each page's first id and read a
+// page (NODE_INDEX_PAGE nodes) at a time. The last NODE_INDEX_CACHE_PAGES pages read stay in memory.
+pub struct DiskNodes {
+    _temp: TempFile,
+    file: File,
+    len: usize,
+    first_ids: Vec<i64>,
+    cache: RefCell<PageCache>,
+}
+
+#[derive(Default)]
+struct PageCache {
+    pages: HashMap<usize, Vec<CompactNode>>,
+    // Oldest first; the first one goes when the cache is full.
+    order: VecDeque<usize>,
+}
+
+impl DiskNodes {
+    // K-way merge of the sorted runs into one sorted file.
+    fn merge(runs: &TempFile, bounds: &[(u64, u64)], len: usize) -> io::Result<Self> {
+        let mut readers = bounds.iter().map(|&(start, end)| -> io::Result<_> {
+            let mut file = File::open(&runs.0)?;
+            file.seek(SeekFrom::Start(start * NODE_SIZE))?;
+            Ok((BufReader::with_capacity(1 << 20, file), end - start))
+        }).collect::<io::Result<Vec<_>>>()?;
+        let mut next = |run: usize| -> io::Result<Option<CompactNode>> {
+            let (reader, left) = &mut readers[run];
+            if *left == 0 { return Ok(None); }
+            *left -= 1;
+            let mut bytes = [0u8; NODE_SIZE as usize];
+            reader.read_exact(&mut bytes)?;
+            Ok(Some(bytemuck::pod_read_unaligned(&bytes)))
+        };
+
+        let (temp, file) = TempFile::create("nodes")?;
+        let mut writer = BufWriter::with_capacity(1 << 20, file.try_clone()?);
+        let mut heap = BinaryHeap::new();
+        let mut heads = vec![None; bounds.len()];
+        for (run, head) in heads.iter_mut().enumerate() {
+            *head = next(run)?;
+            if let Some(node) = head { heap.push(Reverse((node.id, run))); }
+        }
+        let mut first_ids = Vec::with_capacity(len / config::NODE_INDEX_PAGE + 1);
+        let mut written = 0;
+        while let Some(Reverse((_, run))) = heap.pop() {
+            let node = heads[run].take().unwrap();
+            if written % config::NODE_INDEX_PAGE == 0 { first_ids.push(node.id); }
+            writer.write_all(bytemuck::bytes_of(&node))?;
+            written += 1;
+            heads[run] = next(run)?;
+            if let Some(node) = heads[run] { heap.push(Reverse((node.id, run))); }
+        }
+        writer.flush()?;
+        log::info!("Merged {} map nodes from {} runs into {}", written, bounds.len(), temp.0.display());
+        Ok(Self { _temp: temp, file, len: written, first_ids, cache: RefCell::default() })
+    }
+
+    fn get(&self, id: i64) -> Option<Vec2> {
+        let page = self.first_ids.partition_point(|&first| first <= id).checked_sub(1)?;
+        let mut cache = self.cache.borrow_mut();
+        if !cache.pages.contains_key(&page) {
+            let nodes = self.read_page(page).map_err(|e| log::warn!("Couldn't read map nodes back: {}", e)).ok()?;
+            if cache.order.len() >= config::NODE_INDEX_CACHE_PAGES && let Some(oldest) = cache.order.pop_front() {
+                cache.pages.remove(&oldest);
+            }
+            cache.pages.insert(page, nodes);
+            cache.order.push_back(page);
+        }
+        let nodes = &cache.pages[&page];
+        nodes.binary_search_by_key(&id, |n| n.id).ok().map(|i| Vec2::new(nodes[i].x, nodes[i].y))
+    }
+
+    fn read_page(&self, page: usize) -> io::Result<Vec<CompactNode>> {
+        let start = page * config::NODE_INDEX_PAGE;
+        let count = config::NODE_INDEX_PAGE.min(self.len - start);
+        let mut bytes = vec![0u8; count * NODE_SIZE as usize];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(start as u64 * NODE_SIZE))?;
+        file.read_exact(&mut bytes)?;
+        Ok(bytes.chunks_exact(NODE_SIZE as usize).map(bytemuck::pod_read_unaligned).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: i64) -> CompactNode {
+        CompactNode { id, x: id as f32, y: -(id as f32) }
+    }
+
+    // Spills every `spill_at` nodes, so a few pages' worth of nodes makes several runs.
+    fn collector(spill_at: usize) -> NodeCollector {
+        NodeCollector { spill_at, ..NodeCollector::default() }
+    }
+
+    fn disk(index: &NodeIndex) -> &DiskNodes {
+        match index {
+            NodeIndex::Disk(disk) => disk,
+            NodeIndex::Memory(_) => panic!("nodes weren't spilled"),
+        }
+    }
+
+    #[test]
+    fn merge_sorts_interleaved_runs() {
+        // 7919 is prime, so this visits every id below `count` once, out of order.
+        let count = config::NODE_INDEX_PAGE * 3 + 17;
+        let mut nodes = collector(1000);
+        for i in 0..count { nodes.push(node((i * 7919 % count) as i64)); }
+        let index = nodes.finish().unwrap();
+        let disk = disk(&index);
+        assert_eq!(index.len(), count);
+        assert_eq!(disk.first_ids.len(), 4);
+
+        let ids: Vec<i64> = (0..disk.first_ids.len()).flat_map(|page| disk.read_page(page).unwrap()).map(|n| n.id).collect();
+        assert_eq!(ids, (0..count as i64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn merge_takes_appended_blocks_and_leftovers() {
+        let mut nodes = collector(500);
+        for block in 0..4 {
+            let mut part = NodeCollector::default();
+            for i in (0..300).rev() { part.push(node(i * 4 + block)); }
+            nodes.append(part);
+        }
+        // Below the spill size, so finish() writes these as the last run.
+        nodes.push(node(-5));
+        let index = nodes.finish().unwrap();
+        disk(&index);
+        assert_eq!(index.len(), 1201);
+        assert_eq!(index.get(-5), Some(Vec2::new(-5.0, 5.0)));
+        for id in 0..1200 { assert_eq!(index.get(id), Some(Vec2::new(id as f32, -(id as f32))), "node {}", id); }
+    }
+
+    #[test]
+    fn lookup_across_page_boundaries() {
+        // Even ids only, so the odd ones between pages are missing.
+        let count = config::NODE_INDEX_PAGE * 2 + 1;
+        let mut nodes = collector(config::NODE_INDEX_PAGE);
+        for i in (0..count as i64).rev() { nodes.push(node(i * 2 + 10)); }
+        let index = nodes.finish().unwrap();
+        let page = config::NODE_INDEX_PAGE as i64;
+        assert_eq!(disk(&index).first_ids, vec![10, page * 2 + 10, page * 4 + 10]);
+
+        for id in [10, page * 2 + 8, page * 2 + 10, page * 4 + 8, page * 4 + 10] {
+            assert_eq!(index.get(id), Some(Vec2::new(id as f32, -(id as f32))), "node {}", id);
+        }
+        for id in [i64::MIN, 9, 11, page * 2 + 9, page * 2 + 11, page * 4 + 11, i64::MAX] {
+            assert_eq!(index.get(id), None, "node {}", id);
+        }
+    }
+
+    #[test]
+    fn small_maps_stay_in_memory() {
+        let mut nodes = NodeCollector::default();
+        for id in [3, 1, 2] { nodes.push(node(id)); }
+        let index = nodes.finish().unwrap();
+        assert!(matches!(index, NodeIndex::Memory(_)));
+        assert_eq!(index.get(2), Some(Vec2::new(2.0, -2.0)));
+        assert_eq!(index.get(4), None);
+    }
+}
@@ -0,0 +1,59 @@
+// notifications.rs
+use std::collections::VecDeque;
+use crate::{config, hud::HudRenderer, text::TextRenderer};
+
+struct Toast {
+    text: String,
+    // Seconds since it was pushed.
+    age: f64,
+}
+
+// Short messages stacked in the top-left corner, newest on top. Each fades in, stays for
+// NOTIFICATION_DURATION and fades out; past NOTIFICATION_MAX the oldest are cut short.
+#[derive(Default)]
+pub struct Notifications {
+    toasts: VecDeque<Toast>,
+}
+
+impl Notifications {
+    // Shows `text`. Pushing the newest message again restarts it instead of stacking a copy.
+    pub fn push(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        if let Some(newest) = self.toasts.back_mut() && newest.text == text {
+            newest.age = newest.age.min(config::NOTIFICATION_FADE);
+            return;
+        }
+        self.toasts.push_back(Toast { text, age: 0.0 });
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        let lifetime = config::NOTIFICATION_DURATION + 2.0 * config::NOTIFICATION_FADE;
+        let overflow = self.toasts.len().saturating_sub(config::NOTIFICATION_MAX);
+        for (i, toast) in self.toasts.iter_mut().enumerate() {
+            toast.age += dt;
+            // Pushed out by newer ones: skip ahead to fading out.
+            if i < overflow { toast.age = toast.age.max(lifetime - config::NOTIFICATION_FADE); }
+        }
+        self.toasts.retain(|t| t.age < lifetime);
+    }
+
+    // Starts at `top`, below whatever else sits in the corner.
+    pub fn draw(&self, hud: &mut HudRenderer, text: &mut TextRenderer, top: f32) {
+        let (size, pad, gap) = (16.0, 8.0, 6.0);
+        let fade = config::NOTIFICATION_FADE;
+        let mut y = top;
+        for toast in self.toasts.iter().rev() {
+            let remaining = config::NOTIFICATION_DURATION + 2.0 * fade - toast.age;
+            let alpha = (toast.age / fade).min(remaining / fade).clamp(0.0, 1.0) as f32;
+            let width = text.measure(&toast.text, size) + pad * 2.0;
+            // Slides in from the edge as it fades in.
+            let x = 12.0 - (1.0 - (toast.age / fade).min(1.0) as f32) * 24.0;
+            let height = size + pad * 2.0;
+            hud.rect([x, y], [x + width, y + height], [0.08, 0.08, 0.08, 0.8 * alpha]);
+            hud.rect([x, y], [x + 3.0, y + height], [1.0, 0.75, 0.35, alpha]);
+            text.text(&toast.text, [x + pad, y + pad], size, [1.0, 1.0, 1.0, alpha]);
+            // Fading out also closes the gap it leaves.
+            y += (height + gap) * alpha.max(if toast.age < fade { 1.0 } else { 0.0 });
+        }
+    }
+}
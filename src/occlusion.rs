@@ -0,0 +1,188 @@
+// occlusion.rs
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::{Arc, atomic::{AtomicU8, Ordering}};
+use crate::{post, shader, shader_cache};
+
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+// Chunk bounds drawn as a box under an occlusion query.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct OcclusionBox {
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+// Skips chunks hidden behind nearer geometry. Each frame the bounds of every chunk that
+// survived frustum culling are depth-tested after the scene, and the sample counts read
+// back decide which chunks the next frame draws. Results arrive a frame or two late, so
+// chunks close to the camera are never tested.
+pub struct OcclusionCuller {
+    pub enabled: bool,
+    pipeline: wgpu::RenderPipeline,
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    box_buffer: wgpu::Buffer,
+    capacity: u32,
+    boxes: Vec<OcclusionBox>,
+    // (view, chunk) of each query issued this frame, in query order.
+    tested: Vec<(usize, (i32, i32))>,
+    // Queries whose results are being read back.
+    in_flight: Vec<(usize, (i32, i32))>,
+    mapping: bool,
+    // Set by the map callback: MAP_DONE or MAP_FAILED.
+    map_state: Arc<AtomicU8>,
+    occluded: HashSet<(usize, (i32, i32))>,
+}
+
+impl OcclusionCuller {
+    pub fn new(device: &wgpu::Device, camera_layout: &wgpu::BindGroupLayout) -> Self {
+        let module = shader_cache::module(device, "Occlusion Shader", shader::OCCLUSION_SHADER);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[camera_layout], push_constant_ranges: &[] });
+        // Runs inside the scene pass, so it matches its targets but writes neither color nor depth.
+        let no_color = |format| Some(wgpu::ColorTargetState { format, blend: None, write_mask: wgpu::ColorWrites::empty() });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Occlusion Pipeline"), layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module, entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<OcclusionBox>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &[
+                        wgpu::VertexAttribute { offset: 0,  shader_location: 0, format: wgpu::VertexFormat::Float32x3 },
+                        wgpu::VertexAttribute { offset: 12, shader_location: 1, format: wgpu::VertexFormat::Float32x3 },
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module, entry_point: "fs_main",
+                targets: &[no_color(post::HDR_FORMAT), no_color(post::NORMAL_FORMAT)],
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, cull_mode: None, ..Default::default() },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float, depth_write_enabled: false, depth_compare: wgpu::CompareFunction::LessEqual, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState { count: post::scene_samples(), mask: !0, alpha_to_coverage_enabled: false },
+            multiview: None,
+        });
+
+        let capacity = 256;
+        let (query_set, resolve_buffer, readback_buffer, box_buffer) = Self::create_buffers(device, capacity);
+        Self {
+            enabled: crate::config::OCCLUSION_CULLING, pipeline, query_set, resolve_buffer, readback_buffer, box_buffer, capacity,
+            boxes: Vec::new(), tested: Vec::new(), in_flight: Vec::new(), mapping: false,
+            map_state: Arc::new(AtomicU8::new(MAP_PENDING)), occluded: HashSet::new(),
+        }
+    }
+
+    fn create_buffers(device: &wgpu::Device, capacity: u32) -> (wgpu::QuerySet, wgpu::Buffer, wgpu::Buffer, wgpu::Buffer) {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor { label: Some("Chunk Occlusion"), ty: wgpu::QueryType::Occlusion, count: capacity });
+        let result_bytes = capacity as u64 * 8;
+        let buffer = |label, size, usage| device.create_buffer(&wgpu::BufferDescriptor { label: Some(label), size, usage, mapped_at_creation: false });
+        (
+            query_set,
+            buffer("Occlusion Resolve", result_bytes, wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC),
+            buffer("Occlusion Readback", result_bytes, wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST),
+            buffer("Occlusion Boxes", capacity as u64 * std::mem::size_of::<OcclusionBox>() as u64, wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST),
+        )
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.occluded.clear();
+    }
+
+    // Picks up the newest query results if the readback has finished.
+    pub fn collect_results(&mut self, device: &wgpu::Device) {
+        if !self.mapping { return; }
+        device.poll(wgpu::Maintain::Poll);
+        match self.map_state.swap(MAP_PENDING, Ordering::Acquire) {
+            MAP_PENDING => return,
+            MAP_FAILED => {
+                self.in_flight.clear();
+                self.mapping = false;
+                return;
+            }
+            _ => {}
+        }
+
+        let slice = self.readback_buffer.slice(..self.in_flight.len() as u64 * 8);
+        {
+            let data = slice.get_mapped_range();
+            let samples: &[u64] = bytemuck::cast_slice(&data);
+            self.occluded = self.in_flight.iter().zip(samples).filter(|(_, count)| **count == 0).map(|(key, _)| *key).collect();
+        }
+        self.readback_buffer.unmap();
+        self.in_flight.clear();
+        self.mapping = false;
+    }
+
+    pub fn is_occluded(&self, view: usize, coord: (i32, i32)) -> bool {
+        self.enabled && self.occluded.contains(&(view, coord))
+    }
+
+    // Number of queries issued so far this frame; tests of one view form a contiguous range.
+    pub fn test_count(&self) -> u32 {
+        self.boxes.len() as u32
+    }
+
+    // Queues a depth test of the chunk's bounds. Skipped while the last results are in flight.
+    pub fn test(&mut self, view: usize, coord: (i32, i32), min: glam::Vec3, max: glam::Vec3) {
+        if !self.enabled || self.mapping { return; }
+        // Slightly inflated so chunk geometry never occludes its own box.
+        let pad = glam::Vec3::splat(1.0);
+        self.boxes.push(OcclusionBox { min: (min - pad).to_array(), max: (max + pad).to_array() });
+        self.tested.push((view, coord));
+    }
+
+    // Uploads this frame's boxes; call before the scene pass.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.boxes.len() as u32 > self.capacity {
+            self.capacity = (self.boxes.len() as u32).next_power_of_two();
+            (self.query_set, self.resolve_buffer, self.readback_buffer, self.box_buffer) = Self::create_buffers(device, self.capacity);
+        }
+        if !self.boxes.is_empty() {
+            queue.write_buffer(&self.box_buffer, 0, bytemuck::cast_slice(&self.boxes));
+        }
+    }
+
+    pub fn query_set(&self) -> Option<&wgpu::QuerySet> {
+        (!self.boxes.is_empty()).then_some(&self.query_set)
+    }
+
+    // Draws the queued boxes of one view; call after that view's chunks so its depth is complete.
+    pub fn draw_tests<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, queries: Range<u32>) {
+        if queries.is_empty() { return; }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_vertex_buffer(0, self.box_buffer.slice(..));
+        for query in queries {
+            pass.begin_occlusion_query(query);
+            pass.draw(0..36, query..query + 1);
+            pass.end_occlusion_query();
+        }
+    }
+
+    // Copies the frame's results to the readback buffer; call after the scene pass.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.boxes.is_empty() { return; }
+        let count = self.boxes.len() as u32;
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, count as u64 * 8);
+        self.in_flight = std::mem::take(&mut self.tested);
+        self.boxes.clear();
+    }
+
+    // Starts reading back the resolved results; call once the frame has been submitted.
+    pub fn after_submit(&mut self) {
+        if self.in_flight.is_empty() || self.mapping { return; }
+        self.mapping = true;
+        let state = self.map_state.clone();
+        self.readback_buffer.slice(..self.in_flight.len() as u64 * 8).map_async(wgpu::MapMode::Read, move |result| {
+            state.store(if result.is_ok() { MAP_DONE } else { MAP_FAILED }, Ordering::Release);
+        });
+    }
+}
@@ -0,0 +1,57 @@
+// orbit.rs
+use glam::DVec3;
+use crate::{camera::Camera, config, world::World};
+
+// Camera circling a fixed point, for looking at a landmark from all sides. Dragging with the
+// left mouse button swings it around and the wheel moves it in and out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orbit {
+    pub target: DVec3,
+    pub distance: f64,
+    // Direction from the camera to the target, same conventions as Camera.
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Orbit {
+    // Orbit around whatever `camera` looks at: the middle of a building, or the spot on a
+    // barrier or the ground. None if the view hits nothing within ORBIT_PICK_DISTANCE.
+    pub fn pick(camera: &Camera, world: &World) -> Option<Self> {
+        let (eye, forward) = (camera.eye.as_vec3(), camera.forward().as_vec3());
+        let hit = world.raycast(eye, forward, config::ORBIT_PICK_DISTANCE)?;
+        let (target, size) = match hit.building.and_then(|b| world.building_bounds(b)) {
+            Some((min, max)) => ((min + max) * 0.5, (max - min).max_element()),
+            None => (hit.position, 0.0),
+        };
+        let distance = (size * config::ORBIT_FIT).max(hit.distance.min(config::ORBIT_DISTANCE_RANGE.1 as f32)) as f64;
+        Some(Self::looking_at(target.as_dvec3(), eye.as_dvec3(), distance))
+    }
+
+    // Orbit at `distance` from `target`, on the side `eye` is on.
+    fn looking_at(target: DVec3, eye: DVec3, distance: f64) -> Self {
+        let dir = (target - eye).try_normalize().unwrap_or(DVec3::X).as_vec3();
+        let (min, max) = config::ORBIT_DISTANCE_RANGE;
+        Self { target, distance: distance.clamp(min, max), yaw: dir.z.atan2(dir.x), pitch: dir.y.asin() }
+    }
+
+    // Swings the camera around by a mouse movement in pixels.
+    pub fn drag(&mut self, delta: glam::DVec2) {
+        self.yaw += delta.x as f32 * config::ORBIT_DRAG_SPEED;
+        self.pitch = (self.pitch - delta.y as f32 * config::ORBIT_DRAG_SPEED).clamp(-1.5, 0.2);
+    }
+
+    // Moves in by ORBIT_ZOOM_STEP per notch, out for negative notches.
+    pub fn zoom(&mut self, notches: f64) {
+        let (min, max) = config::ORBIT_DISTANCE_RANGE;
+        self.distance = (self.distance / config::ORBIT_ZOOM_STEP.powf(notches)).clamp(min, max);
+    }
+
+    // `base` moved onto the orbit, looking at the target, never below street level.
+    pub fn camera(&self, base: &Camera) -> Camera {
+        let forward = Camera { yaw: self.yaw, pitch: self.pitch, ..base.clone() }.forward();
+        let mut eye = self.target - forward * self.distance;
+        eye.y = eye.y.max(1.0);
+        let dir = (self.target - eye).normalize().as_vec3();
+        Camera { eye, yaw: dir.z.atan2(dir.x), pitch: dir.y.asin(), roll: 0.0, ..base.clone() }
+    }
+}
@@ -0,0 +1,114 @@
+// pause.rs
+use winit::keyboard::KeyCode;
+use crate::{hud::HudRenderer, text::TextRenderer};
+
+const ITEMS: [&str; 5] = ["Resume", "Settings", "Teleport", "Waypoints", "Quit"];
+
+pub enum PauseAction {
+    None,
+    Resume,
+    Settings,
+    Teleport(usize),
+    Waypoint(usize),
+    RemoveWaypoint(usize),
+    Quit,
+}
+
+enum Page {
+    Main,
+    // The four teleport slots and Back.
+    Teleport,
+    // Each saved waypoint and Back.
+    Waypoints,
+}
+
+// Escape pauses: the world stops, the scene dims and this menu takes the keyboard.
+// Up/Down pick a row, Enter chooses it.
+pub struct PauseMenu {
+    pub open: bool,
+    page: Page,
+    selected: usize,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        Self { open: false, page: Page::Main, selected: 0 }
+    }
+
+    pub fn show(&mut self) {
+        self.open = true;
+        self.page = Page::Main;
+        self.selected = 0;
+    }
+
+    // Leaves a sub-page; false if already on the main page.
+    pub fn back(&mut self) -> bool {
+        self.selected = match self.page {
+            Page::Main => return false,
+            Page::Teleport => 2,
+            Page::Waypoints => 3,
+        };
+        self.page = Page::Main;
+        true
+    }
+
+    // `waypoints` is how many waypoints the Waypoints page lists.
+    pub fn key(&mut self, key: KeyCode, waypoints: usize) -> PauseAction {
+        let rows = match self.page { Page::Main => ITEMS.len(), Page::Teleport => 5, Page::Waypoints => waypoints + 1 };
+        // A waypoint removed from the end of the list leaves the selection past it.
+        self.selected = self.selected.min(rows - 1);
+        match key {
+            KeyCode::ArrowUp | KeyCode::KeyW => self.selected = (self.selected + rows - 1) % rows,
+            KeyCode::ArrowDown | KeyCode::KeyS => self.selected = (self.selected + 1) % rows,
+            KeyCode::Backspace => { self.back(); }
+            KeyCode::Delete if matches!(self.page, Page::Waypoints) && self.selected < waypoints => return PauseAction::RemoveWaypoint(self.selected),
+            KeyCode::Enter | KeyCode::NumpadEnter | KeyCode::Space => match (&self.page, self.selected) {
+                (Page::Main, 0) => return PauseAction::Resume,
+                (Page::Main, 1) => return PauseAction::Settings,
+                (Page::Main, 2) => {
+                    self.page = Page::Teleport;
+                    self.selected = 0;
+                }
+                (Page::Main, 3) => {
+                    self.page = Page::Waypoints;
+                    self.selected = 0;
+                }
+                (Page::Main, _) => return PauseAction::Quit,
+                (Page::Teleport, 4) => { self.back(); }
+                (Page::Teleport, slot) => return PauseAction::Teleport(slot),
+                (Page::Waypoints, index) if index < waypoints => return PauseAction::Waypoint(index),
+                (Page::Waypoints, _) => { self.back(); }
+            },
+            _ => {}
+        }
+        PauseAction::None
+    }
+
+    // `slots` describes the four teleport slots for the Teleport page, `waypoints` the rows of
+    // the Waypoints page.
+    pub fn draw(&self, hud: &mut HudRenderer, text: &mut TextRenderer, screen: [f32; 2], slots: &[String; 4], waypoints: &[String]) {
+        let (title, rows): (&str, Vec<&str>) = match self.page {
+            Page::Main => ("Paused", ITEMS.to_vec()),
+            Page::Teleport => ("Teleport", slots.iter().map(String::as_str).chain(std::iter::once("Back")).collect()),
+            Page::Waypoints => ("Waypoints", waypoints.iter().map(String::as_str).chain(std::iter::once("Back")).collect()),
+        };
+        let (row_w, row_h, gap) = (420.0, 36.0, 8.0);
+        let x0 = (screen[0] - row_w) * 0.5;
+        let y0 = (screen[1] - rows.len() as f32 * (row_h + gap)) * 0.5;
+        text.text_centered(title, screen[0] * 0.5, y0 - 44.0, 22.0, [1.0, 1.0, 1.0, 1.0]);
+        for (i, label) in rows.iter().enumerate() {
+            let y = y0 + i as f32 * (row_h + gap);
+            let selected = i == self.selected;
+            hud.rect([x0, y], [x0 + row_w, y + row_h], if selected { [0.85, 0.85, 0.85, 0.95] } else { [0.15, 0.15, 0.15, 0.85] });
+            let ink = if selected { [0.05, 0.05, 0.05, 1.0] } else { [0.95, 0.95, 0.95, 1.0] };
+            text.text_centered(label, screen[0] * 0.5, y + 9.0, 18.0, ink);
+        }
+        let hint_y = y0 + rows.len() as f32 * (row_h + gap) + 8.0;
+        let hint = match self.page {
+            Page::Waypoints if waypoints.is_empty() => "B drops a waypoint where you stand",
+            Page::Waypoints => "Enter teleports, Delete removes, Escape goes back",
+            _ => "Up/Down select, Enter chooses, Escape goes back",
+        };
+        text.text_centered(hint, screen[0] * 0.5, hint_y, 14.0, [0.8, 0.8, 0.8, 1.0]);
+    }
+}
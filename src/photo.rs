@@ -0,0 +1,65 @@
+// photo.rs
+use glam::DVec3;
+use crate::{camera::{Camera, CameraController, Projection}, config, world::World};
+
+// Free camera for taking pictures while the world stands still. It starts from player one's
+// view and flies slowly on the movement keys (jump and crouch rise and sink, sprint is
+// faster). Depth of field and exposure only apply while it's out.
+pub struct PhotoMode {
+    pub camera: Camera,
+    // Distance in meters that stays sharp.
+    pub focus: f32,
+    // Blur radius in pixels far from the focus, 0 keeps everything sharp.
+    pub blur: f32,
+    // Exposure offset in stops.
+    pub exposure: f32,
+}
+
+impl PhotoMode {
+    pub fn new(camera: Camera) -> Self {
+        Self { camera: Camera { roll: 0.0, projection: Projection::Perspective, ..camera }, focus: config::PHOTO_FOCUS, blur: 0.0, exposure: 0.0 }
+    }
+
+    pub fn fly(&mut self, controller: &CameraController, dt: f64) {
+        let forward = self.camera.forward();
+        let right = forward.cross(DVec3::Y).normalize_or_zero();
+        let axis = controller.move_axis.as_dvec2();
+        let dir = forward * ((controller.move_fwd as i32 - controller.move_back as i32) as f64 + axis.y)
+            + right * ((controller.move_right as i32 - controller.move_left as i32) as f64 + axis.x)
+            + DVec3::Y * (controller.jump as i32 - controller.crouch as i32) as f64;
+        let speed = if controller.sprint { config::PHOTO_FAST_SPEED } else { config::PHOTO_SPEED };
+        self.camera.eye += dir.clamp_length_max(1.0) * speed * dt;
+    }
+
+    pub fn rotate(&mut self, yaw_delta: f32, pitch_delta: f32) {
+        self.camera.yaw += yaw_delta * config::PHOTO_LOOK_SCALE;
+        self.camera.pitch = (self.camera.pitch + pitch_delta * config::PHOTO_LOOK_SCALE).clamp(-1.5, 1.5);
+    }
+
+    // Moves the focus out by PHOTO_FOCUS_STEP per wheel notch, in for negative notches.
+    pub fn adjust_focus(&mut self, notches: f64) {
+        let (min, max) = config::PHOTO_FOCUS_RANGE;
+        self.focus = (self.focus * config::PHOTO_FOCUS_STEP.powf(notches as f32)).clamp(min, max);
+        log::info!("Focus {:.1} m", self.focus);
+    }
+
+    // Focuses on whatever is in the middle of the picture.
+    pub fn autofocus(&mut self, world: &World) {
+        let (min, max) = config::PHOTO_FOCUS_RANGE;
+        if let Some(hit) = world.raycast(self.camera.eye.as_vec3(), self.camera.forward().as_vec3(), max) {
+            self.focus = hit.distance.max(min);
+            log::info!("Focus {:.1} m", self.focus);
+        }
+    }
+
+    pub fn adjust_blur(&mut self, steps: f32) {
+        self.blur = (self.blur + steps * config::PHOTO_BLUR_STEP).clamp(0.0, config::PHOTO_MAX_BLUR);
+        log::info!("Depth of field blur {:.0} px", self.blur);
+    }
+
+    pub fn adjust_exposure(&mut self, steps: f32) {
+        let range = config::PHOTO_EXPOSURE_RANGE;
+        self.exposure = (self.exposure + steps * config::PHOTO_EXPOSURE_STEP).clamp(-range, range);
+        log::info!("Exposure {:+.2} stops", self.exposure);
+    }
+}
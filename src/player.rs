@@ -0,0 +1,704 @@
+// player.rs
+use glam::{DVec3, Vec2};
+use crate::{bindings::Bindings, camera::{Camera, CameraController}, config, config_file, dynamic_mesh::DynamicMeshes, orbit::Orbit, profiler, vehicle::Car, world::{Ladder, TunnelHit, WallHit, World}};
+
+// Walk runs the physics; Fly is noclip, ignoring gravity and collisions; Drive rides in the
+// car with that index; Climb holds on to a ladder; Orbit leaves the body standing and circles
+// the camera around a landmark.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MovementMode {
+    Walk,
+    Fly,
+    Drive(usize),
+    Climb(Ladder),
+    Orbit(Orbit),
+}
+
+// Rope from the player to the point on a building it was fired at.
+#[derive(Debug, Clone, Copy)]
+pub struct Grapple {
+    pub anchor: DVec3,
+    // Reeled in while the button is held; the rope only pulls once stretched past it.
+    pub length: f64,
+}
+
+// Running along a wall in the air, reduced gravity for up to WALL_RUN_TIME.
+#[derive(Debug, Clone, Copy)]
+struct WallRun {
+    // Horizontal, pointing away from the wall.
+    normal: DVec3,
+    time: f64,
+}
+
+// Pulling up onto a ledge: the player rises until the feet clear `top`, then steps over.
+#[derive(Debug, Clone, Copy)]
+struct Mantle {
+    top: f64,
+    // Horizontal, pointing away from the wall.
+    normal: DVec3,
+}
+
+// One locally controlled body: its camera, input state and physics.
+pub struct Player {
+    pub camera: Camera,
+    pub controller: CameraController,
+    pub velocity: DVec3,
+    pub on_ground: bool,
+    // Height of the eye above the feet, lowered while crouching.
+    pub eye_height: f64,
+    pub mode: MovementMode,
+    // The view follows from behind and over the shoulder instead of from the eye.
+    pub third_person: bool,
+    // Field of view setting in degrees; the camera's eases around it.
+    pub fov: f32,
+    pub fly_speed: f64,
+    pub grapple: Option<Grapple>,
+    // Grapple input of the last update, so holding the button fires only once.
+    grapple_held: bool,
+    pub gliding: bool,
+    // Deep enough in water to float; buoyancy and drag replace gravity.
+    pub swimming: bool,
+    // Yaw at the end of the last update, for banking into turns.
+    previous_yaw: f32,
+    // Eye position before the latest physics tick; rendering interpolates from it.
+    previous_eye: DVec3,
+    // Vertical speed of the floor being stood on, so elevators carry the player.
+    ground_velocity: f64,
+    wall_run: Option<WallRun>,
+    // One wall-run per jump; landing or jumping off a wall allows another.
+    wall_run_ready: bool,
+    mantle: Option<Mantle>,
+    // Jump input of the last update, so a wall jump needs a fresh press.
+    jump_held: bool,
+    // Seconds left to jump after leaving the ground without jumping.
+    coyote: f64,
+    // Seconds left for a jump pressed in the air to happen on landing.
+    jump_buffer: f64,
+    // Rising from a jump whose height isn't settled yet; letting go of jump cuts it short.
+    jump_rising: bool,
+    interact_held: bool,
+    // Head bob, landing dip and strafe roll on the rendered view.
+    pub view_effects: bool,
+    bob_phase: f64,
+    // 0 standing still up to 1.5 at sprint, eased so bobbing fades in and out.
+    bob_weight: f64,
+    dip: f64,
+    tilt: f32,
+    // Hard-landing feedback: a shake of the view (off without `screen_shake` or view effects)
+    // and the strength of the red vignette, both fading back to 0.
+    pub screen_shake: bool,
+    shake: f64,
+    shake_phase: f64,
+    pub hurt: f32,
+}
+
+impl Player {
+    pub fn new(aspect: f32, eye: DVec3, bindings: Bindings) -> Self {
+        let mut camera = Camera::new(aspect);
+        camera.eye = eye;
+        Self {
+            camera, controller: CameraController::new(bindings), velocity: DVec3::ZERO, on_ground: false,
+            eye_height: config::EYE_HEIGHT, mode: MovementMode::Walk, third_person: false, fov: config_file::get().graphics.fov, fly_speed: config::FLY_SPEED,
+            grapple: None, grapple_held: false, gliding: false, swimming: false, previous_yaw: 0.0,
+            previous_eye: eye, ground_velocity: 0.0, wall_run: None, wall_run_ready: true, mantle: None, jump_held: false,
+            coyote: 0.0, jump_buffer: 0.0, jump_rising: false,
+            interact_held: false, view_effects: config::VIEW_EFFECTS, bob_phase: 0.0, bob_weight: 0.0, dip: 0.0, tilt: 0.0,
+            screen_shake: config::SCREEN_SHAKE, shake: 0.0, shake_phase: 0.0, hurt: 0.0,
+        }
+    }
+
+    // Call before each physics tick, also for players that don't move this tick.
+    pub fn begin_tick(&mut self) {
+        self.previous_eye = self.camera.eye;
+    }
+
+    // Camera for rendering, `alpha` of the way from the previous tick's position to the latest.
+    pub fn interpolated_camera(&self, alpha: f64) -> Camera {
+        let mut camera = self.camera.clone();
+        camera.eye = self.previous_eye.lerp(self.camera.eye, alpha);
+        camera
+    }
+
+    // Camera the player sees through: the interpolated one, or in third person pulled back
+    // over the shoulder and in front of any wall or roof between it and the eye.
+    pub fn view_camera(&self, alpha: f64, world: &World) -> Camera {
+        let mut camera = self.interpolated_camera(alpha);
+        if let MovementMode::Orbit(orbit) = &self.mode { return orbit.camera(&camera); }
+        if self.view_effects {
+            camera.eye.y += self.bob_weight * config::HEAD_BOB * (2.0 * self.bob_phase).sin() - self.dip;
+            camera.roll += self.tilt;
+            if self.screen_shake && self.shake > 0.0 {
+                let amount = (self.shake * config::SHAKE_ANGLE) as f32;
+                camera.yaw += amount * self.shake_phase.sin() as f32;
+                camera.pitch += amount * (self.shake_phase * 1.37 + 1.1).sin() as f32;
+            }
+        }
+        if !self.third_person || matches!(self.mode, MovementMode::Drive(_)) { return camera; }
+        let (sin_yaw, cos_yaw) = camera.yaw.sin_cos();
+        let right = DVec3::new(-(sin_yaw as f64), 0.0, cos_yaw as f64);
+        let offset = right * config::THIRD_PERSON_SHOULDER + DVec3::Y * config::THIRD_PERSON_HEIGHT - camera.forward() * config::THIRD_PERSON_DISTANCE;
+        let (distance, dir) = (offset.length() as f32, offset.normalize().as_vec3());
+        let reach = world.raycast(camera.eye.as_vec3(), dir, distance + config::THIRD_PERSON_WALL_GAP)
+            .map_or(distance, |hit| (hit.distance - config::THIRD_PERSON_WALL_GAP).max(0.0));
+        camera.eye += dir.as_dvec3() * reach as f64;
+        camera
+    }
+
+    // Adds a blocky figure where the player stands as of `alpha` into the latest tick, facing
+    // the view. Drivers are inside their car and get none.
+    pub fn push_avatar(&self, meshes: &mut DynamicMeshes, alpha: f64, color: [f32; 3]) {
+        if matches!(self.mode, MovementMode::Drive(_)) { return; }
+        let eye = self.previous_eye.lerp(self.camera.eye, alpha);
+        let (center, axis) = (Vec2::new(eye.x as f32, eye.z as f32), Vec2::from_angle(self.camera.yaw));
+        let feet = (eye.y - self.eye_height) as f32;
+        let top = (eye.y + config::HEAD_CLEARANCE) as f32;
+        let neck = top - config::AVATAR_HEAD_SIZE;
+        let body = Vec2::new(0.5, 1.6) * config::PLAYER_RADIUS as f32;
+        meshes.push_box(center, axis, body, feet, neck, color);
+        meshes.push_box(center, axis, Vec2::splat(config::AVATAR_HEAD_SIZE * 0.5), neck, top, config::AVATAR_HEAD_COLOR);
+    }
+
+    pub fn toggle_fly(&mut self) {
+        self.mode = match self.mode {
+            MovementMode::Walk | MovementMode::Climb(_) => MovementMode::Fly,
+            MovementMode::Fly => MovementMode::Walk,
+            MovementMode::Drive(_) | MovementMode::Orbit(_) => return,
+        };
+        // Landing starts from rest instead of keeping the flight speed.
+        self.stop();
+    }
+
+    // Puts the player at `eye` on foot and at rest, without interpolating from the old spot.
+    pub fn teleport(&mut self, eye: DVec3) {
+        self.mode = MovementMode::Walk;
+        self.stop();
+        self.eye_height = config::EYE_HEIGHT;
+        self.camera.eye = eye;
+        self.previous_eye = eye;
+    }
+
+    // Drops all momentum and anything the player was holding on to.
+    fn stop(&mut self) {
+        self.velocity = DVec3::ZERO;
+        self.grapple = None;
+        self.gliding = false;
+        self.wall_run = None;
+        self.mantle = None;
+        self.camera.roll = 0.0;
+        self.on_ground = false;
+        self.coyote = 0.0;
+        self.jump_buffer = 0.0;
+        self.jump_rising = false;
+    }
+
+    // True once per press of the interact key.
+    pub fn interact_pressed(&mut self) -> bool {
+        let pressed = self.controller.interact && !self.interact_held;
+        self.interact_held = self.controller.interact;
+        pressed
+    }
+
+    // Gets out of the car being driven, or into the nearest car within CAR_ENTER_DISTANCE
+    // that nobody is driving. `occupied` is kept up to date.
+    pub fn use_car(&mut self, cars: &[Car], occupied: &mut [bool]) {
+        match self.mode {
+            MovementMode::Drive(index) => {
+                let car = &cars[index];
+                occupied[index] = false;
+                self.mode = MovementMode::Walk;
+                self.stop();
+                // Out the driver's side, standing on the road.
+                let side = DVec3::new(-(car.heading.sin() as f64), 0.0, car.heading.cos() as f64);
+                self.eye_height = config::EYE_HEIGHT;
+                self.camera.eye = car.position - side * (config::CAR_WIDTH as f64 * 0.5 + config::PLAYER_RADIUS + 0.3) + DVec3::Y * config::EYE_HEIGHT;
+                self.previous_eye = self.camera.eye;
+            }
+            MovementMode::Walk => {
+                let feet = self.camera.eye - DVec3::Y * self.eye_height;
+                let nearest = cars.iter().enumerate()
+                    .filter(|(i, car)| !occupied[*i] && car.position.distance(feet) <= config::CAR_ENTER_DISTANCE)
+                    .min_by(|(_, a), (_, b)| a.position.distance(feet).total_cmp(&b.position.distance(feet)));
+                let Some((index, car)) = nearest else { return };
+                occupied[index] = true;
+                self.mode = MovementMode::Drive(index);
+                self.stop();
+                self.camera.yaw = car.heading;
+                self.camera.pitch = -0.2;
+            }
+            MovementMode::Fly | MovementMode::Climb(_) | MovementMode::Orbit(_) => {}
+        }
+    }
+
+    // Grabs a ladder in reach when walking into it while facing it. Holding jump doesn't grab,
+    // so jumping off doesn't catch the same ladder again.
+    fn grab_ladder(&mut self, world: &World, forward: DVec3, input_dir: DVec3) -> bool {
+        if self.controller.jump { return false; }
+        let eye = self.camera.eye;
+        let Some(ladder) = world.ladder_at(eye.x as f32, eye.z as f32, (eye.y - self.eye_height) as f32) else { return false };
+        let toward = -DVec3::new(ladder.normal.x as f64, 0.0, ladder.normal.y as f64);
+        if forward.dot(toward) < 0.5 || input_dir.dot(toward) < 0.5 { return false; }
+        self.stop();
+        self.mode = MovementMode::Climb(ladder);
+        true
+    }
+
+    // Forward and back climb up and down. Climbing past the top steps onto the roof, reaching
+    // the ground lets go, and a fresh jump pushes off the wall.
+    pub fn climb(&mut self, world: &World, ladder: Ladder, dt: f64) {
+        self.apply_look_rate(dt);
+        let jumped = self.controller.jump && !self.jump_held;
+        self.jump_held = self.controller.jump;
+        let normal = DVec3::new(ladder.normal.x as f64, 0.0, ladder.normal.y as f64);
+        if jumped {
+            self.mode = MovementMode::Walk;
+            self.velocity = normal * config::CLIMB_PUSH_OFF;
+            return;
+        }
+
+        let input = ((self.controller.move_fwd as i32 - self.controller.move_back as i32) as f64 + self.controller.move_axis.y as f64).clamp(-1.0, 1.0);
+        let anchor = ladder.climb_position();
+        let mut eye = DVec3::new(anchor.x as f64, self.camera.eye.y + input * config::CLIMB_SPEED * dt, anchor.y as f64);
+        let feet = eye.y - self.eye_height;
+        if feet >= ladder.top as f64 {
+            // Over the edge, clear of the wall's collision slab.
+            let onto = anchor.as_dvec2() - ladder.normal.as_dvec2() * (config::LADDER_STANDOFF as f64 + 2.0 * config::PLAYER_RADIUS + config::WALL_THICKNESS);
+            eye = DVec3::new(onto.x, ladder.top as f64 + self.eye_height, onto.y);
+            self.mode = MovementMode::Walk;
+        } else if input < 0.0 && let Some(ground) = world.floor_at(eye, self.eye_height) && feet <= ground.floor as f64 {
+            eye.y = ground.floor as f64 + self.eye_height;
+            self.mode = MovementMode::Walk;
+        }
+        self.camera.eye = eye;
+    }
+
+    // Chase camera behind the driven car. Looking around orbits it, the view swings back
+    // behind the car while it moves, and it pulls back as the car speeds up.
+    pub fn ride(&mut self, car: &Car, dt: f64) {
+        self.apply_look_rate(dt);
+        if car.speed.abs() > 1.0 {
+            let behind = (car.heading - self.camera.yaw + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+            self.camera.yaw += behind * (1.0 - (-config::CAR_CAMERA_FOLLOW * dt as f32).exp());
+        }
+        let distance = config::CAR_CAMERA_DISTANCE + car.speed.abs() * config::CAR_CAMERA_STRETCH;
+        let pivot = car.position + DVec3::Y * config::CAR_CAMERA_HEIGHT;
+        self.camera.eye = pivot - self.camera.forward() * distance;
+        self.camera.eye.y = self.camera.eye.y.max(0.5);
+        self.velocity = car.forward() * car.speed;
+    }
+
+    // Scales the fly speed by FLY_SPEED_STEP per mouse wheel notch.
+    pub fn adjust_fly_speed(&mut self, notches: f64) {
+        let (min, max) = config::FLY_SPEED_RANGE;
+        self.fly_speed = (self.fly_speed * config::FLY_SPEED_STEP.powf(notches)).clamp(min, max);
+    }
+
+    // Starts orbiting what the player looks at, or goes back to walking.
+    pub fn toggle_orbit(&mut self, world: &World) {
+        match self.mode {
+            MovementMode::Orbit(_) => self.mode = MovementMode::Walk,
+            MovementMode::Walk | MovementMode::Fly => match Orbit::pick(&self.camera, world) {
+                Some(orbit) => {
+                    self.stop();
+                    self.mode = MovementMode::Orbit(orbit);
+                }
+                None => log::info!("Nothing to orbit in view"),
+            },
+            MovementMode::Drive(_) | MovementMode::Climb(_) => {}
+        }
+    }
+
+    // Advances the view effects by `dt`. They follow walking and fade out in other modes.
+    pub fn update_view_effects(&mut self, dt: f64) {
+        let walking = self.mode == MovementMode::Walk && self.on_ground && !self.swimming;
+        let velocity = glam::DVec2::new(self.velocity.x, self.velocity.z);
+        let speed = velocity.length();
+        let ease = 1.0 - (-8.0 * dt).exp();
+        let weight = if walking { (speed / config_file::get().physics.walk_speed).min(1.5) } else { 0.0 };
+        self.bob_weight += (weight - self.bob_weight) * ease;
+        if walking { self.bob_phase = (self.bob_phase + speed * dt / config::HEAD_BOB_STRIDE * std::f64::consts::PI).rem_euclid(std::f64::consts::TAU); }
+        self.dip *= (-config::LANDING_RECOVERY * dt).exp();
+        self.shake *= (-config::SHAKE_RECOVERY * dt).exp();
+        self.shake_phase = (self.shake_phase + dt * config::SHAKE_FREQUENCY * std::f64::consts::TAU).rem_euclid(100.0 * std::f64::consts::TAU);
+        self.hurt = (self.hurt - (config::HURT_RECOVERY * dt) as f32).max(0.0);
+
+        let (sin_yaw, cos_yaw) = self.camera.yaw.sin_cos();
+        let strafe = velocity.dot(glam::DVec2::new(-(sin_yaw as f64), cos_yaw as f64)) / config_file::get().physics.walk_speed;
+        let tilt = if walking { strafe.clamp(-1.0, 1.0) as f32 * config::STRAFE_TILT } else { 0.0 };
+        self.tilt += (tilt - self.tilt) * ease as f32;
+    }
+
+    // Moves the field of view setting by FOV_STEP per step.
+    pub fn adjust_fov(&mut self, steps: f32) {
+        let (min, max) = config::FOV_RANGE;
+        self.fov = (self.fov + steps * config::FOV_STEP).clamp(min, max);
+    }
+
+    // Eases the camera's field of view toward the setting, widened at sprint speed on foot
+    // and narrowed while zooming.
+    pub fn update_fov(&mut self, dt: f64) {
+        let speed = glam::DVec2::new(self.velocity.x, self.velocity.z).length();
+        let sprint = if self.mode == MovementMode::Walk && self.controller.sprint {
+            ((speed - config_file::get().physics.walk_speed) / (config_file::get().physics.sprint_speed - config_file::get().physics.walk_speed)).clamp(0.0, 1.0) as f32
+        } else { 0.0 };
+        let target = if self.controller.zoom { config::ZOOM_FOV.min(self.fov) } else { self.fov + sprint * config::SPRINT_FOV_KICK };
+        self.camera.fov_y += (target - self.camera.fov_y) * (1.0 - (-config::FOV_EASE * dt as f32).exp());
+    }
+
+    // Look speed scale, so zooming in slows turning by as much as it narrows the view.
+    pub fn look_scale(&self) -> f32 {
+        self.camera.fov_y / self.fov
+    }
+
+    pub fn rotate(&mut self, yaw_delta: f32, pitch_delta: f32) {
+        self.camera.yaw += yaw_delta;
+        self.camera.pitch = (self.camera.pitch + pitch_delta).clamp(-1.5, 1.5);
+    }
+
+    fn apply_look_rate(&mut self, dt: f64) {
+        let look = self.controller.look_rate();
+        if look != glam::Vec2::ZERO {
+            let rate = config::STICK_LOOK_SPEED * self.look_scale() * dt as f32;
+            self.rotate(look.x * rate, look.y * rate);
+        }
+    }
+
+    // Movement input along `forward` and `right`, at most unit length.
+    fn move_input(&self, forward: DVec3, right: DVec3) -> DVec3 {
+        let mut input_dir = DVec3::ZERO;
+        if self.controller.move_fwd { input_dir += forward; }
+        if self.controller.move_back { input_dir -= forward; }
+        if self.controller.move_right { input_dir += right; }
+        if self.controller.move_left { input_dir -= right; }
+        let axis = self.controller.move_axis.as_dvec2();
+        input_dir += forward * axis.y + right * axis.x;
+        if input_dir.length_squared() > 1.0 { input_dir = input_dir.normalize(); }
+        input_dir
+    }
+
+    // Noclip: moves along the view direction, jump rises and crouch sinks.
+    pub fn fly(&mut self, dt: f64) {
+        self.apply_look_rate(dt);
+        let forward = self.camera.forward();
+        let right = forward.cross(DVec3::Y).normalize_or_zero();
+        let vertical = (self.controller.jump as i32 - self.controller.crouch as i32) as f64;
+        let direction = self.move_input(forward, right) + DVec3::Y * vertical;
+        self.velocity = direction.clamp_length_max(1.0) * self.fly_speed;
+        self.camera.eye += self.velocity * dt;
+    }
+
+    // Fires the grapple at the building under the crosshair when the button goes down and
+    // drops it when released.
+    fn update_grapple(&mut self, world: &World) {
+        let (held, was_held) = (self.controller.grapple, self.grapple_held);
+        self.grapple_held = held;
+        if !held { self.grapple = None; return; }
+        if was_held { return; }
+        if let Some(hit) = world.raycast(self.camera.eye.as_vec3(), self.camera.forward().as_vec3(), config::GRAPPLE_RANGE) {
+            self.grapple = Some(Grapple { anchor: hit.position.as_dvec3(), length: hit.distance as f64 });
+        }
+    }
+
+    // Swing constraint: a taut rope cancels velocity away from the anchor and springs back
+    // toward the (shrinking) rope length. Jumping lets go with a boost.
+    fn apply_grapple(&mut self, dt: f64) {
+        let Some(rope) = &mut self.grapple else { return };
+        if self.controller.jump && !self.on_ground {
+            self.grapple = None;
+            self.velocity.y = self.velocity.y.max(0.0) + config_file::get().physics.jump_force;
+            return;
+        }
+        rope.length = (rope.length - config::GRAPPLE_REEL_SPEED * dt).max(config::GRAPPLE_MIN_LENGTH);
+        let to_anchor = rope.anchor - self.camera.eye;
+        let distance = to_anchor.length();
+        if distance <= rope.length { return; }
+        let dir = to_anchor / distance;
+        let toward = self.velocity.dot(dir);
+        if toward < 0.0 { self.velocity -= dir * toward; }
+        self.velocity += dir * (distance - rope.length) * config::GRAPPLE_STIFFNESS * dt;
+    }
+
+    // Glider: the heading follows the view yaw, diving trades height for airspeed and pulling
+    // up bleeds it off, while the sink rate stays capped at GLIDE_FALL_SPEED.
+    fn glide(&mut self, forward: DVec3, dt: f64) {
+        let steer = (self.controller.move_right as i32 - self.controller.move_left as i32) as f32;
+        self.camera.yaw += steer * config::GLIDE_TURN_SPEED * dt as f32;
+        let heading = DVec3::new(self.camera.yaw.cos() as f64, 0.0, self.camera.yaw.sin() as f64);
+        let dive = -(self.camera.pitch as f64).sin();
+        let airspeed = glam::DVec2::new(self.velocity.x, self.velocity.z).dot(glam::DVec2::new(forward.x, forward.z));
+        let (min, max) = config::GLIDE_SPEED_RANGE;
+        let airspeed = (airspeed + (dive * config_file::get().physics.gravity - airspeed * config::GLIDE_DRAG) * dt).clamp(min, max);
+        self.velocity.x = heading.x * airspeed;
+        self.velocity.z = heading.z * airspeed;
+        self.velocity.y = (self.velocity.y - config_file::get().physics.gravity * dt).max(-config::GLIDE_FALL_SPEED * (1.0 + dive.max(0.0)));
+    }
+
+    // Buoyancy pulls the eye up to float just above `surface` and drag slows every direction.
+    // Jump strokes up, crouch dives.
+    fn swim(&mut self, surface: f64, dt: f64) {
+        let below = surface + config::SWIM_FLOAT_HEIGHT - self.camera.eye.y;
+        let lift = if below > 0.0 { below.min(1.0) * config::BUOYANCY } else { -config_file::get().physics.gravity };
+        let stroke = (self.controller.jump as i32 - self.controller.crouch as i32) as f64 * config::SWIM_STROKE_ACCELERATION;
+        self.velocity.y += (lift + stroke) * dt;
+        self.velocity *= (1.0 - config::WATER_DRAG * dt).max(0.0);
+    }
+
+    // Grabs the wall the player bumped into in the air: ledges within MANTLE_REACH are climbed
+    // when moving toward them, taller walls are run along when moving fast enough beside them.
+    fn grab_wall(&mut self, hit: WallHit, input_dir: DVec3) {
+        if self.grapple.is_some() || self.mantle.is_some() { return; }
+        let normal = DVec3::new(hit.normal.x, 0.0, hit.normal.z).normalize_or_zero();
+        let rise = hit.top as f64 - (self.camera.eye.y - self.eye_height);
+        if rise > 0.0 && rise <= config::MANTLE_REACH && input_dir.dot(-normal) > 0.5 {
+            self.mantle = Some(Mantle { top: hit.top as f64, normal });
+            self.wall_run = None;
+            self.gliding = false;
+        } else if rise > config::MANTLE_REACH && self.wall_run.is_none() && self.wall_run_ready && input_dir != DVec3::ZERO {
+            let along = self.velocity - normal * self.velocity.dot(normal);
+            if glam::DVec2::new(along.x, along.z).length() < config::WALL_RUN_MIN_SPEED { return; }
+            self.wall_run = Some(WallRun { normal, time: 0.0 });
+            self.wall_run_ready = false;
+            self.gliding = false;
+            self.velocity.y = self.velocity.y.max(0.0);
+        }
+    }
+
+    // Keeps a wall-run going while the wall is still beside the player and they keep moving.
+    // A fresh jump kicks off the wall.
+    fn apply_wall_run(&mut self, world: &World, input_dir: DVec3, dt: f64) {
+        let Some(run) = &mut self.wall_run else { return };
+        run.time += dt;
+        if self.controller.jump && !self.jump_held {
+            self.velocity += run.normal * config::WALL_JUMP_PUSH;
+            self.velocity.y = config_file::get().physics.jump_force;
+            self.wall_run = None;
+            self.wall_run_ready = true;
+            return;
+        }
+        let wall = world.check_collision(self.camera.eye - run.normal * config::WALL_RUN_REACH, self.eye_height)
+            .filter(|hit| hit.normal.dot(run.normal) > 0.7);
+        match wall {
+            Some(hit) if run.time < config::WALL_RUN_TIME && !self.on_ground && input_dir != DVec3::ZERO => {
+                run.normal = DVec3::new(hit.normal.x, 0.0, hit.normal.z).normalize_or_zero();
+            }
+            _ => self.wall_run = None,
+        }
+    }
+
+    // Climbs straight up the wall, then steps onto the ledge once the feet are over it.
+    // Crouching lets go.
+    fn apply_mantle(&mut self) {
+        let Some(ledge) = self.mantle else { return };
+        if self.controller.crouch {
+            self.mantle = None;
+        } else if self.camera.eye.y - self.eye_height >= ledge.top {
+            self.velocity = -ledge.normal * config::MANTLE_PUSH;
+            self.mantle = None;
+        } else {
+            self.velocity = DVec3::Y * config::MANTLE_SPEED;
+        }
+    }
+
+    // Rolls the camera into turns while gliding, away from the wall while wall-running, and
+    // levels it out otherwise.
+    fn bank(&mut self, dt: f64) {
+        let dt = dt as f32;
+        let target = if let Some(run) = &self.wall_run {
+            let (sin_yaw, cos_yaw) = self.camera.yaw.sin_cos();
+            run.normal.dot(DVec3::new(-(sin_yaw as f64), 0.0, cos_yaw as f64)) as f32 * config::WALL_RUN_TILT
+        } else if self.gliding && dt > 0.0 {
+            ((self.camera.yaw - self.previous_yaw) / dt * config::GLIDE_BANK).clamp(-config::GLIDE_MAX_BANK, config::GLIDE_MAX_BANK)
+        } else { 0.0 };
+        let step = config::GLIDE_BANK_SPEED * dt;
+        self.camera.roll += (target - self.camera.roll).clamp(-step, step);
+        self.previous_yaw = self.camera.yaw;
+    }
+
+    // Horizontal velocity after `dt` of moving toward `input_dir` at up to `speed`. On the
+    // ground friction slows the player first, less so when sliding on a crouch. In the air
+    // input only adds speed along its direction, so jumps and rope swings carry their momentum.
+    fn accelerate(&self, input_dir: DVec3, speed: f64, dt: f64) -> glam::DVec2 {
+        let mut horizontal = glam::DVec2::new(self.velocity.x, self.velocity.z);
+        let input = glam::DVec2::new(input_dir.x, input_dir.z);
+        // Water drag brakes as hard as it accelerates.
+        if self.swimming {
+            return horizontal + (input * speed - horizontal).clamp_length_max(config::MOVE_ACCELERATION * speed * dt);
+        }
+        if self.on_ground {
+            let current = horizontal.length();
+            let friction = if self.controller.crouch && current > config::SLIDE_MIN_SPEED { config::SLIDE_FRICTION } else { config::GROUND_FRICTION };
+            let slowed = (current - friction * current.max(config::FRICTION_STOP_SPEED) * dt).max(0.0);
+            if current > 0.0 { horizontal *= slowed / current; }
+        }
+        let Some(direction) = input.try_normalize() else { return horizontal };
+        let target = speed * input.length();
+        let rate = if self.on_ground { config::MOVE_ACCELERATION } else { config::AIR_ACCELERATION };
+        let gain = (target - horizontal.dot(direction)).clamp(0.0, rate * target * dt);
+        horizontal + direction * gain
+    }
+
+    // Jumps from the ground, or shortly after running off an edge, on a held or recently
+    // pressed jump. Releasing jump while still rising cuts the jump short.
+    fn apply_jump(&mut self, dt: f64) {
+        if self.controller.jump && !self.jump_held {
+            self.jump_buffer = config::JUMP_BUFFER_TIME;
+        } else {
+            self.jump_buffer = (self.jump_buffer - dt).max(0.0);
+        }
+        self.coyote = if self.on_ground { config::COYOTE_TIME } else { (self.coyote - dt).max(0.0) };
+
+        let can_jump = self.coyote > 0.0 && !self.swimming && self.wall_run.is_none() && self.mantle.is_none() && self.grapple.is_none();
+        if can_jump && (self.controller.jump || self.jump_buffer > 0.0) {
+            // Jumping off a rising elevator keeps its speed.
+            let lift = if self.on_ground { self.ground_velocity.max(0.0) } else { 0.0 };
+            self.velocity.y = config_file::get().physics.jump_force + lift;
+            self.on_ground = false;
+            self.coyote = 0.0;
+            self.jump_buffer = 0.0;
+            self.jump_rising = true;
+        } else if self.jump_rising && (self.velocity.y <= 0.0 || self.on_ground) {
+            self.jump_rising = false;
+        } else if self.jump_rising && !self.controller.jump {
+            self.velocity.y *= config::JUMP_CUT;
+            self.jump_rising = false;
+        }
+    }
+
+    pub fn update(&mut self, world: &World, dt: f64) {
+        self.apply_look_rate(dt);
+        self.update_grapple(world);
+        let eye = self.camera.eye;
+        let surface = world.water_surface(eye.x as f32, eye.z as f32).map(f64::from);
+        self.swimming = surface.is_some_and(|s| eye.y - self.eye_height < s - config::SWIM_DEPTH);
+        // Gliding opens once falling and lasts while jump is held in the air.
+        self.gliding = self.controller.jump && !self.on_ground && !self.swimming && self.grapple.is_none()
+            && self.wall_run.is_none() && self.mantle.is_none() && (self.gliding || self.velocity.y < 0.0);
+
+        let (sin_yaw, cos_yaw) = self.camera.yaw.sin_cos();
+        let forward = DVec3::new(cos_yaw as f64, 0.0, sin_yaw as f64).normalize();
+        let right = DVec3::new(-(sin_yaw as f64), 0.0, cos_yaw as f64).normalize();
+        let input_dir = self.move_input(forward, right);
+        if self.grab_ladder(world, forward, input_dir) { return; }
+
+        // Crouching wins over sprinting.
+        let speed = if self.swimming { config::SWIM_SPEED }
+            else if self.controller.crouch { config_file::get().physics.crouch_speed }
+            else if self.controller.sprint { config_file::get().physics.sprint_speed }
+            else { config_file::get().physics.walk_speed };
+        let horizontal = self.accelerate(input_dir, speed, dt);
+        if self.gliding {
+            self.glide(forward, dt);
+        } else {
+            self.velocity.x = horizontal.x;
+            self.velocity.z = horizontal.y;
+        }
+
+        // Grounded players move the eye directly; in the air only the body shape changes. This
+        // also raises the eye after stepping up a ledge.
+        let target_eye = if self.controller.crouch && !self.swimming { config::CROUCH_EYE_HEIGHT } else { config::EYE_HEIGHT };
+        let eye_step = (target_eye - self.eye_height).clamp(-config::CROUCH_TRANSITION_SPEED * dt, config::CROUCH_TRANSITION_SPEED * dt);
+        self.eye_height += eye_step;
+        if self.on_ground { self.camera.eye.y += eye_step; }
+        self.apply_wall_run(world, input_dir, dt);
+        if let Some(surface) = surface && self.swimming {
+            self.swim(surface, dt);
+        } else if self.wall_run.is_some() {
+            self.velocity.y -= config::WALL_RUN_GRAVITY * dt;
+        } else if !self.gliding {
+            self.velocity.y -= config_file::get().physics.gravity * dt;
+            self.velocity.y = self.velocity.y.max(config_file::get().physics.terminal_velocity);
+        }
+        self.apply_grapple(dt);
+        self.apply_mantle();
+
+        self.apply_jump(dt);
+
+        // Landing speed, for the dip in the view.
+        let (was_on_ground, fall_speed) = (self.on_ground, -self.velocity.y);
+        let _scope = profiler::scope("collision");
+        let mut remaining_dt = dt;
+        while remaining_dt > 0.0 {
+            let step = remaining_dt.min(config::PHYSICS_STEP_SIZE);
+            let mut next_pos = self.camera.eye + self.velocity * step;
+            if self.on_ground { next_pos.y += self.ground_velocity * step; }
+            // Resting on a wall top counts as standing.
+            let mut supported = false;
+            // Wall bumped into while airborne, for wall-runs and ledge grabs.
+            let mut wall_contact = None;
+
+            for _ in 0..config::MAX_PHYSICS_STEPS {
+                let Some(hit) = world.check_collision(next_pos, self.eye_height) else { break };
+                let dot = self.velocity.dot(hit.normal);
+                // Walking into a curb or low ledge lifts the feet onto it. The eye stays put
+                // and catches up through the crouch transition, so the step isn't a jolt.
+                let rise = hit.top as f64 - (next_pos.y - self.eye_height);
+                if self.on_ground && dot < 0.0 && rise > 0.0 && rise <= config::STEP_HEIGHT {
+                    self.eye_height -= rise;
+                    continue;
+                }
+                if !self.on_ground && dot < 0.0 && hit.normal.y.abs() < 0.3 { wall_contact = Some(hit); }
+                if dot < 0.0 { self.velocity -= hit.normal * dot; }
+                next_pos += hit.normal * (hit.depth + 0.0001);
+                supported |= hit.normal.y > 0.7;
+            }
+
+            // Walking out of the tunnel network sideways is blocked like a wall.
+            let ground = match world.floor_at(next_pos, self.eye_height) {
+                Some(hit) => hit,
+                // A swimmer with the eye above the bank climbs out onto it.
+                None if self.swimming && let Some(hit) = world.floor_at(DVec3::new(next_pos.x, next_pos.y.max(self.eye_height), next_pos.z), self.eye_height)
+                    .filter(|hit| (hit.floor as f64) < next_pos.y) => {
+                    next_pos.y = next_pos.y.max(hit.floor as f64 + self.eye_height);
+                    hit
+                }
+                None => {
+                    next_pos.x = self.camera.eye.x;
+                    next_pos.z = self.camera.eye.z;
+                    self.velocity.x = 0.0;
+                    self.velocity.z = 0.0;
+                    world.floor_at(next_pos, self.eye_height).unwrap_or(TunnelHit { floor: 0.0, ceiling: None, velocity: 0.0 })
+                }
+            };
+            if let Some(ceiling) = ground.ceiling {
+                let max_eye = ceiling as f64 - 0.2;
+                if next_pos.y > max_eye {
+                    next_pos.y = max_eye;
+                    self.velocity.y = self.velocity.y.min(0.0);
+                }
+            }
+
+            let floor_eye = ground.floor as f64 + self.eye_height;
+            if next_pos.y <= floor_eye {
+                next_pos.y = floor_eye;
+                self.velocity.y = 0.0;
+                self.on_ground = true;
+                self.ground_velocity = ground.velocity as f64;
+            } else {
+                // Stepping off a moving elevator keeps its vertical speed.
+                if self.on_ground { self.velocity.y += self.ground_velocity; }
+                self.on_ground = supported;
+                self.ground_velocity = 0.0;
+            }
+
+            self.camera.eye = next_pos;
+            if let Some(hit) = wall_contact && !self.on_ground && !self.swimming { self.grab_wall(hit, input_dir); }
+            remaining_dt -= step;
+        }
+        if !was_on_ground && self.on_ground && fall_speed > config::LANDING_DIP_MIN_SPEED {
+            self.dip = self.dip.max(((fall_speed - config::LANDING_DIP_MIN_SPEED) * config::LANDING_DIP).min(config::LANDING_DIP_MAX));
+        }
+        if !was_on_ground && self.on_ground && !self.swimming && fall_speed > config::HARD_LANDING_SPEED.0 {
+            let (min, max) = config::HARD_LANDING_SPEED;
+            let strength = ((fall_speed - min) / (max - min)).min(1.0);
+            self.shake = self.shake.max(strength);
+            self.hurt = self.hurt.max(strength as f32);
+        }
+        if self.on_ground {
+            self.gliding = false;
+            self.wall_run = None;
+            self.mantle = None;
+            self.wall_run_ready = true;
+        }
+        self.jump_held = self.controller.jump;
+        self.bank(dt);
+    }
+}
@@ -0,0 +1,73 @@
+// poi.rs
+use glam::Vec2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceKind {
+    // Shops, stations, monuments and the like.
+    Point,
+    // Neighbourhoods, squares and parks (`place` and `leisure` nodes), announced on arrival.
+    District,
+    // A named building footprint, at its centroid.
+    Building,
+}
+
+#[derive(Debug, Clone)]
+struct Place {
+    // Lowercased for lookups.
+    key: String,
+    name: String,
+    position: Vec2,
+    kind: PlaceKind,
+}
+
+// Named points of interest from the map's nodes and named buildings, looked up by name
+// (e.g. for `--spawn`) or by position for the landmark labels.
+#[derive(Debug, Clone, Default)]
+pub struct PoiIndex {
+    places: Vec<Place>,
+}
+
+impl PoiIndex {
+    // Nodes with a `name` and one of these keys are indexed.
+    pub fn is_poi_key(key: &str) -> bool {
+        matches!(key, "place" | "tourism" | "amenity" | "historic" | "leisure" | "railway" | "public_transport" | "shop")
+    }
+
+    pub fn push(&mut self, name: &str, position: Vec2, kind: PlaceKind) {
+        self.places.push(Place { key: name.to_lowercase(), name: name.to_string(), position, kind });
+    }
+
+    // Adds `other`'s places after these, as when joining per-block results.
+    pub fn append(&mut self, mut other: PoiIndex) {
+        self.places.append(&mut other.places);
+    }
+
+    pub fn len(&self) -> usize {
+        self.places.len()
+    }
+
+    // Position of the place named `name`, ignoring case. Without an exact match, the
+    // shortest name containing it wins.
+    pub fn find(&self, name: &str) -> Option<Vec2> {
+        let name = name.trim().to_lowercase();
+        if let Some(p) = self.places.iter().find(|p| p.key == name) { return Some(p.position); }
+        self.places.iter().filter(|p| p.key.contains(&name)).min_by_key(|p| p.key.len()).map(|p| p.position)
+    }
+
+    // Named buildings within `radius` of `center`, by name and centroid.
+    pub fn buildings_near(&self, center: Vec2, radius: f32) -> impl Iterator<Item = (&str, Vec2)> {
+        self.places.iter()
+            .filter(move |p| p.kind == PlaceKind::Building && p.position.distance_squared(center) <= radius * radius)
+            .map(|p| (p.name.as_str(), p.position))
+    }
+
+    // The closest district within `radius` of `center`, by name and position.
+    pub fn nearest_district(&self, center: Vec2, radius: f32) -> Option<(&str, Vec2)> {
+        self.places.iter()
+            .filter(|p| p.kind == PlaceKind::District)
+            .map(|p| (p, p.position.distance_squared(center)))
+            .filter(|&(_, d)| d <= radius * radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(p, _)| (p.name.as_str(), p.position))
+    }
+}
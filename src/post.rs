@@ -0,0 +1,762 @@
+// post.rs
+use std::time::Instant;
+use wgpu::util::DeviceExt;
+use serde::{Deserialize, Serialize};
+use crate::{config, config_file, shader, shader_cache};
+
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+// World-space normal in rgb, fraction of the color that is ambient light in alpha.
+pub const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const AO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+// Log-luminance is averaged down a mip chain of this size (must match LUM_SIZE in the shader).
+const LUMINANCE_SIZE: u32 = 256;
+const LUMINANCE_MIPS: u32 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tonemapper {
+    // Exposure only; values above 1 clip.
+    None,
+    Reinhard,
+    // Filmic curve with a soft shoulder.
+    Aces,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AntiAliasing {
+    // One sample per pixel and nothing else; cheapest, but edges crawl.
+    Off,
+    // 4x multisampling; smooths geometry edges only.
+    Msaa,
+    // One sample per pixel, jittered every frame and accumulated over time; also catches
+    // shading aliasing and edges thinner than a pixel.
+    Taa,
+}
+
+// Samples per pixel of the scene's color, normal and depth targets. graphics.anti_aliasing only
+// changes on a restart, so this holds for the whole run.
+pub fn scene_samples() -> u32 {
+    match config_file::get().graphics.anti_aliasing {
+        AntiAliasing::Msaa => 4,
+        AntiAliasing::Off | AntiAliasing::Taa => 1,
+    }
+}
+
+// Shaders declare the scene depth as multisampled. Without MSAA it's a plain depth texture,
+// whose textureLoad takes the same arguments (a mip level instead of a sample index).
+pub fn scene_depth_shader(source: &str) -> String {
+    if scene_samples() > 1 { source.to_string() } else { source.replace("texture_depth_multisampled_2d", "texture_depth_2d") }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniform {
+    // x: sample radius (m), y: strength (0 disables), z: sample count, w: fade-out distance (m).
+    ssao: [f32; 4],
+    // x: key (target average luminance), y/z: exposure range, w: adaptation speed.
+    exposure: [f32; 4],
+    // x: seconds since the last frame, y: fixed exposure (0 = auto), z: tonemapper, w: render scale.
+    frame: [f32; 4],
+    // x: threshold, y: soft knee, z: intensity (0 disables).
+    bloom: [f32; 4],
+    // x: focus distance (m), y: blur radius in pixels (0 disables), z/w: near and far clip distances.
+    dof: [f32; 4],
+}
+
+fn fullscreen_pipeline(
+    device: &wgpu::Device, label: &str, source: &str, entry_point: &str, layouts: &[&wgpu::BindGroupLayout], format: wgpu::TextureFormat,
+    blend: Option<wgpu::BlendState>,
+) -> wgpu::RenderPipeline {
+    let module = shader_cache::module(device, label, source);
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: layouts, push_constant_ranges: &[] });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label), layout: Some(&layout),
+        vertex: wgpu::VertexState { module: &module, entry_point: "vs_main", buffers: &[] },
+        fragment: Some(wgpu::FragmentState {
+            module: &module, entry_point,
+            targets: &[Some(wgpu::ColorTargetState { format, blend, write_mask: wgpu::ColorWrites::ALL })],
+        }),
+        primitive: wgpu::PrimitiveState::default(), depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(), multiview: None,
+    })
+}
+
+fn texture_entry(binding: u32, sample_type: wgpu::TextureSampleType, multisampled: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding, visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture { sample_type, view_dimension: wgpu::TextureViewDimension::D2, multisampled }, count: None,
+    }
+}
+
+fn uniform_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding, visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None,
+    }
+}
+
+fn sampler_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry { binding, visibility: wgpu::ShaderStages::FRAGMENT, ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering), count: None }
+}
+
+fn view_entry(binding: u32, view: &wgpu::TextureView) -> wgpu::BindGroupEntry<'_> {
+    wgpu::BindGroupEntry { binding, resource: wgpu::BindingResource::TextureView(view) }
+}
+
+fn draw_fullscreen(encoder: &mut wgpu::CommandEncoder, label: &str, target: &wgpu::TextureView, pipeline: &wgpu::RenderPipeline, bind_group: &wgpu::BindGroup) {
+    draw_fullscreen_with(encoder, label, target, wgpu::LoadOp::Clear(wgpu::Color::BLACK), pipeline, bind_group);
+}
+
+fn draw_fullscreen_with(
+    encoder: &mut wgpu::CommandEncoder, label: &str, target: &wgpu::TextureView, load: wgpu::LoadOp<wgpu::Color>,
+    pipeline: &wgpu::RenderPipeline, bind_group: &wgpu::BindGroup,
+) {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target, resolve_target: None,
+            ops: wgpu::Operations { load, store: wgpu::StoreOp::Store },
+        })],
+        depth_stencil_attachment: None, timestamp_writes: None, occlusion_query_set: None,
+    });
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.draw(0..3, 0..1);
+}
+
+// Offscreen targets the scene renders into; the composite pass writes them to the surface.
+struct Targets {
+    // The multisampled targets are None without MSAA, where the scene renders straight
+    // into `hdr` and `normal`.
+    hdr_msaa: Option<wgpu::TextureView>,
+    hdr_texture: wgpu::Texture,
+    hdr: wgpu::TextureView,
+    // Copy of the resolved scene the water pass reflects while drawing over `hdr`.
+    scene_copy_texture: wgpu::Texture,
+    scene_copy: wgpu::TextureView,
+    normal_msaa: Option<wgpu::TextureView>,
+    normal: wgpu::TextureView,
+    ao: wgpu::TextureView,
+}
+
+impl Targets {
+    fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let texture = |label, format, sample_count, usage| device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label), size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1, sample_count, dimension: wgpu::TextureDimension::D2, format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | usage, view_formats: &[],
+        });
+        let create = |label, format, sample_count| texture(label, format, sample_count, wgpu::TextureUsages::empty()).create_view(&wgpu::TextureViewDescriptor::default());
+        let multisampled = |label, format| (scene_samples() > 1).then(|| create(label, format, scene_samples()));
+        // COPY_DST so the TAA resolve can write back into it.
+        let hdr_texture = texture("HDR", HDR_FORMAT, 1, wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST);
+        let scene_copy_texture = texture("Scene Copy", HDR_FORMAT, 1, wgpu::TextureUsages::COPY_DST);
+        Self {
+            hdr_msaa: multisampled("HDR MSAA", HDR_FORMAT),
+            hdr: hdr_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            hdr_texture,
+            scene_copy: scene_copy_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            scene_copy_texture,
+            normal_msaa: multisampled("Normal MSAA", NORMAL_FORMAT),
+            normal: create("Normal", NORMAL_FORMAT, 1),
+            ao: create("SSAO", AO_FORMAT, 1),
+        }
+    }
+
+    fn bytes(width: u32, height: u32) -> u64 {
+        let pixels = width as u64 * height as u64;
+        let msaa = if scene_samples() > 1 { scene_samples() as u64 } else { 0 };
+        pixels * (8 * msaa + 8 + 8 + 8 * msaa + 8 + 1)
+    }
+}
+
+// The scene writes its multisampled target and resolves it into `resolved`, or writes `resolved` directly.
+fn scene_attachment<'a>(msaa: Option<&'a wgpu::TextureView>, resolved: &'a wgpu::TextureView, clear: wgpu::Color) -> wgpu::RenderPassColorAttachment<'a> {
+    match msaa {
+        Some(view) => wgpu::RenderPassColorAttachment {
+            view, resolve_target: Some(resolved),
+            ops: wgpu::Operations { load: wgpu::LoadOp::Clear(clear), store: wgpu::StoreOp::Discard },
+        },
+        None => wgpu::RenderPassColorAttachment {
+            view: resolved, resolve_target: None,
+            ops: wgpu::Operations { load: wgpu::LoadOp::Clear(clear), store: wgpu::StoreOp::Store },
+        },
+    }
+}
+
+// Eye adaptation: the scene's average log-luminance is reduced down a mip chain to one
+// texel, and the exposure eases toward the value that maps it to the configured key.
+// Exposure ping-pongs between two 1x1 textures so each frame can read the previous one.
+struct AutoExposure {
+    luminance_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    adapt_pipeline: wgpu::RenderPipeline,
+    source_layout: wgpu::BindGroupLayout,
+    mip_views: Vec<wgpu::TextureView>,
+    // Bind groups reading each mip level, used to produce the next one.
+    mip_bind_groups: Vec<wgpu::BindGroup>,
+    hdr_bind_group: wgpu::BindGroup,
+    exposure_views: [wgpu::TextureView; 2],
+    adapt_bind_groups: [wgpu::BindGroup; 2],
+    current: usize,
+}
+
+impl AutoExposure {
+    fn new(device: &wgpu::Device, hdr: &wgpu::TextureView, uniform: &wgpu::Buffer) -> Self {
+        let float = wgpu::TextureSampleType::Float { filterable: false };
+        let source_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[texture_entry(0, float, false)], label: Some("Luminance Layout"),
+        });
+        let adapt_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[texture_entry(0, float, false), texture_entry(1, float, false), uniform_entry(2)], label: Some("Exposure Layout"),
+        });
+        let luminance_pipeline = fullscreen_pipeline(device, "Luminance", shader::EXPOSURE_SHADER, "fs_luminance", &[&source_layout], wgpu::TextureFormat::R16Float, None);
+        let downsample_pipeline = fullscreen_pipeline(device, "Luminance Downsample", shader::EXPOSURE_SHADER, "fs_downsample", &[&source_layout], wgpu::TextureFormat::R16Float, None);
+        let adapt_pipeline = fullscreen_pipeline(device, "Exposure Adapt", shader::EXPOSURE_SHADER, "fs_adapt", &[&adapt_layout], wgpu::TextureFormat::R32Float, None);
+
+        let luminance = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Luminance"), size: wgpu::Extent3d { width: LUMINANCE_SIZE, height: LUMINANCE_SIZE, depth_or_array_layers: 1 },
+            mip_level_count: LUMINANCE_MIPS, sample_count: 1, dimension: wgpu::TextureDimension::D2, format: wgpu::TextureFormat::R16Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING, view_formats: &[],
+        });
+        let mip_views: Vec<wgpu::TextureView> = (0..LUMINANCE_MIPS).map(|mip| luminance.create_view(&wgpu::TextureViewDescriptor {
+            base_mip_level: mip, mip_level_count: Some(1), ..Default::default()
+        })).collect();
+        let source_group = |view| device.create_bind_group(&wgpu::BindGroupDescriptor { layout: &source_layout, entries: &[view_entry(0, view)], label: None });
+        let mip_bind_groups = mip_views.iter().map(source_group).collect();
+        let hdr_bind_group = source_group(hdr);
+
+        let exposure = || device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Exposure"), size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            mip_level_count: 1, sample_count: 1, dimension: wgpu::TextureDimension::D2, format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING, view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default());
+        let exposure_views = [exposure(), exposure()];
+        // Group i writes exposure i, reading the other one as the previous frame's value.
+        let adapt_group = |i: usize| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &adapt_layout,
+            entries: &[
+                view_entry(0, &mip_views[LUMINANCE_MIPS as usize - 1]), view_entry(1, &exposure_views[1 - i]),
+                wgpu::BindGroupEntry { binding: 2, resource: uniform.as_entire_binding() },
+            ],
+            label: None,
+        });
+        let adapt_bind_groups = [adapt_group(0), adapt_group(1)];
+
+        Self {
+            luminance_pipeline, downsample_pipeline, adapt_pipeline, source_layout, mip_views, mip_bind_groups, hdr_bind_group,
+            exposure_views, adapt_bind_groups, current: 0,
+        }
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, hdr: &wgpu::TextureView) {
+        self.hdr_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor { layout: &self.source_layout, entries: &[view_entry(0, hdr)], label: None });
+    }
+
+    fn bytes() -> u64 {
+        // R16 mip chain (~4/3 of the base level) plus the two R32 exposure texels.
+        LUMINANCE_SIZE as u64 * LUMINANCE_SIZE as u64 * 2 * 4 / 3 + 8
+    }
+
+    fn render(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        draw_fullscreen(encoder, "Luminance Pass", &self.mip_views[0], &self.luminance_pipeline, &self.hdr_bind_group);
+        for mip in 1..LUMINANCE_MIPS as usize {
+            draw_fullscreen(encoder, "Luminance Downsample", &self.mip_views[mip], &self.downsample_pipeline, &self.mip_bind_groups[mip - 1]);
+        }
+        self.current = 1 - self.current;
+        draw_fullscreen(encoder, "Exposure Adapt", &self.exposure_views[self.current], &self.adapt_pipeline, &self.adapt_bind_groups[self.current]);
+    }
+}
+
+// Mip chain of the bright parts of the exposed image at half resolution and below.
+struct BloomTargets {
+    views: Vec<wgpu::TextureView>,
+    // Prefilter reads the HDR target and the exposure, one group per exposure texture.
+    prefilter_bind_groups: [wgpu::BindGroup; 2],
+    // Group i reads mip i.
+    mip_bind_groups: Vec<wgpu::BindGroup>,
+    bytes: u64,
+}
+
+struct BloomLayouts {
+    prefilter: wgpu::BindGroupLayout,
+    chain: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+// Bloom: bright pixels above a soft threshold are downsampled into a mip chain, then each
+// level is blurred back up onto the one above, and the composite adds the result.
+struct Bloom {
+    layouts: BloomLayouts,
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    targets: BloomTargets,
+}
+
+impl Bloom {
+    fn new(device: &wgpu::Device, width: u32, height: u32, hdr: &wgpu::TextureView, uniform: &wgpu::Buffer, exposure: &AutoExposure) -> Self {
+        let filterable = wgpu::TextureSampleType::Float { filterable: true };
+        let chain_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[texture_entry(0, filterable, false), sampler_entry(1), uniform_entry(2)], label: Some("Bloom Layout"),
+        });
+        let prefilter_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[texture_entry(0, filterable, false), sampler_entry(1), uniform_entry(2), texture_entry(3, wgpu::TextureSampleType::Float { filterable: false }, false)],
+            label: Some("Bloom Prefilter Layout"),
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear, min_filter: wgpu::FilterMode::Linear, ..Default::default()
+        });
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent { src_factor: wgpu::BlendFactor::One, dst_factor: wgpu::BlendFactor::One, operation: wgpu::BlendOperation::Add },
+            alpha: wgpu::BlendComponent::REPLACE,
+        };
+        let prefilter_pipeline = fullscreen_pipeline(device, "Bloom Prefilter", shader::BLOOM_SHADER, "fs_prefilter", &[&prefilter_layout], HDR_FORMAT, None);
+        let downsample_pipeline = fullscreen_pipeline(device, "Bloom Downsample", shader::BLOOM_SHADER, "fs_downsample", &[&chain_layout], HDR_FORMAT, None);
+        let upsample_pipeline = fullscreen_pipeline(device, "Bloom Upsample", shader::BLOOM_SHADER, "fs_upsample", &[&chain_layout], HDR_FORMAT, Some(additive));
+
+        let layouts = BloomLayouts { prefilter: prefilter_layout, chain: chain_layout, sampler };
+        let targets = Self::create_targets(device, &layouts, width, height, hdr, uniform, exposure);
+        Self { layouts, prefilter_pipeline, downsample_pipeline, upsample_pipeline, targets }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device, layouts: &BloomLayouts, width: u32, height: u32, hdr: &wgpu::TextureView, uniform: &wgpu::Buffer, exposure: &AutoExposure,
+    ) -> BloomTargets {
+        let (width, height) = ((width / 2).max(1), (height / 2).max(1));
+        let levels = config::BLOOM_LEVELS.min(32 - width.min(height).leading_zeros()).max(1);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Bloom"), size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: levels, sample_count: 1, dimension: wgpu::TextureDimension::D2, format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING, view_formats: &[],
+        });
+        let views: Vec<wgpu::TextureView> = (0..levels).map(|mip| texture.create_view(&wgpu::TextureViewDescriptor {
+            base_mip_level: mip, mip_level_count: Some(1), ..Default::default()
+        })).collect();
+        let sampler = wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&layouts.sampler) };
+        let uniform = wgpu::BindGroupEntry { binding: 2, resource: uniform.as_entire_binding() };
+        let prefilter = |i: usize| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layouts.prefilter,
+            entries: &[view_entry(0, hdr), sampler.clone(), uniform.clone(), view_entry(3, &exposure.exposure_views[i])],
+            label: None,
+        });
+        let mip_bind_groups = views.iter().map(|view| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layouts.chain, entries: &[view_entry(0, view), sampler.clone(), uniform.clone()], label: None,
+        })).collect();
+        BloomTargets {
+            prefilter_bind_groups: [prefilter(0), prefilter(1)], mip_bind_groups, views,
+            bytes: width as u64 * height as u64 * 8 * 4 / 3,
+        }
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32, hdr: &wgpu::TextureView, uniform: &wgpu::Buffer, exposure: &AutoExposure) {
+        self.targets = Self::create_targets(device, &self.layouts, width, height, hdr, uniform, exposure);
+    }
+
+    fn view(&self) -> &wgpu::TextureView {
+        &self.targets.views[0]
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, exposure: usize) {
+        let t = &self.targets;
+        draw_fullscreen(encoder, "Bloom Prefilter", &t.views[0], &self.prefilter_pipeline, &t.prefilter_bind_groups[exposure]);
+        for mip in 1..t.views.len() {
+            draw_fullscreen(encoder, "Bloom Downsample", &t.views[mip], &self.downsample_pipeline, &t.mip_bind_groups[mip - 1]);
+        }
+        for mip in (0..t.views.len() - 1).rev() {
+            draw_fullscreen_with(encoder, "Bloom Upsample", &t.views[mip], wgpu::LoadOp::Load, &self.upsample_pipeline, &t.mip_bind_groups[mip + 1]);
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TaaUniform {
+    inv_view_proj: [[f32; 4]; 4],
+    prev_view_proj: [[f32; 4]; 4],
+    viewport: [f32; 4],
+    // x: weight of the new frame, y: 1 when the history holds this view's last frame.
+    params: [f32; 4],
+}
+
+// Reprojection matrices of one player's viewport.
+struct TaaView {
+    uniform: TaaUniform,
+    // Unjittered view-projection of the latest update, the next one's previous.
+    view_proj: glam::Mat4,
+    has_history: bool,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+struct TaaHistory {
+    textures: [wgpu::Texture; 2],
+    views: [wgpu::TextureView; 2],
+    // Group i writes history i, reading the current frame, the depth and history 1 - i.
+    bind_groups: [wgpu::BindGroup; 2],
+}
+
+// Temporal anti-aliasing: every frame the projection is offset by a different sub-pixel
+// jitter and the result is blended into a history buffer. The history is reprojected from
+// depth and the previous view-projection alone (the city doesn't move, so there's no
+// velocity buffer) and clamped to the current pixel's neighbourhood to limit ghosting.
+struct Taa {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    history: TaaHistory,
+    views: Vec<TaaView>,
+    current: usize,
+    frame: u32,
+}
+
+impl Taa {
+    fn new(device: &wgpu::Device, hdr: &wgpu::TextureView, depth: &wgpu::TextureView, width: u32, height: u32, view_count: usize) -> Self {
+        let unfilterable = wgpu::TextureSampleType::Float { filterable: false };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                texture_entry(0, unfilterable, false), texture_entry(1, wgpu::TextureSampleType::Float { filterable: true }, false),
+                texture_entry(2, wgpu::TextureSampleType::Depth, false), sampler_entry(3),
+            ],
+            label: Some("TAA Layout"),
+        });
+        let view_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { entries: &[uniform_entry(0)], label: Some("TAA View Layout") });
+        let pipeline = fullscreen_pipeline(device, "TAA", shader::TAA_SHADER, "fs_main", &[&layout, &view_layout], HDR_FORMAT, None);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear, min_filter: wgpu::FilterMode::Linear, ..Default::default()
+        });
+
+        let views = (0..view_count).map(|_| {
+            let uniform = TaaUniform {
+                inv_view_proj: glam::Mat4::IDENTITY.to_cols_array_2d(), prev_view_proj: glam::Mat4::IDENTITY.to_cols_array_2d(),
+                viewport: [0.0; 4], params: [config::TAA_BLEND, 0.0, 0.0, 0.0],
+            };
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("TAA Uniform"), contents: bytemuck::cast_slice(&[uniform]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &view_layout, entries: &[wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }], label: None,
+            });
+            TaaView { uniform, view_proj: glam::Mat4::IDENTITY, has_history: false, buffer, bind_group }
+        }).collect();
+
+        let history = Self::create_history(device, &layout, &sampler, hdr, depth, width, height);
+        Self { pipeline, layout, sampler, history, views, current: 0, frame: 0 }
+    }
+
+    fn create_history(
+        device: &wgpu::Device, layout: &wgpu::BindGroupLayout, sampler: &wgpu::Sampler, hdr: &wgpu::TextureView, depth: &wgpu::TextureView, width: u32, height: u32,
+    ) -> TaaHistory {
+        let texture = || device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("TAA History"), size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1, sample_count: 1, dimension: wgpu::TextureDimension::D2, format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC, view_formats: &[],
+        });
+        let textures = [texture(), texture()];
+        let views = [0, 1].map(|i| textures[i].create_view(&wgpu::TextureViewDescriptor::default()));
+        let bind_group = |i: usize| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[view_entry(0, hdr), view_entry(1, &views[1 - i]), view_entry(2, depth), wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::Sampler(sampler) }],
+            label: None,
+        });
+        let bind_groups = [bind_group(0), bind_group(1)];
+        TaaHistory { textures, views, bind_groups }
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, hdr: &wgpu::TextureView, depth: &wgpu::TextureView, width: u32, height: u32) {
+        self.history = Self::create_history(device, &self.layout, &self.sampler, hdr, depth, width, height);
+        for view in &mut self.views { view.has_history = false; }
+    }
+
+    fn bytes(width: u32, height: u32) -> u64 {
+        width as u64 * height as u64 * 8 * 2
+    }
+
+    // Halton (2, 3) point of this frame, centered on the pixel.
+    fn jitter(&self) -> glam::Vec2 {
+        let halton = |mut index: u32, base: u32| {
+            let (mut result, mut fraction) = (0.0, 1.0);
+            while index > 0 {
+                fraction /= base as f32;
+                result += fraction * (index % base) as f32;
+                index /= base;
+            }
+            result
+        };
+        let index = self.frame % config::TAA_JITTER_FRAMES.max(1) + 1;
+        glam::Vec2::new(halton(index, 2) - 0.5, halton(index, 3) - 0.5)
+    }
+
+    fn update_view(&mut self, queue: &wgpu::Queue, view: usize, view_proj: glam::Mat4, viewport: [f32; 4]) {
+        let view = &mut self.views[view];
+        // A moved or resized viewport would reproject into the wrong part of the history.
+        if view.uniform.viewport != viewport { view.has_history = false; }
+        view.uniform.prev_view_proj = view.view_proj.to_cols_array_2d();
+        view.uniform.inv_view_proj = view_proj.inverse().to_cols_array_2d();
+        view.uniform.viewport = viewport;
+        view.uniform.params[1] = if view.has_history { 1.0 } else { 0.0 };
+        view.view_proj = view_proj;
+        queue.write_buffer(&view.buffer, 0, bytemuck::cast_slice(&[view.uniform]));
+    }
+
+    // Blends the frame in `hdr_texture` into the history, then writes the result back over it.
+    fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder, hdr_texture: &wgpu::Texture, viewports: &[[f32; 4]]) {
+        self.current = 1 - self.current;
+        self.frame = self.frame.wrapping_add(1);
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("TAA Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.history.views[self.current], resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: None, timestamp_writes: None, occlusion_query_set: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.history.bind_groups[self.current], &[]);
+            for (view, &[x, y, w, h]) in self.views.iter().zip(viewports) {
+                pass.set_viewport(x, y, w, h, 0.0, 1.0);
+                pass.set_bind_group(1, &view.bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+        }
+        for view in self.views.iter_mut().take(viewports.len()) { view.has_history = true; }
+        let history = &self.history.textures[self.current];
+        encoder.copy_texture_to_texture(history.as_image_copy(), hdr_texture.as_image_copy(), history.size());
+    }
+}
+
+// Post-processing chain: scene -> HDR + normal targets -> TAA (if selected) -> SSAO -> exposure ->
+// bloom -> composite (ambient occlusion, exposure, bloom and tonemapping) onto the surface.
+pub struct PostProcess {
+    targets: Targets,
+    uniform: PostUniform,
+    uniform_buffer: wgpu::Buffer,
+    ssao_layout: wgpu::BindGroupLayout,
+    ssao_bind_group: wgpu::BindGroup,
+    ssao_pipeline: wgpu::RenderPipeline,
+    exposure: AutoExposure,
+    bloom: Bloom,
+    taa: Option<Taa>,
+    composite_layout: wgpu::BindGroupLayout,
+    // One per exposure texture, matching AutoExposure::current.
+    composite_bind_groups: [wgpu::BindGroup; 2],
+    composite_pipeline: wgpu::RenderPipeline,
+    last_frame: Instant,
+    bytes: u64,
+}
+
+impl PostProcess {
+    pub fn new(
+        device: &wgpu::Device, surface_format: wgpu::TextureFormat, camera_layout: &wgpu::BindGroupLayout, depth: &wgpu::TextureView, width: u32, height: u32,
+        view_count: usize,
+    ) -> Self {
+        let targets = Targets::new(device, width, height);
+        let (min_exposure, max_exposure) = config::EXPOSURE_RANGE;
+        let graphics = &config_file::get().graphics;
+        let uniform = PostUniform {
+            ssao: [config::SSAO_RADIUS, if graphics.ssao { config::SSAO_STRENGTH } else { 0.0 }, config::SSAO_SAMPLES as f32, config::SSAO_MAX_DISTANCE],
+            exposure: [config::EXPOSURE_KEY, min_exposure, max_exposure, config::EXPOSURE_ADAPT_SPEED],
+            frame: [0.0, if config::AUTO_EXPOSURE { 0.0 } else { config::EXPOSURE }, config_file::get().graphics.tonemapper as u32 as f32, 1.0],
+            bloom: [config::BLOOM_THRESHOLD, config::BLOOM_KNEE, if graphics.bloom { config::BLOOM_INTENSITY } else { 0.0 }, 0.0],
+            dof: [0.0, 0.0, config::Z_NEAR, config_file::far_plane()],
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Uniform"), contents: bytemuck::cast_slice(&[uniform]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let unfilterable = wgpu::TextureSampleType::Float { filterable: false };
+        let ssao_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[texture_entry(0, wgpu::TextureSampleType::Depth, scene_samples() > 1), texture_entry(1, unfilterable, false), uniform_entry(2)],
+            label: Some("SSAO Layout"),
+        });
+        let composite_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                texture_entry(0, wgpu::TextureSampleType::Float { filterable: true }, false), texture_entry(1, unfilterable, false), texture_entry(2, unfilterable, false),
+                uniform_entry(3), texture_entry(4, unfilterable, false), texture_entry(5, wgpu::TextureSampleType::Float { filterable: true }, false),
+                sampler_entry(6), texture_entry(7, wgpu::TextureSampleType::Depth, scene_samples() > 1),
+            ],
+            label: Some("Composite Layout"),
+        });
+
+        let ssao_pipeline = fullscreen_pipeline(device, "SSAO", &scene_depth_shader(shader::SSAO_SHADER), "fs_main", &[camera_layout, &ssao_layout], AO_FORMAT, None);
+        let composite_pipeline = fullscreen_pipeline(device, "Composite", &scene_depth_shader(shader::COMPOSITE_SHADER), "fs_main", &[&composite_layout], surface_format, None);
+        let exposure = AutoExposure::new(device, &targets.hdr, &uniform_buffer);
+        let bloom = Bloom::new(device, width, height, &targets.hdr, &uniform_buffer, &exposure);
+        let taa = (config_file::get().graphics.anti_aliasing == AntiAliasing::Taa).then(|| Taa::new(device, &targets.hdr, depth, width, height, view_count));
+
+        let ssao_bind_group = Self::ssao_bind_group(device, &ssao_layout, &targets, depth, &uniform_buffer);
+        let composite_bind_groups = Self::composite_bind_groups(device, &composite_layout, &targets, depth, &uniform_buffer, &exposure, &bloom);
+        let bytes = Self::target_bytes(width, height, &bloom, taa.is_some());
+        Self {
+            targets, uniform, uniform_buffer, ssao_layout, ssao_bind_group, ssao_pipeline, exposure, bloom, taa, composite_layout, composite_bind_groups, composite_pipeline,
+            last_frame: Instant::now(), bytes,
+        }
+    }
+
+    fn ssao_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, targets: &Targets, depth: &wgpu::TextureView, uniform: &wgpu::Buffer) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[view_entry(0, depth), view_entry(1, &targets.normal), wgpu::BindGroupEntry { binding: 2, resource: uniform.as_entire_binding() }],
+            label: Some("SSAO Bind Group"),
+        })
+    }
+
+    fn composite_bind_groups(
+        device: &wgpu::Device, layout: &wgpu::BindGroupLayout, targets: &Targets, depth: &wgpu::TextureView, uniform: &wgpu::Buffer, exposure: &AutoExposure,
+        bloom: &Bloom,
+    ) -> [wgpu::BindGroup; 2] {
+        let composite = |i: usize| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                view_entry(0, &targets.hdr), view_entry(1, &targets.normal), view_entry(2, &targets.ao),
+                wgpu::BindGroupEntry { binding: 3, resource: uniform.as_entire_binding() }, view_entry(4, &exposure.exposure_views[i]),
+                view_entry(5, bloom.view()), wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::Sampler(&bloom.layouts.sampler) },
+                view_entry(7, depth),
+            ],
+            label: Some("Composite Bind Group"),
+        });
+        [composite(0), composite(1)]
+    }
+
+    // Recreates the targets at the new size; `depth` is the context's freshly created depth view.
+    pub fn resize(&mut self, device: &wgpu::Device, depth: &wgpu::TextureView, width: u32, height: u32) {
+        self.targets = Targets::new(device, width, height);
+        self.exposure.resize(device, &self.targets.hdr);
+        self.bloom.resize(device, width, height, &self.targets.hdr, &self.uniform_buffer, &self.exposure);
+        if let Some(taa) = &mut self.taa { taa.resize(device, &self.targets.hdr, depth, width, height); }
+        self.ssao_bind_group = Self::ssao_bind_group(device, &self.ssao_layout, &self.targets, depth, &self.uniform_buffer);
+        self.composite_bind_groups = Self::composite_bind_groups(device, &self.composite_layout, &self.targets, depth, &self.uniform_buffer, &self.exposure, &self.bloom);
+        self.bytes = Self::target_bytes(width, height, &self.bloom, self.taa.is_some());
+    }
+
+    fn target_bytes(width: u32, height: u32, bloom: &Bloom, taa: bool) -> u64 {
+        let taa = if taa { Taa::bytes(width, height) } else { 0 };
+        Targets::bytes(width, height) + AutoExposure::bytes() + bloom.targets.bytes + taa
+    }
+
+    pub fn gpu_bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    // Fraction of the surface size the scene targets cover; the composite upsamples by its inverse.
+    pub fn set_render_scale(&mut self, scale: f32) {
+        self.uniform.frame[3] = scale;
+    }
+
+    fn ssao_enabled(&self) -> bool {
+        self.uniform.ssao[1] > 0.0
+    }
+
+    // Turns ambient occlusion and bloom on or off, as a graphics preset change asks.
+    pub fn set_effects(&mut self, ssao: bool, bloom: bool) {
+        self.uniform.ssao[1] = if ssao { config::SSAO_STRENGTH } else { 0.0 };
+        self.uniform.bloom[2] = if bloom { config::BLOOM_INTENSITY } else { 0.0 };
+    }
+
+    // Color attachments of the scene pass, in the order the scene shader writes them.
+    pub fn scene_attachments(&self, clear: wgpu::Color) -> [Option<wgpu::RenderPassColorAttachment<'_>>; 2] {
+        [
+            Some(scene_attachment(self.targets.hdr_msaa.as_ref(), &self.targets.hdr, clear)),
+            Some(scene_attachment(self.targets.normal_msaa.as_ref(), &self.targets.normal, wgpu::Color::TRANSPARENT)),
+        ]
+    }
+
+    pub fn scene_copy(&self) -> &wgpu::TextureView {
+        &self.targets.scene_copy
+    }
+
+    // Copies the resolved scene for the water to reflect, then begins a pass drawing over
+    // the resolved color and normal targets.
+    pub fn water_pass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+        let size = self.targets.hdr_texture.size();
+        encoder.copy_texture_to_texture(self.targets.hdr_texture.as_image_copy(), self.targets.scene_copy_texture.as_image_copy(), size);
+        let load = wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store };
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Water Pass"),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment { view: &self.targets.hdr, resolve_target: None, ops: load }),
+                Some(wgpu::RenderPassColorAttachment { view: &self.targets.normal, resolve_target: None, ops: load }),
+            ],
+            depth_stencil_attachment: None, timestamp_writes: None, occlusion_query_set: None,
+        })
+    }
+
+    // Sub-pixel offset (in pixels) to shift this frame's projection by; zero without TAA.
+    pub fn jitter(&self) -> glam::Vec2 {
+        self.taa.as_ref().map_or(glam::Vec2::ZERO, Taa::jitter)
+    }
+
+    // Records a view's unjittered view-projection for reprojecting the next frame's history.
+    pub fn update_taa_view(&mut self, queue: &wgpu::Queue, view: usize, view_proj: glam::Mat4, viewport: [f32; 4]) {
+        if let Some(taa) = &mut self.taa { taa.update_view(queue, view, view_proj, viewport); }
+    }
+
+    // Accumulates the finished scene into the TAA history; call after the scene and water passes.
+    pub fn resolve_taa(&mut self, encoder: &mut wgpu::CommandEncoder, viewports: &[[f32; 4]]) {
+        if let Some(taa) = &mut self.taa { taa.resolve(encoder, &self.targets.hdr_texture, viewports); }
+    }
+
+    // Computes ambient occlusion per viewport; the camera bind groups supply each view's matrices.
+    pub fn ssao<'a>(&self, encoder: &mut wgpu::CommandEncoder, views: impl Iterator<Item = ([f32; 4], &'a wgpu::BindGroup)>) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("SSAO Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.targets.ao, resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::WHITE), store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: None, timestamp_writes: None, occlusion_query_set: None,
+        });
+        if !self.ssao_enabled() { return; }
+        pass.set_pipeline(&self.ssao_pipeline);
+        pass.set_bind_group(1, &self.ssao_bind_group, &[]);
+        for ([x, y, w, h], camera) in views {
+            pass.set_viewport(x, y, w, h, 0.0, 1.0);
+            pass.set_bind_group(0, camera, &[]);
+            pass.draw(0..3, 0..1);
+        }
+    }
+
+    // Blurs what's nearer or farther than `focus` meters, up to `blur` pixels; 0 turns it off.
+    pub fn set_depth_of_field(&mut self, focus: f32, blur: f32) {
+        self.uniform.dof[0] = focus;
+        self.uniform.dof[1] = blur;
+    }
+
+    // Brightens or darkens the image by `stops` on top of the configured exposure.
+    pub fn set_exposure_bias(&mut self, stops: f32) {
+        let scale = 2f32.powf(stops);
+        self.uniform.exposure[0] = config::EXPOSURE_KEY * scale;
+        if !config::AUTO_EXPOSURE { self.uniform.frame[1] = config::EXPOSURE * scale; }
+    }
+
+    // Measures the frame's luminance and adapts the exposure the composite pass applies,
+    // then extracts the bloom from the exposed image.
+    pub fn update_exposure(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {
+        let now = Instant::now();
+        self.uniform.frame[0] = now.duration_since(self.last_frame).as_secs_f32().min(0.25);
+        self.last_frame = now;
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+        self.exposure.render(encoder);
+        if self.uniform.bloom[2] > 0.0 {
+            self.bloom.render(encoder, self.exposure.current);
+        }
+    }
+
+    // Begins the pass writing the final image to `surface`; the caller draws overlays into it.
+    pub fn composite<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder, surface: &'a wgpu::TextureView) -> wgpu::RenderPass<'a> {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface, resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: None, timestamp_writes: None, occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.composite_pipeline);
+        pass.set_bind_group(0, &self.composite_bind_groups[self.exposure.current], &[]);
+        pass.draw(0..3, 0..1);
+        pass
+    }
+}
@@ -0,0 +1,71 @@
+// preset.rs
+use serde::{Deserialize, Serialize};
+use crate::{config, post::AntiAliasing};
+
+// Coordinated graphics settings, picked with graphics.preset, --preset or the settings menu.
+// Each fills in the graphics values the config file and command line leave unset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphicsPreset {
+    Low,
+    Medium,
+    High,
+    Ultra,
+}
+
+// The values a preset sets, named as in the [graphics] section.
+#[derive(Debug, Clone, Serialize)]
+pub struct PresetValues {
+    pub anti_aliasing: AntiAliasing,
+    pub draw_distance: f32,
+    pub fog_start: f32,
+    pub fog_end: f32,
+    pub shadows: bool,
+    pub shadow_map_size: u32,
+    pub lod_distance: f32,
+    pub ssao: bool,
+    pub bloom: bool,
+    pub reflections: bool,
+}
+
+impl GraphicsPreset {
+    const ALL: [Self; 4] = [Self::Low, Self::Medium, Self::High, Self::Ultra];
+
+    // Next or previous preset, for the settings menu.
+    pub fn step(self, steps: i32) -> Self {
+        let i = Self::ALL.iter().position(|&p| p == self).unwrap_or(0) as i32;
+        Self::ALL[(i + steps).clamp(0, Self::ALL.len() as i32 - 1) as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Low => "Low",
+            Self::Medium => "Medium",
+            Self::High => "High",
+            Self::Ultra => "Ultra",
+        }
+    }
+
+    // High is the config.rs defaults.
+    pub fn values(self) -> PresetValues {
+        match self {
+            Self::Low => PresetValues {
+                anti_aliasing: AntiAliasing::Off, draw_distance: 5000.0, fog_start: 3000.0, fog_end: 4800.0,
+                shadows: false, shadow_map_size: 1024, lod_distance: 600.0, ssao: false, bloom: false, reflections: false,
+            },
+            Self::Medium => PresetValues {
+                anti_aliasing: AntiAliasing::Taa, draw_distance: 9000.0, fog_start: 6000.0, fog_end: 8600.0,
+                shadows: true, shadow_map_size: 1024, lod_distance: 1000.0, ssao: false, bloom: true, reflections: false,
+            },
+            Self::High => PresetValues {
+                anti_aliasing: config::ANTI_ALIASING, draw_distance: config::DRAW_DISTANCE, fog_start: config::FOG_START, fog_end: config::FOG_END,
+                shadows: config::SHADOWS_ENABLED, shadow_map_size: config::SHADOW_MAP_SIZE, lod_distance: config::LOD_DISTANCE,
+                ssao: config::SSAO_ENABLED, bloom: config::BLOOM_ENABLED, reflections: config::SSR_ENABLED,
+            },
+            Self::Ultra => PresetValues {
+                anti_aliasing: AntiAliasing::Msaa, draw_distance: 20000.0, fog_start: 14000.0, fog_end: 19000.0,
+                shadows: true, shadow_map_size: 4096, lod_distance: 3000.0, ssao: true, bloom: true, reflections: true,
+            },
+        }
+    }
+}
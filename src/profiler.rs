@@ -0,0 +1,241 @@
+// profiler.rs
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex, atomic::{AtomicU8, Ordering}};
+use std::time::{Duration, Instant};
+use crate::{config, config_file};
+
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+// Stretches of a frame's GPU work, in the order they're recorded. Each ends at the
+// timestamp its mark writes and starts at the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuSpan {
+    Shadows,
+    Scene,
+    Water,
+    Post,
+    Ui,
+}
+
+impl GpuSpan {
+    const ALL: [GpuSpan; 5] = [GpuSpan::Shadows, GpuSpan::Scene, GpuSpan::Water, GpuSpan::Post, GpuSpan::Ui];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GpuSpan::Shadows => "shadows",
+            GpuSpan::Scene => "scene",
+            GpuSpan::Water => "water",
+            GpuSpan::Post => "post",
+            GpuSpan::Ui => "UI",
+        }
+    }
+}
+
+// Timestamps at the frame start and the end of each span.
+const TIMESTAMPS: u32 = GpuSpan::ALL.len() as u32 + 1;
+
+// CPU scopes that ended since the profiler last collected them: name, start and length.
+static SCOPES: Mutex<Vec<(&'static str, Instant, Duration)>> = Mutex::new(Vec::new());
+
+// Times the code up to the end of the enclosing block: `let _scope = profiler::scope("update");`
+pub fn scope(name: &'static str) -> Scope {
+    Scope { name, start: Instant::now() }
+}
+
+pub struct Scope {
+    name: &'static str,
+    start: Instant,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if let Ok(mut scopes) = SCOPES.lock() { scopes.push((self.name, self.start, self.start.elapsed())); }
+    }
+}
+
+// One frame's timestamps on their way back from the GPU.
+struct Readback {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    // Set by the map callback: MAP_DONE or MAP_FAILED.
+    map_state: Arc<AtomicU8>,
+    mapping: bool,
+    submitted: Instant,
+}
+
+// Frame timings for the stats overlay: GPU spans from timestamp queries (where the adapter
+// has them) and the CPU scopes. Results are averaged over recent frames, and with paths.trace
+// set every span is also written out as Chrome tracing JSON (chrome://tracing, Perfetto).
+// GPU results arrive a few frames late; a frame is skipped when every readback is busy.
+pub struct Profiler {
+    readbacks: Vec<Readback>,
+    // Readback recording this frame's timestamps.
+    current: Option<usize>,
+    // Nanoseconds per timestamp tick.
+    period: f32,
+    gpu_ms: [f32; GpuSpan::ALL.len()],
+    // Milliseconds per frame by scope name, in the order first seen.
+    cpu_ms: Vec<(&'static str, f32)>,
+    start: Instant,
+    trace: Option<BufWriter<File>>,
+}
+
+impl Profiler {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let readbacks = if device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            (0..config::PROFILER_READBACKS).map(|_| Readback::new(device)).collect()
+        } else {
+            log::info!("GPU timestamps aren't supported; the profiler only times the CPU");
+            Vec::new()
+        };
+        Self {
+            readbacks, current: None, period: queue.get_timestamp_period(), gpu_ms: [0.0; GpuSpan::ALL.len()], cpu_ms: Vec::new(),
+            start: Instant::now(), trace: config_file::get().paths.trace.as_deref().and_then(open_trace),
+        }
+    }
+
+    pub fn has_gpu_timing(&self) -> bool {
+        !self.readbacks.is_empty()
+    }
+
+    pub fn gpu_ms(&self) -> impl Iterator<Item = (&'static str, f32)> + '_ {
+        GpuSpan::ALL.iter().zip(self.gpu_ms).map(|(span, ms)| (span.name(), ms))
+    }
+
+    pub fn cpu_ms(&self) -> &[(&'static str, f32)] {
+        &self.cpu_ms
+    }
+
+    // Folds in finished GPU results and writes the frame's first timestamp; call before
+    // recording any GPU work.
+    pub fn begin_frame(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        if self.readbacks.is_empty() { return; }
+        device.poll(wgpu::Maintain::Poll);
+        for i in 0..self.readbacks.len() { self.collect(i); }
+        self.current = self.readbacks.iter().position(|r| !r.mapping);
+        if let Some(i) = self.current { encoder.write_timestamp(&self.readbacks[i].query_set, 0); }
+    }
+
+    // Ends `span` at this point in the encoder.
+    pub fn mark(&self, encoder: &mut wgpu::CommandEncoder, span: GpuSpan) {
+        let Some(i) = self.current else { return };
+        encoder.write_timestamp(&self.readbacks[i].query_set, span as u32 + 1);
+    }
+
+    // Copies the frame's timestamps to its readback buffer; call after the last mark.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(i) = self.current else { return };
+        let readback = &self.readbacks[i];
+        encoder.resolve_query_set(&readback.query_set, 0..TIMESTAMPS, &readback.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&readback.resolve_buffer, 0, &readback.readback_buffer, 0, TIMESTAMPS as u64 * 8);
+    }
+
+    // Starts reading the frame's timestamps back and takes in the CPU scopes that ended
+    // during it; call once the frame has been submitted.
+    pub fn end_frame(&mut self) {
+        if let Some(readback) = self.current.take().map(|i| &mut self.readbacks[i]) {
+            readback.mapping = true;
+            readback.submitted = Instant::now();
+            let state = readback.map_state.clone();
+            readback.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                state.store(if result.is_ok() { MAP_DONE } else { MAP_FAILED }, Ordering::Release);
+            });
+        }
+
+        let scopes = SCOPES.lock().map(|mut scopes| std::mem::take(&mut *scopes)).unwrap_or_default();
+        let mut totals: Vec<(&'static str, f32)> = self.cpu_ms.iter().map(|&(name, _)| (name, 0.0)).collect();
+        for &(name, start, length) in &scopes {
+            let ms = length.as_secs_f32() * 1000.0;
+            match totals.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, total)) => *total += ms,
+                None => totals.push((name, ms)),
+            }
+            self.trace_event(name, "cpu", 1, start.duration_since(self.start).as_secs_f64() * 1e6, length.as_secs_f64() * 1e6);
+        }
+        for (i, (name, total)) in totals.into_iter().enumerate() {
+            match self.cpu_ms.get_mut(i) {
+                Some((_, ms)) => *ms += (total - *ms) * config::PROFILER_SMOOTHING,
+                None => self.cpu_ms.push((name, total)),
+            }
+        }
+    }
+
+    fn collect(&mut self, i: usize) {
+        let readback = &mut self.readbacks[i];
+        if !readback.mapping { return; }
+        match readback.map_state.swap(MAP_PENDING, Ordering::Acquire) {
+            MAP_PENDING => return,
+            MAP_FAILED => {
+                readback.mapping = false;
+                return;
+            }
+            _ => {}
+        }
+        let ticks: Vec<u64> = bytemuck::cast_slice(&readback.readback_buffer.slice(..).get_mapped_range()).to_vec();
+        readback.readback_buffer.unmap();
+        readback.mapping = false;
+
+        // GPU and CPU clocks aren't related, so the trace lines the frame's GPU work up with its submission.
+        let mut at = readback.submitted.duration_since(self.start).as_secs_f64() * 1e6;
+        for (span, pair) in GpuSpan::ALL.into_iter().zip(ticks.windows(2)) {
+            let us = pair[1].saturating_sub(pair[0]) as f64 * self.period as f64 / 1000.0;
+            let ms = &mut self.gpu_ms[span as usize];
+            *ms += (us as f32 / 1000.0 - *ms) * config::PROFILER_SMOOTHING;
+            self.trace_event(span.name(), "gpu", 2, at, us);
+            at += us;
+        }
+    }
+
+    fn trace_event(&mut self, name: &str, category: &str, thread: u32, start_us: f64, length_us: f64) {
+        let Some(trace) = &mut self.trace else { return };
+        let event = format!(",\n{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{:.1},\"dur\":{:.1}}}", name, category, thread, start_us, length_us);
+        if let Err(e) = trace.write_all(event.as_bytes()) {
+            log::warn!("Stopped writing the profiler trace: {}", e);
+            self.trace = None;
+        }
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        if let Some(trace) = &mut self.trace && let Err(e) = trace.write_all(b"\n]\n").and_then(|_| trace.flush()) {
+            log::warn!("Couldn't finish the profiler trace: {}", e);
+        }
+    }
+}
+
+impl Readback {
+    fn new(device: &wgpu::Device) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor { label: Some("Profiler Timestamps"), ty: wgpu::QueryType::Timestamp, count: TIMESTAMPS });
+        let buffer = |label, usage| device.create_buffer(&wgpu::BufferDescriptor { label: Some(label), size: TIMESTAMPS as u64 * 8, usage, mapped_at_creation: false });
+        Self {
+            query_set,
+            resolve_buffer: buffer("Profiler Resolve", wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC),
+            readback_buffer: buffer("Profiler Readback", wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST),
+            map_state: Arc::new(AtomicU8::new(MAP_PENDING)), mapping: false, submitted: Instant::now(),
+        }
+    }
+}
+
+// Starts a trace file with the names of its two rows; events follow, each led by a comma.
+fn open_trace(path: &str) -> Option<BufWriter<File>> {
+    let header = "[\n{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":1,\"args\":{\"name\":\"CPU\"}},\n{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":2,\"args\":{\"name\":\"GPU\"}}";
+    match File::create(path).and_then(|file| {
+        let mut trace = BufWriter::new(file);
+        trace.write_all(header.as_bytes())?;
+        Ok(trace)
+    }) {
+        Ok(trace) => {
+            log::info!("Writing profiler trace to {}", path);
+            Some(trace)
+        }
+        Err(e) => {
+            log::warn!("Couldn't create profiler trace {}: {}", path, e);
+            None
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// render_scale.rs
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::config;
+
+// GPU frame times averaged before deciding on a new scale.
+const FRAME_WINDOW: usize = 30;
+
+// Dynamic resolution: the scene renders at `scale` times the window size and the composite
+// pass upsamples it. Frame times are taken from acquiring the surface to the GPU finishing
+// the frame, so vsync and the frame limiter don't count. The scale steps down while frames
+// miss the target and back up once there's clear headroom, settling between steps.
+pub struct RenderScale {
+    pub scale: f32,
+    // Highest scale allowed; lowered when GPU memory runs short.
+    pub max: f32,
+    frame_times: VecDeque<f64>,
+    // Written by the queue's work-done callback, read on the next update.
+    finished: Arc<Mutex<Option<Duration>>>,
+    cooldown: f64,
+}
+
+impl RenderScale {
+    pub fn new() -> Self {
+        Self {
+            scale: config::RENDER_SCALE_RANGE.1, max: config::RENDER_SCALE_RANGE.1, frame_times: VecDeque::with_capacity(FRAME_WINDOW),
+            finished: Arc::new(Mutex::new(None)), cooldown: 0.0,
+        }
+    }
+
+    // Scene target size for a window of the given size.
+    pub fn apply(&self, width: u32, height: u32) -> (u32, u32) {
+        ((width as f32 * self.scale).round().max(1.0) as u32, (height as f32 * self.scale).round().max(1.0) as u32)
+    }
+
+    // Times the work submitted so far, counted from `start`; call right after submitting a frame.
+    pub fn track(&self, queue: &wgpu::Queue, start: Instant) {
+        if !config::DYNAMIC_RESOLUTION { return; }
+        let finished = self.finished.clone();
+        queue.on_submitted_work_done(move || {
+            if let Ok(mut slot) = finished.lock() { *slot = Some(start.elapsed()); }
+        });
+    }
+
+    // Folds in the last finished frame; returns true when the scale changed and the scene
+    // targets need to be recreated.
+    pub fn update(&mut self, dt: f64) -> bool {
+        if !config::DYNAMIC_RESOLUTION { return false; }
+        self.cooldown -= dt;
+        let Some(frame_time) = self.finished.lock().ok().and_then(|mut slot| slot.take()) else { return false };
+        if self.frame_times.len() == FRAME_WINDOW { self.frame_times.pop_front(); }
+        self.frame_times.push_back(frame_time.as_secs_f64());
+        if self.cooldown > 0.0 || self.frame_times.len() < FRAME_WINDOW { return false; }
+
+        let average = self.frame_times.iter().sum::<f64>() / FRAME_WINDOW as f64;
+        let target = 1.0 / config::TARGET_FPS as f64;
+        let min = config::RENDER_SCALE_RANGE.0;
+        let scale = if average > target {
+            (self.scale - config::RENDER_SCALE_STEP).max(min)
+        } else if average < target * config::RENDER_SCALE_HEADROOM as f64 {
+            (self.scale + config::RENDER_SCALE_STEP).min(self.max)
+        } else {
+            self.scale
+        };
+        if scale == self.scale { return false; }
+
+        log::info!("Render scale {:.0}% -> {:.0}% (GPU frame {:.1} ms)", self.scale * 100.0, scale * 100.0, average * 1000.0);
+        self.scale = scale;
+        self.frame_times.clear();
+        self.cooldown = config::RENDER_SCALE_COOLDOWN;
+        true
+    }
+
+    // Lowers the highest scale to `max`; returns false when it was already there.
+    pub fn cap(&mut self, max: f32) -> bool {
+        let max = max.max(config::RENDER_SCALE_RANGE.0);
+        if max >= self.max { return false; }
+        self.max = max;
+        self.scale = self.scale.min(max);
+        true
+    }
+}
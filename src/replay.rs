@@ -0,0 +1,153 @@
+// replay.rs
+use glam::DVec3;
+use crate::{camera::{Camera, CameraController}, config, config_file};
+
+// Replay files start with this, followed by the frames as raw little-endian structs.
+const MAGIC: &[u8; 8] = b"SKYRPL01";
+
+// Held inputs, one bit each in ReplayFrame::buttons, with the labels shown during playback.
+const BUTTONS: [&str; 10] = ["Fwd", "Back", "Left", "Right", "Jump", "Sprint", "Crouch", "Grapple", "Interact", "Zoom"];
+
+// Player one's camera and held inputs at one moment of a recording.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ReplayFrame {
+    eye: [f64; 3],
+    // Seconds since the recording started.
+    time: f32,
+    yaw: f32,
+    pitch: f32,
+    buttons: u32,
+}
+
+impl ReplayFrame {
+    fn new(time: f64, camera: &Camera, controller: &CameraController) -> Self {
+        let held = [
+            controller.move_fwd, controller.move_back, controller.move_left, controller.move_right, controller.jump,
+            controller.sprint, controller.crouch, controller.grapple, controller.interact, controller.zoom,
+        ];
+        let buttons = held.iter().enumerate().fold(0, |bits, (i, &on)| bits | (on as u32) << i);
+        Self { eye: camera.eye.to_array(), time: time as f32, yaw: camera.yaw, pitch: camera.pitch, buttons }
+    }
+}
+
+enum ReplayState {
+    Idle,
+    Recording,
+    Playing,
+}
+
+// Records player one's movement at REPLAY_SAMPLE_RATE into REPLAY_FILE, and plays a replay
+// file back in place of player one's view with the recorded inputs on screen, for sharing
+// runs and chasing down physics bugs.
+pub struct Replay {
+    frames: Vec<ReplayFrame>,
+    // Seconds into the recording or playback.
+    time: f64,
+    state: ReplayState,
+}
+
+impl Replay {
+    pub fn new() -> Self {
+        Self { frames: Vec::new(), time: 0.0, state: ReplayState::Idle }
+    }
+
+    pub fn toggle_recording(&mut self) {
+        if let ReplayState::Recording = self.state {
+            self.state = ReplayState::Idle;
+            self.save();
+        } else {
+            self.frames.clear();
+            self.time = 0.0;
+            self.state = ReplayState::Recording;
+            log::info!("Recording a replay (F5 stops)");
+        }
+    }
+
+    // Plays REPLAY_FILE, or stops playback.
+    pub fn toggle_playback(&mut self) {
+        if let ReplayState::Playing = self.state {
+            self.state = ReplayState::Idle;
+            return;
+        }
+        match Self::load() {
+            Ok(frames) if frames.len() >= 2 => {
+                log::info!("Playing {} ({:.1} s)", config_file::get().paths.replay, frames[frames.len() - 1].time);
+                self.frames = frames;
+                self.time = 0.0;
+                self.state = ReplayState::Playing;
+            }
+            Ok(_) => log::info!("{} is too short to play", config_file::get().paths.replay),
+            Err(e) => log::warn!("Couldn't load {}: {}", config_file::get().paths.replay, e),
+        }
+    }
+
+    fn save(&self) {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.frames));
+        match std::fs::write(&config_file::get().paths.replay, bytes) {
+            Ok(()) => log::info!("Saved a {:.1} s replay to {}", self.time, config_file::get().paths.replay),
+            Err(e) => log::warn!("Couldn't save {}: {}", config_file::get().paths.replay, e),
+        }
+    }
+
+    fn load() -> Result<Vec<ReplayFrame>, String> {
+        let bytes = std::fs::read(&config_file::get().paths.replay).map_err(|e| e.to_string())?;
+        let body = bytes.strip_prefix(MAGIC).ok_or("not a replay file")?;
+        let size = std::mem::size_of::<ReplayFrame>();
+        if body.len() % size != 0 { return Err("truncated replay".to_string()); }
+        Ok(body.chunks_exact(size).map(bytemuck::pod_read_unaligned).collect())
+    }
+
+    // Advances playback, or samples player one's camera and inputs while recording.
+    pub fn update(&mut self, dt: f64, camera: &Camera, controller: &CameraController) {
+        match self.state {
+            ReplayState::Idle => {}
+            ReplayState::Recording => {
+                self.time += dt;
+                let due = self.frames.last().is_none_or(|last| self.time - last.time as f64 >= 1.0 / config::REPLAY_SAMPLE_RATE);
+                if due { self.frames.push(ReplayFrame::new(self.time, camera, controller)); }
+            }
+            ReplayState::Playing => {
+                self.time += dt;
+                if self.time >= self.frames[self.frames.len() - 1].time as f64 { self.state = ReplayState::Idle; }
+            }
+        }
+    }
+
+    // The two frames around the playback time and how far between them it is.
+    fn current(&self) -> Option<(&ReplayFrame, &ReplayFrame, f64)> {
+        let ReplayState::Playing = self.state else { return None };
+        let next = self.frames.partition_point(|f| (f.time as f64) <= self.time).clamp(1, self.frames.len() - 1);
+        let (a, b) = (&self.frames[next - 1], &self.frames[next]);
+        let span = (b.time - a.time) as f64;
+        Some((a, b, if span > 0.0 { ((self.time - a.time as f64) / span).clamp(0.0, 1.0) } else { 1.0 }))
+    }
+
+    // `base` moved to the recorded camera while playing.
+    pub fn camera(&self, base: &Camera) -> Option<Camera> {
+        let (a, b, t) = self.current()?;
+        let turn = (b.yaw - a.yaw + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+        Some(Camera {
+            eye: DVec3::from(a.eye).lerp(DVec3::from(b.eye), t),
+            yaw: a.yaw + turn * t as f32,
+            pitch: a.pitch + (b.pitch - a.pitch) * t as f32,
+            roll: 0.0,
+            ..base.clone()
+        })
+    }
+
+    // Playback progress and the inputs held at that point, for the overlay.
+    pub fn status(&self) -> Option<String> {
+        match self.state {
+            ReplayState::Idle => None,
+            ReplayState::Recording => Some(format!("Recording {:.1} s (F5 stops)", self.time)),
+            ReplayState::Playing => {
+                let (a, _, _) = self.current()?;
+                let held: Vec<&str> = BUTTONS.iter().enumerate().filter(|(i, _)| a.buttons & (1 << i) != 0).map(|(_, name)| *name).collect();
+                let length = self.frames[self.frames.len() - 1].time;
+                Some(format!("Replay {:.1} / {:.1} s  {}", self.time, length, held.join(" ")))
+            }
+        }
+    }
+}
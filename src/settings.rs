@@ -0,0 +1,193 @@
+// settings.rs
+use glam::DVec2;
+use serde::{Deserialize, Serialize};
+use winit::keyboard::KeyCode;
+use crate::{config, config_file, crosshair::CrosshairStyle, display::PresentPreference, hud::HudRenderer, preset::GraphicsPreset, text::TextRenderer};
+
+// How mouse movement turns player one's view. Missing fields fall back to the config defaults.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseSettings {
+    // Radians per pixel.
+    pub sensitivity: f64,
+    pub invert_y: bool,
+    // Seconds the view takes to catch up with the mouse, 0 for raw input.
+    pub smoothing: f64,
+    // Extra gain per 1000 pixels/second of mouse speed, 0 for a linear response.
+    pub acceleration: f64,
+}
+
+impl Default for MouseSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: config_file::get().controls.mouse_sensitivity, invert_y: config_file::get().controls.invert_y,
+            smoothing: config::MOUSE_SMOOTHING, acceleration: config::MOUSE_ACCELERATION,
+        }
+    }
+}
+
+impl MouseSettings {
+    // Yaw and pitch for a frame's mouse movement in pixels. `velocity` (pixels per second)
+    // carries the smoothed mouse speed from frame to frame.
+    pub fn look(&self, delta: DVec2, velocity: &mut DVec2, dt: f64) -> DVec2 {
+        let raw = delta / dt;
+        *velocity = if self.smoothing > 0.0 { velocity.lerp(raw, 1.0 - (-dt / self.smoothing).exp()) } else { raw };
+        let gain = (1.0 + self.acceleration * velocity.length() / 1000.0).min(config::MOUSE_ACCELERATION_MAX_GAIN);
+        let turn = *velocity * dt * self.sensitivity * gain;
+        DVec2::new(turn.x, if self.invert_y { turn.y } else { -turn.y })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub mouse: MouseSettings,
+    pub crosshair: CrosshairStyle,
+    // Hard-landing feedback, for players who find it uncomfortable.
+    pub screen_shake: bool,
+    pub damage_vignette: bool,
+    pub present_mode: PresentPreference,
+    // Frames per second; None renders as fast as the present mode allows.
+    pub frame_limit: Option<u32>,
+    // None until one is picked here; graphics.preset applies until then.
+    pub graphics_preset: Option<GraphicsPreset>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            mouse: MouseSettings::default(), crosshair: CrosshairStyle::default(),
+            screen_shake: config::SCREEN_SHAKE, damage_vignette: config::DAMAGE_VIGNETTE,
+            present_mode: config_file::get().graphics.present_mode, frame_limit: config_file::get().graphics.frame_limit, graphics_preset: None,
+        }
+    }
+}
+
+impl Settings {
+    // What the menu saved last time, or the defaults.
+    fn saved() -> Self {
+        std::fs::read_to_string(&config_file::get().paths.settings).ok()
+            .and_then(|text| serde_json::from_str(&text).map_err(|e| log::warn!("Ignoring {}: {}", config_file::get().paths.settings, e)).ok())
+            .unwrap_or_default()
+    }
+}
+
+// Applies the graphics preset the menu saved last time; call before anything sizes GPU
+// resources by it. A preset given on the command line wins.
+pub fn restore_graphics_preset() {
+    if config_file::from_command_line("graphics.preset") { return; }
+    if let Some(preset) = Settings::saved().graphics_preset { config_file::restore_preset(preset); }
+}
+
+const ROWS: [&str; 10] = [
+    "Mouse sensitivity", "Invert Y", "Smoothing", "Acceleration", "Crosshair", "Screen shake", "Damage vignette", "Graphics preset", "Present mode", "Frame limit",
+];
+
+// In-game settings (F10). Up/Down pick a row, Left/Right change it; closing the menu saves to
+// the settings file (paths.settings), which is read back on the next start.
+pub struct SettingsMenu {
+    pub settings: Settings,
+    pub open: bool,
+    selected: usize,
+}
+
+impl SettingsMenu {
+    pub fn load() -> Self {
+        let mut settings = Settings::saved();
+        // What was asked for on the command line wins over what the menu saved last time.
+        let graphics = &config_file::get().graphics;
+        if config_file::from_command_line("graphics.present_mode") { settings.present_mode = graphics.present_mode; }
+        if config_file::from_command_line("graphics.frame_limit") { settings.frame_limit = graphics.frame_limit; }
+        if config_file::from_command_line("graphics.preset") { settings.graphics_preset = Some(graphics.preset); }
+        Self { settings, open: false, selected: 0 }
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(&self.settings).map_err(|e| e.to_string())
+            .and_then(|text| std::fs::write(&config_file::get().paths.settings, text).map_err(|e| e.to_string()));
+        if let Err(e) = result { log::warn!("Couldn't save {}: {}", config_file::get().paths.settings, e); }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        if !self.open { self.save(); }
+    }
+
+    pub fn key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::ArrowUp | KeyCode::KeyW => self.selected = (self.selected + ROWS.len() - 1) % ROWS.len(),
+            KeyCode::ArrowDown | KeyCode::KeyS => self.selected = (self.selected + 1) % ROWS.len(),
+            KeyCode::ArrowLeft | KeyCode::KeyA => self.adjust(-1.0),
+            KeyCode::ArrowRight | KeyCode::KeyD | KeyCode::Enter | KeyCode::Space => self.adjust(1.0),
+            _ => {}
+        }
+    }
+
+    fn adjust(&mut self, steps: f64) {
+        let mouse = &mut self.settings.mouse;
+        match self.selected {
+            0 => {
+                let (min, max) = config::MOUSE_SENSITIVITY_RANGE;
+                mouse.sensitivity = (mouse.sensitivity * config::MOUSE_SENSITIVITY_STEP.powf(steps)).clamp(min, max);
+            }
+            1 => mouse.invert_y = !mouse.invert_y,
+            2 => mouse.smoothing = (mouse.smoothing + steps * config::MOUSE_SMOOTHING_STEP).clamp(0.0, config::MOUSE_SMOOTHING_MAX),
+            3 => mouse.acceleration = (mouse.acceleration + steps * config::MOUSE_ACCELERATION_STEP).clamp(0.0, config::MOUSE_ACCELERATION_MAX),
+            4 => self.settings.crosshair = self.settings.crosshair.step(steps as i32),
+            5 => self.settings.screen_shake = !self.settings.screen_shake,
+            6 => self.settings.damage_vignette = !self.settings.damage_vignette,
+            7 => self.settings.graphics_preset = Some(config_file::get().graphics.preset.step(steps as i32)),
+            8 => self.settings.present_mode = self.settings.present_mode.step(steps as i32),
+            _ => {
+                // Off, then each of FRAME_LIMIT_STEPS; a limit from the config file that isn't
+                // one of them moves to its nearest neighbour.
+                let limits = config::FRAME_LIMIT_STEPS;
+                let index = match self.settings.frame_limit {
+                    None => 0,
+                    Some(fps) if steps > 0.0 => limits.iter().position(|&l| l > fps).unwrap_or(limits.len() - 1) as i32,
+                    Some(fps) => limits.iter().rposition(|&l| l < fps).map_or(-1, |i| i as i32) + 2,
+                };
+                let index = (index + steps as i32).clamp(0, limits.len() as i32);
+                self.settings.frame_limit = (index > 0).then(|| limits[index as usize - 1]);
+            }
+        }
+    }
+
+    fn value(&self, row: usize) -> String {
+        let mouse = &self.settings.mouse;
+        match row {
+            0 => format!("{:.2}x", mouse.sensitivity / config_file::get().controls.mouse_sensitivity),
+            1 => on_off(mouse.invert_y),
+            2 => if mouse.smoothing > 0.0 { format!("{:.0} ms", mouse.smoothing * 1000.0) } else { "Off".to_string() },
+            3 => if mouse.acceleration > 0.0 { format!("{:.1}", mouse.acceleration) } else { "Off".to_string() },
+            4 => self.settings.crosshair.name().to_string(),
+            5 => on_off(self.settings.screen_shake),
+            6 => on_off(self.settings.damage_vignette),
+            7 => self.settings.graphics_preset.unwrap_or(config_file::get().graphics.preset).name().to_string(),
+            8 => self.settings.present_mode.name().to_string(),
+            _ => self.settings.frame_limit.map_or("Off".to_string(), |fps| format!("{} FPS", fps)),
+        }
+    }
+
+    pub fn draw(&self, hud: &mut HudRenderer, text: &mut TextRenderer, screen: [f32; 2]) {
+        let (row_w, row_h, gap) = (420.0, 36.0, 8.0);
+        let x0 = (screen[0] - row_w) * 0.5;
+        let y0 = (screen[1] - ROWS.len() as f32 * (row_h + gap)) * 0.5;
+        text.text_centered("Settings", screen[0] * 0.5, y0 - 44.0, 22.0, [1.0, 1.0, 1.0, 1.0]);
+        for (i, label) in ROWS.iter().enumerate() {
+            let y = y0 + i as f32 * (row_h + gap);
+            let selected = i == self.selected;
+            hud.rect([x0, y], [x0 + row_w, y + row_h], if selected { [0.85, 0.85, 0.85, 0.95] } else { [0.15, 0.15, 0.15, 0.85] });
+            let ink = if selected { [0.05, 0.05, 0.05, 1.0] } else { [0.95, 0.95, 0.95, 1.0] };
+            let value = self.value(i);
+            text.text(label, [x0 + 12.0, y + 9.0], 18.0, ink);
+            text.text(&value, [x0 + row_w - 12.0 - text.measure(&value, 18.0), y + 9.0], 18.0, ink);
+        }
+        let hint_y = y0 + ROWS.len() as f32 * (row_h + gap) + 8.0;
+        text.text_centered("Up/Down select, Left/Right change, F10 closes", screen[0] * 0.5, hint_y, 14.0, [0.8, 0.8, 0.8, 1.0]);
+    }
+}
+
+fn on_off(value: bool) -> String {
+    if value { "On" } else { "Off" }.to_string()
+}
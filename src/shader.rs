@@ -1,20 +1,99 @@
 // shader.rs
 
-// Double-sided lighting is achieved by abs(dot(normal, light_dir))
-// Fog is calculated based on distance from camera position.
-pub const SCENE_SHADER: &str = r#"
+// camera::CameraUniform and its binding, put in front of every shader that reads the camera.
+macro_rules! camera_uniform {
+    () => { r#"
 struct CameraUniform {
     view_proj: mat4x4<f32>,
     screen_size: vec2<f32>,
     fog_dist: vec2<f32>,
     camera_pos: vec4<f32>,
+    sun_dir: vec4<f32>,
+    sky_color: vec4<f32>,
+    zenith_color: vec4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    viewport: vec4<f32>,
+    fog: vec4<f32>,
+    relative_view_proj: mat4x4<f32>,
 };
 @group(0) @binding(0) var<uniform> camera: CameraUniform;
+"# };
+}
+
+// Height fog and aerial haze shared by the scene, the sky and the water; needs camera_uniform!().
+macro_rules! fog {
+    () => { r#"
+// Optical depth of a medium thinning exponentially with height, along `dist` meters of `dir`.
+fn optical_depth(density: f32, falloff: f32, dir: vec3<f32>, dist: f32) -> f32 {
+    let climb = falloff * dir.y * dist;
+    var depth = density * dist * exp(-falloff * (camera.camera_pos.y - camera.fog.z));
+    if (abs(climb) > 0.0001) { depth *= (1.0 - exp(-climb)) / climb; }
+    return depth;
+}
+
+// Opacity of the ground fog plus the aerial haze (a thinner layer kilometers deep) in
+// front of a point `dist` away along `dir`.
+fn fog_opacity(dir: vec3<f32>, dist: f32) -> f32 {
+    return 1.0 - exp(-optical_depth(camera.fog.x, camera.fog.y, dir, dist) - optical_depth(camera.fog.w, 0.0005, dir, dist));
+}
+
+// Horizon tint brightened toward the sun, where the haze scatters its light.
+fn fog_color(dir: vec3<f32>) -> vec3<f32> {
+    let sun_dir = camera.sun_dir.xyz;
+    let scatter = pow(max(dot(dir, sun_dir), 0.0), 8.0) * smoothstep(-0.1, 0.05, sun_dir.y);
+    return camera.sky_color.rgb + vec3<f32>(1.0, 0.8, 0.6) * scatter * 0.4;
+}
+
+// With the camera under water, camera_pos.w is the murk density everything fades into.
+fn underwater(color: vec3<f32>, dist: f32) -> vec3<f32> {
+    let murk = vec3<f32>(0.03, 0.1, 0.11) * mix(0.15, 1.0, camera.sun_dir.w);
+    return mix(color, murk, 1.0 - exp(-camera.camera_pos.w * dist));
+}
+"# };
+}
+
+// Double-sided lighting is achieved by abs(dot(normal, light_dir)), for the sun and for the
+// street lights of the fragment's light cluster
+// Fog is exponential height fog plus aerial haze, faded fully to the sky near the draw distance.
+pub const SCENE_SHADER: &str = concat!(camera_uniform!(), fog!(), r#"
+@group(1) @binding(0) var facade_tex: texture_2d_array<f32>;
+@group(1) @binding(1) var facade_sampler: sampler;
+
+struct ShadowUniform {
+    light_view_proj: array<mat4x4<f32>, 3>,
+    splits: vec4<f32>,
+    params: vec4<f32>,
+};
+@group(2) @binding(0) var<uniform> shadow: ShadowUniform;
+@group(2) @binding(1) var shadow_map: texture_depth_2d_array;
+@group(2) @binding(2) var shadow_sampler: sampler_comparison;
+
+struct PointLight {
+    // xyz: world position, w: radius.
+    position: vec4<f32>,
+    color: vec4<f32>,
+};
+struct ClusterUniform {
+    view: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    // x: near, y: far, z: light count, w: max lights per cluster.
+    params: vec4<f32>,
+    grid: vec4<u32>,
+};
+@group(3) @binding(0) var<uniform> clusters: ClusterUniform;
+@group(3) @binding(1) var<storage, read> lights: array<PointLight>;
+@group(3) @binding(2) var<storage, read> cluster_counts: array<u32>;
+@group(3) @binding(3) var<storage, read> cluster_lights: array<u32>;
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
-    @location(1) normal: vec3<f32>,
-    @location(2) color: vec3<f32>,
+    // Packed: w of the normal and a of the color are unused.
+    @location(1) normal: vec4<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) facade: vec4<f32>,
+    // Per draw: xyz the mesh origin relative to the camera (positions are relative to it), w 0
+    // while the chunk is hidden in the haze and 1 once it has faded in.
+    @location(4) instance: vec4<f32>,
 };
 
 struct VertexOutput {
@@ -22,68 +101,1062 @@ struct VertexOutput {
     @location(0) color: vec3<f32>,
     @location(1) world_pos: vec3<f32>,
     @location(2) normal: vec3<f32>,
+    @location(3) facade: vec4<f32>,
+    @location(4) appear: f32,
 };
 
+fn hash21(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+// 1 when lit by the sun, 0 in full shadow; 3x3 PCF over the cascade covering `dist`.
+fn sun_visibility(world_pos: vec3<f32>, normal: vec3<f32>, dist: f32) -> f32 {
+    if (shadow.splits.w < 0.5 || dist >= shadow.splits.z) { return 1.0; }
+    var cascade = 2u;
+    if (dist < shadow.splits.y) { cascade = 1u; }
+    if (dist < shadow.splits.x) { cascade = 0u; }
+
+    // Push the lookup off the surface, scaled with the cascade's texel footprint.
+    let offset = normal * (0.05 + dist * 0.002);
+    let clip = shadow.light_view_proj[cascade] * vec4<f32>(world_pos + offset, 1.0);
+    let uv = clip.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || clip.z > 1.0) { return 1.0; }
+
+    let layer = i32(shadow.params.x) + i32(cascade);
+    var sum = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let texel = vec2<f32>(f32(x), f32(y)) * shadow.params.y;
+            sum += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + texel, layer, clip.z);
+        }
+    }
+    return sum / 9.0;
+}
+
+// Diffuse light from the point lights binned into this fragment's cluster.
+fn point_lighting(world_pos: vec3<f32>, normal: vec3<f32>, frag_xy: vec2<f32>) -> vec3<f32> {
+    if (clusters.params.z < 0.5) { return vec3<f32>(0.0); }
+    let near = clusters.params.x;
+    let far = clusters.params.y;
+    let depth = -(clusters.view * vec4<f32>(world_pos, 1.0)).z;
+    if (depth >= far) { return vec3<f32>(0.0); }
+
+    let grid = clusters.grid.xyz;
+    let tile = min(vec2<u32>(max((frag_xy - camera.viewport.xy) / camera.viewport.zw, vec2<f32>(0.0)) * vec2<f32>(grid.xy)), grid.xy - 1u);
+    let slice = min(u32(log(max(depth, near) / near) / log(far / near) * f32(grid.z)), grid.z - 1u);
+    let cluster = (slice * grid.y + tile.y) * grid.x + tile.x;
+    let max_lights = u32(clusters.params.w);
+
+    var sum = vec3<f32>(0.0);
+    for (var i = 0u; i < cluster_counts[cluster]; i++) {
+        let light = lights[cluster_lights[cluster * max_lights + i]];
+        let to_light = light.position.xyz - world_pos;
+        let d = length(to_light);
+        // Inverse-square falloff windowed to reach zero at the light's radius.
+        let window = pow(clamp(1.0 - pow(d / light.position.w, 4.0), 0.0, 1.0), 2.0);
+        sum += light.color.rgb * abs(dot(normal, to_light / max(d, 0.001))) * window / (1.0 + d * d * 0.02);
+    }
+    return sum;
+}
+
 @vertex
 fn vs_main(model: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    out.world_pos = model.position;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
-    out.normal = model.normal;
-    out.color = model.color;
+    // Transformed relative to the camera so vertices far from the world origin don't jitter.
+    let relative = model.position + model.instance.xyz;
+    out.world_pos = relative + camera.camera_pos.xyz;
+    out.clip_position = camera.relative_view_proj * vec4<f32>(relative, 1.0);
+    out.normal = model.normal.xyz;
+    out.color = model.color.rgb;
+    out.facade = model.facade;
+    out.appear = model.instance.w;
     return out;
 }
 
+// Color plus the inputs of the SSAO pass.
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    // Normal facing the camera; alpha is the share of the color that came from ambient light.
+    @location(1) normal: vec4<f32>,
+};
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let sun_dir = normalize(vec3<f32>(0.5, 1.0, 0.5));
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let sun_dir = camera.sun_dir.xyz;
+    let daylight = camera.sun_dir.w;
     let normal = normalize(in.normal);
     
-    // Lighting: abs() handles double-sided walls (OSM data often has arbitrary winding)
+    // Lighting: abs() handles double-sided walls (OSM data often has arbitrary winding).
+    // At night a dim fixed moon light takes over from the sun.
     let diff = abs(dot(normal, sun_dir));
+    let moon_diff = abs(dot(normal, normalize(vec3<f32>(-0.4, 1.0, -0.3))));
+    let dist = distance(in.world_pos, camera.camera_pos.xyz);
+    let facing = normal * sign(dot(normal, sun_dir));
+    let visibility = sun_visibility(in.world_pos, facing, dist);
     
-    let light = 0.2 + (diff * 0.8);
+    let ambient = mix(0.08, 0.2, daylight);
+    let light = mix(0.08 + moon_diff * 0.12, 0.2 + (diff * 0.8 * visibility), daylight);
+
+    // Facade window grid. Sampled unconditionally so derivatives stay in uniform control flow.
+    let texel = textureSample(facade_tex, facade_sampler, in.facade.xy, max(i32(in.facade.w), 0));
+    let textured = step(0.0, in.facade.w);
+    let levels = in.facade.z;
+    let cell = floor(in.facade.xy);
+    // No windows in the parapet above the top level.
+    let window = texel.a * textured * step(in.facade.y, levels);
+    // Taller buildings keep more lights on; each window is on or off independently, and
+    // lights switch on as the sun goes down.
+    let lit_chance = clamp(0.25 + levels * 0.01, 0.25, 0.55) * (1.0 - daylight * 0.9);
+    let lit = step(1.0 - lit_chance, hash21(cell));
+    let wall = mix(in.color, in.color * texel.rgb * 2.0, textured);
+    // Wet streets darken the ground and, further down, pick up a sheen of the sky.
+    let wet = camera.sky_color.w * step(0.9, normal.y) * step(in.world_pos.y, 0.5);
+    let albedo = mix(wall, vec3<f32>(0.04, 0.05, 0.07), window) * (1.0 - 0.55 * wet);
+    let glow = vec3<f32>(1.0, 0.82, 0.55) * window * lit * (0.6 + 0.4 * hash21(cell + 17.0)) * (1.0 - daylight * 0.7);
     
-    // Height fog/gradient to give depth to the city
+    // Height gradient to give depth to the city
     let height_gradient = clamp((in.world_pos.y + 20.0) / 150.0, 0.4, 1.0);
-    let lit_color = in.color * light * height_gradient;
+    let lamps = point_lighting(in.world_pos, normal, in.clip_position.xy);
+    let lit_color = albedo * (light * height_gradient + lamps) + glow;
 
-    // Distance Fog
-    let dist = distance(in.world_pos, camera.camera_pos.xyz);
-    let fog_factor = smoothstep(camera.fog_dist.x, camera.fog_dist.y, dist);
+    // Height fog and aerial haze, reaching the plain sky color by the draw distance
+    let view_dir = (in.world_pos - camera.camera_pos.xyz) / max(dist, 0.0001);
+    let fade = smoothstep(camera.fog_dist.x, camera.fog_dist.y, dist);
+    // Newly visible chunks emerge from the haze the same way.
+    let fog_factor = max(max(fog_opacity(view_dir, dist), fade), 1.0 - in.appear);
+    let haze = mix(fog_color(view_dir), camera.sky_color.rgb, fade);
+    let sheen = fog_color(reflect(view_dir, normal)) * pow(1.0 - abs(view_dir.y), 5.0) * wet * 0.5;
     
-    return vec4<f32>(mix(lit_color, vec3<f32>(0.0, 0.0, 0.0), fog_factor), 1.0);
+    let luma = vec3<f32>(0.2126, 0.7152, 0.0722);
+    let ambient_share = dot(albedo * ambient * height_gradient, luma) / max(dot(lit_color, luma), 0.0001) * (1.0 - fog_factor);
+    let view_normal = normal * sign(dot(normal, camera.camera_pos.xyz - in.world_pos));
+
+    var out: FragmentOutput;
+    out.color = vec4<f32>(underwater(mix(lit_color + sheen, haze, fog_factor), dist), 1.0);
+    out.normal = vec4<f32>(view_normal, clamp(ambient_share, 0.0, 1.0));
+    return out;
 }
-"#;
 
-// Simple UI shader for the crosshair
-pub const UI_SHADER: &str = r#"
+// Debug views: flat edges for the wireframe pipeline, world normals as colors.
+@fragment
+fn fs_wireframe(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = vec4<f32>(0.2, 1.0, 0.4, 1.0);
+    out.normal = vec4<f32>(0.0);
+    return out;
+}
+
+@fragment
+fn fs_normals(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = vec4<f32>(normalize(in.normal) * 0.5 + 0.5, 1.0);
+    out.normal = vec4<f32>(0.0);
+    return out;
+}
+"#);
+
+// Procedural sky drawn as a fullscreen triangle behind the scene: a horizon-to-zenith
+// gradient plus a sun disk with forward scattering glow around it
+pub const SKY_SHADER: &str = concat!(camera_uniform!(), fog!(), r#"
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    var pos = vec2<f32>(-1.0, -1.0);
+    if (in_vertex_index == 1u) { pos = vec2<f32>(3.0, -1.0); }
+    if (in_vertex_index == 2u) { pos = vec2<f32>(-1.0, 3.0); }
+    var out: VertexOutput;
+    out.position = vec4<f32>(pos, 1.0, 1.0);
+    out.ndc = pos;
+    return out;
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let far = camera.inv_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+    let dir = normalize(far.xyz / far.w - camera.camera_pos.xyz);
+    let sun_dir = camera.sun_dir.xyz;
+
+    // Rayleigh-like: the sky thickens toward the horizon, so favour the horizon color there.
+    let up = clamp(dir.y, 0.0, 1.0);
+    var color = mix(camera.sky_color.rgb, camera.zenith_color.rgb, pow(up, 0.45));
+    // Below the horizon fades to a darker ground haze.
+    color = mix(color, camera.sky_color.rgb * 0.35, clamp(-dir.y * 4.0, 0.0, 1.0));
+
+    // Mie-like halo and the sun disk; both fade out as the sun sets.
+    let cos_angle = dot(dir, sun_dir);
+    let sun_up = smoothstep(-0.1, 0.05, sun_dir.y);
+    let halo = pow(max(cos_angle, 0.0), 12.0) * 0.35 + pow(max(cos_angle, 0.0), 200.0) * 0.6;
+    let disk = smoothstep(0.9994, 0.9997, cos_angle);
+    let sun_color = mix(vec3<f32>(1.0, 0.55, 0.3), vec3<f32>(1.0, 0.95, 0.85), clamp(sun_dir.y * 4.0, 0.0, 1.0));
+    color += sun_color * (halo + disk * 4.0) * sun_up;
+
+    // Aerial perspective: the haze in front of the farthest geometry, so the skyline
+    // blends into the sky instead of ending at a hard edge.
+    color = mix(color, fog_color(dir), fog_opacity(dir, camera.fog_dist.y) * (1.0 - disk));
+    color = underwater(color, camera.fog_dist.y);
+
+    // No normal and no ambient share, so SSAO leaves the sky alone.
+    var out: FragmentOutput;
+    out.color = vec4<f32>(color, 1.0);
+    out.normal = vec4<f32>(0.0);
+    return out;
+}
+"#);
+
+// Screen-space ambient occlusion for one viewport. Samples a hemisphere around each
+// pixel's surface and counts how many sample points end up behind the depth buffer.
+pub const SSAO_SHADER: &str = concat!(camera_uniform!(), r#"
+struct PostUniform {
+    ssao: vec4<f32>,
+    exposure: vec4<f32>,
+    frame: vec4<f32>,
+    bloom: vec4<f32>,
+};
+@group(1) @binding(0) var depth_tex: texture_depth_multisampled_2d;
+@group(1) @binding(1) var normal_tex: texture_2d<f32>;
+@group(1) @binding(2) var<uniform> post: PostUniform;
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) vec4<f32> {
+    var pos = vec2<f32>(-1.0, -1.0);
+    if (in_vertex_index == 1u) { pos = vec2<f32>(3.0, -1.0); }
+    if (in_vertex_index == 2u) { pos = vec2<f32>(-1.0, 3.0); }
+    return vec4<f32>(pos, 0.0, 1.0);
+}
+
+fn hash21(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+// World position of the surface under `pixel`; w is 0 where only sky was drawn.
+fn world_at(pixel: vec2<i32>) -> vec4<f32> {
+    let depth = textureLoad(depth_tex, pixel, 0);
+    let uv = (vec2<f32>(pixel) + 0.5 - camera.viewport.xy) / camera.viewport.zw;
+    let world = camera.inv_view_proj * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    return vec4<f32>(world.xyz / world.w, select(1.0, 0.0, depth >= 1.0));
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(frag_coord.xy);
+    let center = world_at(pixel);
+    let normal = textureLoad(normal_tex, pixel, 0).xyz;
+    if (center.w == 0.0 || dot(normal, normal) < 0.01) { return vec4<f32>(1.0); }
+
+    let pos = center.xyz;
+    let dist = distance(pos, camera.camera_pos.xyz);
+    let max_dist = post.ssao.w;
+    if (dist > max_dist) { return vec4<f32>(1.0); }
+
+    let n = normalize(normal);
+    let helper = select(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), abs(n.y) > 0.9);
+    let t = normalize(cross(helper, n));
+    let b = cross(n, t);
+
+    let radius = post.ssao.x;
+    let count = max(i32(post.ssao.z), 1);
+    // Depth precision drops with distance, so the self-occlusion bias grows with it.
+    let bias = 0.02 + dist * 0.001;
+    // Per-pixel rotation trades banding for noise, which the composite blur smooths out.
+    let rotation = hash21(frag_coord.xy) * 6.2831853;
+    let view_min = camera.viewport.xy;
+    let view_max = camera.viewport.xy + camera.viewport.zw;
+    var occlusion = 0.0;
+    for (var i = 0; i < count; i++) {
+        let fi = f32(i);
+        // Cosine-weighted hemisphere spiral, sample lengths clustered near the surface.
+        let r = (fi + 0.5) / f32(count);
+        let angle = fi * 2.3999632 + rotation;
+        let sin_theta = sqrt(r);
+        let dir = (t * cos(angle) + b * sin(angle)) * sin_theta + n * sqrt(1.0 - r);
+        let scale = mix(0.1, 1.0, pow(hash21(frag_coord.xy + fi * 7.31), 2.0));
+        let sample_pos = pos + dir * radius * scale;
+
+        let clip = camera.view_proj * vec4<f32>(sample_pos, 1.0);
+        if (clip.w <= 0.0) { continue; }
+        let ndc = clip.xy / clip.w;
+        let sample_pixel = view_min + (vec2<f32>(ndc.x, -ndc.y) * 0.5 + 0.5) * camera.viewport.zw;
+        if (any(sample_pixel < view_min) || any(sample_pixel >= view_max)) { continue; }
+
+        let scene = world_at(vec2<i32>(sample_pixel));
+        if (scene.w == 0.0) { continue; }
+        let scene_dist = distance(scene.xyz, camera.camera_pos.xyz);
+        let sample_dist = distance(sample_pos, camera.camera_pos.xyz);
+        // Geometry far in front of the sample (e.g. a pole between camera and wall) doesn't count.
+        let in_range = smoothstep(0.0, 1.0, radius / max(abs(dist - scene_dist), 0.001));
+        occlusion += step(scene_dist, sample_dist - bias) * in_range;
+    }
+
+    let ao = 1.0 - occlusion / f32(count);
+    let fade = smoothstep(max_dist * 0.7, max_dist, dist);
+    return vec4<f32>(vec3<f32>(mix(ao, 1.0, fade)), 1.0);
+}
+"#);
+
+// Writes the HDR scene to the surface: darkens the ambient share of each pixel by the
+// blurred occlusion term, applies the adapted exposure, adds the bloom and tonemaps
+pub const COMPOSITE_SHADER: &str = r#"
+struct PostUniform {
+    ssao: vec4<f32>,
+    exposure: vec4<f32>,
+    frame: vec4<f32>,
+    bloom: vec4<f32>,
+    dof: vec4<f32>,
+};
+@group(0) @binding(0) var hdr_tex: texture_2d<f32>;
+@group(0) @binding(1) var normal_tex: texture_2d<f32>;
+@group(0) @binding(2) var ao_tex: texture_2d<f32>;
+@group(0) @binding(3) var<uniform> post: PostUniform;
+@group(0) @binding(4) var exposure_tex: texture_2d<f32>;
+@group(0) @binding(5) var bloom_tex: texture_2d<f32>;
+@group(0) @binding(6) var linear_sampler: sampler;
+@group(0) @binding(7) var depth_tex: texture_depth_multisampled_2d;
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) vec4<f32> {
+    var pos = vec2<f32>(-1.0, -1.0);
+    if (in_vertex_index == 1u) { pos = vec2<f32>(3.0, -1.0); }
+    if (in_vertex_index == 2u) { pos = vec2<f32>(-1.0, 3.0); }
+    return vec4<f32>(pos, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    // The scene targets may be smaller than the surface (dynamic resolution); color is
+    // upsampled bilinearly, the rest is read from the nearest texel.
+    let source_pos = frag_coord.xy * post.frame.w;
+    let uv = source_pos / vec2<f32>(textureDimensions(hdr_tex));
+    let last = vec2<i32>(textureDimensions(ao_tex)) - 1;
+    let pixel = min(vec2<i32>(source_pos), last);
+    var color = textureSampleLevel(hdr_tex, linear_sampler, uv, 0.0).rgb;
+
+    // Depth of field (photo mode): a disc of taps that widens away from the focus distance.
+    if (post.dof.y > 0.0) {
+        let depth = textureLoad(depth_tex, pixel, 0);
+        let distance = post.dof.z * post.dof.w / (post.dof.w - depth * (post.dof.w - post.dof.z));
+        let radius = min(post.dof.y * abs(1.0 - post.dof.x / distance), post.dof.y);
+        if (radius > 0.5) {
+            let texel = 1.0 / vec2<f32>(textureDimensions(hdr_tex));
+            var sum = color;
+            for (var i = 0; i < 16; i++) {
+                let angle = f32(i) * 2.39996;
+                let offset = vec2<f32>(cos(angle), sin(angle)) * sqrt((f32(i) + 0.5) / 16.0) * radius;
+                sum += textureSampleLevel(hdr_tex, linear_sampler, uv + offset * texel, 0.0).rgb;
+            }
+            color = sum / 17.0;
+        }
+    }
+    let ambient_share = textureLoad(normal_tex, pixel, 0).a;
+
+    var ao = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            ao += textureLoad(ao_tex, clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), last), 0).r;
+        }
+    }
+    ao /= 9.0;
+
+    let shade = 1.0 - ambient_share * (1.0 - ao) * post.ssao.y;
+    let bloom = textureSampleLevel(bloom_tex, linear_sampler, uv, 0.0).rgb * post.bloom.z;
+    let exposed = color * shade * textureLoad(exposure_tex, vec2<i32>(0), 0).r + bloom;
+
+    // Tonemapper: 0 none, 1 Reinhard, 2 ACES (Narkowicz fit).
+    var mapped = exposed;
+    if (post.frame.z > 1.5) {
+        mapped = (exposed * (2.51 * exposed + 0.03)) / (exposed * (2.43 * exposed + 0.59) + 0.14);
+    } else if (post.frame.z > 0.5) {
+        mapped = exposed / (1.0 + dot(exposed, vec3<f32>(0.2126, 0.7152, 0.0722)));
+    }
+    return vec4<f32>(clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
+}
+"#;
+
+// Auto-exposure: log-luminance of the HDR scene, a 2x2 box downsample per mip level, and
+// the per-frame adaptation of the exposure toward the key value
+pub const EXPOSURE_SHADER: &str = r#"
+struct PostUniform {
+    ssao: vec4<f32>,
+    exposure: vec4<f32>,
+    frame: vec4<f32>,
+    bloom: vec4<f32>,
+};
+@group(0) @binding(0) var source_tex: texture_2d<f32>;
+@group(0) @binding(1) var previous_tex: texture_2d<f32>;
+@group(0) @binding(2) var<uniform> post: PostUniform;
+
+const LUM_SIZE = 256.0;
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) vec4<f32> {
+    var pos = vec2<f32>(-1.0, -1.0);
+    if (in_vertex_index == 1u) { pos = vec2<f32>(3.0, -1.0); }
+    if (in_vertex_index == 2u) { pos = vec2<f32>(-1.0, 3.0); }
+    return vec4<f32>(pos, 0.0, 1.0);
+}
+
+@fragment
+fn fs_luminance(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(source_tex));
+    let color = textureLoad(source_tex, vec2<i32>(frag_coord.xy / LUM_SIZE * size), 0).rgb;
+    let lum = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    return vec4<f32>(log(max(lum, 0.0001)), 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_downsample(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(frag_coord.xy) * 2;
+    let sum = textureLoad(source_tex, p, 0).r + textureLoad(source_tex, p + vec2<i32>(1, 0), 0).r
+        + textureLoad(source_tex, p + vec2<i32>(0, 1), 0).r + textureLoad(source_tex, p + vec2<i32>(1, 1), 0).r;
+    return vec4<f32>(sum * 0.25, 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_adapt() -> @location(0) vec4<f32> {
+    if (post.frame.y > 0.0) { return vec4<f32>(post.frame.y, 0.0, 0.0, 1.0); }
+    let average = exp(textureLoad(source_tex, vec2<i32>(0), 0).r);
+    let target_exposure = clamp(post.exposure.x / average, post.exposure.y, post.exposure.z);
+    var previous = textureLoad(previous_tex, vec2<i32>(0), 0).r;
+    // Nothing to adapt from on the first frame.
+    if (previous <= 0.0) { previous = target_exposure; }
+    let rate = 1.0 - exp(-post.frame.x * post.exposure.w);
+    return vec4<f32>(mix(previous, target_exposure, rate), 0.0, 0.0, 1.0);
+}
+"#;
+
+// Bloom chain: a soft-threshold prefilter of the exposed scene, 4-tap bilinear downsamples
+// and 9-tap tent upsamples added onto each larger level
+pub const BLOOM_SHADER: &str = r#"
+struct PostUniform {
+    ssao: vec4<f32>,
+    exposure: vec4<f32>,
+    frame: vec4<f32>,
+    bloom: vec4<f32>,
+};
+@group(0) @binding(0) var source_tex: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+@group(0) @binding(2) var<uniform> post: PostUniform;
+@group(0) @binding(3) var exposure_tex: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
     @location(0) uv: vec2<f32>,
 };
+
 @vertex
 fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    var pos = vec2<f32>(-1.0, -1.0);
+    if (in_vertex_index == 1u) { pos = vec2<f32>(3.0, -1.0); }
+    if (in_vertex_index == 2u) { pos = vec2<f32>(-1.0, 3.0); }
     var out: VertexOutput;
-    let size = 0.003; 
-    var pos = vec2<f32>(0.0);
-    var uv = vec2<f32>(0.0);
-    
-    // Simple quad generation from index
-    if (in_vertex_index == 0u) { pos = vec2<f32>(-size, -size); uv = vec2<f32>(0.0, 0.0); }
-    if (in_vertex_index == 1u) { pos = vec2<f32>( size, -size); uv = vec2<f32>(1.0, 0.0); }
-    if (in_vertex_index == 2u) { pos = vec2<f32>(-size,  size); uv = vec2<f32>(0.0, 1.0); }
-    if (in_vertex_index == 3u) { pos = vec2<f32>( size,  size); uv = vec2<f32>(1.0, 1.0); }
-    
-    out.position = vec4<f32>(pos.x, pos.y * 1.77, 0.0, 1.0);
-    out.uv = uv;
+    out.clip_position = vec4<f32>(pos, 0.0, 1.0);
+    out.uv = vec2<f32>(pos.x * 0.5 + 0.5, 0.5 - pos.y * 0.5);
+    return out;
+}
+
+fn sample(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(source_tex, source_sampler, uv, 0.0).rgb;
+}
+
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Clamped so single very bright pixels don't flicker as large blobs.
+    let color = min(sample(in.uv) * textureLoad(exposure_tex, vec2<i32>(0), 0).r, vec3<f32>(64.0));
+    let brightness = max(color.r, max(color.g, color.b));
+    let threshold = post.bloom.x;
+    let knee = threshold * post.bloom.y + 0.0001;
+    let soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    let contribution = max(soft * soft / (4.0 * knee), brightness - threshold) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source_tex));
+    let sum = sample(in.uv + texel * vec2<f32>(-1.0, -1.0)) + sample(in.uv + texel * vec2<f32>(1.0, -1.0))
+        + sample(in.uv + texel * vec2<f32>(-1.0, 1.0)) + sample(in.uv + texel * vec2<f32>(1.0, 1.0));
+    return vec4<f32>(sum * 0.25, 1.0);
+}
+
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source_tex));
+    var sum = sample(in.uv) * 4.0;
+    sum += (sample(in.uv + vec2<f32>(texel.x, 0.0)) + sample(in.uv - vec2<f32>(texel.x, 0.0))
+        + sample(in.uv + vec2<f32>(0.0, texel.y)) + sample(in.uv - vec2<f32>(0.0, texel.y))) * 2.0;
+    sum += sample(in.uv + texel) + sample(in.uv - texel)
+        + sample(in.uv + vec2<f32>(texel.x, -texel.y)) + sample(in.uv + vec2<f32>(-texel.x, texel.y));
+    return vec4<f32>(sum / 16.0, 1.0);
+}
+"#;
+
+// TAA resolve: reprojects the history through depth and the previous view-projection,
+// clamps it to the current 3x3 neighbourhood and blends the new frame in
+pub const TAA_SHADER: &str = r#"
+struct TaaUniform {
+    inv_view_proj: mat4x4<f32>,
+    prev_view_proj: mat4x4<f32>,
+    viewport: vec4<f32>,
+    params: vec4<f32>,
+};
+@group(0) @binding(0) var current_tex: texture_2d<f32>;
+@group(0) @binding(1) var history_tex: texture_2d<f32>;
+@group(0) @binding(2) var depth_tex: texture_depth_2d;
+@group(0) @binding(3) var history_sampler: sampler;
+@group(1) @binding(0) var<uniform> taa: TaaUniform;
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) vec4<f32> {
+    var pos = vec2<f32>(-1.0, -1.0);
+    if (in_vertex_index == 1u) { pos = vec2<f32>(3.0, -1.0); }
+    if (in_vertex_index == 2u) { pos = vec2<f32>(-1.0, 3.0); }
+    return vec4<f32>(pos, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(frag_coord.xy);
+    let size = vec2<i32>(textureDimensions(current_tex));
+    let current = textureLoad(current_tex, pixel, 0).rgb;
+    if (taa.params.y < 0.5) { return vec4<f32>(current, 1.0); }
+
+    // Where this pixel's surface was on screen last frame.
+    let uv = (frag_coord.xy - taa.viewport.xy) / taa.viewport.zw;
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    let depth = textureLoad(depth_tex, pixel, 0);
+    let world = taa.inv_view_proj * vec4<f32>(ndc, depth, 1.0);
+    let prev_clip = taa.prev_view_proj * vec4<f32>(world.xyz / world.w, 1.0);
+    let prev_ndc = prev_clip.xy / prev_clip.w;
+    let prev_uv = vec2<f32>(prev_ndc.x * 0.5 + 0.5, 0.5 - prev_ndc.y * 0.5);
+    if (prev_clip.w <= 0.0 || any(prev_uv < vec2<f32>(0.0)) || any(prev_uv > vec2<f32>(1.0))) {
+        return vec4<f32>(current, 1.0);
+    }
+    let prev_pixel = taa.viewport.xy + prev_uv * taa.viewport.zw;
+    let history = textureSampleLevel(history_tex, history_sampler, prev_pixel / vec2<f32>(size), 0.0).rgb;
+
+    // History outside the range of the current neighbourhood belongs to something that
+    // was disoccluded or changed, so pull it back in.
+    var lo = current;
+    var hi = current;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let p = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let c = textureLoad(current_tex, p, 0).rgb;
+            lo = min(lo, c);
+            hi = max(hi, c);
+        }
+    }
+    let clamped = clamp(history, lo, hi);
+
+    // Weigh by inverse luminance so single very bright samples don't flicker.
+    let w_history = (1.0 - taa.params.x) / (1.0 + dot(clamped, vec3<f32>(0.2126, 0.7152, 0.0722)));
+    let w_current = taa.params.x / (1.0 + dot(current, vec3<f32>(0.2126, 0.7152, 0.0722)));
+    return vec4<f32>((clamped * w_history + current * w_current) / (w_history + w_current), 1.0);
+}
+"#;
+
+// Chunk bounding boxes drawn under occlusion queries; only the depth test matters
+pub const OCCLUSION_SHADER: &str = concat!(camera_uniform!(), r#"
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) idx: u32, @location(0) box_min: vec3<f32>, @location(1) box_max: vec3<f32>) -> @builtin(position) vec4<f32> {
+    // Corner bits are (x, y, z); two triangles per face.
+    var corners = array<u32, 36>(
+        0u, 1u, 3u, 0u, 3u, 2u,  4u, 6u, 7u, 4u, 7u, 5u,
+        0u, 4u, 5u, 0u, 5u, 1u,  2u, 3u, 7u, 2u, 7u, 6u,
+        0u, 2u, 6u, 0u, 6u, 4u,  1u, 5u, 7u, 1u, 7u, 3u,
+    );
+    let c = corners[idx];
+    let t = vec3<f32>(f32(c & 1u), f32((c >> 1u) & 1u), f32((c >> 2u) & 1u));
+    return camera.view_proj * vec4<f32>(mix(box_min, box_max, t), 1.0);
+}
+
+@fragment
+fn fs_main() -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = vec4<f32>(0.0);
+    out.normal = vec4<f32>(0.0);
+    return out;
+}
+"#);
+
+// Colored debug lines (chunk bounds, collision walls) in world space
+pub const DEBUG_LINE_SHADER: &str = concat!(camera_uniform!(), r#"
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = in.color;
+    out.normal = vec4<f32>(0.0);
+    return out;
+}
+"#);
+
+// The building under the crosshair, drawn a second time over the lit scene with additive blending
+pub const HIGHLIGHT_SHADER: &str = concat!(camera_uniform!(), r#"
+struct HighlightUniform {
+    // rgb: tint, a: strength.
+    color: vec4<f32>,
+    // x: seconds since start.
+    params: vec4<f32>,
+};
+@group(1) @binding(0) var<uniform> highlight: HighlightUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(1) normal: vec4<f32>, @location(2) origin: vec3<f32>) -> VertexOutput {
+    // `origin` is the chunk's, relative to the camera, as in the scene shader.
+    let relative = position + origin;
+    var out: VertexOutput;
+    out.clip_position = camera.relative_view_proj * vec4<f32>(relative, 1.0);
+    out.world_pos = relative + camera.camera_pos.xyz;
+    out.normal = normal.xyz;
+    return out;
+}
+
+// Added on top of the lit building: a faint pulsing glow over every face plus a brighter
+// rim where the surface turns away from the viewer, which outlines the silhouette.
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let view_dir = normalize(camera.camera_pos.xyz - in.world_pos);
+    let rim = pow(1.0 - abs(dot(normalize(in.normal), view_dir)), 3.0);
+    let pulse = 0.8 + 0.2 * sin(highlight.params.x * 4.0);
+    var out: FragmentOutput;
+    out.color = vec4<f32>(highlight.color.rgb * highlight.color.a * (0.25 + rim) * pulse, 0.0);
+    out.normal = vec4<f32>(0.0);
+    return out;
+}
+"#);
+
+// Shimmering walls around chunks the loader hasn't delivered yet. One instance per chunk
+// (x, z: its corner, w: side length); 24 vertices make its four walls.
+pub const STREAMING_SHADER: &str = concat!(camera_uniform!(), r#"
+struct StreamingUniform {
+    // rgb: tint, a: strength.
+    color: vec4<f32>,
+    // x: seconds since start, y: wall height.
+    params: vec4<f32>,
+};
+@group(1) @binding(0) var<uniform> streaming: StreamingUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // x: meters along the wall, y: 0 at the foot to 1 at the top.
+    @location(0) uv: vec2<f32>,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32, @location(0) chunk: vec4<f32>) -> VertexOutput {
+    var corners = array<vec2<f32>, 4>(vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 0.0), vec2<f32>(1.0, 1.0), vec2<f32>(0.0, 1.0));
+    var quad = array<vec2<f32>, 6>(vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 0.0), vec2<f32>(0.0, 1.0), vec2<f32>(1.0, 0.0), vec2<f32>(1.0, 1.0), vec2<f32>(0.0, 1.0));
+    let wall = in_vertex_index / 6u;
+    let uv = quad[in_vertex_index % 6u];
+    let ground = chunk.xz + mix(corners[wall], corners[(wall + 1u) % 4u], uv.x) * chunk.w;
+    let world = vec3<f32>(ground.x, uv.y * streaming.params.y, ground.y);
+    var out: VertexOutput;
+    out.clip_position = camera.relative_view_proj * vec4<f32>(world - camera.camera_pos.xyz, 1.0);
+    out.uv = vec2<f32>(uv.x * chunk.w, uv.y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let t = streaming.params.x;
+    let bands = 0.5 + 0.5 * sin(in.uv.y * 24.0 - t * 3.0 + sin(in.uv.x * 0.02 + t) * 2.0);
+    let fade = (1.0 - in.uv.y) * (1.0 - in.uv.y);
+    let alpha = fade * (0.3 + 0.7 * bands) * streaming.color.a;
+    var out: FragmentOutput;
+    out.color = vec4<f32>(streaming.color.rgb * alpha, 0.0);
+    out.normal = vec4<f32>(0.0);
+    return out;
+}
+"#);
+
+// Rain streaks and snow flakes around the camera, instanced quads placed procedurally
+pub const WEATHER_SHADER: &str = concat!(camera_uniform!(), r#"
+struct WeatherUniform {
+    // x: seconds, y: fall speed, z: box size, w: 0 rain, 1 snow.
+    params: vec4<f32>,
+    color: vec4<f32>,
+    // x: width, y: length.
+    size: vec4<f32>,
+};
+@group(1) @binding(0) var depth_tex: texture_depth_multisampled_2d;
+@group(1) @binding(1) var<uniform> weather: WeatherUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) alpha: f32,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+fn hash31(n: u32) -> vec3<f32> {
+    var x = n * 747796405u + 2891336453u;
+    x = ((x >> ((x >> 28u) + 4u)) ^ x) * 277803737u;
+    let a = (x >> 22u) ^ x;
+    let b = a * 1664525u + 1013904223u;
+    let c = b * 1664525u + 1013904223u;
+    return vec3<f32>(f32(a & 0xffffu), f32(b & 0xffffu), f32(c & 0xffffu)) / 65535.0;
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32, @builtin(instance_index) instance: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex];
+    let seed = hash31(instance);
+    let t = weather.params.x;
+    let box_size = weather.params.z;
+    let snow = weather.params.w > 0.5;
+    let eye = camera.camera_pos.xyz;
+
+    // Each particle falls through a fixed lattice position; wrapping it into the box around
+    // the camera makes the field endless without moving anything on the CPU.
+    var pos = seed * box_size;
+    pos.y -= t * weather.params.y * (0.8 + 0.4 * seed.z);
+    if (snow) {
+        pos.x += sin(t * 0.9 + seed.y * 40.0) * 0.6;
+        pos.z += cos(t * 0.7 + seed.x * 40.0) * 0.6;
+    }
+    pos = eye + (fract((pos - eye) / box_size) - 0.5) * box_size;
+
+    let to_eye = normalize(eye - pos);
+    var right = normalize(cross(vec3<f32>(0.0, 1.0, 0.0), to_eye));
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if (snow) { up = cross(to_eye, right); }
+    let world = pos + right * corner.x * weather.size.x * 0.5 + up * corner.y * weather.size.y * 0.5;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(world, 1.0);
+    out.uv = corner;
+    // Fade toward the box edges so particles don't pop in as they wrap around.
+    out.alpha = 1.0 - smoothstep(0.3, 0.5, distance(pos, eye) / box_size);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let depth = textureLoad(depth_tex, vec2<i32>(in.clip_position.xy), 0);
+    if (in.clip_position.z > depth) { discard; }
+
+    // Flakes are round, streaks fade out toward their ends.
+    var shape = 1.0 - smoothstep(0.4, 1.0, length(in.uv));
+    if (weather.params.w < 0.5) { shape = (1.0 - abs(in.uv.x)) * (1.0 - in.uv.y * in.uv.y); }
+    let light = mix(0.08, 1.0, camera.sun_dir.w);
+    var out: FragmentOutput;
+    out.color = vec4<f32>(weather.color.rgb * light, weather.color.a * shape * in.alpha);
+    out.normal = vec4<f32>(0.0);
+    return out;
+}
+"#);
+
+// Water surfaces: animated wave normals, screen-space reflections of the scene with the
+// procedural sky as fallback, Fresnel blend over a dark body color, then fog
+pub const WATER_SHADER: &str = concat!(camera_uniform!(), fog!(), r#"
+struct WaterUniform {
+    // x: seconds, y: wave strength, z: SSR steps (0 disables), w: SSR max distance.
+    params: vec4<f32>,
+    color: vec4<f32>,
+};
+@group(1) @binding(0) var scene_tex: texture_2d<f32>;
+@group(1) @binding(1) var depth_tex: texture_depth_multisampled_2d;
+@group(1) @binding(2) var<uniform> water: WaterUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+// Slope (d/dx, d/dz) of a few sine waves moving in different directions.
+fn wave_slope(p: vec2<f32>, t: f32) -> vec2<f32> {
+    var dirs = array<vec2<f32>, 4>(vec2<f32>(0.8, 0.6), vec2<f32>(-0.5, 0.85), vec2<f32>(0.3, -0.95), vec2<f32>(-0.9, -0.2));
+    var slope = vec2<f32>(0.0);
+    var freq = 0.35;
+    var amp = 1.0;
+    for (var i = 0; i < 4; i++) {
+        let d = dirs[i];
+        slope += d * cos(dot(d, p) * freq + t * (1.0 + f32(i) * 0.7)) * amp * freq;
+        freq *= 1.9;
+        amp *= 0.55;
+    }
+    return slope;
+}
+
+fn sky(dir: vec3<f32>) -> vec3<f32> {
+    let color = mix(camera.sky_color.rgb, camera.zenith_color.rgb, pow(clamp(dir.y, 0.0, 1.0), 0.45));
+    let sun = pow(max(dot(dir, camera.sun_dir.xyz), 0.0), 400.0) * smoothstep(-0.1, 0.05, camera.sun_dir.y);
+    return color + vec3<f32>(1.0, 0.9, 0.75) * sun * 8.0;
+}
+
+// Marches the reflected ray through the depth buffer with steps growing with distance.
+// Returns the scene color where it passes behind a surface, and how much to trust it.
+fn trace_reflection(origin: vec3<f32>, dir: vec3<f32>) -> vec4<f32> {
+    let steps = i32(water.params.z);
+    var prev_t = 0.0;
+    for (var i = 1; i <= steps; i++) {
+        let t = water.params.w * pow(f32(i) / f32(steps), 2.0);
+        let p = origin + dir * t;
+        let clip = camera.view_proj * vec4<f32>(p, 1.0);
+        if (clip.w <= 0.0) { break; }
+        let ndc = clip.xyz / clip.w;
+        if (any(abs(ndc.xy) > vec2<f32>(1.0))) { break; }
+        let pixel = vec2<i32>(camera.viewport.xy + vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5) * camera.viewport.zw);
+        let scene_depth = textureLoad(depth_tex, pixel, 0);
+        if (ndc.z > scene_depth && scene_depth < 1.0) {
+            // Only a hit if the ray is just behind the surface, not passing far behind it.
+            let surface = camera.inv_view_proj * vec4<f32>(ndc.xy, scene_depth, 1.0);
+            let behind = distance(p, camera.camera_pos.xyz) - distance(surface.xyz / surface.w, camera.camera_pos.xyz);
+            if (behind < (t - prev_t) * 1.5 + 1.0) {
+                let edge = 1.0 - smoothstep(0.85, 1.0, max(abs(ndc.x), abs(ndc.y)));
+                return vec4<f32>(textureLoad(scene_tex, pixel, 0).rgb, edge);
+            }
+        }
+        prev_t = t;
+    }
+    return vec4<f32>(0.0);
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+    out.world_pos = position;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    // No depth attachment in this pass; test against the scene by hand.
+    if (in.clip_position.z > textureLoad(depth_tex, vec2<i32>(in.clip_position.xy), 0)) { discard; }
+
+    let to_eye = camera.camera_pos.xyz - in.world_pos;
+    let dist = length(to_eye);
+    let v = to_eye / dist;
+    let slope = wave_slope(in.world_pos.xz, water.params.x) * water.params.y;
+    let n = normalize(vec3<f32>(-slope.x, 1.0, -slope.y));
+    var r = reflect(-v, n);
+    r = normalize(vec3<f32>(r.x, abs(r.y), r.z));
+
+    var reflection = sky(r);
+    if (water.params.z > 0.0) {
+        let hit = trace_reflection(in.world_pos, r);
+        reflection = mix(reflection, hit.rgb, hit.a);
+    }
+    let fresnel = 0.02 + 0.98 * pow(1.0 - max(dot(n, v), 0.0), 5.0);
+    let body = water.color.rgb * mix(0.15, 1.0, camera.sun_dir.w);
+    let color = mix(body, reflection, fresnel);
+
+    let fade = smoothstep(camera.fog_dist.x, camera.fog_dist.y, dist);
+    let fog = max(fog_opacity(-v, dist), fade);
+    let haze = mix(fog_color(-v), camera.sky_color.rgb, fade);
+
+    // Zero normal: no ambient occlusion on the water.
+    var out: FragmentOutput;
+    out.color = vec4<f32>(underwater(mix(color, haze, fog), dist), 1.0);
+    out.normal = vec4<f32>(0.0);
+    return out;
+}
+"#);
+
+// Bins point lights into the view's froxel clusters: one invocation per cluster tests
+// every light's sphere against the cluster's view-space bounds
+pub const CLUSTER_SHADER: &str = r#"
+struct PointLight {
+    // xyz: world position, w: radius.
+    position: vec4<f32>,
+    color: vec4<f32>,
+};
+struct ClusterUniform {
+    view: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    // x: near, y: far, z: light count, w: max lights per cluster.
+    params: vec4<f32>,
+    grid: vec4<u32>,
+};
+@group(0) @binding(0) var<uniform> clusters: ClusterUniform;
+@group(0) @binding(1) var<storage, read> lights: array<PointLight>;
+@group(0) @binding(2) var<storage, read_write> cluster_counts: array<u32>;
+@group(0) @binding(3) var<storage, read_write> cluster_lights: array<u32>;
+
+// View-space point on the ray through `ndc`, `depth` meters in front of the camera.
+fn view_point(ndc: vec2<f32>, depth: f32) -> vec3<f32> {
+    let p = clusters.inv_proj * vec4<f32>(ndc, 1.0, 1.0);
+    let dir = p.xyz / p.w;
+    return dir * (depth / -dir.z);
+}
+
+// Slices are spaced exponentially between the near and far distance.
+fn slice_depth(slice: u32) -> f32 {
+    return clusters.params.x * pow(clusters.params.y / clusters.params.x, f32(slice) / f32(clusters.grid.z));
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let grid = clusters.grid.xyz;
+    let index = id.x;
+    if (index >= grid.x * grid.y * grid.z) { return; }
+    let x = index % grid.x;
+    let y = (index / grid.x) % grid.y;
+    let z = index / (grid.x * grid.y);
+
+    // Tile rows run top to bottom like framebuffer pixels.
+    let ndc_min = vec2<f32>(f32(x) / f32(grid.x) * 2.0 - 1.0, 1.0 - f32(y + 1u) / f32(grid.y) * 2.0);
+    let ndc_max = vec2<f32>(f32(x + 1u) / f32(grid.x) * 2.0 - 1.0, 1.0 - f32(y) / f32(grid.y) * 2.0);
+    var lo = vec3<f32>(1e9);
+    var hi = vec3<f32>(-1e9);
+    for (var c = 0u; c < 8u; c++) {
+        let ndc = vec2<f32>(select(ndc_min.x, ndc_max.x, (c & 1u) != 0u), select(ndc_min.y, ndc_max.y, (c & 2u) != 0u));
+        let p = view_point(ndc, slice_depth(z + (c >> 2u)));
+        lo = min(lo, p);
+        hi = max(hi, p);
+    }
+
+    let max_lights = u32(clusters.params.w);
+    let light_count = u32(clusters.params.z);
+    var count = 0u;
+    for (var i = 0u; i < light_count && count < max_lights; i++) {
+        let light = lights[i];
+        let center = (clusters.view * vec4<f32>(light.position.xyz, 1.0)).xyz;
+        if (distance(clamp(center, lo, hi), center) <= light.position.w) {
+            cluster_lights[index * max_lights + count] = i;
+            count++;
+        }
+    }
+    cluster_counts[index] = count;
+}
+"#;
+
+// Depth-only pass rendering the scene from the sun into one shadow cascade
+pub const SHADOW_SHADER: &str = r#"
+@group(0) @binding(0) var<uniform> light_view_proj: mat4x4<f32>;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(1) origin: vec3<f32>) -> @builtin(position) vec4<f32> {
+    // Chunk vertices are relative to `origin`, the chunk's corner in world space. Shadow
+    // texels are far coarser than the precision lost adding it back here.
+    return light_view_proj * vec4<f32>(position + origin, 1.0);
+}
+"#;
+
+// Crosshair: one instanced quad per view, sized in window pixels and centered in the viewport.
+// Styles: 0 dot, 1 cross, 2 ring.
+pub const UI_SHADER: &str = concat!(camera_uniform!(), r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Offset from the center in window pixels.
+    @location(0) offset: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) params: vec4<f32>,
+};
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32, @location(0) color: vec4<f32>, @location(1) params: vec4<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    let corner = vec2<f32>(f32(in_vertex_index & 1u), f32(in_vertex_index >> 1u)) * 2.0 - 1.0;
+    // One pixel of margin for the antialiased edge.
+    let half = params.x * 0.5 + 1.0;
+    out.offset = corner * half;
+    out.position = vec4<f32>(out.offset * params.w * 2.0 / camera.screen_size, 0.0, 1.0);
+    out.color = color;
+    out.params = params;
+    return out;
+}
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let radius = in.params.x * 0.5;
+    let thickness = in.params.z;
+    let p = abs(in.offset);
+    // Signed distance to the shape's edge in pixels, negative inside.
+    var d = length(in.offset) - radius;
+    if (in.params.y > 1.5) {
+        d = abs(length(in.offset) - radius + thickness * 0.5) - thickness * 0.5;
+    } else if (in.params.y > 0.5) {
+        // Four arms with a gap at the center.
+        let arm = max(min(p.x, p.y) - thickness * 0.5, max(p.x, p.y) - radius);
+        d = max(arm, thickness - max(p.x, p.y));
+    }
+    let alpha = clamp(0.5 - d, 0.0, 1.0) * in.color.a;
+    if (alpha <= 0.0) { discard; }
+    return vec4<f32>(in.color.rgb, alpha);
+}
+"#);
+
+// Red edge of a view after a hard landing: one instanced quad per view filling its viewport,
+// darkening toward the corners with the instance's strength.
+pub const VIGNETTE_SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32, @location(0) color: vec4<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    let corner = vec2<f32>(f32(in_vertex_index & 1u), f32(in_vertex_index >> 1u)) * 2.0 - 1.0;
+    out.position = vec4<f32>(corner, 0.0, 1.0);
+    out.uv = corner;
+    out.color = color;
     return out;
 }
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let dist = distance(in.uv, vec2<f32>(0.5));
-    if (dist > 0.5) { discard; }
-    return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+    let edge = smoothstep(0.35, 1.35, length(in.uv));
+    return vec4<f32>(in.color.rgb, edge * in.color.a);
 }
 "#;
 
@@ -92,7 +1165,7 @@ pub const LOADING_SHADER: &str = r#"
 struct Uniforms {
     screen_size: vec2<f32>,
     progress: f32,
-    _pad: f32,
+    failed: f32,
 };
 @group(0) @binding(0) var<uniform> u: Uniforms;
 
@@ -104,33 +1177,6 @@ fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) ve
     return vec4<f32>(pos, 0.0, 1.0);
 }
 
-fn has_pixel(char_idx: i32, x: i32, y: i32) -> bool {
-    if (char_idx == 32) { return false; } // Space
-    if (char_idx == 76) { if (x == 0 || y == 4) { return true; } return false; } // L
-    if (char_idx == 111) { if (y==0||y==4) { return x==1; } return x!=1; } // o
-    if (char_idx == 97) { if (y==0||y==2) { return true; } if (y==1) { return x!=1; } return x==2 || (x==0 && y>2); } // a
-    if (char_idx == 100) { if (x==2) { return true; } if (y==2||y==4) { return x>0; } if (y==3) { return x==0; } return false; } // d
-    if (char_idx == 105) { return x == 1 && y != 1; } // i
-    if (char_idx == 110) { if (y==0) { return false; } if (y==1) { return true; } return x!=1; } // n
-    if (char_idx == 103) { if (y==0) { return x>0; } if (y==2) { return x>0; } if (y==4) { return x<2; } if (x==2) { return true; } if (x==0 && y>0 && y<3) { return true; } return false; } // g
-    if (char_idx == 37) { if (x==0 && y==0) { return true; } if (x==2 && y==4) { return true; } if (x==1 && y==2) { return true; } if (x==2 && y==1) { return true; } if (x==0 && y==3) { return true; } return false; } // %
-    
-    if (char_idx >= 48 && char_idx <= 57) {
-        let d = char_idx - 48;
-        if (d == 0) { return x!=1 || (y!=1 && y!=2 && y!=3); }
-        if (d == 1) { return x == 1; } 
-        if (d == 2) { return y==0 || y==2 || y==4 || (x==2 && y==1) || (x==0 && y==3); }
-        if (d == 3) { return y==0 || y==2 || y==4 || x==2; }
-        if (d == 4) { return y==2 || x==2 || (x==0 && y<2); }
-        if (d == 5) { return y==0 || y==2 || y==4 || (x==0 && y==1) || (x==2 && y==3); }
-        if (d == 6) { return y==0 || y==2 || y==4 || x==0 || (x==2 && y>2); }
-        if (d == 7) { return y==0 || x==2; }
-        if (d == 8) { return y==0 || y==2 || y==4 || x==0 || x==2; }
-        if (d == 9) { return y==0 || y==2 || y==4 || x==2 || (x==0 && y<2); }
-    }
-    return false;
-}
-
 @fragment
 fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
     let screen_pos = frag_coord.xy;
@@ -147,67 +1193,147 @@ fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
     let dy = abs(screen_pos.y - center.y); 
     if (dx < half_w && dy < half_h) { color = vec3<f32>(0.1, 0.1, 0.1); }
     
-    // Draw Bar Fill
-    let fill_w = bar_width * u.progress;
+    // Draw Bar Fill (solid red once loading has failed)
+    let failed = u.failed > 0.5;
+    var fill_w = bar_width * u.progress;
+    var fill_color = vec3<f32>(1.0, 1.0, 1.0);
+    if (failed) { fill_w = bar_width; fill_color = vec3<f32>(0.8, 0.1, 0.1); }
     let start_x = center.x - half_w;
     if (screen_pos.x >= start_x && screen_pos.x < start_x + fill_w) {
-        if (dy < half_h) { color = vec3<f32>(1.0, 1.0, 1.0); }
+        if (dy < half_h) { color = fill_color; }
     }
 
-    // Draw Text
-    let scale = 3.0;
-    let char_w = 3.0 * scale; 
-    let char_h = 5.0 * scale;
-    let spacing = 2.0 * scale;
-    
-    let pct = i32(clamp(u.progress * 100.0, 0.0, 100.0));
-    var num_digits = 1;
-    if (pct >= 10) { num_digits = 2; }
-    if (pct >= 100) { num_digits = 3; }
-    
-    let total_chars = 8 + num_digits + 1; // "Loading " + Digits + "%"
-    let total_w = f32(total_chars) * (char_w + spacing) - spacing;
-    
-    let text_start_x = center.x - total_w * 0.5;
-    let text_start_y = center.y - 30.0; 
-    
-    if (screen_pos.y >= text_start_y && screen_pos.y < text_start_y + char_h) {
-        let rel_x = screen_pos.x - text_start_x;
-        if (rel_x >= 0.0 && rel_x < total_w) {
-            let slot = i32(rel_x / (char_w + spacing));
-            let in_x = rel_x % (char_w + spacing);
-            
-            if (in_x < char_w) {
-                let gx = i32(in_x / scale);
-                let gy = i32((screen_pos.y - text_start_y) / scale);
-                var c = 32;
-                
-                // "Loading "
-                if (slot == 0) { c = 76; } 
-                else if (slot == 1) { c = 111; } 
-                else if (slot == 2) { c = 97; } 
-                else if (slot == 3) { c = 100; } 
-                else if (slot == 4) { c = 105; } 
-                else if (slot == 5) { c = 110; } 
-                else if (slot == 6) { c = 103; } 
-                else if (slot == 7) { c = 32; } 
-                // Digits
-                else if (slot < 8 + num_digits) {
-                    let d_idx = slot - 8;
-                    var d = 0;
-                    if (num_digits == 3) { if (d_idx==0) {d=pct/100;} if (d_idx==1) {d=(pct/10)%10;} if (d_idx==2) {d=pct%10;} }
-                    else if (num_digits == 2) { if (d_idx==0) {d=pct/10;} if (d_idx==1) {d=pct%10;} }
-                    else { d=pct; }
-                    c = 48 + d;
-                } 
-                // "%"
-                else { c = 37; } 
-                
-                if (has_pixel(c, gx, gy)) { color = vec3<f32>(1.0, 1.0, 1.0); }
-            }
-        }
-    }
-    
     return vec4<f32>(color, 1.0);
 }
-"#;
\ No newline at end of file
+"#;
+// Flat-colored HUD rects given in pixel coordinates (origin top-left)
+pub const HUD_SHADER: &str = r#"
+struct Screen {
+    size: vec2<f32>,
+    _pad: vec2<f32>,
+};
+@group(0) @binding(0) var<uniform> screen: Screen;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) idx: u32, @location(0) rect_min: vec2<f32>, @location(1) rect_max: vec2<f32>, @location(2) color: vec4<f32>) -> VertexOutput {
+    let corner = vec2<f32>(f32(idx & 1u), f32(idx >> 1u));
+    let pixel = mix(rect_min, rect_max, corner);
+    let ndc = pixel / screen.size * 2.0 - 1.0;
+    var out: VertexOutput;
+    out.position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+// Glyph quads from the text atlas, in pixel coordinates (origin top-left)
+pub const TEXT_SHADER: &str = r#"
+struct Screen {
+    size: vec2<f32>,
+    _pad: vec2<f32>,
+};
+@group(0) @binding(0) var<uniform> screen: Screen;
+@group(0) @binding(1) var atlas: texture_2d<f32>;
+@group(0) @binding(2) var atlas_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) idx: u32,
+    @location(0) rect_min: vec2<f32>, @location(1) rect_max: vec2<f32>,
+    @location(2) uv_min: vec2<f32>, @location(3) uv_max: vec2<f32>,
+    @location(4) color: vec4<f32>,
+) -> VertexOutput {
+    let corner = vec2<f32>(f32(idx & 1u), f32(idx >> 1u));
+    let pixel = mix(rect_min, rect_max, corner);
+    let ndc = pixel / screen.size * 2.0 - 1.0;
+    var out: VertexOutput;
+    out.position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.uv = mix(uv_min, uv_max, corner);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The atlas holds coverage, which is the glyph's alpha.
+    let coverage = textureSample(atlas, atlas_sampler, in.uv).r;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
+"#;
+// Corner minimap: a disc sampling the footprint texture turned so the player's heading points
+// up, with the player arrow in the middle. Pixel coordinates, origin top-left.
+pub const MINIMAP_SHADER: &str = r#"
+struct Minimap {
+    // xy: disc center in pixels, z: radius in pixels.
+    disc: vec4<f32>,
+    screen: vec4<f32>,
+    // xy: the player's texture coordinate, z: texture units per pixel.
+    view: vec4<f32>,
+    // xy: the player's heading on the ground (x, z).
+    heading: vec4<f32>,
+    arrow_color: vec4<f32>,
+};
+@group(0) @binding(0) var<uniform> map: Minimap;
+@group(0) @binding(1) var footprints: texture_2d<f32>;
+@group(0) @binding(2) var footprint_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Pixels from the disc center.
+    @location(0) offset: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) idx: u32) -> VertexOutput {
+    let corner = vec2<f32>(f32(idx & 1u), f32(idx >> 1u)) * 2.0 - 1.0;
+    let offset = corner * map.disc.z;
+    let ndc = (map.disc.xy + offset) / map.screen.xy * 2.0 - 1.0;
+    var out: VertexOutput;
+    out.position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.offset = offset;
+    return out;
+}
+
+// Which side of the edge a -> b the point lies on.
+fn edge(a: vec2<f32>, b: vec2<f32>, p: vec2<f32>) -> f32 {
+    return (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let p = in.offset;
+    let dist = length(p);
+    if (dist > map.disc.z) { discard; }
+    if (dist > map.disc.z - 2.0) { return vec4<f32>(0.9, 0.9, 0.9, 0.9); }
+
+    // Up on screen is the heading, right is the heading turned clockwise seen from above.
+    let forward = map.heading.xy;
+    let right = vec2<f32>(-forward.y, forward.x);
+    let uv = map.view.xy + (right * p.x - forward * p.y) * map.view.z;
+    var color = textureSampleLevel(footprints, footprint_sampler, clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0)), 0.0);
+
+    let tip = vec2<f32>(0.0, -8.0);
+    let left = vec2<f32>(-5.5, 6.0);
+    let right_corner = vec2<f32>(5.5, 6.0);
+    let notch = vec2<f32>(0.0, 3.0);
+    let in_left = edge(tip, left, p) <= 0.0 && edge(left, notch, p) <= 0.0 && edge(notch, tip, p) <= 0.0;
+    let in_right = edge(tip, notch, p) <= 0.0 && edge(notch, right_corner, p) <= 0.0 && edge(right_corner, tip, p) <= 0.0;
+    if (in_left || in_right) { color = map.arrow_color; }
+    return color;
+}
+"#;
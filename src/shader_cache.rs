@@ -0,0 +1,106 @@
+// shader_cache.rs
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicUsize, Ordering}};
+use crate::{post, shader, state::ScenePipelines};
+
+// Compiled modules by a hash of their WGSL, so a source that several pipelines share (or
+// that a reload asks for again) is only compiled once.
+static MODULES: Mutex<Option<HashMap<u64, Arc<wgpu::ShaderModule>>>> = Mutex::new(None);
+// The worker's scene pipelines once it has built them, and whether the game has asked for them;
+// once it has, the worker no longer builds them.
+static SCENE: Mutex<Option<ScenePipelines>> = Mutex::new(None);
+static SCENE_TAKEN: AtomicBool = AtomicBool::new(false);
+// Steps the worker has gone through, a shader each and then the scene pipelines, and how many
+// it started with.
+static DONE: AtomicUsize = AtomicUsize::new(0);
+static TOTAL: AtomicUsize = AtomicUsize::new(0);
+
+// The game's shaders as the renderers create them. The scene-depth ones depend on
+// graphics.anti_aliasing, which only changes on a restart.
+fn game_shaders() -> Vec<(&'static str, String)> {
+    vec![
+        ("Scene Shader", shader::SCENE_SHADER.into()),
+        ("Sky Shader", shader::SKY_SHADER.into()),
+        ("Shadow Shader", shader::SHADOW_SHADER.into()),
+        ("Cluster Shader", shader::CLUSTER_SHADER.into()),
+        ("Water Shader", post::scene_depth_shader(shader::WATER_SHADER)),
+        ("Weather Shader", post::scene_depth_shader(shader::WEATHER_SHADER)),
+        ("SSAO", post::scene_depth_shader(shader::SSAO_SHADER)),
+        ("Composite", post::scene_depth_shader(shader::COMPOSITE_SHADER)),
+        ("Exposure", shader::EXPOSURE_SHADER.into()),
+        ("Bloom", shader::BLOOM_SHADER.into()),
+        ("TAA", shader::TAA_SHADER.into()),
+        ("Occlusion Shader", shader::OCCLUSION_SHADER.into()),
+        ("Highlight Shader", shader::HIGHLIGHT_SHADER.into()),
+        ("Streaming Shader", shader::STREAMING_SHADER.into()),
+        ("Debug Line Shader", shader::DEBUG_LINE_SHADER.into()),
+        ("UI Shader", shader::UI_SHADER.into()),
+        ("Vignette Shader", shader::VIGNETTE_SHADER.into()),
+        ("HUD Shader", shader::HUD_SHADER.into()),
+        ("Minimap Shader", shader::MINIMAP_SHADER.into()),
+    ]
+}
+
+fn key(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn compile(device: &wgpu::Device, label: &str, source: &str) -> Arc<wgpu::ShaderModule> {
+    Arc::new(device.create_shader_module(wgpu::ShaderModuleDescriptor { label: Some(label), source: wgpu::ShaderSource::Wgsl(source.into()) }))
+}
+
+// Compiles the game's shaders and builds the scene pipelines on a worker thread while the map
+// loads, so building the renderers afterwards doesn't stall on them.
+pub fn precompile(device: Arc<wgpu::Device>) {
+    let shaders = game_shaders();
+    TOTAL.store(shaders.len() + 1, Ordering::Relaxed);
+    let spawned = std::thread::Builder::new().name("shader compiler".into()).spawn(move || {
+        let started = std::time::Instant::now();
+        for (label, source) in shaders {
+            let key = key(&source);
+            // Skipped when a renderer already needed it and compiled it itself.
+            let cached = MODULES.lock().is_ok_and(|modules| modules.as_ref().is_some_and(|m| m.contains_key(&key)));
+            if !cached {
+                let module = compile(&device, label, &source);
+                if let Ok(mut modules) = MODULES.lock() { modules.get_or_insert_with(HashMap::new).entry(key).or_insert(module); }
+            }
+            DONE.fetch_add(1, Ordering::Relaxed);
+        }
+        if !SCENE_TAKEN.load(Ordering::Acquire) {
+            let pipelines = ScenePipelines::new(&device);
+            if let Ok(mut scene) = SCENE.lock() { *scene = Some(pipelines); }
+        }
+        DONE.fetch_add(1, Ordering::Relaxed);
+        log::info!("Compiled {} shaders and the scene pipelines in {:.2} s", TOTAL.load(Ordering::Relaxed) - 1, started.elapsed().as_secs_f32());
+    });
+    if let Err(e) = spawned {
+        log::warn!("Couldn't start the shader compiler thread ({}); shaders compile as they're needed", e);
+        TOTAL.store(0, Ordering::Relaxed);
+    }
+}
+
+// (compiled, total) while the worker is still going.
+pub fn progress() -> Option<(usize, usize)> {
+    let (done, total) = (DONE.load(Ordering::Relaxed), TOTAL.load(Ordering::Relaxed));
+    (done < total).then_some((done, total))
+}
+
+// The worker's scene pipelines if they're ready, otherwise built here.
+pub fn scene_pipelines(device: &wgpu::Device) -> ScenePipelines {
+    SCENE_TAKEN.store(true, Ordering::Release);
+    SCENE.lock().ok().and_then(|mut scene| scene.take()).unwrap_or_else(|| ScenePipelines::new(device))
+}
+
+// The module for `source`: the worker's if it got there first, otherwise compiled here.
+pub fn module(device: &wgpu::Device, label: &str, source: &str) -> Arc<wgpu::ShaderModule> {
+    let key = key(source);
+    if let Some(module) = MODULES.lock().ok().and_then(|modules| modules.as_ref()?.get(&key).cloned()) { return module; }
+    let module = compile(device, label, source);
+    match MODULES.lock() {
+        Ok(mut modules) => modules.get_or_insert_with(HashMap::new).entry(key).or_insert(module).clone(),
+        Err(_) => module,
+    }
+}
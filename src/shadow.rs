@@ -0,0 +1,282 @@
+// shadow.rs
+use glam::{Mat4, Vec3, Vec4};
+use wgpu::util::DeviceExt;
+use crate::{camera::Camera, config, config_file, shader, shader_cache, vertex::PackedVertex, world::World};
+
+// Must match the array length of ShadowUniform in SCENE_SHADER.
+pub const CASCADES: usize = 3;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniform {
+    light_view_proj: [[[f32; 4]; 4]; CASCADES],
+    // xyz: far distance of each cascade, w: 1 when shadows are drawn this frame.
+    splits: [f32; 4],
+    // x: first texture layer of this view, y: texel size in UV units.
+    params: [f32; 4],
+}
+
+// Depth target and light matrix of one cascade of one view.
+struct CascadePass {
+    target: wgpu::TextureView,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    // Light-space bounding sphere in world xz, for culling chunks.
+    center: glam::Vec2,
+    radius: f32,
+}
+
+// Shadow cascades of one player's viewport, bound at group 2 of the scene pipeline.
+struct ShadowView {
+    uniform: ShadowUniform,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    cascades: Vec<CascadePass>,
+}
+
+// Cascaded sun shadow maps. Cascades are nested (each starts at the near plane) so picking
+// one by distance from the camera is always conservative.
+pub struct ShadowMaps {
+    pub enabled: bool,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    cascade_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pipeline: wgpu::RenderPipeline,
+    views: Vec<ShadowView>,
+    // Texels per side of each cascade.
+    size: u32,
+    // Chunk meshes drawn into the cascades this frame, and their world origins by instance.
+    casters: Vec<(i32, i32)>,
+    origin_buffer: wgpu::Buffer,
+    origin_capacity: usize,
+}
+
+impl ShadowMaps {
+    // What the scene shader samples the cascades through.
+    pub fn layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry { binding: 0, visibility: wgpu::ShaderStages::FRAGMENT, ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None },
+                wgpu::BindGroupLayoutEntry { binding: 1, visibility: wgpu::ShaderStages::FRAGMENT, ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Depth, view_dimension: wgpu::TextureViewDimension::D2Array, multisampled: false }, count: None },
+                wgpu::BindGroupLayoutEntry { binding: 2, visibility: wgpu::ShaderStages::FRAGMENT, ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison), count: None },
+            ], label: Some("Shadow Layout"),
+        })
+    }
+
+    pub fn new(device: &wgpu::Device, view_count: usize) -> Self {
+        let size = config_file::get().graphics.shadow_map_size;
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"), mag_filter: wgpu::FilterMode::Linear, min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual), ..Default::default()
+        });
+
+        let bind_group_layout = Self::layout(device);
+        let cascade_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry { binding: 0, visibility: wgpu::ShaderStages::VERTEX, ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None }],
+            label: Some("Cascade Layout"),
+        });
+        let views = Self::create_views(device, size, view_count, &bind_group_layout, &cascade_layout, &sampler);
+
+        let shader = shader_cache::module(device, "Shadow Shader", shader::SHADOW_SHADER);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[&cascade_layout], push_constant_ranges: &[] });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"), layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader, entry_point: "vs_main",
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<PackedVertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &[wgpu::VertexAttribute { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x3 }],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: 16, step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &[wgpu::VertexAttribute { offset: 0, shader_location: 1, format: wgpu::VertexFormat::Float32x3 }],
+                    },
+                ],
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, cull_mode: None, ..Default::default() },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float, depth_write_enabled: true, depth_compare: wgpu::CompareFunction::LessEqual, stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState { constant: 2, slope_scale: 2.0, clamp: 0.0 },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let origin_capacity = 1024;
+        Self {
+            enabled: config_file::get().graphics.shadows, bind_group_layout, cascade_layout, sampler, pipeline, views, size,
+            casters: Vec::new(), origin_buffer: Self::create_origin_buffer(device, origin_capacity), origin_capacity,
+        }
+    }
+
+    // Depth textures, uniforms and bind groups of every view's cascades at `size` texels.
+    fn create_views(device: &wgpu::Device, size: u32, view_count: usize, layout: &wgpu::BindGroupLayout, cascade_layout: &wgpu::BindGroupLayout, sampler: &wgpu::Sampler) -> Vec<ShadowView> {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Maps"), size: wgpu::Extent3d { width: size, height: size, depth_or_array_layers: (view_count * CASCADES) as u32 },
+            mip_level_count: 1, sample_count: 1, dimension: wgpu::TextureDimension::D2, format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING, view_formats: &[],
+        });
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor { dimension: Some(wgpu::TextureViewDimension::D2Array), ..Default::default() });
+        (0..view_count).map(|v| {
+            let uniform = ShadowUniform {
+                light_view_proj: [Mat4::IDENTITY.to_cols_array_2d(); CASCADES],
+                splits: [0.0; 4], params: [(v * CASCADES) as f32, 1.0 / size as f32, 0.0, 0.0],
+            };
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Shadow Uniform"), contents: bytemuck::cast_slice(&[uniform]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&array_view) },
+                    wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(sampler) },
+                ], label: None,
+            });
+            let cascades = (0..CASCADES).map(|c| {
+                let target = texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2), base_array_layer: (v * CASCADES + c) as u32, array_layer_count: Some(1), ..Default::default()
+                });
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Cascade Uniform"), contents: bytemuck::cast_slice(&[Mat4::IDENTITY.to_cols_array_2d()]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: cascade_layout, entries: &[wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }], label: None,
+                });
+                CascadePass { target, buffer, bind_group, center: glam::Vec2::ZERO, radius: 0.0 }
+            }).collect();
+            ShadowView { uniform, buffer, bind_group, cascades }
+        }).collect()
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    // Recreates the cascades at `size` texels; the GPU budget shrinks them when memory runs low.
+    pub fn resize(&mut self, device: &wgpu::Device, size: u32) {
+        self.views = Self::create_views(device, size, self.views.len(), &self.bind_group_layout, &self.cascade_layout, &self.sampler);
+        self.size = size;
+    }
+
+    fn create_origin_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Caster Origins"), size: capacity as u64 * 16,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+        })
+    }
+
+    pub fn gpu_bytes(&self) -> u64 {
+        self.size as u64 * self.size as u64 * 4 * (self.views.len() * CASCADES) as u64
+    }
+
+    pub fn bind_group(&self, view: usize) -> &wgpu::BindGroup {
+        &self.views[view].bind_group
+    }
+
+    fn is_drawn(&self, sun_dir: Vec3) -> bool {
+        self.enabled && sun_dir.y > 0.02
+    }
+
+    // Refits the cascades of one view to its camera and the current sun direction.
+    pub fn update(&mut self, queue: &wgpu::Queue, view: usize, camera: &Camera, sun_dir: Vec3) {
+        let drawn = self.is_drawn(sun_dir);
+        let shadow_view = &mut self.views[view];
+        shadow_view.uniform.splits = [config::SHADOW_CASCADE_SPLITS[0], config::SHADOW_CASCADE_SPLITS[1], config::SHADOW_CASCADE_SPLITS[2], if drawn { 1.0 } else { 0.0 }];
+        if drawn {
+            let eye = camera.eye.as_vec3();
+            let forward = camera.forward().as_vec3();
+            let right = forward.cross(Vec3::Y).normalize_or_zero();
+            let up = right.cross(forward);
+            let tan_half = (camera.fov_y.to_radians() * 0.5).tan();
+
+            for (c, cascade) in shadow_view.cascades.iter_mut().enumerate() {
+                let far = config::SHADOW_CASCADE_SPLITS[c];
+                let mut corners = Vec::with_capacity(8);
+                for d in [config::Z_NEAR, far] {
+                    let (h, w) = (d * tan_half, d * tan_half * camera.aspect);
+                    for (sx, sy) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+                        corners.push(eye + forward * d + right * (w * sx) + up * (h * sy));
+                    }
+                }
+                let center = corners.iter().copied().sum::<Vec3>() / corners.len() as f32;
+                // Rounded up so the projection size, and with it the texel grid, stays fixed as the camera turns.
+                let radius = corners.iter().map(|p| p.distance(center)).fold(0.0, f32::max).ceil();
+
+                let light_up = if sun_dir.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+                let depth = radius + config::SHADOW_CASTER_DEPTH;
+                let light_view = Mat4::look_at_rh(center + sun_dir * depth, center, light_up);
+                let mut light_proj = Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, depth + radius);
+
+                // Snap the origin to whole texels so shadow edges don't shimmer while moving.
+                let texels = self.size as f32 * 0.5;
+                let origin = (light_proj * light_view * Vec4::new(0.0, 0.0, 0.0, 1.0)) * texels;
+                let offset = (origin.truncate().truncate().round() - origin.truncate().truncate()) / texels;
+                light_proj.w_axis.x += offset.x;
+                light_proj.w_axis.y += offset.y;
+
+                let matrix = (light_proj * light_view).to_cols_array_2d();
+                shadow_view.uniform.light_view_proj[c] = matrix;
+                cascade.center = glam::Vec2::new(center.x, center.z);
+                cascade.radius = radius;
+                queue.write_buffer(&cascade.buffer, 0, bytemuck::cast_slice(&[matrix]));
+            }
+        }
+        queue.write_buffer(&shadow_view.buffer, 0, bytemuck::cast_slice(&[shadow_view.uniform]));
+    }
+
+    // Lists the chunk meshes that can cast shadows and uploads their origins; call once per
+    // frame before `render`.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, world: &World, sun_dir: Vec3) {
+        self.casters.clear();
+        if !self.is_drawn(sun_dir) { return; }
+        self.casters.extend(world.chunks.iter().filter(|(_, c)| c.mesh.is_some()).map(|(coord, _)| *coord));
+        if self.casters.is_empty() { return; }
+        if self.casters.len() > self.origin_capacity {
+            self.origin_capacity = self.casters.len().next_power_of_two();
+            self.origin_buffer = Self::create_origin_buffer(device, self.origin_capacity);
+        }
+        let origins: Vec<[f32; 4]> = self.casters.iter().map(|coord| world.chunks[coord].origin().as_vec3().extend(0.0).to_array()).collect();
+        queue.write_buffer(&self.origin_buffer, 0, bytemuck::cast_slice(&origins));
+    }
+
+    // Renders the depth cascades of one view; call before the scene pass. Returns the number
+    // of draw calls.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, view: usize, world: &World, sun_dir: Vec3) -> usize {
+        if !self.is_drawn(sun_dir) || self.casters.is_empty() { return 0; }
+        let mut draws = 0;
+        let chunk_radius = (config_file::chunk_size() * config_file::chunk_size() * 2.0).sqrt() * 0.5;
+        for cascade in &self.views[view].cascades {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"), color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &cascade.target,
+                    depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: wgpu::StoreOp::Store }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None, occlusion_query_set: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &cascade.bind_group, &[]);
+            pass.set_vertex_buffer(1, self.origin_buffer.slice(..));
+            for (instance, coord) in self.casters.iter().enumerate() {
+                let chunk = &world.chunks[coord];
+                let Some(mesh) = &chunk.mesh else { continue };
+                // Casters can sit outside the sphere toward the sun; allow for tall buildings.
+                let reach = cascade.radius + chunk_radius + config::SHADOW_CASTER_DEPTH;
+                if chunk.center().distance_squared(cascade.center) > reach * reach { continue; }
+                let (page, first_index, index_count, base_vertex) = mesh.draw(0..mesh.index_count);
+                let page = world.meshes.page(page);
+                pass.set_vertex_buffer(0, page.vertex_buffer.slice(..));
+                pass.set_index_buffer(page.index_buffer.slice(..), page.index_format);
+                let instance = instance as u32;
+                pass.draw_indexed(first_index..first_index + index_count, base_vertex, instance..instance + 1);
+                draws += 1;
+            }
+        }
+        draws
+    }
+}
@@ -0,0 +1,69 @@
+// spawn.rs
+use glam::{DVec3, Vec2};
+use crate::{config, config_file, map_loader, poi::PoiIndex, world::World};
+
+// Where the players start: "lat,lon" or the name of a place in the POI index. Set with
+// `--spawn`, a map package's `spawn` override or the config's map.spawn, in that order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpawnPoint {
+    Origin,
+    Coordinates { lat: f64, lon: f64 },
+    Place(String),
+}
+
+impl SpawnPoint {
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+        if value.is_empty() { return Self::Origin; }
+        if let Some((lat, lon)) = value.split_once(',')
+            && let (Ok(lat), Ok(lon)) = (lat.trim().parse::<f64>(), lon.trim().parse::<f64>())
+            && (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) {
+            return Self::Coordinates { lat, lon };
+        }
+        Self::Place(value.to_string())
+    }
+
+    // Local position on the map with its origin at `origin`, or None (after logging why)
+    // if it's unknown or outside the world.
+    pub fn resolve(&self, origin: (f64, f64), places: Option<&PoiIndex>) -> Option<Vec2> {
+        let position = match self {
+            Self::Origin => return None,
+            Self::Coordinates { lat, lon } => Vec2::from(map_loader::coords_to_local(*lat, *lon, origin)),
+            Self::Place(name) => match places.and_then(|p| p.find(name)) {
+                Some(p) => p,
+                None => {
+                    log::warn!("Spawn place '{}' not found in the map's points of interest", name);
+                    return None;
+                }
+            },
+        };
+        if position.abs().max_element() >= config_file::world_size() / 2.0 {
+            log::warn!("Spawn point {:?} lies outside the world", self);
+            return None;
+        }
+        Some(position)
+    }
+}
+
+// Eye position for a player spawning near `target`: the target itself on the street, or if
+// it's inside a building, the nearest free spot on rings around it. With none in reach the
+// player stands on the roof.
+pub fn find_spawn_eye(world: &World, target: Vec2) -> DVec3 {
+    let is_free = |p: Vec2| {
+        let eye = DVec3::new(p.x as f64, config::EYE_HEIGHT, p.y as f64);
+        world.roof_at(p.x, p.y, f32::INFINITY).is_none() && world.check_collision(eye, config::EYE_HEIGHT).is_none()
+    };
+    let rings = (config::SPAWN_SEARCH_RADIUS / config::SPAWN_SEARCH_STEP) as usize;
+    let mut candidates = std::iter::once(target).chain((1..=rings).flat_map(|ring| {
+        let radius = ring as f32 * config::SPAWN_SEARCH_STEP;
+        let steps = (std::f32::consts::TAU * radius / config::SPAWN_SEARCH_STEP).ceil() as usize;
+        (0..steps).map(move |i| target + Vec2::from_angle(i as f32 / steps as f32 * std::f32::consts::TAU) * radius)
+    }));
+    match candidates.find(|p| is_free(*p)) {
+        Some(p) => DVec3::new(p.x as f64, config::EYE_HEIGHT, p.y as f64),
+        None => {
+            let roof = world.roof_at(target.x, target.y, f32::INFINITY).unwrap_or(0.0);
+            DVec3::new(target.x as f64, roof as f64 + config::EYE_HEIGHT, target.y as f64)
+        }
+    }
+}
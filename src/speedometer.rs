@@ -0,0 +1,62 @@
+// speedometer.rs
+use glam::{DVec2, DVec3};
+use crate::{config, hud::HudRenderer, text::TextRenderer, world::World};
+
+// Speed and altitude readout in the bottom-left corner of each view (H toggles). Velocity is
+// measured from how far the camera moved, so it's right in every movement mode and in a car.
+pub struct Speedometer {
+    pub visible: bool,
+    // Per view: the eye last frame, and the smoothed velocity.
+    last_eyes: Vec<Option<DVec3>>,
+    velocities: Vec<DVec3>,
+}
+
+impl Speedometer {
+    pub fn new(view_count: usize) -> Self {
+        Self { visible: config::SPEEDOMETER, last_eyes: vec![None; view_count], velocities: vec![DVec3::ZERO; view_count] }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    // Call once per frame with the view's (uninterpolated) eye. Jumps too large to be movement,
+    // like teleports and respawns, restart the measurement.
+    pub fn update(&mut self, view: usize, eye: DVec3, dt: f64) {
+        let last = self.last_eyes[view].replace(eye);
+        let Some(last) = last else { return };
+        let moved = eye - last;
+        if moved.length() > config::SPEEDOMETER_MAX_JUMP {
+            self.velocities[view] = DVec3::ZERO;
+            return;
+        }
+        let ease = 1.0 - (-dt / config::SPEEDOMETER_SMOOTHING).exp();
+        self.velocities[view] = self.velocities[view].lerp(moved / dt, ease);
+    }
+
+    // Horizontal speed in m/s and km/h, climb rate, and height of the feet over whatever is
+    // below them (the street when nothing is hit).
+    pub fn draw(&self, hud: &mut HudRenderer, text: &mut TextRenderer, viewport: [f32; 4], view: usize, eye: DVec3, world: &World) {
+        let [x, y, _, h] = viewport;
+        let velocity = self.velocities[view];
+        let speed = DVec2::new(velocity.x, velocity.z).length();
+        let below = world.raycast(eye.as_vec3(), glam::Vec3::NEG_Y, eye.y as f32).map_or(eye.y, |hit| hit.distance as f64);
+        let altitude = (below - config::EYE_HEIGHT).max(0.0);
+        let climb = if velocity.y.abs() < 0.05 { 0.0 } else { velocity.y };
+
+        let lines = [
+            format!("{:.1} m/s  {:.0} km/h", speed, speed * 3.6),
+            format!("{}{:.1} m/s vertical", if climb > 0.0 { "+" } else { "" }, climb),
+            format!("{:.0} m above ground", altitude),
+        ];
+        let (size, pad, line) = (16.0, 8.0, 20.0);
+        let width = lines.iter().map(|l| text.measure(l, size)).fold(0.0, f32::max) + pad * 2.0;
+        // Above the movement mode line.
+        let (x0, y1) = (x + 12.0, y + h - 40.0);
+        let y0 = y1 - lines.len() as f32 * line - pad * 2.0 + (line - size);
+        hud.rect([x0, y0], [x0 + width, y1], [0.0, 0.0, 0.0, 0.45]);
+        for (i, l) in lines.iter().enumerate() {
+            text.text(l, [x0 + pad, y0 + pad + i as f32 * line], size, [1.0, 1.0, 1.0, 0.9]);
+        }
+    }
+}
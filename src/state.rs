@@ -1,16 +1,20 @@
 use winit::{window::Window, event::*};
 use wgpu::util::DeviceExt;
-use std::time::Instant;
-use crate::{camera::*, world::*, shader, config, vertex::Vertex};
+use std::{collections::{HashMap, HashSet}, time::Instant};
+use crate::{benchmark::{self, Benchmark}, bindings::BindingsFile, camera::*, capture::{self, CaptureEvent, CaptureStart, InputCapture}, config_file::ConfigWatcher, chunk_fade::ChunkFades, cinematic::Cinematic, compass, console::{Command, Console}, crosshair::Crosshairs, labels, map_loader, poi::PoiIndex, debug::{DebugLines, DebugMode}, dynamic_mesh::DynamicMeshes, facade::FacadeTextures, game_mode::{GameMode, ModeKind}, photo::PhotoMode, replay::Replay, settings::SettingsMenu, speedometer::Speedometer, stats_overlay::StatsOverlay, streaming::StreamingIndicator, gpu_budget::{Allocation, Degradation, GpuBudget}, upload::ChunkUploads, mesh_cache::MeshCache, profiler::{self, GpuSpan, Profiler}, highlight::BuildingHighlight, hud::HudRenderer, minimap::Minimap, notifications::Notifications, pause::{PauseAction, PauseMenu}, text::TextRenderer, lighting::ClusteredLights, mesh_arena::IndirectDraws, occlusion::OcclusionCuller, player::{MovementMode, Player}, post::{self, PostProcess}, render_scale::RenderScale, shadow::ShadowMaps, spawn::SpawnPoint, time_of_day::TimeOfDay, water::WaterRenderer, vehicle::Car, vignette::DamageVignette, waypoints::Waypoints, weather::{Weather, WeatherParticles}, world::*, shader, shader_cache, config, config_file, vertex::PackedVertex};
 
 pub struct GpuContext {
     pub surface: wgpu::Surface<'static>,
-    pub device: wgpu::Device,
+    // Shared with the shader compiler thread.
+    pub device: std::sync::Arc<wgpu::Device>,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
     pub msaa_texture: wgpu::TextureView,
     pub depth_texture: wgpu::TextureView,
+    pub adapter_info: wgpu::AdapterInfo,
+    // What the surface supports, for switching present modes at runtime.
+    pub present_modes: Vec<wgpu::PresentMode>,
 }
 
 impl GpuContext {
@@ -24,29 +28,35 @@ impl GpuContext {
             force_fallback_adapter: false,
         }).await.unwrap();
 
-        let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor::default(), None).await.unwrap();
+        // Multi-draw batches chunk draws where available (it needs first_instance for the
+        // per-draw fades); IndirectDraws falls back without it.
+        // Line polygons are only needed for the wireframe debug view.
+        let required_features = adapter.features() & (wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE | wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::TIMESTAMP_QUERY);
+        let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor { required_features, ..Default::default() }, None).await.unwrap();
         let config = surface.get_default_config(&adapter, size.width, size.height).unwrap();
         let mut final_config = config.clone();
         
         let caps = surface.get_capabilities(&adapter);
-        if caps.present_modes.contains(&wgpu::PresentMode::Mailbox) {
-            final_config.present_mode = wgpu::PresentMode::Mailbox;
-        } else {
-            final_config.present_mode = wgpu::PresentMode::Fifo;
-        }
+        final_config.present_mode = crate::display::pick_present_mode(config_file::get().graphics.present_mode, &caps.present_modes);
         surface.configure(&device, &final_config);
 
         let msaa_texture = Self::create_msaa(&device, &final_config);
-        let depth_texture = Self::create_depth(&device, &final_config);
+        let depth_texture = Self::create_depth(&device, final_config.width, final_config.height);
+
+        let adapter_info = adapter.get_info();
+        Self { surface, device: std::sync::Arc::new(device), queue, config: final_config, size, msaa_texture, depth_texture, adapter_info, present_modes: caps.present_modes }
+    }
 
-        Self { surface, device, queue, config: final_config, size, msaa_texture, depth_texture }
+    // Size of the MSAA color target (4 samples) and the scene depth, 4 bytes per sample each.
+    pub fn render_target_bytes(&self) -> u64 {
+        self.config.width as u64 * self.config.height as u64 * 4 * (4 + post::scene_samples() as u64)
     }
     
-    fn create_depth(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::TextureView {
+    fn create_depth(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
         let desc = wgpu::TextureDescriptor {
-            label: Some("Depth"), size: wgpu::Extent3d { width: config.width, height: config.height, depth_or_array_layers: 1 },
-            mip_level_count: 1, sample_count: 4, dimension: wgpu::TextureDimension::D2, format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT, view_formats: &[],
+            label: Some("Depth"), size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1, sample_count: post::scene_samples(), dimension: wgpu::TextureDimension::D2, format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING, view_formats: &[],
         };
         device.create_texture(&desc).create_view(&wgpu::TextureViewDescriptor::default())
     }
@@ -67,284 +77,1864 @@ impl GpuContext {
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
             self.msaa_texture = Self::create_msaa(&self.device, &self.config);
-            self.depth_texture = Self::create_depth(&self.device, &self.config);
+            self.depth_texture = Self::create_depth(&self.device, self.config.width, self.config.height);
         }
     }
+
+    // Reconfigures the surface for `preference` if that picks a different mode. Returns whether it did.
+    pub fn set_present_mode(&mut self, preference: crate::display::PresentPreference) -> bool {
+        let mode = crate::display::pick_present_mode(preference, &self.present_modes);
+        if mode == self.config.present_mode { return false; }
+        self.config.present_mode = mode;
+        self.surface.configure(&self.device, &self.config);
+        true
+    }
+
+    // Recreates the scene depth at a size other than the surface's (dynamic resolution).
+    pub fn resize_depth(&mut self, width: u32, height: u32) {
+        self.depth_texture = Self::create_depth(&self.device, width, height);
+    }
+}
+
+// Screen fade around moving the players far away: out to black, wait for the destination
+// chunk, then back in.
+struct Teleport {
+    target: glam::Vec2,
+    // 0 clear, 1 black.
+    fade: f32,
+    arrived: bool,
+}
+
+// Camera uniform of one player; each split-screen viewport binds its own.
+struct PlayerView {
+    uniform: CameraUniform,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    // Set before `write` while the view's camera is below a water surface.
+    underwater: bool,
+}
+
+impl PlayerView {
+    fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, camera: &Camera, viewport: [f32; 4]) -> Self {
+        let uniform = CameraUniform {
+            view_proj: camera.build_view_projection_matrix().to_cols_array_2d(), screen_size: [viewport[2], viewport[3]],
+            fog_dist: [config_file::get().graphics.fog_start, config_file::get().graphics.fog_end], camera_pos: [camera.eye.x as f32, camera.eye.y as f32, camera.eye.z as f32, 0.0],
+            sun_dir: [0.0, 1.0, 0.0, 1.0], sky_color: [0.0; 4], zenith_color: [0.0; 4],
+            inv_view_proj: glam::Mat4::IDENTITY.to_cols_array_2d(), viewport,
+            fog: [config::FOG_DENSITY, config::FOG_HEIGHT_FALLOFF, config::FOG_BASE_HEIGHT, if config::AERIAL_PERSPECTIVE { config::AERIAL_DENSITY } else { 0.0 }],
+            relative_view_proj: camera.build_relative_view_projection_matrix().to_cols_array_2d(),
+        };
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"), contents: bytemuck::cast_slice(&[uniform]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout, entries: &[wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }], label: None,
+        });
+        Self { uniform, buffer, bind_group, underwater: false }
+    }
+
+    // `jitter` shifts the projection by a fraction of a pixel for TAA.
+    fn write(&mut self, queue: &wgpu::Queue, camera: &Camera, viewport: [f32; 4], time: &TimeOfDay, weather: &Weather, jitter: glam::Vec2) {
+        let offset = glam::Vec3::new(jitter.x * 2.0 / viewport[2], -jitter.y * 2.0 / viewport[3], 0.0);
+        let view_proj = glam::Mat4::from_translation(offset) * camera.build_view_projection_matrix();
+        self.uniform.view_proj = view_proj.to_cols_array_2d();
+        self.uniform.inv_view_proj = view_proj.inverse().to_cols_array_2d();
+        self.uniform.relative_view_proj = (glam::Mat4::from_translation(offset) * camera.build_relative_view_projection_matrix()).to_cols_array_2d();
+        let murk = if self.underwater { config::UNDERWATER_FOG_DENSITY } else { 0.0 };
+        self.uniform.camera_pos = [camera.eye.x as f32, camera.eye.y as f32, camera.eye.z as f32, murk];
+        self.uniform.screen_size = [viewport[2], viewport[3]];
+        self.uniform.viewport = viewport;
+        self.uniform.sun_dir = time.sun_direction().extend(time.daylight()).to_array();
+        let [r, g, b] = time.horizon_color();
+        self.uniform.sky_color = [r, g, b, weather.wetness];
+        let [r, g, b] = time.zenith_color();
+        self.uniform.zenith_color = [r, g, b, 0.0];
+        let fog = weather.fog_scale() * weather.fog_multiplier;
+        self.uniform.fog[0] = config::FOG_DENSITY * fog;
+        self.uniform.fog[3] = if config::AERIAL_PERSPECTIVE { config::AERIAL_DENSITY * fog } else { 0.0 };
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+}
+
+// The camera uniform every view binds at group 0.
+fn camera_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0, visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None,
+        }], label: None,
+    })
+}
+
+// The chunk and sky pipelines, the heaviest to build. They only need the device, so the shader
+// worker builds them during loading; see shader_cache::scene_pipelines.
+pub struct ScenePipelines {
+    pub render: wgpu::RenderPipeline,
+    pub normals: wgpu::RenderPipeline,
+    // Only where the adapter draws line polygons.
+    pub wireframe: Option<wgpu::RenderPipeline>,
+    pub sky: wgpu::RenderPipeline,
+}
+
+impl ScenePipelines {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader_module = shader_cache::module(device, "Scene Shader", shader::SCENE_SHADER);
+        // Layouts equal to the ones the renderers bind against, which wgpu treats as the same.
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&camera_layout(device), &FacadeTextures::layout(device), &ShadowMaps::layout(device), &ClusteredLights::layout(device)], push_constant_ranges: &[],
+        });
+
+        let render = scene_pipeline(device, "Render Pipeline", &layout, &shader_module, "fs_main", wgpu::PolygonMode::Fill);
+        let normals = scene_pipeline(device, "Normals Pipeline", &layout, &shader_module, "fs_normals", wgpu::PolygonMode::Fill);
+        let wireframe = device.features().contains(wgpu::Features::POLYGON_MODE_LINE)
+            .then(|| scene_pipeline(device, "Wireframe Pipeline", &layout, &shader_module, "fs_wireframe", wgpu::PolygonMode::Line));
+
+        let sky_shader = shader_cache::module(device, "Sky Shader", shader::SKY_SHADER);
+        let sky = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky Pipeline"), layout: Some(&layout),
+            vertex: wgpu::VertexState { module: &sky_shader, entry_point: "vs_main", buffers: &[] },
+            fragment: Some(wgpu::FragmentState {
+                module: &sky_shader, entry_point: "fs_main",
+                targets: &SCENE_TARGETS,
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float, depth_write_enabled: false, depth_compare: wgpu::CompareFunction::Always, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState { count: post::scene_samples(), mask: !0, alpha_to_coverage_enabled: false },
+            multiview: None,
+        });
+        Self { render, normals, wireframe, sky }
+    }
+}
+
+// Chunk mesh pipeline drawing through the scene shader's `fragment_entry`.
+fn scene_pipeline(
+    device: &wgpu::Device, label: &str, layout: &wgpu::PipelineLayout, module: &wgpu::ShaderModule, fragment_entry: &str, polygon_mode: wgpu::PolygonMode,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label), layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module, entry_point: "vs_main",
+            buffers: &[
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<PackedVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &[
+                        wgpu::VertexAttribute { offset: 0,  shader_location: 0, format: wgpu::VertexFormat::Float32x3 },
+                        wgpu::VertexAttribute { offset: 12, shader_location: 1, format: wgpu::VertexFormat::Snorm8x4 },
+                        wgpu::VertexAttribute { offset: 16, shader_location: 2, format: wgpu::VertexFormat::Unorm8x4 },
+                        wgpu::VertexAttribute { offset: 20, shader_location: 3, format: wgpu::VertexFormat::Float16x4 },
+                    ],
+                },
+                // Per draw: mesh origin relative to the camera and fade, from ChunkFades.
+                wgpu::VertexBufferLayout {
+                    array_stride: 16, step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &[wgpu::VertexAttribute { offset: 0, shader_location: 4, format: wgpu::VertexFormat::Float32x4 }],
+                },
+            ],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module, entry_point: fragment_entry,
+            targets: &SCENE_TARGETS,
+        }),
+        primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, cull_mode: None, polygon_mode, ..Default::default() },
+        depth_stencil: Some(wgpu::DepthStencilState { 
+            format: wgpu::TextureFormat::Depth32Float, depth_write_enabled: true, depth_compare: wgpu::CompareFunction::Less, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default() 
+        }),
+        multisample: wgpu::MultisampleState { count: post::scene_samples(), mask: !0, alpha_to_coverage_enabled: false },
+        multiview: None,
+    })
+}
+
+const SCENE_TARGETS: [Option<wgpu::ColorTargetState>; 2] = [
+    Some(wgpu::ColorTargetState { format: post::HDR_FORMAT, blend: Some(wgpu::BlendState::REPLACE), write_mask: wgpu::ColorWrites::ALL }),
+    Some(wgpu::ColorTargetState { format: post::NORMAL_FORMAT, blend: Some(wgpu::BlendState::REPLACE), write_mask: wgpu::ColorWrites::ALL }),
+];
+
+// Chunk meshes drawn and culled in the last frame, summed over viewports.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RenderStats {
+    pub drawn_chunks: usize,
+    pub culled_chunks: usize,
+    // Drawn chunks that used their LOD mesh.
+    pub lod_chunks: usize,
+    // In the frustum but hidden behind nearer geometry last frame.
+    pub occluded_chunks: usize,
+    // Chunk, water and shadow caster draws.
+    pub draw_calls: usize,
+    pub triangles: usize,
 }
 
 pub struct GameState {
     pub ctx: GpuContext, 
     render_pipeline: wgpu::RenderPipeline,
-    ui_pipeline: wgpu::RenderPipeline,
+    normals_pipeline: wgpu::RenderPipeline,
+    // None when the adapter can't rasterize polygons as lines.
+    wireframe_pipeline: Option<wgpu::RenderPipeline>,
+    debug_lines: DebugLines,
+    // Elevator platforms and cars.
+    dynamic_meshes: DynamicMeshes,
+    pub debug_mode: DebugMode,
+    sky_pipeline: wgpu::RenderPipeline,
+    crosshairs: Crosshairs,
+    vignette: DamageVignette,
+    facades: FacadeTextures,
+    shadows: ShadowMaps,
+    lights: ClusteredLights,
+    post: PostProcess,
+    render_scale: RenderScale,
+    occlusion: OcclusionCuller,
+    chunk_draws: IndirectDraws,
+    chunk_fades: ChunkFades,
+    water: WaterRenderer,
+    highlight: BuildingHighlight,
+    pub streaming: StreamingIndicator,
+    config_watcher: ConfigWatcher,
+    weather_particles: WeatherParticles,
+    hud: HudRenderer,
+    text: TextRenderer,
+    minimap: Minimap,
     pub world: World,
-    pub camera: Camera,
-    camera_controller: CameraController,
-    camera_uniform: CameraUniform,
-    camera_buffer: wgpu::Buffer,
-    camera_bind_group: wgpu::BindGroup,
+    pub time_of_day: TimeOfDay,
+    pub weather: Weather,
+    budget: GpuBudget,
+    pub uploads: ChunkUploads,
+    mesh_cache: MeshCache,
+    // Chunks unloaded for being far from every player, to be streamed again once one comes back.
+    unloaded: HashSet<(i32, i32)>,
+    profiler: Profiler,
+    // Player 0 uses keyboard and mouse, player 1 a gamepad (or the secondary key layout).
+    pub players: Vec<Player>,
+    // One per player, parked on a road near them once it streams in.
+    cars: Vec<Car>,
+    views: Vec<PlayerView>,
+    pub split_screen: bool,
+    // Held M: every view renders the overhead map around its player.
+    map_view: bool,
+    pub game_mode: GameMode,
+    show_scoreboard: bool,
+    stats: RenderStats,
+    stats_overlay: StatsOverlay,
+    #[cfg(feature = "gamepad")]
+    gamepad: Option<crate::gamepad::GamepadInput>,
     pub mouse_captured: bool,
+    // Mouse motion since the last update, applied right before simulating.
+    pending_look: glam::DVec2,
+    // Smoothed mouse speed in pixels per second, see MouseSettings::look.
+    look_velocity: glam::DVec2,
+    settings: SettingsMenu,
+    // Escape: stops the world and shows the pause menu.
+    pause: PauseMenu,
+    // Set by the pause menu's Quit; main exits the event loop.
+    pub quit_requested: bool,
     last_frame_time: Instant,
-    velocity: glam::DVec3, 
-    on_ground: bool,
+    // Frame time not yet simulated, less than one physics tick.
+    physics_accumulator: f64,
+    // Where the players go once the chunk there has streamed in.
+    pending_spawn: Option<glam::Vec2>,
+    teleport: Option<Teleport>,
+    pub cinematic: Cinematic,
+    // Recording while the --benchmark flight plays.
+    benchmark: Option<Benchmark>,
+    replay: Replay,
+    capture: InputCapture,
+    // Spots saved with Shift+1-4, in local x, z.
+    teleport_slots: [Option<glam::Vec2>; 4],
+    // Latitude and longitude of the map's local origin.
+    pub map_origin: (f64, f64),
+    // Named places and buildings; the named buildings get labels (L toggles).
+    pub places: PoiIndex,
+    show_labels: bool,
+    // B drops one; the pause menu lists them.
+    pub waypoints: Waypoints,
+    pub notifications: Notifications,
+    console: Console,
+    // Set by the reload_chunks command; main streams the map file again.
+    pub reload_requested: bool,
+    // Unloaded chunks a player has come back to; main asks the loader to mesh them again.
+    pub restream_requests: Vec<(i32, i32)>,
+    // Frame rate cap in effect, and whether it or the present mode just changed; main rebuilds
+    // its frame limiter then.
+    pub frame_limit: Option<u32>,
+    pub display_changed: bool,
+    speedometer: Speedometer,
+    // The district player one was last announced entering, and where it is.
+    current_district: Option<(String, glam::Vec2)>,
+    shift_held: bool,
+    // F12: the world stands still and player one's view belongs to a free camera.
+    photo: Option<PhotoMode>,
+    // F1 settings and developer panels; None until main attaches the window.
+    #[cfg(feature = "egui")]
+    dev_ui: Option<crate::dev_ui::DevUi>,
 }
 
 impl GameState {
-    pub fn new(ctx: GpuContext) -> Self {
+    pub fn new(mut ctx: GpuContext) -> Self {
         let aspect = ctx.config.width as f32 / ctx.config.height as f32;
-        let camera = Camera::new(aspect);
+        let spawn = glam::DVec3::new(0.0, 50.0, 0.0);
+        let bindings = BindingsFile::load();
+        let players = vec![
+            Player::new(aspect, spawn, bindings.player_one),
+            Player::new(aspect, spawn + config::PLAYER_TWO_SPAWN_OFFSET, bindings.player_two),
+        ];
+        let viewport = [0.0, 0.0, ctx.config.width as f32, ctx.config.height as f32];
+
+        let camera_bind_group_layout = camera_layout(&ctx.device);
         
-        let mut camera_uniform = CameraUniform { 
-            view_proj: [[0.0; 4]; 4], screen_size: [ctx.config.width as f32, ctx.config.height as f32], 
-            fog_dist: [config::FOG_START, config::FOG_END], camera_pos: [camera.eye.x as f32, camera.eye.y as f32, camera.eye.z as f32, 0.0],
+        let facades = FacadeTextures::new(&ctx.device, &ctx.queue);
+        let shadows = ShadowMaps::new(&ctx.device, players.len());
+        let lights = ClusteredLights::new(&ctx.device, players.len());
+        let occlusion = OcclusionCuller::new(&ctx.device, &camera_bind_group_layout);
+        let chunk_draws = IndirectDraws::new(&ctx.device);
+        let chunk_fades = ChunkFades::new(&ctx.device, players.len());
+        let render_scale = RenderScale::new();
+        let (scene_width, scene_height) = render_scale.apply(ctx.config.width, ctx.config.height);
+        ctx.resize_depth(scene_width, scene_height);
+        let post = PostProcess::new(&ctx.device, ctx.config.format, &camera_bind_group_layout, &ctx.depth_texture, scene_width, scene_height, players.len());
+        let water = WaterRenderer::new(&ctx.device, &camera_bind_group_layout, &post, &ctx.depth_texture);
+        let highlight = BuildingHighlight::new(&ctx.device, &camera_bind_group_layout, players.len());
+        let streaming = StreamingIndicator::new(&ctx.device, &camera_bind_group_layout);
+        let weather_particles = WeatherParticles::new(&ctx.device, &camera_bind_group_layout, &ctx.depth_texture);
+
+        let ScenePipelines { render: render_pipeline, normals: normals_pipeline, wireframe: wireframe_pipeline, sky: sky_pipeline } = shader_cache::scene_pipelines(&ctx.device);
+        let debug_lines = DebugLines::new(&ctx.device, &camera_bind_group_layout);
+
+        // Drawn in the composite pass, on top of the post-processed image.
+        let crosshairs = Crosshairs::new(&ctx.device, ctx.config.format, &camera_bind_group_layout, players.len());
+        let vignette = DamageVignette::new(&ctx.device, ctx.config.format, players.len());
+        let speedometer = Speedometer::new(players.len());
+
+        let budget = GpuBudget::from_adapter(&ctx.adapter_info);
+        let hud = HudRenderer::new(&ctx.device, ctx.config.format, 1, None);
+        let text = TextRenderer::new(&ctx.device, ctx.config.format, 1, None);
+        let minimap = Minimap::new(&ctx.device, ctx.config.format);
+        let game_mode = GameMode::new(ModeKind::FreeRoam, players.len());
+        let views = players.iter().map(|p| PlayerView::new(&ctx.device, &camera_bind_group_layout, &p.camera, viewport)).collect();
+
+        #[cfg(feature = "gamepad")]
+        let gamepad = crate::gamepad::GamepadInput::new();
+        #[cfg(feature = "gamepad")]
+        let split_screen = config::SPLIT_SCREEN || gamepad.as_ref().is_some_and(|g| g.is_connected());
+        #[cfg(not(feature = "gamepad"))]
+        let split_screen = config::SPLIT_SCREEN;
+
+        let dynamic_meshes = DynamicMeshes::new(&ctx.device, players.len());
+        let profiler = Profiler::new(&ctx.device, &ctx.queue);
+
+        let mut state = Self {
+            ctx, render_pipeline, normals_pipeline, wireframe_pipeline, debug_lines, dynamic_meshes, debug_mode: DebugMode::Off, sky_pipeline, crosshairs, vignette, facades, shadows, lights, post, render_scale, occlusion, chunk_draws, chunk_fades, water, highlight, streaming, config_watcher: ConfigWatcher::new(), weather_particles, hud, text, minimap,
+            world: World::new(), time_of_day: TimeOfDay::new(), weather: Weather::new(), budget, uploads: ChunkUploads::new(), mesh_cache: MeshCache::new(), unloaded: HashSet::new(), profiler,
+            players, cars: Vec::new(), views, split_screen, map_view: false,
+            game_mode, show_scoreboard: false, stats: RenderStats::default(), stats_overlay: StatsOverlay::new(),
+            #[cfg(feature = "gamepad")]
+            gamepad,
+            mouse_captured: false, pending_look: glam::DVec2::ZERO, look_velocity: glam::DVec2::ZERO, settings: SettingsMenu::load(), pause: PauseMenu::new(), quit_requested: false, last_frame_time: Instant::now(),
+            physics_accumulator: 0.0, pending_spawn: None,
+            teleport: None, cinematic: Cinematic::load(), benchmark: None, replay: Replay::new(), capture: InputCapture::new(), teleport_slots: [None; 4], map_origin: (0.0, 0.0), places: PoiIndex::default(), show_labels: config::LANDMARK_LABELS, waypoints: Waypoints::load(), notifications: Notifications::default(), console: Console::new(), reload_requested: false, restream_requests: Vec::new(), frame_limit: config_file::get().graphics.frame_limit, display_changed: false, speedometer, current_district: None, shift_held: false, photo: None,
+            #[cfg(feature = "egui")]
+            dev_ui: None,
         };
-        camera_uniform.view_proj = camera.build_view_projection_matrix().to_cols_array_2d();
+        state.sync_viewports();
+        state.post.set_render_scale(state.scene_scale());
+        // settings.json may ask for another present mode or frame limit than the config.
+        state.apply_display_settings();
+        state
+    }
 
-        let camera_buffer = ctx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Buffer"), contents: bytemuck::cast_slice(&[camera_uniform]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
+    pub fn primary(&self) -> &Player {
+        &self.players[0]
+    }
 
-        let camera_bind_group_layout = ctx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0, visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None,
-            }], label: None,
-        });
-        
-        let camera_bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &camera_bind_group_layout, entries: &[wgpu::BindGroupEntry { binding: 0, resource: camera_buffer.as_entire_binding() }], label: None,
-        });
+    fn active_players(&self) -> usize {
+        if self.split_screen { self.players.len() } else { 1 }
+    }
 
-        let shader_module = ctx.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Scene Shader"), source: wgpu::ShaderSource::Wgsl(shader::SCENE_SHADER.into()),
-        });
+    // Viewport rect (x, y, w, h) in pixels; split screen stacks players top to bottom.
+    fn viewport(&self, index: usize) -> [f32; 4] {
+        let w = self.ctx.config.width as f32;
+        let h = self.ctx.config.height as f32;
+        if !self.split_screen { return [0.0, 0.0, w, h]; }
+        let slice_h = (h / self.players.len() as f32).floor();
+        [0.0, slice_h * index as f32, w, slice_h]
+    }
 
-        let render_pipeline_layout = ctx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None, bind_group_layouts: &[&camera_bind_group_layout], push_constant_ranges: &[],
-        });
+    // Camera a view renders from: its player's, or the overhead one in map view.
+    fn view_camera(&self, index: usize) -> Camera {
+        if index == 0 && let Some(photo) = &self.photo { return photo.camera.clone(); }
+        let camera = self.players[index].view_camera(self.physics_accumulator * config::PHYSICS_TICK_RATE, &self.world);
+        if index == 0 && let Some(flight) = self.cinematic.camera(&camera) { return flight; }
+        if index == 0 && let Some(replayed) = self.replay.camera(&camera) { return replayed; }
+        if self.map_view { camera.map_view() } else { camera }
+    }
 
-        let render_pipeline = ctx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"), layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader_module, entry_point: "vs_main",
-                buffers: &[wgpu::VertexBufferLayout {
-                    array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                    step_mode: wgpu::VertexStepMode::Vertex,
-                    attributes: &[
-                        wgpu::VertexAttribute { offset: 0,  shader_location: 0, format: wgpu::VertexFormat::Float32x3 },
-                        wgpu::VertexAttribute { offset: 12, shader_location: 1, format: wgpu::VertexFormat::Float32x3 },
-                        wgpu::VertexAttribute { offset: 24, shader_location: 2, format: wgpu::VertexFormat::Float32x3 },
-                    ],
-                }],
+    // Each active player's position and heading, marked on every map view.
+    fn draw_map_markers(&mut self, viewports: &[[f32; 4]]) {
+        for (i, &[x, y, w, h]) in viewports.iter().enumerate() {
+            let view_proj = self.view_camera(i).build_view_projection_matrix();
+            let to_screen = |p: glam::Vec3| {
+                let ndc = view_proj.project_point3(p);
+                [x + (ndc.x * 0.5 + 0.5) * w, y + (0.5 - ndc.y * 0.5) * h]
+            };
+            for (player, color) in self.players[..viewports.len()].iter().zip(config::PLAYER_COLORS) {
+                let eye = player.interpolated_camera(self.physics_accumulator * config::PHYSICS_TICK_RATE).eye.as_vec3();
+                let forward = player.camera.forward().as_vec3();
+                let heading = glam::Vec3::new(forward.x, 0.0, forward.z).normalize_or_zero();
+                let [cx, cy] = to_screen(eye);
+                self.hud.rect([cx - 6.0, cy - 6.0], [cx + 6.0, cy + 6.0], [0.0, 0.0, 0.0, 0.8]);
+                self.hud.rect([cx - 4.0, cy - 4.0], [cx + 4.0, cy + 4.0], color);
+                // Dotted line toward where the player is looking.
+                for step in 1..=5 {
+                    let [px, py] = to_screen(eye + heading * (step as f32 * config::MAP_VIEW_EXTENT * 0.02));
+                    self.hud.rect([px - 2.0, py - 2.0], [px + 2.0, py + 2.0], color);
+                }
+            }
+        }
+    }
+
+    // Actual ratio of the scene targets to the surface, after rounding to whole pixels.
+    fn scene_scale(&self) -> f32 {
+        let (width, _) = self.render_scale.apply(self.ctx.config.width, self.ctx.config.height);
+        width as f32 / self.ctx.config.width as f32
+    }
+
+    // Viewport in the (possibly scaled down) scene targets.
+    fn scene_viewport(&self, index: usize) -> [f32; 4] {
+        let scale = self.scene_scale();
+        self.viewport(index).map(|v| (v * scale).round())
+    }
+
+    // Recreates everything sized to the scene targets after a window resize or scale change.
+    fn resize_scene_targets(&mut self) {
+        let (width, height) = self.render_scale.apply(self.ctx.config.width, self.ctx.config.height);
+        self.ctx.resize_depth(width, height);
+        self.post.resize(&self.ctx.device, &self.ctx.depth_texture, width, height);
+        self.post.set_render_scale(self.scene_scale());
+        self.water.resize(&self.ctx.device, &self.post, &self.ctx.depth_texture);
+        self.weather_particles.resize(&self.ctx.device, &self.ctx.depth_texture);
+    }
+
+    fn sync_viewports(&mut self) {
+        for i in 0..self.players.len() {
+            let [_, _, w, h] = self.viewport(i);
+            self.players[i].camera.aspect = w / h.max(1.0);
+        }
+    }
+
+    pub fn toggle_split_screen(&mut self) {
+        self.split_screen = !self.split_screen;
+        self.sync_viewports();
+        log::info!("Split screen {}", if self.split_screen { "enabled" } else { "disabled" });
+    }
+
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        self.ctx.resize(new_size);
+        self.resize_scene_targets();
+        self.sync_viewports();
+    }
+
+    pub fn cycle_game_mode(&mut self) {
+        let kind = self.game_mode.kind.next();
+        self.game_mode = GameMode::new(kind, self.players.len());
+        if self.game_mode.is_active() && !self.split_screen { self.toggle_split_screen(); }
+        log::info!("Game mode: {}", kind.name());
+        self.notifications.push(format!("Game mode: {}", kind.name()));
+    }
+
+    pub fn cycle_debug_mode(&mut self) {
+        self.debug_mode = self.debug_mode.next();
+        if self.debug_mode == DebugMode::Wireframe && self.wireframe_pipeline.is_none() {
+            log::warn!("Wireframe view needs line polygon mode, which this adapter lacks");
+            self.debug_mode = self.debug_mode.next();
+        }
+        log::info!("Debug view: {}", self.debug_mode.name());
+    }
+
+    // The F3 overlay, with the previous frame's render stats.
+    fn draw_stats_overlay(&mut self, screen: [f32; 2]) {
+        let lines = self.stats_lines();
+        self.stats_overlay.draw(&mut self.hud, &mut self.text, screen, &lines);
+    }
+
+    fn stats_lines(&self) -> [String; 6] {
+        let stats = self.stats;
+        let eye = self.primary().camera.eye;
+        let (cx, cz) = World::chunk_coord_at(eye.x as f32, eye.z as f32);
+        let mb = 1024 * 1024;
+        let backlog = self.uploads.backlog();
+        [
+            format!("Draw calls {}  Triangles {:.2}M", stats.draw_calls, stats.triangles as f64 / 1e6),
+            format!("Chunks {} / {}  ({} LOD, {} culled, {} occluded)", stats.drawn_chunks, self.world.chunks.len(), stats.lod_chunks, stats.culled_chunks, stats.occluded_chunks),
+            format!(
+                "GPU memory {} / {} MB  (meshes {} MB in {} MB of pages, {} waiting to upload, {} MB)",
+                self.budget.used() / mb, self.budget.budget / mb, self.world.gpu_bytes / mb, self.world.meshes.gpu_bytes() / mb, backlog.0, backlog.1 / mb,
+            ),
+            format!("Position {:.1}, {:.1}, {:.1}  chunk ({}, {})", eye.x, eye.y, eye.z, cx, cz),
+            if self.profiler.has_gpu_timing() {
+                let spans: Vec<String> = self.profiler.gpu_ms().map(|(name, ms)| format!("{} {:.2}", name, ms)).collect();
+                format!("GPU {:.2} ms  ({})", self.profiler.gpu_ms().map(|(_, ms)| ms).sum::<f32>(), spans.join(", "))
+            } else {
+                "GPU timing unavailable".to_string()
             },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader_module, entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState { format: ctx.config.format, blend: Some(wgpu::BlendState::REPLACE), write_mask: wgpu::ColorWrites::ALL })],
-            }),
-            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, cull_mode: None, ..Default::default() },
-            depth_stencil: Some(wgpu::DepthStencilState { 
-                format: wgpu::TextureFormat::Depth32Float, depth_write_enabled: true, depth_compare: wgpu::CompareFunction::Less, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default() 
-            }),
-            multisample: wgpu::MultisampleState { count: 4, mask: !0, alpha_to_coverage_enabled: false },
-            multiview: None,
-        });
+            format!("CPU  {}", self.profiler.cpu_ms().iter().map(|(name, ms)| format!("{} {:.2} ms", name, ms)).collect::<Vec<_>>().join(", ")),
+        ]
+    }
+
+    #[cfg(feature = "egui")]
+    pub fn attach_dev_ui(&mut self, window: std::sync::Arc<Window>) {
+        self.dev_ui = Some(crate::dev_ui::DevUi::new(&self.ctx.device, self.ctx.config.format, window));
+    }
+
+    // True while a menu or the developer panels have the cursor, so clicks shouldn't capture the mouse.
+    pub fn ui_has_pointer(&self) -> bool {
+        if self.pause.open { return true; }
+        #[cfg(feature = "egui")]
+        if self.dev_ui.as_ref().is_some_and(|ui| ui.open) { return true; }
+        false
+    }
 
-        let ui_shader = ctx.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("UI Shader"), source: wgpu::ShaderSource::Wgsl(shader::UI_SHADER.into()),
+    // F1 opens the panels; while open they see every window event first.
+    #[cfg(feature = "egui")]
+    fn dev_ui_input(&mut self, event: &WindowEvent) -> bool {
+        use winit::keyboard::{KeyCode, PhysicalKey};
+        let Some(ui) = &mut self.dev_ui else { return false };
+        if let WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(KeyCode::F1), state: ElementState::Pressed, repeat: false, .. }, .. } = event {
+            self.mouse_captured = !ui.toggle();
+            self.look_velocity = glam::DVec2::ZERO;
+            // Control changes are kept once the panels close, like the F10 menu.
+            if !ui.open { self.settings.save(); }
+            return true;
+        }
+        ui.on_window_event(event)
+    }
+
+    // Graphics, controls, fog, time of day and debug panels for the egui layer.
+    #[cfg(feature = "egui")]
+    fn dev_panels(&mut self, ctx: &egui::Context) {
+        use crate::weather::WeatherKind;
+        egui::Window::new("Graphics").default_pos([12.0, 40.0]).show(ctx, |ui| {
+            ui.checkbox(&mut self.shadows.enabled, "Shadows");
+            ui.checkbox(&mut self.occlusion.enabled, "Occlusion culling");
+            let mut effects = self.players[0].view_effects;
+            if ui.checkbox(&mut effects, "View effects").changed() {
+                for player in &mut self.players { player.view_effects = effects; }
+            }
+            let (min, max) = config::FOV_RANGE;
+            ui.add(egui::Slider::new(&mut self.players[0].fov, min..=max).text("Field of view").suffix("°"));
+            ui.label(format!("Render scale {:.0}%", self.render_scale.scale * 100.0));
         });
-        
-        let ui_pipeline = ctx.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("UI Pipeline"), layout: None,
-            vertex: wgpu::VertexState { module: &ui_shader, entry_point: "vs_main", buffers: &[] },
-            fragment: Some(wgpu::FragmentState {
-                module: &ui_shader, entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState { format: ctx.config.format, blend: Some(wgpu::BlendState::ALPHA_BLENDING), write_mask: wgpu::ColorWrites::ALL })],
-            }),
-            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleStrip, ..Default::default() },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float, depth_write_enabled: false, depth_compare: wgpu::CompareFunction::Always, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState { count: 4, mask: !0, alpha_to_coverage_enabled: false },
-            multiview: None,
+        egui::Window::new("Controls").default_pos([12.0, 220.0]).show(ctx, |ui| {
+            let mouse = &mut self.settings.settings.mouse;
+            let (min, max) = config::MOUSE_SENSITIVITY_RANGE;
+            ui.add(egui::Slider::new(&mut mouse.sensitivity, min..=max).logarithmic(true).text("Mouse sensitivity"));
+            ui.checkbox(&mut mouse.invert_y, "Invert Y");
+            ui.add(egui::Slider::new(&mut mouse.smoothing, 0.0..=config::MOUSE_SMOOTHING_MAX).text("Smoothing (s)"));
+            ui.add(egui::Slider::new(&mut mouse.acceleration, 0.0..=config::MOUSE_ACCELERATION_MAX).text("Acceleration"));
+        });
+        egui::Window::new("Environment").default_pos([12.0, 380.0]).show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.time_of_day.hours, 0.0..=23.99).text("Time of day (h)"));
+            ui.horizontal(|ui| {
+                for kind in [WeatherKind::Clear, WeatherKind::Rain, WeatherKind::Snow] {
+                    if ui.selectable_label(self.weather.kind == kind, kind.name()).clicked() { self.weather.set(kind); }
+                }
+            });
+            ui.add(egui::Slider::new(&mut self.weather.fog_multiplier, 0.0..=config::DEV_UI_MAX_FOG).text("Fog"));
+        });
+        egui::Window::new("Debug").default_pos([12.0, 540.0]).show(ctx, |ui| {
+            ui.checkbox(&mut self.stats_overlay.visible, "Stats overlay");
+            let mut mode = self.debug_mode;
+            egui::ComboBox::from_label("Debug view").selected_text(mode.name()).show_ui(ui, |ui| {
+                let mut option = DebugMode::Off;
+                loop {
+                    if option != DebugMode::Wireframe || self.wireframe_pipeline.is_some() {
+                        ui.selectable_value(&mut mode, option, option.name());
+                    }
+                    option = option.next();
+                    if option == DebugMode::Off { break; }
+                }
+            });
+            self.debug_mode = mode;
+            let stats = self.stats;
+            ui.label(format!("Draw calls {}  Triangles {:.2}M", stats.draw_calls, stats.triangles as f64 / 1e6));
+            ui.label(format!("Chunks {} / {}  ({} culled, {} occluded)", stats.drawn_chunks, self.world.chunks.len(), stats.culled_chunks, stats.occluded_chunks));
+            let eye = self.primary().camera.eye;
+            ui.label(format!("Position {:.1}, {:.1}, {:.1}", eye.x, eye.y, eye.z));
+            if ui.button(if self.players[0].mode == MovementMode::Fly { "Stop flying" } else { "Fly" }).clicked() { self.players[0].toggle_fly(); }
         });
+    }
 
-        Self {
-            ctx, render_pipeline, ui_pipeline,
-            world: World::new(),
-            camera, camera_controller: CameraController::new(),
-            camera_uniform, camera_buffer, camera_bind_group,
-            mouse_captured: false, last_frame_time: Instant::now(),
-            velocity: glam::DVec3::ZERO, on_ground: false,
+    // Draws the open panels over the finished frame in `target`.
+    #[cfg(feature = "egui")]
+    fn draw_dev_ui(&mut self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let Some(mut ui) = self.dev_ui.take() else { return };
+        if ui.open {
+            let output = ui.run(|ctx| self.dev_panels(ctx));
+            ui.paint(&self.ctx.device, &self.ctx.queue, encoder, target, [self.ctx.config.width, self.ctx.config.height], output);
         }
+        self.dev_ui = Some(ui);
     }
 
-    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        self.ctx.resize(new_size);
-        self.camera.aspect = self.ctx.config.width as f32 / self.ctx.config.height as f32;
-        self.camera_uniform.screen_size = [self.ctx.config.width as f32, self.ctx.config.height as f32];
+    // Fills the debug line buffer for the current mode, around the primary player, plus the
+    // ropes of grappling players.
+    fn build_debug_lines(&mut self) {
+        self.debug_lines.clear();
+        let alpha = self.physics_accumulator * config::PHYSICS_TICK_RATE;
+        for player in &self.players[..self.active_players()] {
+            let Some(rope) = player.grapple else { continue };
+            let camera = player.interpolated_camera(alpha);
+            let forward = camera.forward().as_vec3();
+            let right = forward.cross(glam::Vec3::Y).normalize_or_zero();
+            // From the right hand, sagging in the middle while there's slack.
+            let hand = camera.eye.as_vec3() + right * 0.3 - glam::Vec3::Y * 0.4 + forward * 0.3;
+            let anchor = rope.anchor.as_vec3();
+            let sag = (rope.length as f32 - hand.distance(anchor)).max(0.0) * 0.5;
+            let points: Vec<glam::Vec3> = (0..=16).map(|i| {
+                let t = i as f32 / 16.0;
+                hand.lerp(anchor, t) - glam::Vec3::Y * sag * 4.0 * t * (1.0 - t)
+            }).collect();
+            self.debug_lines.strip(&points, config::ROPE_COLOR);
+        }
+        let eye = self.primary().camera.eye.as_vec3();
+        let eye_flat = glam::Vec2::new(eye.x, eye.z);
+        let (draw_distance, lod_distance) = (config_file::get().graphics.draw_distance, self.lod_distance());
+        match self.debug_mode {
+            DebugMode::ChunkBounds => {
+                for (coord, chunk) in &self.world.chunks {
+                    let distance = chunk.center().distance(eye_flat);
+                    if distance > draw_distance { continue; }
+                    // Red: mesh evicted, grey: occluded last frame, yellow: LOD mesh, green: full detail.
+                    let color = match &chunk.mesh {
+                        None => [2.0, 0.2, 0.2, 1.0],
+                        Some(_) if self.occlusion.is_occluded(0, *coord) => [0.6, 0.6, 0.6, 1.0],
+                        Some(mesh) if mesh.indices_at(distance, lod_distance).start > 0 => [2.0, 1.6, 0.2, 1.0],
+                        Some(_) => [0.2, 2.0, 0.4, 1.0],
+                    };
+                    let min = glam::Vec3::new(chunk.min.x, chunk.min_y, chunk.min.y);
+                    let max = glam::Vec3::new(chunk.max.x, chunk.max_y, chunk.max.y);
+                    self.debug_lines.aabb(min, max, color);
+                }
+            }
+            DebugMode::Collision => {
+                let radius = config::DEBUG_COLLISION_RADIUS;
+                let mut seen_roofs = std::collections::HashSet::new();
+                for chunk in self.world.chunks.values() {
+                    if eye_flat.clamp(chunk.min, chunk.max).distance(eye_flat) > radius { continue; }
+                    for wall in &chunk.collision.walls {
+                        if wall.start.distance(eye_flat).min(wall.end.distance(eye_flat)) > radius { continue; }
+                        let corner = |p: glam::Vec2, y: f32| glam::Vec3::new(p.x, y, p.y);
+                        let (a, b) = (corner(wall.start, wall.base), corner(wall.end, wall.base));
+                        let (c, d) = (corner(wall.end, wall.height), corner(wall.start, wall.height));
+                        let color = [2.0, 0.3, 1.6, 1.0];
+                        self.debug_lines.line(a, b, color);
+                        self.debug_lines.line(b, c, color);
+                        self.debug_lines.line(c, d, color);
+                        self.debug_lines.line(d, a, color);
+                    }
+                    let roofs = chunk.collision.roof_cells.iter().flatten();
+                    for roof in roofs.filter(|r| r.corners.iter().any(|c| c.distance(eye_flat) <= radius)) {
+                        let key = roof.corners.map(|c| [c.x.to_bits(), c.y.to_bits()]);
+                        if !seen_roofs.insert(key) { continue; }
+                        let [a, b, c] = roof.corners.map(|c| glam::Vec3::new(c.x, roof.height, c.y));
+                        let color = [1.6, 1.2, 0.2, 1.0];
+                        self.debug_lines.line(a, b, color);
+                        self.debug_lines.line(b, c, color);
+                        self.debug_lines.line(c, a, color);
+                    }
+                    for span in &chunk.collision.tunnels {
+                        let start = glam::Vec3::new(span.start.x, span.floor_start, span.start.y);
+                        let end = glam::Vec3::new(span.end.x, span.floor_end, span.end.y);
+                        self.debug_lines.line(start, end, [0.2, 1.6, 2.0, 1.0]);
+                        if !span.open_top {
+                            let up = glam::Vec3::Y * config::TUNNEL_HEIGHT;
+                            self.debug_lines.line(start + up, end + up, [0.2, 0.8, 1.0, 1.0]);
+                        }
+                    }
+                }
+            }
+            DebugMode::Off | DebugMode::Wireframe | DebugMode::Normals => {}
+        }
+        self.debug_lines.prepare(&self.ctx.device, &self.ctx.queue);
+    }
+
+    // F12: freezes the world and detaches player one's view, or puts everything back as it was.
+    pub fn toggle_photo_mode(&mut self) {
+        if self.photo.take().is_some() {
+            self.post.set_depth_of_field(0.0, 0.0);
+            self.post.set_exposure_bias(0.0);
+            log::info!("Photo mode off");
+        } else {
+            self.photo = Some(PhotoMode::new(self.view_camera(0)));
+            log::info!("Photo mode on (wheel focuses, R autofocuses, ,/. blur, -/= exposure, F12 returns)");
+        }
+    }
+
+    // In photo mode only its own keys and player one's movement keys, which fly the camera, apply.
+    fn photo_input(&mut self, event: &WindowEvent) -> bool {
+        use winit::keyboard::{KeyCode, PhysicalKey};
+        let Some(photo) = &mut self.photo else { return false };
+        if let WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(key), state: ElementState::Pressed, repeat: false, .. }, .. } = event {
+            match key {
+                KeyCode::F12 => { self.toggle_photo_mode(); return true; }
+                KeyCode::Comma | KeyCode::Period => { photo.adjust_blur(if *key == KeyCode::Comma { -1.0 } else { 1.0 }); return true; }
+                KeyCode::Minus | KeyCode::Equal => { photo.adjust_exposure(if *key == KeyCode::Minus { -1.0 } else { 1.0 }); return true; }
+                KeyCode::KeyR => { photo.autofocus(&self.world); return true; }
+                _ => {}
+            }
+        }
+        if let WindowEvent::ModifiersChanged(modifiers) = event { self.shift_held = modifiers.state().shift_key(); }
+        if let WindowEvent::MouseWheel { delta, .. } = event {
+            photo.adjust_focus(match delta {
+                MouseScrollDelta::LineDelta(_, y) => *y as f64,
+                MouseScrollDelta::PixelDelta(pos) => pos.y / 50.0,
+            });
+            return true;
+        }
+        self.players[0].controller.process_events(event)
+    }
+
+    // Escape closes the settings menu or the pause menu's Teleport page, and otherwise pauses or
+    // resumes. Returns whether the mouse should be captured.
+    pub fn escape(&mut self) -> bool {
+        if self.console.open {
+            self.console.open = false;
+        } else if self.settings.open {
+            self.settings.toggle();
+        } else if self.pause.open {
+            if !self.pause.back() { self.resume(); }
+        } else {
+            self.pause.show();
+            // Releases of keys held now would go to the menu, so let go of them up front.
+            self.release_controllers();
+            self.mouse_captured = false;
+        }
+        self.mouse_captured
+    }
+
+    fn release_controllers(&mut self) {
+        for player in &mut self.players { player.controller.release_all(); }
+        self.capture.record(CaptureEvent::ReleaseAll);
+    }
+
+    fn resume(&mut self) {
+        self.pause.open = false;
+        self.mouse_captured = true;
+    }
+
+    // The ~ key opens and closes the console; while it's open it gets every key.
+    fn console_input(&mut self, event: &KeyEvent) {
+        use winit::keyboard::{KeyCode, PhysicalKey};
+        if event.state != ElementState::Pressed { return; }
+        if event.physical_key == PhysicalKey::Code(KeyCode::Backquote) {
+            if event.repeat { return; }
+            self.console.toggle();
+            // Keys held now would be released into the console.
+            if self.console.open { self.release_controllers(); }
+        } else if let Some(line) = self.console.key(event) {
+            self.run_command(&line);
+        }
+    }
+
+    // Runs a console line and prints the outcome.
+    pub fn run_command(&mut self, line: &str) {
+        let reply = match Command::parse(line) {
+            Err(error) => error,
+            Ok(Command::Teleport(target)) => self.teleport_to(&target).unwrap_or_else(|e| e),
+            Ok(Command::Time(hours)) => {
+                if let Some(hours) = hours { self.set_time_of_day(hours); }
+                let minutes = (self.time_of_day.hours * 60.0).round() as u32;
+                format!("Time {:02}:{:02}", minutes / 60 % 24, minutes % 60)
+            }
+            Ok(Command::Fog(multiplier)) => {
+                if let Some(multiplier) = multiplier { self.set_fog_multiplier(multiplier); }
+                format!("Fog x{:.2}", self.weather.fog_multiplier)
+            }
+            Ok(Command::Speed(speed)) => {
+                if let Some(speed) = speed { self.set_fly_speed(speed); }
+                format!("Noclip speed {:.0} m/s", self.players[0].fly_speed)
+            }
+            Ok(Command::Noclip) => {
+                self.toggle_noclip();
+                format!("Noclip {}", if self.players[0].mode == MovementMode::Fly { "on" } else { "off" })
+            }
+            Ok(Command::ReloadChunks) => {
+                self.reload_chunks();
+                "Streaming the map again".to_string()
+            }
+            Ok(Command::ResetBindings) => {
+                self.reset_bindings();
+                format!("Restored the default key bindings to {}", config_file::get().paths.bindings)
+            }
+            Ok(Command::Stats) => {
+                self.stats_overlay.toggle();
+                for line in self.stats_lines() { self.console.print(line); }
+                format!("Stats overlay {}", if self.stats_overlay.visible { "shown" } else { "hidden" })
+            }
+            Ok(Command::Capture) => {
+                if self.capture.is_recording() { self.capture.stop_recording() } else {
+                    self.start_capture();
+                    format!("Capturing input to {} (capture again stops)", config_file::get().paths.capture)
+                }
+            }
+            Ok(Command::Playback(path)) => {
+                if self.capture.is_playing() {
+                    self.capture.stop_playback();
+                    "Stopped playback".to_string()
+                } else {
+                    let path = path.unwrap_or_else(|| config_file::get().paths.capture.clone());
+                    match self.start_playback(&path, false) {
+                        Ok(()) => format!("Playing back {}", path),
+                        Err(e) => e,
+                    }
+                }
+            }
+            Ok(Command::Tour(number)) => match number {
+                Some(number) => self.cinematic.play_tour(number).unwrap_or_else(|e| e),
+                None if self.cinematic.tours.is_empty() => "This map package has no tours".to_string(),
+                None => {
+                    for (i, (name, tour)) in self.cinematic.tours.iter().enumerate() {
+                        self.console.print(format!("{}. {} ({:.0} s)", i + 1, name, tour.duration));
+                    }
+                    return;
+                }
+            },
+            Ok(Command::Clear) => {
+                self.console.clear();
+                return;
+            }
+            Ok(Command::Help) => {
+                for line in Command::help() { self.console.print(line); }
+                return;
+            }
+        };
+        self.console.print(reply);
+    }
+
+    // Records the players' input from here on (see capture.rs). Held keys are let go first so
+    // the capture has the press of everything it releases.
+    pub fn start_capture(&mut self) {
+        for player in &mut self.players { player.controller.release_all(); }
+        self.capture.start_recording(CaptureStart::new(&self.players, self.physics_accumulator, self.world.clock, self.settings.settings.mouse));
+    }
+
+    // Saves a capture still recording, as the game closes.
+    pub fn finish_capture(&mut self) {
+        if self.capture.is_recording() { log::info!("{}", self.capture.stop_recording()); }
+    }
+
+    // Flies the benchmark loop over `map`, then writes the report to `report` and quits.
+    pub fn start_benchmark(&mut self, report: String, map: String) {
+        self.cinematic.play(benchmark::flight());
+        self.benchmark = Some(Benchmark::new(report, map));
+    }
+
+    // Puts the players back where the capture at `path` began and plays its input in place of
+    // live input. With `quit_at_end` the game exits after the last frame.
+    pub fn start_playback(&mut self, path: &str, quit_at_end: bool) -> Result<(), String> {
+        let start = self.capture.start_playback(path, quit_at_end)?;
+        (self.physics_accumulator, self.world.clock) = start.restore(&mut self.players);
+        self.look_velocity = glam::DVec2::ZERO;
+        Ok(())
+    }
+
+    // Applies a frame of played-back input and returns its length.
+    fn play_frame(&mut self, frame: capture::CaptureFrame, quit: bool) -> f64 {
+        let active = self.active_players();
+        for event in &frame.events {
+            match event {
+                CaptureEvent::Wheel(notches) => { self.wheel(*notches); }
+                event => capture::apply(event, &mut self.players[..active]),
+            }
+        }
+        if let Some(pad) = &frame.pad { pad.apply(&mut self.players[1].controller); }
+        self.pending_look = glam::DVec2::from(frame.look);
+        if !self.capture.is_playing() {
+            // Positions at the end, to compare runs by.
+            let positions: Vec<String> = self.players[..active].iter().map(|p| format!("{:.3?}", p.camera.eye.to_array())).collect();
+            log::info!("Input playback finished; players at {}", positions.join(", "));
+            self.notifications.push("Input playback finished");
+            if quit { self.quit_requested = true; }
+        }
+        frame.dt
+    }
+
+    // Teleports to coordinates, a waypoint or a place in the POI index, in that order.
+    pub fn teleport_to(&mut self, target: &SpawnPoint) -> Result<String, String> {
+        if let SpawnPoint::Place(name) = target
+            && let Some(waypoint) = self.waypoints.list.iter().find(|w| w.name.eq_ignore_ascii_case(name)) {
+            let position = waypoint.position(self.map_origin);
+            let reply = format!("Teleporting to {}", waypoint.name);
+            self.teleport_local(glam::Vec2::new(position.x as f32, position.z as f32));
+            return Ok(reply);
+        }
+        let position = target.resolve(self.map_origin, Some(&self.places)).ok_or_else(|| format!("Can't teleport to {:?}", target))?;
+        self.teleport_local(position);
+        Ok(format!("Teleporting to ({:.0}, {:.0})", position.x, position.y))
+    }
+
+    pub fn set_time_of_day(&mut self, hours: f64) {
+        self.time_of_day.hours = hours.rem_euclid(24.0);
+    }
+
+    pub fn set_fog_multiplier(&mut self, multiplier: f32) {
+        self.weather.fog_multiplier = multiplier.max(0.0);
+    }
+
+    pub fn set_fly_speed(&mut self, speed: f64) {
+        let (min, max) = config::FLY_SPEED_RANGE;
+        self.players[0].fly_speed = speed.clamp(min, max);
+    }
+
+    pub fn toggle_noclip(&mut self) {
+        self.players[0].toggle_fly();
+        log::info!("Noclip {}", if self.players[0].mode == MovementMode::Fly { "enabled" } else { "disabled" });
+    }
+
+    // Uses a map package's facade textures in place of the generated ones of their styles.
+    pub fn set_facade_textures(&mut self, overrides: &[(crate::facade::FacadeStyle, Vec<u8>)]) {
+        self.facades.set_overrides(&self.ctx.device, &self.ctx.queue, overrides);
+    }
+
+    // Asks main to stream every chunk from the map file again, bringing back meshes the GPU
+    // budget evicted. Chunks are replaced as they arrive.
+    pub fn reload_chunks(&mut self) {
+        self.reload_requested = true;
+        // Every chunk comes back, and the far ones are set aside again as they arrive.
+        self.unloaded.clear();
+    }
+
+    // Applies skyroam.toml when it changes on disk.
+    fn watch_config(&mut self, dt: f64) {
+        if let Some(reload) = self.config_watcher.poll(dt) { self.apply_reload(reload); }
+    }
+
+    // Applies a reloaded config, or a graphics preset picked in the settings menu, and says
+    // what did and didn't take effect.
+    fn apply_reload(&mut self, reload: Result<config_file::Reload, String>) {
+        let reload = match reload {
+            Ok(reload) => reload,
+            Err(e) => {
+                log::error!("{}", e);
+                self.notifications.push("Config has errors; kept the current settings");
+                return;
+            }
+        };
+        let config = config_file::get();
+        if reload.applied.iter().any(|key| key == "graphics.fov") {
+            for player in &mut self.players { player.fov = config.graphics.fov; }
+        }
+        if reload.applied.iter().any(|key| key.starts_with("controls.")) {
+            let mouse = &mut self.settings.settings.mouse;
+            (mouse.sensitivity, mouse.invert_y) = (config.controls.mouse_sensitivity, config.controls.invert_y);
+        }
+        let graphics = &config.graphics;
+        if reload.applied.iter().any(|key| key == "graphics.shadows") { self.shadows.enabled = graphics.shadows; }
+        self.post.set_effects(graphics.ssao, graphics.bloom);
+        self.water.set_reflections(graphics.reflections);
+        if !reload.applied.is_empty() {
+            log::info!("Config reloaded: {}", reload.applied.join(", "));
+            self.notifications.push(format!("Config reloaded: {}", reload.applied.join(", ")));
+        }
+        if !reload.needs_restart.is_empty() {
+            log::info!("Config changes waiting for a restart: {}", reload.needs_restart.join(", "));
+            self.notifications.push(format!("Restart to apply: {}", reload.needs_restart.join(", ")));
+        }
+    }
+
+    // Switches to the settings menu's graphics preset and present mode, and flags the frame
+    // limit for main, which owns the frame limiter, when it changed.
+    fn apply_display_settings(&mut self) {
+        let settings = self.settings.settings;
+        if let Some(preset) = settings.graphics_preset && preset != config_file::get().graphics.preset
+            && let Some(reload) = config_file::choose_preset(preset) {
+            self.apply_reload(reload);
+        }
+        let present_changed = self.ctx.set_present_mode(settings.present_mode);
+        if present_changed || settings.frame_limit != self.frame_limit {
+            self.frame_limit = settings.frame_limit;
+            self.display_changed = true;
+        }
+    }
+
+    // Puts both players back on the default bindings and overwrites the bindings file with them.
+    pub fn reset_bindings(&mut self) {
+        let defaults = BindingsFile::default();
+        defaults.save();
+        self.players[0].controller.set_bindings(defaults.player_one);
+        self.players[1].controller.set_bindings(defaults.player_two);
+    }
+
+    // While paused the menu takes every event.
+    fn pause_input(&mut self, event: &WindowEvent) -> bool {
+        use winit::keyboard::PhysicalKey;
+        let WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(key), state: ElementState::Pressed, .. }, .. } = event else { return true };
+        match self.pause.key(*key, self.waypoints.list.len()) {
+            PauseAction::None => {}
+            PauseAction::Resume => self.resume(),
+            PauseAction::Settings => self.settings.toggle(),
+            PauseAction::Teleport(slot) => {
+                self.resume();
+                self.use_teleport_slot(slot);
+            }
+            PauseAction::Waypoint(index) => {
+                self.resume();
+                let target = self.waypoints.list[index].position(self.map_origin);
+                self.teleport_local(glam::Vec2::new(target.x as f32, target.z as f32));
+            }
+            PauseAction::RemoveWaypoint(index) => self.waypoints.remove(index),
+            PauseAction::Quit => self.quit_requested = true,
+        }
+        true
     }
 
     pub fn input(&mut self, event: &WindowEvent) -> bool {
-        self.camera_controller.process_events(event)
+        use winit::keyboard::{KeyCode, PhysicalKey};
+        if let WindowEvent::ModifiersChanged(modifiers) = event { self.shift_held = modifiers.state().shift_key(); }
+        #[cfg(feature = "egui")]
+        if self.dev_ui_input(event) { return true; }
+        // The settings menu takes every key press while it's open.
+        if let WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(key), state: ElementState::Pressed, repeat, .. }, .. } = event
+            && (self.settings.open || *key == KeyCode::F10) {
+            if *key != KeyCode::F10 {
+                self.settings.key(*key);
+                self.apply_display_settings();
+            } else if !repeat { self.settings.toggle(); }
+            return true;
+        }
+        if self.pause.open { return self.pause_input(event); }
+        if let WindowEvent::KeyboardInput { event: key_event, .. } = event
+            && (self.console.open || key_event.physical_key == PhysicalKey::Code(KeyCode::Backquote)) {
+            self.console_input(key_event);
+            return true;
+        }
+        if self.photo.is_some() { return self.photo_input(event); }
+        if let WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(key), state, repeat: false, .. }, .. } = event {
+            let pressed = *state == ElementState::Pressed;
+            match key {
+                KeyCode::F2 if pressed => { self.toggle_split_screen(); return true; }
+                KeyCode::F3 if pressed => {
+                    if self.shift_held { self.cycle_debug_mode(); } else { self.stats_overlay.toggle(); }
+                    return true;
+                }
+                KeyCode::F4 if pressed => { self.cycle_game_mode(); return true; }
+                KeyCode::F6 if pressed => {
+                    self.shadows.enabled = !self.shadows.enabled;
+                    log::info!("Shadows {}", if self.shadows.enabled { "enabled" } else { "disabled" });
+                    return true;
+                }
+                KeyCode::F7 if pressed => {
+                    self.occlusion.toggle();
+                    log::info!("Occlusion culling {}", if self.occlusion.enabled { "enabled" } else { "disabled" });
+                    return true;
+                }
+                KeyCode::F5 if pressed => {
+                    if self.shift_held { self.replay.toggle_playback(); } else { self.replay.toggle_recording(); }
+                    return true;
+                }
+                KeyCode::F8 if pressed => {
+                    self.weather.cycle();
+                    self.notifications.push(format!("Weather: {}", self.weather.kind.name()));
+                    return true;
+                }
+                KeyCode::F12 if pressed => { self.toggle_photo_mode(); return true; }
+                KeyCode::F9 if pressed => {
+                    let enabled = !self.players[0].view_effects;
+                    for player in &mut self.players { player.view_effects = enabled; }
+                    log::info!("View effects {}", if enabled { "enabled" } else { "disabled" });
+                    return true;
+                }
+                KeyCode::KeyM => { self.map_view = pressed; return true; }
+                KeyCode::KeyN if pressed => { self.minimap.toggle(); return true; }
+                KeyCode::KeyH if pressed => { self.speedometer.toggle(); return true; }
+                KeyCode::KeyL if pressed => { self.show_labels = !self.show_labels; return true; }
+                KeyCode::KeyB if pressed => {
+                    self.waypoints.drop_at(&self.view_camera(0), self.map_origin);
+                    if let Some(waypoint) = self.waypoints.list.last() { self.notifications.push(format!("Dropped {}", waypoint.name)); }
+                    return true;
+                }
+                KeyCode::KeyF if pressed => { self.toggle_noclip(); return true; }
+                KeyCode::Tab => { self.show_scoreboard = pressed; return true; }
+                KeyCode::BracketLeft | KeyCode::BracketRight if pressed => {
+                    self.players[0].adjust_fov(if *key == KeyCode::BracketLeft { -1.0 } else { 1.0 });
+                    log::info!("Field of view {:.0}°", self.players[0].fov);
+                    return true;
+                }
+                KeyCode::KeyK if pressed => {
+                    if self.shift_held { self.cinematic.clear(); } else { self.cinematic.drop_keyframe(&self.view_camera(0)); }
+                    return true;
+                }
+                KeyCode::KeyP if pressed => {
+                    if self.shift_held { self.cinematic.save(); } else { self.cinematic.toggle_playback(); }
+                    return true;
+                }
+                KeyCode::Minus | KeyCode::Equal if pressed => {
+                    self.cinematic.adjust_duration(if *key == KeyCode::Minus { -1.0 } else { 1.0 });
+                    return true;
+                }
+                KeyCode::KeyO if pressed => { self.players[0].toggle_orbit(&self.world); return true; }
+                KeyCode::KeyV if pressed => { self.players[0].third_person = !self.players[0].third_person; return true; }
+                KeyCode::Digit1 | KeyCode::Digit2 | KeyCode::Digit3 | KeyCode::Digit4 if pressed => {
+                    let slot = match key { KeyCode::Digit1 => 0, KeyCode::Digit2 => 1, KeyCode::Digit3 => 2, _ => 3 };
+                    if self.shift_held { self.save_teleport_slot(slot); } else { self.use_teleport_slot(slot); }
+                    return true;
+                }
+                _ => {}
+            }
+        }
+        // Played-back input drives the players instead.
+        if self.capture.is_playing() { return false; }
+        if let WindowEvent::MouseWheel { delta, .. } = event {
+            let notches = match delta {
+                MouseScrollDelta::LineDelta(_, y) => *y as f64,
+                MouseScrollDelta::PixelDelta(pos) => pos.y / 50.0,
+            };
+            if self.wheel(notches) {
+                self.capture.record(CaptureEvent::Wheel(notches));
+                return true;
+            }
+        }
+        let Some((source, inputs, pressed)) = controller_input(event) else { return false };
+        let active = self.active_players();
+        let handled = self.players[..active].iter_mut().any(|p| p.controller.apply(source, &inputs, pressed));
+        if handled { self.capture.record(CaptureEvent::Input { source, inputs, pressed }); }
+        handled
     }
 
-    pub fn update_camera_rotation(&mut self, delta: (f64, f64)) {
+    // The mouse wheel sets the flying speed or the orbit distance.
+    fn wheel(&mut self, notches: f64) -> bool {
+        match &mut self.players[0].mode {
+            MovementMode::Fly => self.players[0].adjust_fly_speed(notches),
+            MovementMode::Orbit(orbit) => orbit.zoom(notches),
+            _ => return false,
+        }
+        true
+    }
+
+    // Device events can arrive long before the next update; summing them here and applying
+    // them at the top of update() keeps look latency to the sampling point.
+    pub fn accumulate_mouse_delta(&mut self, delta: (f64, f64)) {
         if self.mouse_captured {
-            let sensitivity = 0.003;
-            self.camera.yaw += delta.0 as f32 * sensitivity;
-            self.camera.pitch -= delta.1 as f32 * sensitivity;
-            self.camera.pitch = self.camera.pitch.clamp(-1.5, 1.5);
-        }
-    }
-
-    fn check_collision(&self, new_pos: glam::DVec3) -> Option<(glam::DVec3, f64)> {
-        let check_dist = config::PLAYER_RADIUS + config::WALL_THICKNESS;
-        let center_offset = config::WORLD_SIZE / 2.0;
-        let logic_cx = ((new_pos.x as f32 + center_offset) / config::CHUNK_SIZE).floor() as i32;
-        let logic_cz = ((new_pos.z as f32 + center_offset) / config::CHUNK_SIZE).floor() as i32;
-
-        let mut best_hit = None;
-        let mut min_dist_sq = check_dist * check_dist;
-
-        for ox in -1..=1 {
-            for oz in -1..=1 {
-                if let Some(chunk) = self.world.chunks.get(&(logic_cx + ox, logic_cz + oz)) {
-                    if let Some(walls) = chunk.collision.get_walls(new_pos.x as f32, new_pos.z as f32) {
-                        for wall in walls {
-                            if (new_pos.y as f32) > wall.height { continue; }
-                            
-                            let p_flat = glam::DVec2::new(new_pos.x, new_pos.z);
-                            let a = glam::DVec2::new(wall.start.x as f64, wall.start.y as f64);
-                            let b = glam::DVec2::new(wall.end.x as f64, wall.end.y as f64);
-                            let ab = b - a;
-                            let ap = p_flat - a;
-                            let t = (ap.dot(ab) / ab.length_squared()).clamp(0.0, 1.0);
-                            let closest = a + ab * t;
-                            let dist_sq = p_flat.distance_squared(closest);
-                            
-                            if dist_sq < min_dist_sq {
-                                min_dist_sq = dist_sq;
-                                let push = p_flat - closest;
-                                if push.length_squared() > 1e-12 {
-                                    let dist = dist_sq.sqrt();
-                                    best_hit = Some((glam::DVec3::new(push.x/dist, 0.0, push.y/dist), check_dist - dist));
-                                } else {
-                                    best_hit = Some((glam::DVec3::X, check_dist));
-                                }
-                            }
-                        }
+            self.pending_look += glam::DVec2::new(delta.0, delta.1);
+        }
+    }
+
+    fn apply_mouse_look(&mut self, dt: f64, mouse: crate::settings::MouseSettings) {
+        let delta = std::mem::take(&mut self.pending_look);
+        if self.settings.open || self.pause.open {
+            self.look_velocity = glam::DVec2::ZERO;
+            return;
+        }
+        let look = mouse.look(delta, &mut self.look_velocity, dt);
+        if let Some(photo) = &mut self.photo {
+            photo.rotate(look.x as f32, look.y as f32);
+            return;
+        }
+        let player = &mut self.players[0];
+        if let MovementMode::Orbit(orbit) = &mut player.mode {
+            if player.controller.grapple && delta != glam::DVec2::ZERO { orbit.drag(delta); }
+            return;
+        }
+        if look == glam::DVec2::ZERO { return; }
+        let scale = player.look_scale() as f64;
+        player.rotate((look.x * scale) as f32, (look.y * scale) as f32);
+    }
+
+    // Evicts the chunk meshes that have gone unseen longest when estimated VRAM use nears the
+    // budget; ties go to the farthest. If that isn't enough, gives up a step of quality.
+    fn enforce_budget(&mut self) {
+        self.measure_budget();
+        if !self.budget.is_over_high_water() { return; }
+        self.evict_meshes(true);
+        if self.budget.is_over_high_water() { self.degrade(); }
+    }
+
+    fn measure_budget(&mut self) {
+        self.budget.set(Allocation::RenderTargets, self.ctx.render_target_bytes() + self.post.gpu_bytes());
+        self.budget.set(Allocation::ShadowMaps, self.shadows.gpu_bytes() + self.lights.gpu_bytes());
+        self.budget.set(Allocation::ChunkMeshes, self.world.meshes.gpu_bytes());
+    }
+
+    // Evicts down to the low water mark a mesh page at a time, as a page's buffers are only
+    // released once every chunk in it is gone. Pages go by their most recently seen chunk, oldest
+    // first and ties to the farthest; pages with a chunk within GPU_BUDGET_PROTECT_RADIUS are kept.
+    // The meshes of every page picked go back to the mesh cache in one readback, or with `copy`
+    // off aren't read back at all.
+    fn evict_meshes(&mut self, copy: bool) {
+        let eye = self.primary().camera.eye;
+        let eye = glam::Vec2::new(eye.x as f32, eye.z as f32);
+        // Each page's chunks, when the last of them was seen and how far the nearest is.
+        let mut pages = HashMap::new();
+        for (coord, chunk) in &self.world.chunks {
+            let Some(mesh) = &chunk.mesh else { continue };
+            let distance = chunk.center().distance(eye);
+            for page in mesh.pages() {
+                let (coords, seen, nearest) = pages.entry(page).or_insert((Vec::new(), chunk.last_seen, distance));
+                coords.push(*coord);
+                *seen = (*seen).max(chunk.last_seen);
+                *nearest = nearest.min(distance);
+            }
+        }
+        let mut pages: Vec<_> = pages.into_iter().filter(|(_, (_, _, nearest))| *nearest > config::GPU_BUDGET_PROTECT_RADIUS).collect();
+        pages.sort_by(|(_, (_, seen_a, nearest_a)), (_, (_, seen_b, nearest_b))| seen_a.cmp(seen_b).then(nearest_b.total_cmp(nearest_a)));
+
+        let mut excess = self.budget.used().saturating_sub(self.budget.low_water_bytes());
+        let mut coords = Vec::new();
+        for (page, (page_coords, _, _)) in pages {
+            if excess == 0 { break; }
+            excess = excess.saturating_sub(self.world.meshes.page_bytes(page));
+            coords.extend(page_coords);
+        }
+        let before = self.world.meshes.gpu_bytes();
+        let evicted = self.world.evict_meshes(&coords, &mut self.mesh_cache, &self.ctx.device, &self.ctx.queue, copy);
+        self.budget.set(Allocation::ChunkMeshes, self.world.meshes.gpu_bytes());
+        if evicted == 0 { return; }
+        log::warn!(
+            "GPU budget exceeded: evicted {} least recently seen chunk meshes ({} MB freed, {} / {} MB in use)",
+            evicted, (before - self.world.meshes.gpu_bytes()) / (1024 * 1024), self.budget.used() / (1024 * 1024), self.budget.budget / (1024 * 1024),
+        );
+    }
+
+    // Gives up the next step of quality that still has something to give; false once none do.
+    fn degrade(&mut self) -> bool {
+        while let Some(step) = self.budget.next_degradation() {
+            let applied = match step {
+                Degradation::LodDistance => {
+                    self.budget.lod_scale *= config::GPU_BUDGET_LOD_SCALE;
+                    log::warn!("GPU memory low: drawing simplified chunk meshes from {:.0} m", self.lod_distance());
+                    true
+                }
+                Degradation::ShadowSize => {
+                    let size = self.shadows.size() / 2;
+                    let shrink = size >= config::GPU_BUDGET_SHADOW_MIN;
+                    if shrink {
+                        self.shadows.resize(&self.ctx.device, size);
+                        log::warn!("GPU memory low: shadow cascades reduced to {}x{}", size, size);
+                    }
+                    shrink
+                }
+                Degradation::SceneScale => {
+                    let capped = self.render_scale.cap(self.render_scale.max - config::GPU_BUDGET_SCALE_STEP);
+                    if capped {
+                        self.resize_scene_targets();
+                        log::warn!("GPU memory low: scene and reflection targets capped at {:.0}% resolution", self.render_scale.max * 100.0);
                     }
+                    capped
                 }
+            };
+            if applied {
+                self.measure_budget();
+                return true;
             }
         }
-        best_hit
+        false
+    }
+
+    // Frees what it can after the surface ran out of memory: chunk meshes down to the low
+    // water mark and a step of quality. False when there was nothing left to free. The meshes
+    // aren't read back, as that needs a buffer; the loader builds them again instead.
+    pub fn recover_from_out_of_memory(&mut self) -> bool {
+        self.measure_budget();
+        let used = self.world.meshes.gpu_bytes();
+        self.evict_meshes(false);
+        self.degrade() || self.world.meshes.gpu_bytes() < used
+    }
+
+    fn lod_distance(&self) -> f32 {
+        config_file::get().graphics.lod_distance * self.budget.lod_scale
+    }
+
+    // Queues evicted chunk meshes within the draw distance for upload again, nearest first,
+    // while they fit below the budget's low water mark so they aren't evicted straight back.
+    // Meshes without a copy in the cache are asked of the loader.
+    fn restore_evicted(&mut self) {
+        self.mesh_cache.collect(&self.ctx.device);
+        let eye = self.primary().camera.eye;
+        let eye = glam::Vec2::new(eye.x as f32, eye.z as f32);
+        let reach = config_file::get().graphics.draw_distance;
+        let mut evicted: Vec<((i32, i32), f32)> = self.world.chunks.iter()
+            .filter(|(coord, c)| c.mesh.is_none() && self.mesh_cache.size(**coord).is_some() && !self.uploads.is_pending(**coord))
+            .map(|(coord, c)| (*coord, c.center().distance(eye)))
+            .filter(|(_, dist)| *dist <= reach)
+            .collect();
+        if evicted.is_empty() { return; }
+        evicted.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        let mut room = self.budget.low_water_bytes().saturating_sub(self.budget.used() + self.uploads.backlog().1);
+        for (coord, _) in evicted.into_iter().take(config::CHUNK_UPLOAD_COUNT) {
+            let Some(bytes) = self.mesh_cache.size(coord).filter(|&bytes| bytes <= room) else { break };
+            room -= bytes;
+            match self.mesh_cache.take(coord) {
+                Some(mesh) => self.uploads.push(mesh),
+                None => {
+                    self.streaming.requeue(&[coord]);
+                    self.restream_requests.push(coord);
+                }
+            }
+        }
+    }
+
+    // Takes a chunk from the loader, on the first stream or coming back after an unload, and
+    // queues its mesh for upload. Chunks past the unload distance, as most are on a reload, are
+    // left unloaded instead.
+    pub fn insert_chunk(&mut self, chunk: ChunkData) {
+        self.streaming.arrived(chunk.coord);
+        self.mesh_cache.discard(chunk.coord);
+        let half = glam::Vec2::splat(config_file::chunk_size() * 0.5);
+        if self.anchor_distance(chunk_origin(chunk.coord) + half) > config_file::get().graphics.draw_distance + config::CHUNK_UNLOAD_MARGIN {
+            self.unloaded.insert(chunk.coord);
+            return;
+        }
+        self.unloaded.remove(&chunk.coord);
+        if let Some(mesh) = self.world.insert_chunk(chunk) { self.uploads.push(mesh); }
+    }
+
+    // Distance from `center` to the nearest player, or to where a spawn or teleport is headed.
+    // Chunks stay loaded around all of these.
+    fn anchor_distance(&self, center: glam::Vec2) -> f32 {
+        let eyes = self.players.iter().map(|p| glam::Vec2::new(p.camera.eye.x as f32, p.camera.eye.z as f32));
+        eyes.chain(self.pending_spawn).chain(self.teleport.as_ref().map(|t| t.target))
+            .map(|anchor| anchor.distance(center))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    // Unloads the chunks more than CHUNK_UNLOAD_MARGIN past the draw distance from every player,
+    // collision and all, and asks for them again once a player is back within the draw
+    // distance. The margin between the two keeps a chunk near the edge from going back and forth.
+    fn unload_far_chunks(&mut self) {
+        let reach = config_file::get().graphics.draw_distance;
+        let far: Vec<(i32, i32)> = self.world.chunks.iter()
+            .filter(|(_, chunk)| self.anchor_distance(chunk.center()) > reach + config::CHUNK_UNLOAD_MARGIN)
+            .map(|(coord, _)| *coord)
+            .collect();
+        for coord in far {
+            self.world.remove_chunk(coord);
+            self.mesh_cache.discard(coord);
+            self.unloaded.insert(coord);
+        }
+
+        let half = glam::Vec2::splat(config_file::chunk_size() * 0.5);
+        let back: Vec<(i32, i32)> = self.unloaded.iter().copied().filter(|&coord| self.anchor_distance(chunk_origin(coord) + half) <= reach).collect();
+        if back.is_empty() { return; }
+        for coord in &back { self.unloaded.remove(coord); }
+        self.streaming.requeue(&back);
+        self.restream_requests.extend(back);
+    }
+
+    // Moves the players to `target` (local x, z) once its chunk has loaded.
+    pub fn spawn_at(&mut self, target: glam::Vec2) {
+        self.pending_spawn = Some(target);
+        self.request_chunk_at(target);
+        self.place_pending_spawn(false);
+    }
+
+    // Asks the loader again for the chunk under `target` if it was unloaded, so a spawn or
+    // teleport there doesn't wait on it forever.
+    fn request_chunk_at(&mut self, target: glam::Vec2) {
+        let coord = World::chunk_coord_at(target.x, target.y);
+        if !self.unloaded.remove(&coord) { return; }
+        self.streaming.requeue(&[coord]);
+        self.restream_requests.push(coord);
+    }
+
+    // Places the players at the pending spawn if its chunk is in, or regardless with `force`.
+    pub fn place_pending_spawn(&mut self, force: bool) {
+        let Some(target) = self.pending_spawn else { return };
+        if !force && !self.world.chunks.contains_key(&World::chunk_coord_at(target.x, target.y)) { return; }
+        self.pending_spawn = None;
+        let mut eye = crate::spawn::find_spawn_eye(&self.world, target);
+        eye.y = eye.y.max(config_file::get().map.spawn_height + config::EYE_HEIGHT);
+        for (i, player) in self.players.iter_mut().enumerate() {
+            player.teleport(if i == 0 { eye } else { eye + config::PLAYER_TWO_SPAWN_OFFSET });
+        }
+        log::info!("Spawned at ({:.0}, {:.0})", eye.x, eye.z);
+    }
+
+    // Fades out, moves the players to (lat, lon) on the map and fades back in.
+    pub fn teleport(&mut self, lat: f64, lon: f64) {
+        let destination = SpawnPoint::Coordinates { lat, lon };
+        if let Some(target) = destination.resolve(self.map_origin, None) { self.teleport_local(target); }
+    }
+
+    // Same for a local (x, z) position. Ignored while another teleport is underway.
+    pub fn teleport_local(&mut self, target: glam::Vec2) {
+        if self.teleport.is_some() { return; }
+        self.teleport = Some(Teleport { target, fade: 0.0, arrived: false });
+        self.request_chunk_at(target);
+    }
+
+    fn update_teleport(&mut self, dt: f64) {
+        let Some(teleport) = &mut self.teleport else { return };
+        let step = (dt / config::TELEPORT_FADE_TIME) as f32;
+        if teleport.arrived {
+            teleport.fade -= step;
+            if teleport.fade <= 0.0 { self.teleport = None; }
+        } else if teleport.fade < 1.0 {
+            teleport.fade = (teleport.fade + step).min(1.0);
+            // Black: hand over to the spawn logic, which waits for the chunk to load.
+            if teleport.fade >= 1.0 { self.pending_spawn = Some(teleport.target); }
+        } else if self.pending_spawn.is_none() {
+            teleport.arrived = true;
+        }
+    }
+
+    // Goes to the spot saved in `slot`, or its preset in config::TELEPORT_SLOTS.
+    fn use_teleport_slot(&mut self, slot: usize) {
+        if let Some(target) = self.teleport_slots[slot] {
+            self.teleport_local(target);
+        } else if let Some((lat, lon)) = config::TELEPORT_SLOTS[slot] {
+            self.teleport(lat, lon);
+        } else {
+            log::info!("Teleport slot {} is empty (Shift+{} saves the current spot)", slot + 1, slot + 1);
+            self.notifications.push(format!("Teleport slot {} is empty (Shift+{} saves)", slot + 1, slot + 1));
+        }
+    }
+
+    fn teleport_slot_label(&self, slot: usize) -> String {
+        match (self.teleport_slots[slot], config::TELEPORT_SLOTS[slot]) {
+            (Some(target), _) => format!("{}. Saved spot ({:.0}, {:.0})", slot + 1, target.x, target.y),
+            (None, Some((lat, lon))) => format!("{}. {:.5}, {:.5}", slot + 1, lat, lon),
+            (None, None) => format!("{}. Empty (Shift+{} saves)", slot + 1, slot + 1),
+        }
+    }
+
+    fn save_teleport_slot(&mut self, slot: usize) {
+        let eye = self.primary().camera.eye;
+        self.teleport_slots[slot] = Some(glam::Vec2::new(eye.x as f32, eye.z as f32));
+        log::info!("Saved teleport slot {} at ({:.0}, {:.0})", slot + 1, eye.x, eye.z);
+        self.notifications.push(format!("Saved teleport slot {}", slot + 1));
+    }
+
+    // Parks each player's car on the road nearest to them, once one has streamed in.
+    fn spawn_cars(&mut self) {
+        if self.pending_spawn.is_some() { return; }
+        while let Some(player) = self.players.get(self.cars.len()) {
+            let [r, g, b, _] = config::PLAYER_COLORS[self.cars.len() % config::PLAYER_COLORS.len()];
+            let Some(car) = Car::spawn_near(&self.world, player.camera.eye, [r, g, b]) else { break };
+            self.cars.push(car);
+        }
     }
 
     pub fn update(&mut self) {
+        let _scope = profiler::scope("update");
         let now = Instant::now();
-        let dt = now.duration_since(self.last_frame_time).as_secs_f64().clamp(0.0001, 0.1);
+        let frame_time = now.duration_since(self.last_frame_time).as_secs_f64();
+        let mut dt = frame_time.clamp(0.0001, 0.1);
         self.last_frame_time = now;
 
-        let (sin_yaw, cos_yaw) = self.camera.yaw.sin_cos();
-        let forward = glam::DVec3::new(cos_yaw as f64, 0.0, sin_yaw as f64).normalize();
-        let right = glam::DVec3::new(-(sin_yaw as f64), 0.0, cos_yaw as f64).normalize();
-
-        let mut input_dir = glam::DVec3::ZERO;
-        if self.camera_controller.move_fwd { input_dir += forward; }
-        if self.camera_controller.move_back { input_dir -= forward; }
-        if self.camera_controller.move_right { input_dir += right; }
-        if self.camera_controller.move_left { input_dir -= right; }
-        if input_dir.length_squared() > 0.0 { input_dir = input_dir.normalize(); }
-        
-        self.velocity.x = input_dir.x * config::MOVE_SPEED;
-        self.velocity.z = input_dir.z * config::MOVE_SPEED;
-        self.velocity.y -= config::GRAVITY * dt;
-        self.velocity.y = self.velocity.y.max(config::TERMINAL_VELOCITY);
-
-        if self.on_ground && self.camera_controller.jump {
-            self.velocity.y = config::JUMP_FORCE;
-            self.on_ground = false;
+        #[cfg(feature = "gamepad")]
+        if let Some(pad) = &mut self.gamepad
+            && pad.poll(&mut self.players[1].controller) && !self.split_screen {
+            self.toggle_split_screen();
         }
+        #[cfg(feature = "gamepad")]
+        let pad = self.gamepad.as_ref().filter(|pad| pad.is_connected()).map(|_| capture::PadState::read(&self.players[1].controller));
+        #[cfg(not(feature = "gamepad"))]
+        let pad = None;
+        self.capture.record_frame(dt, self.pending_look, pad);
+        let mouse = self.capture.mouse().unwrap_or(self.settings.settings.mouse);
+        if let Some((frame, quit)) = self.capture.next_frame() { dt = self.play_frame(frame, quit); }
 
-        let mut remaining_dt = dt;
-        while remaining_dt > 0.0 {
-            let step = remaining_dt.min(config::PHYSICS_STEP_SIZE);
-            let mut next_pos = self.camera.eye + self.velocity * step;
-            
-            for _ in 0..config::MAX_PHYSICS_STEPS {
-                if let Some((normal, depth)) = self.check_collision(next_pos) {
-                    let dot = self.velocity.dot(normal);
-                    if dot < 0.0 { self.velocity -= normal * dot; }
-                    next_pos += normal * (depth + 0.0001); 
-                } else { break; }
+        self.stats_overlay.record_frame(dt);
+        self.notifications.update(dt);
+        self.watch_config(dt);
+        if self.render_scale.update(dt) { self.resize_scene_targets(); }
+        let eye = self.primary().camera.eye;
+        let upload_scope = profiler::scope("upload");
+        let upload_started = Instant::now();
+        self.uploads.pump(&mut self.world, &self.ctx.device, &self.ctx.queue, glam::Vec2::new(eye.x as f32, eye.z as f32));
+        self.enforce_budget();
+        self.restore_evicted();
+        self.unload_far_chunks();
+        let upload_time = upload_started.elapsed().as_secs_f64();
+        drop(upload_scope);
+        self.apply_mouse_look(dt, mouse);
+        let screen_shake = self.settings.settings.screen_shake;
+        for player in &mut self.players { player.screen_shake = screen_shake; }
+        let active = self.active_players();
+        if self.pause.open {
+            // Paused: nothing moves, the views are only rewritten for resizes.
+        } else if let Some(photo) = &mut self.photo {
+            photo.fly(&self.players[0].controller, dt);
+            self.post.set_depth_of_field(photo.focus, photo.blur);
+            self.post.set_exposure_bias(photo.exposure);
+        } else {
+            self.simulate(dt);
+        }
+        if let Some(bench) = &mut self.benchmark {
+            if !self.pause.open { bench.record(frame_time, upload_time, self.uploads.backlog().0, self.budget.used()); }
+            if !self.cinematic.is_playing() {
+                bench.finish(&self.ctx.adapter_info);
+                self.benchmark = None;
+                self.quit_requested = true;
             }
+        }
+
+        let jitter = self.post.jitter();
+        for i in 0..active {
+            let viewport = self.scene_viewport(i);
+            let camera = self.view_camera(i);
+            self.views[i].underwater = self.world.water_surface(camera.eye.x as f32, camera.eye.z as f32).is_some_and(|s| camera.eye.y < s as f64);
+            self.views[i].write(&self.ctx.queue, &camera, viewport, &self.time_of_day, &self.weather, jitter);
+            self.post.update_taa_view(&self.ctx.queue, i, camera.build_view_projection_matrix(), viewport);
+            self.shadows.update(&self.ctx.queue, i, &camera, self.time_of_day.sun_direction());
+            self.lights.update(&self.ctx.queue, i, &camera, &self.world, self.time_of_day.daylight());
+            if i == 0 && self.photo.is_some() { self.highlight.clear(i); } else { self.highlight.update(i, &self.players[i].camera, &self.world); }
+            self.crosshairs.update(i, &self.players[i].camera, &self.world);
+            self.speedometer.update(i, self.players[i].camera.eye, dt);
+        }
+    }
 
-            if next_pos.y <= 1.8 {
-                next_pos.y = 1.8;
-                self.velocity.y = 0.0;
-                self.on_ground = true;
-            } else { self.on_ground = false; }
+    // Advances everything that moves on its own: the clock, weather, teleports, playback and physics.
+    fn simulate(&mut self, dt: f64) {
+        self.time_of_day.update(dt);
+        self.weather.update(dt);
 
-            self.camera.eye = next_pos;
-            remaining_dt -= step;
+        // Physics runs in fixed ticks so jumps and wall slides don't depend on the framerate;
+        // the leftover time interpolates the rendered cameras between the last two ticks.
+        let active = self.active_players();
+        self.update_teleport(dt);
+        self.cinematic.update(dt);
+        self.place_pending_spawn(false);
+        self.spawn_cars();
+        let tick = 1.0 / config::PHYSICS_TICK_RATE;
+        let mut occupied = vec![false; self.cars.len()];
+        for player in &self.players {
+            if let MovementMode::Drive(car) = player.mode { occupied[car] = true; }
+        }
+        self.physics_accumulator += dt;
+        while self.physics_accumulator >= tick {
+            self.physics_accumulator -= tick;
+            self.world.tick(tick);
+            for car in &mut self.cars { car.begin_tick(); }
+            for (i, player) in self.players[..active].iter_mut().enumerate() {
+                player.begin_tick();
+                if self.game_mode.is_frozen(i) { continue; }
+                if player.interact_pressed() { player.use_car(&self.cars, &mut occupied); }
+                match player.mode {
+                    MovementMode::Walk => player.update(&self.world, tick),
+                    MovementMode::Fly => player.fly(tick),
+                    MovementMode::Climb(ladder) => player.climb(&self.world, ladder, tick),
+                    MovementMode::Orbit(_) => {}
+                    MovementMode::Drive(car) => {
+                        self.cars[car].drive(&player.controller, &self.world, tick);
+                        player.ride(&self.cars[car], tick);
+                    }
+                }
+                player.update_fov(tick);
+                player.update_view_effects(tick);
+            }
         }
+        self.game_mode.update(&self.players[..active], dt);
+        self.replay.update(dt, &self.players[0].camera, &self.players[0].controller);
+        self.announce_district();
+    }
 
-        self.camera_uniform.view_proj = self.camera.build_view_projection_matrix().to_cols_array_2d();
-        self.camera_uniform.camera_pos = [self.camera.eye.x as f32, self.camera.eye.y as f32, self.camera.eye.z as f32, 0.0];
-        self.ctx.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+    // "Entered: ..." when player one comes within DISTRICT_RADIUS of a park or neighbourhood.
+    // The last one is only left behind at 1.5x that, so walking its edge doesn't repeat it.
+    fn announce_district(&mut self) {
+        let eye = self.players[0].camera.eye;
+        let position = glam::Vec2::new(eye.x as f32, eye.z as f32);
+        if let Some((_, center)) = &self.current_district && center.distance(position) < config::DISTRICT_RADIUS * 1.5 { return; }
+        let nearest = self.places.nearest_district(position, config::DISTRICT_RADIUS).map(|(name, center)| (name.to_string(), center));
+        if let Some((name, _)) = &nearest { self.notifications.push(format!("Entered: {}", name)); }
+        self.current_district = nearest;
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         let output = self.ctx.surface.get_current_texture()?;
+        let frame_start = Instant::now();
+        let render_scope = profiler::scope("render");
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
         let mut encoder = self.ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        
+        self.profiler.begin_frame(&self.ctx.device, &mut encoder);
+
+        let screen = [self.ctx.config.width as f32, self.ctx.config.height as f32];
+        let [r, g, b] = self.time_of_day.horizon_color();
+        let sky = wgpu::Color { r: r as f64, g: g as f64, b: b as f64, a: 1.0 };
+        // Overlays use window pixels, everything up to the composite the scaled scene targets.
+        let viewports: Vec<[f32; 4]> = (0..self.active_players()).map(|i| self.viewport(i)).collect();
+        let scene_viewports: Vec<[f32; 4]> = (0..self.active_players()).map(|i| self.scene_viewport(i)).collect();
+        // Flythroughs and photos are taken without overlays.
+        let show_ui = !self.cinematic.hides_ui() && self.photo.is_none();
+        let show_minimap = show_ui && self.minimap.visible && !self.map_view;
+        if show_minimap {
+            let camera = self.view_camera(0);
+            self.minimap.prepare(&self.ctx.queue, &self.world, &mut self.text, screen, viewports[0], &camera);
+        }
+        if show_ui {
+            self.game_mode.draw_overlay(&mut self.hud, &mut self.text, &viewports, screen, self.show_scoreboard);
+            if self.map_view { self.draw_map_markers(&viewports); }
+            for (player, &[x, y, _, h]) in self.players.iter().zip(&viewports).filter(|(p, _)| p.mode == MovementMode::Fly) {
+                self.text.text(&format!("Noclip {:.0} m/s (F)", player.fly_speed), [x + 12.0, y + h - 28.0], 16.0, [1.0, 1.0, 1.0, 0.8]);
+            }
+            for (player, &[x, y, _, h]) in self.players.iter().zip(&viewports) {
+                let MovementMode::Drive(car) = player.mode else { continue };
+                self.text.text(&format!("Driving {:.0} km/h", self.cars[car].speed.abs() * 3.6), [x + 12.0, y + h - 28.0], 16.0, [1.0, 1.0, 1.0, 0.8]);
+            }
+            for (player, &[x, y, _, h]) in self.players.iter().zip(&viewports).filter(|(p, _)| p.gliding) {
+                let airspeed = glam::DVec2::new(player.velocity.x, player.velocity.z).length();
+                self.text.text(&format!("Gliding {:.0} m/s", airspeed), [x + 12.0, y + h - 28.0], 16.0, [1.0, 1.0, 1.0, 0.8]);
+            }
+            if self.show_labels && !self.map_view {
+                for (i, &viewport) in viewports.iter().enumerate() {
+                    let camera = self.view_camera(i);
+                    labels::draw(&mut self.hud, &mut self.text, &self.places, &self.world, &camera, viewport);
+                }
+            }
+            if !self.map_view {
+                for (i, &viewport) in viewports.iter().enumerate() {
+                    let camera = self.view_camera(i);
+                    self.waypoints.draw(&mut self.hud, &mut self.text, &camera, viewport, self.map_origin);
+                }
+            }
+            if self.speedometer.visible && !self.map_view {
+                for (i, &viewport) in viewports.iter().enumerate() {
+                    self.speedometer.draw(&mut self.hud, &mut self.text, viewport, i, self.players[i].camera.eye, &self.world);
+                }
+            }
+            if config::COMPASS && !self.map_view {
+                for (i, &viewport) in viewports.iter().enumerate() {
+                    let camera = self.view_camera(i);
+                    let forward = camera.forward();
+                    let coords = map_loader::local_to_coords(camera.eye.x, camera.eye.z, self.map_origin);
+                    compass::draw(&mut self.hud, &mut self.text, viewport, compass::bearing(forward.x, forward.z), coords);
+                }
+            }
+            if let Some(status) = self.replay.status().or_else(|| self.capture.status()) {
+                // Below the compass when it's shown.
+                let y = if config::COMPASS && !self.map_view { 70.0 } else { 12.0 };
+                self.text.text_centered(&status, screen[0] * 0.5, y, 16.0, [1.0, 1.0, 1.0, 0.9]);
+            }
+            if self.stats_overlay.visible { self.draw_stats_overlay(screen); }
+            self.streaming.draw_hud(&mut self.hud, &mut self.text, screen);
+            if self.debug_mode != DebugMode::Off {
+                self.text.text(&format!("Debug view: {} (Shift+F3)", self.debug_mode.name()), [12.0, 12.0], 16.0, [1.0, 1.0, 0.4, 1.0]);
+            }
+        }
+        if let Some(teleport) = &self.teleport && show_ui {
+            self.hud.rect([0.0, 0.0], screen, [0.0, 0.0, 0.0, teleport.fade]);
+        }
+        // Below the debug view line in the top-left corner.
+        if show_ui { self.notifications.draw(&mut self.hud, &mut self.text, 40.0); }
+        // The settings menu opened from the pause menu replaces it over the dimmed scene.
+        if self.pause.open {
+            self.hud.rect([0.0, 0.0], screen, [0.0, 0.0, 0.0, 0.55]);
+            if !self.settings.open {
+                let slots = std::array::from_fn(|slot| self.teleport_slot_label(slot));
+                let waypoints = self.waypoints.rows(self.view_camera(0).eye, self.map_origin);
+                self.pause.draw(&mut self.hud, &mut self.text, screen, &slots, &waypoints);
+            }
+        }
+        if self.settings.open { self.settings.draw(&mut self.hud, &mut self.text, screen); }
+        if self.console.open { self.console.draw(&mut self.hud, &mut self.text, screen); }
+        self.hud.prepare(&self.ctx.device, &self.ctx.queue, screen);
+        self.text.prepare(&self.ctx.device, &self.ctx.queue, screen);
+
+        let sun_dir = self.time_of_day.sun_direction();
+        let mut stats = RenderStats::default();
+        self.shadows.prepare(&self.ctx.device, &self.ctx.queue, &self.world, sun_dir);
+        for i in 0..viewports.len() {
+            stats.draw_calls += self.shadows.render(&mut encoder, i, &self.world, sun_dir);
+        }
+        self.lights.compute(&mut encoder, viewports.len());
+        self.profiler.mark(&mut encoder, GpuSpan::Shadows);
+
+        // Cull before the pass so this frame's occlusion test boxes can be uploaded first.
+        self.occlusion.collect_results(&self.ctx.device);
+        // Adjusted culling distance (Draw Dist + Chunk Radius Buffer) to prevent popping
+        let chunk_radius = (config_file::chunk_size() * config_file::chunk_size() * 2.0).sqrt() * 0.5;
+        let safe_draw_dist_sq = (config_file::get().graphics.draw_distance + chunk_radius).powi(2);
+        let lod_distance = self.lod_distance();
+        // Occlusion results lag a frame or two, so chunks this close are always drawn.
+        let occlusion_safe_dist_sq = (chunk_radius * 2.0).powi(2);
+        let mut draws = Vec::with_capacity(viewports.len());
+        let mut water_draws = Vec::with_capacity(viewports.len());
+        let mut tests = Vec::with_capacity(viewports.len());
+        self.chunk_fades.begin_frame();
+        let now = Instant::now();
+        for i in 0..viewports.len() {
+            let camera = self.view_camera(i);
+            let view_proj = camera.build_view_projection_matrix();
+            let frustum = Frustum::from_mat4(view_proj);
+            let cam_pos_vec = glam::Vec2::new(camera.eye.x as f32, camera.eye.z as f32);
+
+            let first_test = self.occlusion.test_count();
+            let mut visible = Vec::new();
+            let mut water = Vec::new();
+            for (coord, chunk) in &mut self.world.chunks {
+                let Some(mesh) = &chunk.mesh else { continue };
+
+                // Distance Cull
+                let dist_sq = cam_pos_vec.distance_squared(chunk.center());
+                if dist_sq > safe_draw_dist_sq {
+                    stats.culled_chunks += 1;
+                    continue;
+                }
+                let fade = self.chunk_fades.in_range(i, *coord, mesh.uploaded);
+
+                // Frustum Cull
+                let min = glam::Vec3::new(chunk.min.x, config::CHUNK_MIN_Y, chunk.min.y);
+                let max = glam::Vec3::new(chunk.max.x, config::CHUNK_MAX_Y, chunk.max.y);
+                if !frustum.intersects_aabb(&min, &max) {
+                    stats.culled_chunks += 1;
+                    continue;
+                }
+
+                // Occlusion Cull, tested against the chunk's actual height. Results from the
+                // player's own view don't apply to the map view, so it skips the test.
+                if dist_sq > occlusion_safe_dist_sq && !self.map_view {
+                    let (min, max) = (glam::Vec3::new(chunk.min.x, chunk.min_y, chunk.min.y), glam::Vec3::new(chunk.max.x, chunk.max_y, chunk.max.y));
+                    self.occlusion.test(i, *coord, min, max);
+                    if self.occlusion.is_occluded(i, *coord) {
+                        stats.occluded_chunks += 1;
+                        continue;
+                    }
+                }
+
+                let range = mesh.indices_at(dist_sq.sqrt(), lod_distance);
+                if range.start > 0 { stats.lod_chunks += 1; }
+                stats.drawn_chunks += 1;
+                stats.triangles += range.len() / 3;
+                chunk.last_seen = now;
+                visible.push((dist_sq, mesh.draw(range), (chunk.origin() - camera.eye).as_vec3(), fade));
+                water.extend(mesh.water_draw());
+            }
+            // Nearest first, so the buildings up close fill the depth buffer and early-Z skips
+            // shading what they hide.
+            visible.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+            for &(_, _, relative_origin, fade) in &visible { self.chunk_fades.push(relative_origin, fade); }
+            stats.draw_calls += visible.len() + water.len();
+            draws.push(visible.into_iter().map(|(_, draw, _, _)| draw).collect());
+            water_draws.push(water);
+            tests.push(first_test..self.occlusion.test_count());
+        }
+        self.occlusion.prepare(&self.ctx.device, &self.ctx.queue);
+        self.chunk_draws.build(draws);
+        self.chunk_draws.prepare(&self.ctx.device, &self.ctx.queue);
+        self.chunk_fades.prepare(&self.ctx.device, &self.ctx.queue);
+        self.water.draws.build(water_draws);
+        self.water.prepare(&self.ctx.device, &self.ctx.queue);
+        self.weather_particles.prepare(&self.ctx.queue, &self.weather);
+        // Rendered cameras trail the simulation by the unsimulated remainder of a tick.
+        let elevator_time = self.world.clock - (1.0 / config::PHYSICS_TICK_RATE - self.physics_accumulator);
+        self.dynamic_meshes.clear();
+        for chunk in self.world.chunks.values().filter(|c| c.mesh.is_some()) {
+            for elevator in &chunk.elevators { elevator.push_mesh(&mut self.dynamic_meshes, elevator_time); }
+        }
+        let alpha = self.physics_accumulator * config::PHYSICS_TICK_RATE;
+        for car in &self.cars { car.push_mesh(&mut self.dynamic_meshes, alpha); }
+        // Each view hides its own player's avatar unless it looks on from outside.
+        let mut hidden_avatars = Vec::with_capacity(scene_viewports.len());
+        for (player, [r, g, b, _]) in self.players[..scene_viewports.len()].iter().zip(config::PLAYER_COLORS) {
+            let first = self.dynamic_meshes.box_count();
+            player.push_avatar(&mut self.dynamic_meshes, alpha, [r, g, b]);
+            let own_view = !player.third_person && !self.map_view && !matches!(player.mode, MovementMode::Orbit(_));
+            hidden_avatars.push(if own_view { first..self.dynamic_meshes.box_count() } else { 0..0 });
+        }
+        let eyes: Vec<glam::DVec3> = (0..scene_viewports.len()).map(|i| self.view_camera(i).eye).collect();
+        self.dynamic_meshes.prepare(&self.ctx.device, &self.ctx.queue, &eyes);
+        self.highlight.prepare(&self.ctx.queue, &eyes);
+        self.streaming.prepare(&self.ctx.queue);
+        self.crosshairs.prepare(&self.ctx.queue, self.settings.settings.crosshair, self.scene_scale());
+        let vignette = self.settings.settings.damage_vignette;
+        self.vignette.prepare(&self.ctx.queue, self.players.iter().map(|p| if vignette { p.hurt } else { 0.0 }));
+        self.build_debug_lines();
+        let chunk_pipeline = match (self.debug_mode, &self.wireframe_pipeline) {
+            (DebugMode::Wireframe, Some(wireframe)) => wireframe,
+            (DebugMode::Normals, _) => &self.normals_pipeline,
+            _ => &self.render_pipeline,
+        };
+
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.ctx.msaa_texture, resolve_target: Some(&view),
-                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
-                })],
+                color_attachments: &self.post.scene_attachments(sky),
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                     view: &self.ctx.depth_texture,
                     depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: wgpu::StoreOp::Store }),
                     stencil_ops: None,
                 }),
-                timestamp_writes: None, occlusion_query_set: None,
+                timestamp_writes: None, occlusion_query_set: self.occlusion.query_set(),
             });
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-
-            let view_proj = self.camera.build_view_projection_matrix();
-            let frustum = Frustum::from_mat4(view_proj);
-            let cam_pos_vec = glam::Vec2::new(self.camera.eye.x as f32, self.camera.eye.z as f32);
-            
-            // Adjusted culling distance (Draw Dist + Chunk Radius Buffer) to prevent popping
-            let chunk_radius = (config::CHUNK_SIZE * config::CHUNK_SIZE * 2.0).sqrt() * 0.5;
-            let safe_draw_dist_sq = (config::DRAW_DISTANCE + chunk_radius).powi(2);
+            for (i, &[x, y, w, h]) in scene_viewports.iter().enumerate() {
+                render_pass.set_viewport(x, y, w, h, 0.0, 1.0);
+                render_pass.set_bind_group(0, &self.views[i].bind_group, &[]);
+                render_pass.set_bind_group(1, &self.facades.bind_group, &[]);
+                render_pass.set_bind_group(2, self.shadows.bind_group(i), &[]);
+                render_pass.set_bind_group(3, self.lights.bind_group(i), &[]);
 
-            for chunk in self.world.chunks.values() {
-                // Distance Cull
-                let cx = (chunk.min.x + chunk.max.x) * 0.5;
-                let cz = (chunk.min.y + chunk.max.y) * 0.5;
-                if cam_pos_vec.distance_squared(glam::Vec2::new(cx, cz)) > safe_draw_dist_sq { continue; }
+                // Sky first; it doesn't write depth so the scene draws over it.
+                render_pass.set_pipeline(&self.sky_pipeline);
+                render_pass.draw(0..3, 0..1);
 
-                // Frustum Cull
-                let min = glam::Vec3::new(chunk.min.x, config::CHUNK_MIN_Y, chunk.min.y);
-                let max = glam::Vec3::new(chunk.max.x, config::CHUNK_MAX_Y, chunk.max.y);
-                if frustum.intersects_aabb(&min, &max) {
-                    render_pass.set_vertex_buffer(0, chunk.vertex_buffer.slice(..));
-                    render_pass.set_index_buffer(chunk.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                    render_pass.draw_indexed(0..chunk.index_count, 0, 0..1);
+                render_pass.set_pipeline(chunk_pipeline);
+                render_pass.set_vertex_buffer(1, self.chunk_fades.buffer().slice(..));
+                for batch in self.chunk_draws.batches.iter().filter(|b| b.view == i) {
+                    self.chunk_draws.draw(&mut render_pass, &self.world.meshes, batch);
                 }
+                self.dynamic_meshes.draw(&mut render_pass, i, hidden_avatars[i].clone());
+                self.highlight.draw(&mut render_pass, &self.world.meshes, i);
+                self.streaming.draw(&mut render_pass);
+
+                self.debug_lines.draw(&mut render_pass);
+
+                // Test boxes go last so they see this view's complete depth.
+                self.occlusion.draw_tests(&mut render_pass, tests[i].clone());
+            }
+        }
+        self.occlusion.resolve(&mut encoder);
+        self.profiler.mark(&mut encoder, GpuSpan::Scene);
+
+        // Precipitation shares the water pass: both test against the finished scene depth.
+        if !self.water.is_empty() || !self.weather_particles.is_empty() {
+            let mut water_pass = self.post.water_pass(&mut encoder);
+            for (i, &[x, y, w, h]) in scene_viewports.iter().enumerate() {
+                water_pass.set_viewport(x, y, w, h, 0.0, 1.0);
+                water_pass.set_bind_group(0, &self.views[i].bind_group, &[]);
+                self.water.draw(&mut water_pass, &self.world.meshes, i);
+                self.weather_particles.draw(&mut water_pass);
+            }
+        }
+
+        self.profiler.mark(&mut encoder, GpuSpan::Water);
+        self.stats = stats;
+
+        self.post.resolve_taa(&mut encoder, &scene_viewports);
+        self.post.ssao(&mut encoder, scene_viewports.iter().copied().zip(self.views.iter().map(|v| &v.bind_group)));
+        self.post.update_exposure(&self.ctx.queue, &mut encoder);
+        self.profiler.mark(&mut encoder, GpuSpan::Post);
+
+        {
+            let mut composite_pass = self.post.composite(&mut encoder, &view);
+            // Per-viewport HUD; the map view marks players instead of a crosshair.
+            for (i, &[x, y, w, h]) in viewports.iter().enumerate().filter(|&(i, _)| !self.map_view && (i > 0 || self.photo.is_none())) {
+                composite_pass.set_viewport(x, y, w, h, 0.0, 1.0);
+                self.vignette.draw(&mut composite_pass, i);
+                composite_pass.set_bind_group(0, &self.views[i].bind_group, &[]);
+                self.crosshairs.draw(&mut composite_pass, i);
             }
 
-            render_pass.set_pipeline(&self.ui_pipeline);
-            render_pass.draw(0..4, 0..1); 
+            composite_pass.set_viewport(0.0, 0.0, screen[0], screen[1], 0.0, 1.0);
+            if show_minimap { self.minimap.draw(&mut composite_pass); }
+            self.hud.draw(&mut composite_pass);
+            self.text.draw(&mut composite_pass);
         }
-        self.ctx.queue.submit(std::iter::once(encoder.finish()));
+        #[cfg(feature = "egui")]
+        self.draw_dev_ui(&mut encoder, &view);
+        self.profiler.mark(&mut encoder, GpuSpan::Ui);
+        self.profiler.resolve(&mut encoder);
+        let submission = self.ctx.queue.submit(std::iter::once(encoder.finish()));
+        self.render_scale.track(&self.ctx.queue, frame_start);
         output.present();
+        self.occlusion.after_submit();
+        drop(render_scope);
+        self.profiler.end_frame();
+
+        // Block until the GPU has finished this frame so the next one samples input fresh
+        // instead of queueing behind it.
+        if config::LOW_LATENCY_MODE {
+            self.ctx.device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission));
+        }
         Ok(())
     }
 }
\ No newline at end of file
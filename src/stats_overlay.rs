@@ -0,0 +1,69 @@
+// stats_overlay.rs
+use std::collections::VecDeque;
+use crate::{config, hud::HudRenderer, text::TextRenderer};
+
+// On-screen performance and world stats (F3): FPS, a graph of recent frame times and the
+// lines GameState fills in (draw calls, chunks, memory, position).
+pub struct StatsOverlay {
+    pub visible: bool,
+    // Seconds per frame, oldest first, at most STATS_GRAPH_FRAMES.
+    frame_times: VecDeque<f32>,
+}
+
+impl StatsOverlay {
+    pub fn new() -> Self {
+        Self { visible: config::STATS_OVERLAY, frame_times: VecDeque::with_capacity(config::STATS_GRAPH_FRAMES) }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn record_frame(&mut self, dt: f64) {
+        if self.frame_times.len() == config::STATS_GRAPH_FRAMES { self.frame_times.pop_front(); }
+        self.frame_times.push_back(dt as f32);
+    }
+
+    // Panel in the top-right corner with `lines` under the frame rate and the graph at the bottom.
+    pub fn draw(&self, hud: &mut HudRenderer, text: &mut TextRenderer, screen: [f32; 2], lines: &[String]) {
+        let (size, line_h, pad) = (15.0, 19.0, 10.0);
+        // Average and worst frame over roughly the last second.
+        let (mut total, mut worst, mut count) = (0.0, 0.0f32, 0);
+        for &dt in self.frame_times.iter().rev() {
+            if total >= 1.0 { break; }
+            total += dt;
+            worst = worst.max(dt);
+            count += 1;
+        }
+        let average = if count > 0 { total / count as f32 } else { 0.0 };
+        let fps = if average > 0.0 { 1.0 / average } else { 0.0 };
+        let header = format!("FPS {:.0}  ({:.1} ms avg, {:.1} ms max)", fps, average * 1000.0, worst * 1000.0);
+
+        let graph_h = 48.0;
+        let width = lines.iter().chain(std::iter::once(&header)).map(|l| text.measure(l, size)).fold(config::STATS_GRAPH_FRAMES as f32 * 2.0, f32::max) + pad * 2.0;
+        let height = pad * 2.0 + line_h * (lines.len() + 1) as f32 + 6.0 + graph_h;
+        let (x0, y0) = (screen[0] - width - 12.0, 12.0);
+        hud.rect([x0, y0], [x0 + width, y0 + height], [0.0, 0.0, 0.0, 0.55]);
+
+        let ink = [0.95, 0.95, 0.95, 1.0];
+        text.text(&header, [x0 + pad, y0 + pad], size, ink);
+        for (i, line) in lines.iter().enumerate() {
+            text.text(line, [x0 + pad, y0 + pad + line_h * (i + 1) as f32], size, ink);
+        }
+
+        // Frame-time bars scaled so STATS_GRAPH_MAX_MS fills the graph, with 60 and 30 FPS marks.
+        let (gx, gy, gw) = (x0 + pad, y0 + height - pad - graph_h, width - pad * 2.0);
+        let bar_w = gw / config::STATS_GRAPH_FRAMES as f32;
+        let height_of = |ms: f32| (ms / config::STATS_GRAPH_MAX_MS).min(1.0) * graph_h;
+        for (i, &dt) in self.frame_times.iter().enumerate() {
+            let ms = dt * 1000.0;
+            let color = if ms > 33.4 { [1.0, 0.3, 0.25, 0.9] } else if ms > 16.7 { [1.0, 0.8, 0.2, 0.9] } else { [0.3, 0.9, 0.4, 0.9] };
+            let x = gx + i as f32 * bar_w;
+            hud.rect([x, gy + graph_h - height_of(ms)], [x + bar_w.max(1.0), gy + graph_h], color);
+        }
+        for ms in [1000.0 / 60.0, 1000.0 / 30.0] {
+            let y = gy + graph_h - height_of(ms);
+            hud.rect([gx, y], [gx + gw, y + 1.0], [1.0, 1.0, 1.0, 0.35]);
+        }
+    }
+}
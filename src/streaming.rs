@@ -0,0 +1,133 @@
+// streaming.rs
+use std::{collections::HashSet, time::Instant};
+use wgpu::util::DeviceExt;
+use crate::{config, config_file, hud::HudRenderer, map_loader, post, shader, shader_cache, text::TextRenderer, world};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct StreamingUniform {
+    // rgb: tint, a: strength.
+    color: [f32; 4],
+    // x: seconds since start, y: wall height.
+    params: [f32; 4],
+}
+
+// Feedback while the loader streams chunks in during play: counts in a small HUD panel, and
+// shimmering walls in the scene around every chunk that hasn't arrived yet.
+pub struct StreamingIndicator {
+    pending: HashSet<(i32, i32)>,
+    total: usize,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
+    start: Instant,
+}
+
+impl StreamingIndicator {
+    pub fn new(device: &wgpu::Device, camera_layout: &wgpu::BindGroupLayout) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0, visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None,
+            }],
+            label: Some("Streaming Layout"),
+        });
+        let module = shader_cache::module(device, "Streaming Shader", shader::STREAMING_SHADER);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[camera_layout, &layout], push_constant_ranges: &[] });
+        let additive = wgpu::BlendComponent { src_factor: wgpu::BlendFactor::One, dst_factor: wgpu::BlendFactor::One, operation: wgpu::BlendOperation::Add };
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Streaming Pipeline"), layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module, entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: 16, step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &[wgpu::VertexAttribute { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x4 }],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module, entry_point: "fs_main",
+                // The normal target keeps what's behind the walls for SSAO.
+                targets: &[
+                    Some(wgpu::ColorTargetState { format: post::HDR_FORMAT, blend: Some(wgpu::BlendState { color: additive, alpha: additive }), write_mask: wgpu::ColorWrites::ALL }),
+                    Some(wgpu::ColorTargetState { format: post::NORMAL_FORMAT, blend: None, write_mask: wgpu::ColorWrites::empty() }),
+                ],
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, cull_mode: None, ..Default::default() },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float, depth_write_enabled: false, depth_compare: wgpu::CompareFunction::LessEqual, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState { count: post::scene_samples(), mask: !0, alpha_to_coverage_enabled: false },
+            multiview: None,
+        });
+
+        let [r, g, b] = config::STREAMING_COLOR;
+        let uniform = StreamingUniform { color: [r, g, b, config::STREAMING_STRENGTH], params: [0.0, config::STREAMING_WALL_HEIGHT, 0.0, 0.0] };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Streaming Uniform"), contents: bytemuck::cast_slice(&[uniform]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout, entries: &[wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() }], label: None,
+        });
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Streaming Chunks"), size: (config::CHUNK_GRID_AXIS * config::CHUNK_GRID_AXIS * 16) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+        });
+        Self { pending: HashSet::new(), total: 0, pipeline, bind_group, uniform_buffer, instance_buffer, start: Instant::now() }
+    }
+
+    // The loader announced the chunks it's about to mesh.
+    pub fn queue(&mut self, coords: Vec<(i32, i32)>) {
+        self.total = coords.len();
+        self.pending = coords.into_iter().collect();
+    }
+
+    // Chunks asked for again after being unloaded for distance, counted on top of any still coming.
+    pub fn requeue(&mut self, coords: &[(i32, i32)]) {
+        if self.pending.is_empty() { self.total = 0; }
+        self.total += coords.len();
+        self.pending.extend(coords);
+    }
+
+    pub fn arrived(&mut self, coord: (i32, i32)) {
+        self.pending.remove(&coord);
+    }
+
+    pub fn prepare(&self, queue: &wgpu::Queue) {
+        if self.pending.is_empty() { return; }
+        let chunks: Vec<[f32; 4]> = self.pending.iter().map(|&coord| {
+            let corner = world::chunk_origin(coord);
+            [corner.x, 0.0, corner.y, config_file::chunk_size()]
+        }).collect();
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&chunks));
+        let time = self.start.elapsed().as_secs_f32();
+        queue.write_buffer(&self.uniform_buffer, std::mem::size_of::<[f32; 4]>() as u64, bytemuck::cast_slice(&[time]));
+    }
+
+    // Expects the view's camera bind group at group 0; rebinds group 1.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        if self.pending.is_empty() { return; }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(1, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        pass.draw(0..24, 0..self.pending.len() as u32);
+    }
+
+    // Queued, meshing and arrived counts above the bottom edge while chunks are outstanding.
+    // The loader meshes one batch at a time, so the next CHUNK_BATCH pending ones are in work.
+    pub fn draw_hud(&self, hud: &mut HudRenderer, text: &mut TextRenderer, screen: [f32; 2]) {
+        if self.pending.is_empty() { return; }
+        let meshing = self.pending.len().min(map_loader::CHUNK_BATCH);
+        let arrived = self.total - self.pending.len();
+        let line = format!("Streaming chunks: {} queued, {} meshing, {} / {} uploaded", self.pending.len() - meshing, meshing, arrived, self.total);
+        let size = 14.0;
+        let width = text.measure(&line, size) + 24.0;
+        let (x0, y0) = ((screen[0] - width) * 0.5, screen[1] - 52.0);
+        hud.rect([x0, y0], [x0 + width, y0 + 36.0], [0.0, 0.0, 0.0, 0.55]);
+        text.text_centered(&line, screen[0] * 0.5, y0 + 6.0, size, [1.0, 1.0, 1.0, 0.9]);
+        let progress = arrived as f32 / self.total.max(1) as f32;
+        let [r, g, b] = config::STREAMING_COLOR;
+        hud.rect([x0 + 12.0, y0 + 26.0], [x0 + width - 12.0, y0 + 30.0], [1.0, 1.0, 1.0, 0.15]);
+        hud.rect([x0 + 12.0, y0 + 26.0], [x0 + 12.0 + (width - 24.0) * progress, y0 + 30.0], [r, g, b, 0.9]);
+    }
+}
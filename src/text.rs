@@ -0,0 +1,247 @@
+// text.rs
+use std::collections::HashMap;
+use wgpu::util::DeviceExt;
+use crate::{shader, shader_cache};
+
+const FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSansMono.ttf");
+const ATLAS_SIZE: u32 = 1024;
+const PADDING: u32 = 1; // empty texels between glyphs so linear filtering doesn't bleed
+
+// One glyph quad in pixels with its atlas UVs, drawn as an instanced quad.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GlyphInstance {
+    min: [f32; 2],
+    max: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    color: [f32; 4],
+}
+
+// Where a rasterized glyph sits in the atlas, and how to place it relative to the pen.
+#[derive(Copy, Clone)]
+struct AtlasGlyph {
+    origin: [u32; 2],
+    size: [u32; 2],
+    offset: [f32; 2],
+}
+
+struct TextRun {
+    text: String,
+    pos: [f32; 2],
+    size: f32,
+    color: [f32; 4],
+}
+
+// Glyphs are rasterized on first use into a shelf-packed coverage atlas, keyed by
+// character and pixel size. When the atlas fills up it is cleared and the frame is
+// laid out again, so only the glyphs still on screen take space.
+struct GlyphAtlas {
+    texture: wgpu::Texture,
+    glyphs: HashMap<(char, u32), AtlasGlyph>,
+    // Packing cursor: x in the current shelf, top of the shelf, and its tallest glyph so far.
+    cursor: [u32; 2],
+    shelf_height: u32,
+}
+
+impl GlyphAtlas {
+    fn clear(&mut self) {
+        self.glyphs.clear();
+        self.cursor = [PADDING, PADDING];
+        self.shelf_height = 0;
+    }
+
+    // Returns None when the glyph doesn't fit anymore.
+    fn glyph(&mut self, queue: &wgpu::Queue, font: &fontdue::Font, c: char, px: u32) -> Option<AtlasGlyph> {
+        if let Some(glyph) = self.glyphs.get(&(c, px)) { return Some(*glyph); }
+        let (metrics, coverage) = font.rasterize(c, px as f32);
+        let [w, h] = [metrics.width as u32, metrics.height as u32];
+        if self.cursor[0] + w + PADDING > ATLAS_SIZE {
+            self.cursor = [PADDING, self.cursor[1] + self.shelf_height + PADDING];
+            self.shelf_height = 0;
+        }
+        if self.cursor[1] + h + PADDING > ATLAS_SIZE { return None; }
+
+        let glyph = AtlasGlyph {
+            origin: self.cursor, size: [w, h],
+            offset: [metrics.xmin as f32, -(metrics.ymin as f32 + h as f32)],
+        };
+        if w > 0 && h > 0 {
+            queue.write_texture(
+                wgpu::ImageCopyTexture { texture: &self.texture, mip_level: 0, origin: wgpu::Origin3d { x: glyph.origin[0], y: glyph.origin[1], z: 0 }, aspect: wgpu::TextureAspect::All },
+                &coverage,
+                wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(w), rows_per_image: Some(h) },
+                wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
+            );
+        }
+        self.cursor[0] += w + PADDING;
+        self.shelf_height = self.shelf_height.max(h);
+        self.glyphs.insert((c, px), glyph);
+        Some(glyph)
+    }
+}
+
+// Anti-aliased screen-space text, used by the loading screen, the map menu and the HUD.
+// Queue strings with `text`, then `prepare` and `draw` like the HUD rects.
+pub struct TextRenderer {
+    font: fontdue::Font,
+    atlas: GlyphAtlas,
+    pipeline: wgpu::RenderPipeline,
+    screen_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    instance_buffer: wgpu::Buffer,
+    capacity: usize,
+    runs: Vec<TextRun>,
+    instances: Vec<GlyphInstance>,
+    drawn: u32,
+}
+
+impl TextRenderer {
+    // `depth_format` must match the depth attachment of the pass drawing the text, if it has one.
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, depth_format: Option<wgpu::TextureFormat>) -> Self {
+        let font = fontdue::Font::from_bytes(FONT, fontdue::FontSettings::default()).expect("bundled font is valid");
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Glyph Atlas"), size: wgpu::Extent3d { width: ATLAS_SIZE, height: ATLAS_SIZE, depth_or_array_layers: 1 },
+            mip_level_count: 1, sample_count: 1, dimension: wgpu::TextureDimension::D2, format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST, view_formats: &[],
+        });
+        let atlas_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear, min_filter: wgpu::FilterMode::Linear, ..Default::default()
+        });
+
+        let shader = shader_cache::module(device, "Text Shader", shader::TEXT_SHADER);
+        let screen_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Text Screen"), contents: bytemuck::cast_slice(&[[1.0f32; 4]]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry { binding: 0, visibility: wgpu::ShaderStages::VERTEX, ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None },
+                wgpu::BindGroupLayoutEntry { binding: 1, visibility: wgpu::ShaderStages::FRAGMENT, ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Float { filterable: true }, view_dimension: wgpu::TextureViewDimension::D2, multisampled: false }, count: None },
+                wgpu::BindGroupLayoutEntry { binding: 2, visibility: wgpu::ShaderStages::FRAGMENT, ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering), count: None },
+            ], label: None,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: screen_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&atlas_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ], label: None,
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[&bind_group_layout], push_constant_ranges: &[] });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Text Pipeline"), layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader, entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<GlyphInstance>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &[
+                        wgpu::VertexAttribute { offset: 0,  shader_location: 0, format: wgpu::VertexFormat::Float32x2 },
+                        wgpu::VertexAttribute { offset: 8,  shader_location: 1, format: wgpu::VertexFormat::Float32x2 },
+                        wgpu::VertexAttribute { offset: 16, shader_location: 2, format: wgpu::VertexFormat::Float32x2 },
+                        wgpu::VertexAttribute { offset: 24, shader_location: 3, format: wgpu::VertexFormat::Float32x2 },
+                        wgpu::VertexAttribute { offset: 32, shader_location: 4, format: wgpu::VertexFormat::Float32x4 },
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader, entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState { format, blend: Some(wgpu::BlendState::ALPHA_BLENDING), write_mask: wgpu::ColorWrites::ALL })],
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleStrip, ..Default::default() },
+            depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                format, depth_write_enabled: false, depth_compare: wgpu::CompareFunction::Always, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState { count: sample_count, mask: !0, alpha_to_coverage_enabled: false },
+            multiview: None,
+        });
+
+        let atlas = GlyphAtlas { texture, glyphs: HashMap::new(), cursor: [PADDING, PADDING], shelf_height: 0 };
+        let capacity = 512;
+        let instance_buffer = Self::create_instance_buffer(device, capacity);
+        Self { font, atlas, pipeline, screen_buffer, bind_group, instance_buffer, capacity, runs: Vec::new(), instances: Vec::new(), drawn: 0 }
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Text Glyphs"), size: (capacity * std::mem::size_of::<GlyphInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+        })
+    }
+
+    // Queues a string with its top-left corner at `pos`; `size` is the line height in pixels.
+    pub fn text(&mut self, text: &str, pos: [f32; 2], size: f32, color: [f32; 4]) {
+        self.runs.push(TextRun { text: text.to_string(), pos, size: size.round().max(1.0), color });
+    }
+
+    // Same, but horizontally centered on `center_x`.
+    pub fn text_centered(&mut self, text: &str, center_x: f32, y: f32, size: f32, color: [f32; 4]) {
+        let width = self.measure(text, size);
+        self.text(text, [(center_x - width * 0.5).round(), y], size, color);
+    }
+
+    // Width in pixels of the widest line of `text`.
+    pub fn measure(&self, text: &str, size: f32) -> f32 {
+        let px = size.round().max(1.0);
+        text.lines()
+            .map(|line| line.chars().map(|c| self.font.metrics(c, px).advance_width).sum::<f32>())
+            .fold(0.0, f32::max)
+    }
+
+    fn layout(&mut self, queue: &wgpu::Queue) -> bool {
+        self.instances.clear();
+        let inv_atlas = 1.0 / ATLAS_SIZE as f32;
+        for run in &self.runs {
+            let px = run.size as u32;
+            let ascent = self.font.horizontal_line_metrics(run.size).map_or(run.size * 0.8, |m| m.ascent);
+            let mut baseline = [run.pos[0], (run.pos[1] + ascent).round()];
+            let mut pen_x = baseline[0];
+            for c in run.text.chars() {
+                if c == '\n' {
+                    pen_x = baseline[0];
+                    baseline[1] += run.size;
+                    continue;
+                }
+                let Some(glyph) = self.atlas.glyph(queue, &self.font, c, px) else { return false };
+                if glyph.size[0] > 0 && glyph.size[1] > 0 {
+                    // Snap to whole pixels so the coverage texels map 1:1 onto the screen.
+                    let min = [(pen_x + glyph.offset[0]).round(), baseline[1] + glyph.offset[1]];
+                    let max = [min[0] + glyph.size[0] as f32, min[1] + glyph.size[1] as f32];
+                    let uv_min = [glyph.origin[0] as f32 * inv_atlas, glyph.origin[1] as f32 * inv_atlas];
+                    let uv_max = [(glyph.origin[0] + glyph.size[0]) as f32 * inv_atlas, (glyph.origin[1] + glyph.size[1]) as f32 * inv_atlas];
+                    self.instances.push(GlyphInstance { min, max, uv_min, uv_max, color: run.color });
+                }
+                pen_x += self.font.metrics(c, run.size).advance_width;
+            }
+        }
+        true
+    }
+
+    // Rasterizes any new glyphs and uploads this frame's text; call before the render pass that draws it.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, screen_size: [f32; 2]) {
+        if !self.layout(queue) {
+            self.atlas.clear();
+            if !self.layout(queue) { log::warn!("Text doesn't fit in the glyph atlas, dropping some glyphs"); }
+        }
+        if self.instances.len() > self.capacity {
+            self.capacity = self.instances.len().next_power_of_two();
+            self.instance_buffer = Self::create_instance_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&[[screen_size[0], screen_size[1], 0.0, 0.0]]));
+        if !self.instances.is_empty() {
+            queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.instances));
+        }
+        self.drawn = self.instances.len() as u32;
+        self.runs.clear();
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        if self.drawn == 0 { return; }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        pass.draw(0..4, 0..self.drawn);
+    }
+}
@@ -0,0 +1,73 @@
+// time_of_day.rs
+use glam::Vec3;
+use crate::config;
+
+// Horizon and zenith sky colors keyed by sun elevation (sin of the angle above the horizon).
+const HORIZON_KEYS: [(f32, [f32; 3]); 5] = [
+    (-0.30, [0.005, 0.008, 0.02]), // night
+    (-0.05, [0.08, 0.07, 0.14]),   // twilight
+    (0.05, [0.85, 0.45, 0.25]),    // sunrise / sunset
+    (0.25, [0.62, 0.72, 0.85]),    // morning
+    (1.00, [0.58, 0.70, 0.88]),    // noon
+];
+const ZENITH_KEYS: [(f32, [f32; 3]); 5] = [
+    (-0.30, [0.0, 0.002, 0.01]),
+    (-0.05, [0.02, 0.03, 0.09]),
+    (0.05, [0.15, 0.22, 0.45]),
+    (0.25, [0.18, 0.36, 0.75]),
+    (1.00, [0.12, 0.32, 0.78]),
+];
+
+fn sample_keys(keys: &[(f32, [f32; 3])], elevation: f32) -> [f32; 3] {
+    let mut color = keys[0].1;
+    for pair in keys.windows(2) {
+        let ((e0, c0), (e1, c1)) = (pair[0], pair[1]);
+        if elevation >= e0 {
+            let t = ((elevation - e0) / (e1 - e0)).clamp(0.0, 1.0);
+            color = [c0[0] + (c1[0] - c0[0]) * t, c0[1] + (c1[1] - c0[1]) * t, c0[2] + (c1[2] - c0[2]) * t];
+        }
+    }
+    color
+}
+
+// Advances the sun over a 24 hour clock and derives the lighting the scene shader needs.
+pub struct TimeOfDay {
+    // Hours since midnight, in [0, 24).
+    pub hours: f64,
+}
+
+impl TimeOfDay {
+    pub fn new() -> Self {
+        Self { hours: config::FIXED_TIME_OF_DAY.unwrap_or(config::START_TIME_OF_DAY) }
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        if let Some(fixed) = config::FIXED_TIME_OF_DAY {
+            self.hours = fixed;
+            return;
+        }
+        self.hours = (self.hours + dt * 24.0 / config::DAY_LENGTH_SECONDS).rem_euclid(24.0);
+    }
+
+    // Unit vector toward the sun. It rises in the east (+x) at 06:00 and peaks at noon,
+    // tilted south so midday shadows aren't straight down.
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = ((self.hours - 6.0) / 24.0 * std::f64::consts::TAU) as f32;
+        Vec3::new(angle.cos(), angle.sin(), 0.35).normalize()
+    }
+
+    // 0 at night, 1 in full daylight, ramping through dawn and dusk.
+    pub fn daylight(&self) -> f32 {
+        let elevation = self.sun_direction().y;
+        ((elevation + 0.1) / 0.3).clamp(0.0, 1.0)
+    }
+
+    // Also the fog color, so distant buildings fade into the sky behind them.
+    pub fn horizon_color(&self) -> [f32; 3] {
+        sample_keys(&HORIZON_KEYS, self.sun_direction().y)
+    }
+
+    pub fn zenith_color(&self) -> [f32; 3] {
+        sample_keys(&ZENITH_KEYS, self.sun_direction().y)
+    }
+}
@@ -0,0 +1,92 @@
+// upload.rs
+use std::{collections::VecDeque, num::NonZeroU64, ops::Range};
+use wgpu::util::StagingBelt;
+use crate::{config, config_file, mesh_arena, vertex::PackedVertex, world::{self, World}};
+
+// A chunk mesh that has arrived from the loader but isn't on the GPU yet.
+pub struct PendingMesh {
+    pub coord: (i32, i32),
+    pub vertices: Vec<PackedVertex>,
+    // Full detail indices, then the LOD ones.
+    pub indices: Vec<u32>,
+    pub index_count: u32,
+    pub lod_indices: Range<u32>,
+    pub water: Option<(Vec<PackedVertex>, Vec<u32>)>,
+}
+
+impl PendingMesh {
+    pub fn gpu_bytes(&self) -> u64 {
+        let bytes = |vertices: &[PackedVertex], indices: &[u32]| {
+            std::mem::size_of_val(vertices) as u64 + indices.len() as u64 * mesh_arena::index_size(mesh_arena::index_format(vertices.len()))
+        };
+        bytes(&self.vertices, &self.indices) + self.water.as_ref().map_or(0, |(v, i)| bytes(v, i))
+    }
+}
+
+// Copies through the staging belt into mesh page buffers, recorded on one frame's encoder.
+pub struct Staging<'a> {
+    pub device: &'a wgpu::Device,
+    belt: &'a mut StagingBelt,
+    encoder: &'a mut wgpu::CommandEncoder,
+}
+
+impl Staging<'_> {
+    pub fn write(&mut self, target: &wgpu::Buffer, offset: u64, data: &[u8]) {
+        let Some(size) = NonZeroU64::new(data.len() as u64) else { return };
+        self.belt.write_buffer(self.encoder, target, offset, size, self.device).copy_from_slice(data);
+    }
+}
+
+// Chunk meshes waiting to be uploaded. A frame copies at most CHUNK_UPLOAD_BYTES (and
+// CHUNK_UPLOAD_COUNT meshes), nearest the camera first, so a burst of loaded batches spreads
+// over the next frames instead of stalling one. Collision goes in as soon as a chunk arrives.
+pub struct ChunkUploads {
+    belt: StagingBelt,
+    pending: VecDeque<PendingMesh>,
+}
+
+impl ChunkUploads {
+    pub fn new() -> Self {
+        Self { belt: StagingBelt::new(config::CHUNK_UPLOAD_BYTES), pending: VecDeque::new() }
+    }
+
+    // Queues `mesh`, replacing one still waiting for the same chunk.
+    pub fn push(&mut self, mesh: PendingMesh) {
+        self.pending.retain(|m| m.coord != mesh.coord);
+        self.pending.push_back(mesh);
+    }
+
+    pub fn is_pending(&self, coord: (i32, i32)) -> bool {
+        self.pending.iter().any(|m| m.coord == coord)
+    }
+
+    // Meshes and bytes still waiting, for the stats overlay.
+    pub fn backlog(&self) -> (usize, u64) {
+        (self.pending.len(), self.pending.iter().map(PendingMesh::gpu_bytes).sum())
+    }
+
+    // Uploads this frame's share of the backlog. At least one mesh goes each frame, so one
+    // larger than the budget still makes it.
+    pub fn pump(&mut self, world: &mut World, device: &wgpu::Device, queue: &wgpu::Queue, eye: glam::Vec2) {
+        if self.pending.is_empty() { return; }
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Chunk Uploads") });
+        let (mut bytes, mut count) = (0, 0);
+        while count < config::CHUNK_UPLOAD_COUNT {
+            let Some(nearest) = self.pending.iter().enumerate()
+                .min_by(|a, b| distance(a.1.coord, eye).total_cmp(&distance(b.1.coord, eye)))
+                .map(|(i, _)| i) else { break };
+            let size = self.pending[nearest].gpu_bytes();
+            if count > 0 && bytes + size > config::CHUNK_UPLOAD_BYTES { break; }
+            let mesh = self.pending.remove(nearest).unwrap();
+            world.upload_mesh(&mut Staging { device, belt: &mut self.belt, encoder: &mut encoder }, mesh);
+            (bytes, count) = (bytes + size, count + 1);
+        }
+        self.belt.finish();
+        queue.submit(Some(encoder.finish()));
+        self.belt.recall();
+    }
+}
+
+fn distance(coord: (i32, i32), eye: glam::Vec2) -> f32 {
+    (world::chunk_origin(coord) + glam::Vec2::splat(config_file::chunk_size() * 0.5)).distance(eye)
+}
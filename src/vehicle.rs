@@ -0,0 +1,107 @@
+// vehicle.rs
+use glam::{DVec3, Vec2};
+use crate::{camera::CameraController, config, dynamic_mesh::DynamicMeshes, world::World};
+
+// A drivable car. It stays on the road network: driving off a road's edge pushes it back
+// on, and walls stop it like they stop players.
+pub struct Car {
+    // Center of the car at road level.
+    pub position: DVec3,
+    // Same convention as Camera::yaw.
+    pub heading: f32,
+    // Along the heading, negative when reversing.
+    pub speed: f64,
+    pub color: [f32; 3],
+    // Position and heading before the latest physics tick, for rendering.
+    previous: (DVec3, f32),
+}
+
+impl Car {
+    pub fn new(position: DVec3, heading: f32, color: [f32; 3]) -> Self {
+        Self { position, heading, speed: 0.0, color, previous: (position, heading) }
+    }
+
+    // Parks a car on the road nearest to `near`, lined up with it, if one is within
+    // CAR_SPAWN_RADIUS and its chunk is loaded.
+    pub fn spawn_near(world: &World, near: DVec3, color: [f32; 3]) -> Option<Self> {
+        let road = world.nearest_road(near.x as f32, near.z as f32)?;
+        if road.outside > config::CAR_SPAWN_RADIUS { return None; }
+        let heading = road.direction.y.atan2(road.direction.x);
+        Some(Self::new(DVec3::new(road.center.x as f64, 0.0, road.center.y as f64), heading, color))
+    }
+
+    // Call before each physics tick, also for parked cars.
+    pub fn begin_tick(&mut self) {
+        self.previous = (self.position, self.heading);
+    }
+
+    pub fn forward(&self) -> DVec3 {
+        DVec3::new(self.heading.cos() as f64, 0.0, self.heading.sin() as f64)
+    }
+
+    // Throttle and brake on forward/back, steering on left/right, from keys or the left stick.
+    pub fn drive(&mut self, controller: &CameraController, world: &World, dt: f64) {
+        let axis = controller.move_axis.as_dvec2();
+        let throttle = ((controller.move_fwd as i32 - controller.move_back as i32) as f64 + axis.y).clamp(-1.0, 1.0);
+        let steer = ((controller.move_right as i32 - controller.move_left as i32) as f64 + axis.x).clamp(-1.0, 1.0);
+
+        // Pushing against the direction of travel brakes before it reverses.
+        let braking = throttle != 0.0 && self.speed != 0.0 && throttle.signum() != self.speed.signum();
+        self.speed = if braking {
+            let brake = config::CAR_BRAKING * dt;
+            if self.speed.abs() <= brake { 0.0 } else { self.speed - self.speed.signum() * brake }
+        } else if throttle != 0.0 {
+            (self.speed + throttle * config::CAR_ACCELERATION * dt).clamp(-config::CAR_REVERSE_SPEED, config::CAR_MAX_SPEED)
+        } else {
+            let drag = config::CAR_ROLLING_DRAG * dt;
+            if self.speed.abs() <= drag { 0.0 } else { self.speed - self.speed.signum() * drag }
+        };
+        // Steering follows the direction of travel and fades out toward a standstill.
+        let grip = (self.speed / config::CAR_FULL_STEER_SPEED).clamp(-1.0, 1.0);
+        self.heading += (steer * grip * dt) as f32 * config::CAR_STEER_RATE;
+
+        let mut next = self.position + self.forward() * self.speed * dt;
+        if let Some(road) = world.nearest_road(next.x as f32, next.z as f32) && road.outside > 0.0 {
+            let flat = Vec2::new(next.x as f32, next.z as f32);
+            let out = (flat - road.center).normalize_or_zero();
+            let edge = road.center + out * road.half_width;
+            next.x = edge.x as f64;
+            next.z = edge.y as f64;
+            self.scrape(DVec3::new(out.x as f64, 0.0, out.y as f64));
+        }
+        let radius = config::CAR_WIDTH as f64 * 0.5;
+        for _ in 0..config::MAX_PHYSICS_STEPS {
+            let Some(hit) = world.collide_capsule(next, config::CAR_HEIGHT as f64, radius) else { break };
+            let normal = DVec3::new(hit.normal.x, 0.0, hit.normal.z).normalize_or_zero();
+            next += normal * (hit.depth + 0.0001);
+            self.scrape(normal);
+        }
+        self.position = next;
+    }
+
+    // Loses the part of the speed going into a surface facing `normal`.
+    fn scrape(&mut self, normal: DVec3) {
+        let velocity = self.forward() * self.speed;
+        let into = velocity.dot(normal);
+        if into < 0.0 { self.speed = (velocity - normal * into).dot(self.forward()); }
+    }
+
+    // Position and heading `alpha` of the way from the previous tick to the latest.
+    pub fn interpolated(&self, alpha: f64) -> (DVec3, f32) {
+        let (position, heading) = self.previous;
+        let turn = (self.heading - heading + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+        (position.lerp(self.position, alpha), heading + turn * alpha as f32)
+    }
+
+    // Adds the body and cabin as of `alpha` into the latest tick.
+    pub fn push_mesh(&self, meshes: &mut DynamicMeshes, alpha: f64) {
+        let (position, heading) = self.interpolated(alpha);
+        let center = Vec2::new(position.x as f32, position.z as f32);
+        let axis = Vec2::new(heading.cos(), heading.sin());
+        let (y, half) = (position.y as f32, Vec2::new(config::CAR_LENGTH, config::CAR_WIDTH) * 0.5);
+        let body_top = y + config::CAR_HEIGHT * 0.55;
+        meshes.push_box(center, axis, half, y + 0.3, body_top, self.color);
+        let cabin = [0.08, 0.09, 0.11];
+        meshes.push_box(center - axis * half.x * 0.15, axis, half * Vec2::new(0.5, 0.9), body_top, y + config::CAR_HEIGHT, cabin);
+    }
+}
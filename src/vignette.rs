@@ -0,0 +1,58 @@
+// vignette.rs
+use crate::{config, shader, shader_cache};
+
+// Red vignette over each view that hurts from a hard landing, drawn in the composite pass
+// under the crosshair.
+pub struct DamageVignette {
+    pipeline: wgpu::RenderPipeline,
+    // Per view: rgb, strength.
+    instance_buffer: wgpu::Buffer,
+    strengths: Vec<f32>,
+}
+
+impl DamageVignette {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, view_count: usize) -> Self {
+        let module = shader_cache::module(device, "Vignette Shader", shader::VIGNETTE_SHADER);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[], push_constant_ranges: &[] });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Vignette Pipeline"), layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module, entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: 16, step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &[wgpu::VertexAttribute { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x4 }],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module, entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState { format, blend: Some(wgpu::BlendState::ALPHA_BLENDING), write_mask: wgpu::ColorWrites::ALL })],
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleStrip, ..Default::default() },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Vignette Instances"), size: view_count as u64 * 16, usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+        });
+        Self { pipeline, instance_buffer, strengths: vec![0.0; view_count] }
+    }
+
+    // `strengths` are the views' hurt levels, 0 to 1.
+    pub fn prepare(&mut self, queue: &wgpu::Queue, strengths: impl Iterator<Item = f32>) {
+        for (slot, strength) in self.strengths.iter_mut().zip(strengths) { *slot = strength; }
+        if self.strengths.iter().all(|&s| s <= 0.0) { return; }
+        let [r, g, b] = config::VIGNETTE_COLOR;
+        let instances: Vec<[f32; 4]> = self.strengths.iter().map(|&s| [r, g, b, s]).collect();
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+    }
+
+    // Expects the view's viewport to be set.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, view: usize) {
+        if self.strengths[view] <= 0.0 { return; }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        let instance = view as u32;
+        pass.draw(0..4, instance..instance + 1);
+    }
+}
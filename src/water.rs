@@ -0,0 +1,123 @@
+// water.rs
+use std::time::Instant;
+use wgpu::util::DeviceExt;
+use crate::{config, config_file, mesh_arena::{IndirectDraws, MeshArena}, post::{self, PostProcess}, shader, shader_cache, vertex::PackedVertex};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct WaterUniform {
+    // x: seconds since start (scaled by wave speed), y: wave strength, z: SSR steps (0 disables), w: SSR max distance.
+    params: [f32; 4],
+    color: [f32; 4],
+}
+
+// Draws water surfaces after the scene pass, over the resolved color target. Reflections
+// are traced through the scene's depth buffer against a copy of the resolved color.
+pub struct WaterRenderer {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    uniform: WaterUniform,
+    uniform_buffer: wgpu::Buffer,
+    start: Instant,
+    pub draws: IndirectDraws,
+}
+
+impl WaterRenderer {
+    pub fn new(device: &wgpu::Device, camera_layout: &wgpu::BindGroupLayout, post: &PostProcess, depth: &wgpu::TextureView) -> Self {
+        let texture = |binding, sample_type, multisampled| wgpu::BindGroupLayoutEntry {
+            binding, visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture { sample_type, view_dimension: wgpu::TextureViewDimension::D2, multisampled }, count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                texture(0, wgpu::TextureSampleType::Float { filterable: false }, false),
+                texture(1, wgpu::TextureSampleType::Depth, post::scene_samples() > 1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None,
+                },
+            ],
+            label: Some("Water Layout"),
+        });
+
+        let module = shader_cache::module(device, "Water Shader", &post::scene_depth_shader(shader::WATER_SHADER));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[camera_layout, &layout], push_constant_ranges: &[] });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Water Pipeline"), layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module, entry_point: "vs_main",
+                // Water shares the chunk mesh pages, so it reads positions out of packed vertices.
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<PackedVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &[wgpu::VertexAttribute { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x3 }],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module, entry_point: "fs_main",
+                targets: &[
+                    Some(wgpu::ColorTargetState { format: post::HDR_FORMAT, blend: None, write_mask: wgpu::ColorWrites::ALL }),
+                    Some(wgpu::ColorTargetState { format: post::NORMAL_FORMAT, blend: None, write_mask: wgpu::ColorWrites::ALL }),
+                ],
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, cull_mode: None, ..Default::default() },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let [r, g, b] = config::WATER_COLOR;
+        let uniform = WaterUniform {
+            params: [0.0, config::WATER_WAVE_STRENGTH, if config_file::get().graphics.reflections { config::SSR_STEPS as f32 } else { 0.0 }, config::SSR_MAX_DISTANCE],
+            color: [r, g, b, 0.0],
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Water Uniform"), contents: bytemuck::cast_slice(&[uniform]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = Self::create_bind_group(device, &layout, post, depth, &uniform_buffer);
+        Self { pipeline, layout, bind_group, uniform, uniform_buffer, start: Instant::now(), draws: IndirectDraws::new(device) }
+    }
+
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, post: &PostProcess, depth: &wgpu::TextureView, uniform: &wgpu::Buffer) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(post.scene_copy()) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(depth) },
+                wgpu::BindGroupEntry { binding: 2, resource: uniform.as_entire_binding() },
+            ],
+            label: Some("Water Bind Group"),
+        })
+    }
+
+    // Rebinds the post targets and depth after they were recreated.
+    pub fn resize(&mut self, device: &wgpu::Device, post: &PostProcess, depth: &wgpu::TextureView) {
+        self.bind_group = Self::create_bind_group(device, &self.layout, post, depth, &self.uniform_buffer);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.batches.is_empty()
+    }
+
+    // Turns screen-space reflections on or off; the sky is reflected either way.
+    pub fn set_reflections(&mut self, enabled: bool) {
+        self.uniform.params[2] = if enabled { config::SSR_STEPS as f32 } else { 0.0 };
+    }
+
+    // Uploads the wave time and this frame's draws; call before the water pass.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.uniform.params[0] = self.start.elapsed().as_secs_f32() * config::WATER_WAVE_SPEED;
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+        self.draws.prepare(device, queue);
+    }
+
+    // Draws one view's water; the caller sets the viewport and camera bind group.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, arena: &'a MeshArena, view: usize) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(1, &self.bind_group, &[]);
+        for batch in self.draws.batches.iter().filter(|b| b.view == view) {
+            self.draws.draw(pass, arena, batch);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+// waypoints.rs
+use glam::{DVec3, Vec3};
+use serde::{Deserialize, Serialize};
+use crate::{camera::Camera, config, config_file, hud::HudRenderer, map_loader, text::TextRenderer};
+
+// Stored as coordinates rather than local meters so the file still makes sense with another
+// map origin. `height` is where the beacon stands, meters above the street.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Waypoint {
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(default)]
+    pub height: f64,
+}
+
+impl Waypoint {
+    // Local (x, y, z) of the beacon's foot on the map with its origin at `origin`.
+    pub fn position(&self, origin: (f64, f64)) -> DVec3 {
+        let (x, z) = map_loader::coords_to_local(self.lat, self.lon, origin);
+        DVec3::new(x as f64, self.height, z as f64)
+    }
+}
+
+// Named spots the player drops (B) or passes with `--waypoint NAME=LAT,LON`. They're saved to
+// the waypoints file (paths.waypoints) as they change, drawn as beacons and listed on the pause menu's Waypoints page.
+#[derive(Default)]
+pub struct Waypoints {
+    pub list: Vec<Waypoint>,
+}
+
+impl Waypoints {
+    pub fn load() -> Self {
+        let list = std::fs::read_to_string(&config_file::get().paths.waypoints).ok()
+            .and_then(|text| serde_json::from_str(&text).map_err(|e| log::warn!("Ignoring {}: {}", config_file::get().paths.waypoints, e)).ok())
+            .unwrap_or_default();
+        Self { list }
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(&self.list).map_err(|e| e.to_string())
+            .and_then(|text| std::fs::write(&config_file::get().paths.waypoints, text).map_err(|e| e.to_string()));
+        if let Err(e) = result { log::warn!("Couldn't save {}: {}", config_file::get().paths.waypoints, e); }
+    }
+
+    // Adds a waypoint, replacing any with the same name, and saves.
+    pub fn add(&mut self, waypoint: Waypoint) {
+        log::info!("Waypoint '{}' at {:.5}, {:.5}", waypoint.name, waypoint.lat, waypoint.lon);
+        match self.list.iter_mut().find(|w| w.name == waypoint.name) {
+            Some(existing) => *existing = waypoint,
+            None => self.list.push(waypoint),
+        }
+        self.save();
+    }
+
+    // Drops "Waypoint N" where the camera stands.
+    pub fn drop_at(&mut self, camera: &Camera, origin: (f64, f64)) {
+        let (lat, lon) = map_loader::local_to_coords(camera.eye.x, camera.eye.z, origin);
+        let number = (1..).find(|n| !self.list.iter().any(|w| w.name == format!("Waypoint {}", n))).unwrap_or(1);
+        let height = (camera.eye.y - config::EYE_HEIGHT).max(0.0);
+        self.add(Waypoint { name: format!("Waypoint {}", number), lat, lon, height });
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.list.len() { return; }
+        let waypoint = self.list.remove(index);
+        log::info!("Removed waypoint '{}'", waypoint.name);
+        self.save();
+    }
+
+    // "NAME=LAT,LON", as passed to `--waypoint`.
+    pub fn parse(value: &str) -> Option<Waypoint> {
+        let (name, coords) = value.rsplit_once('=')?;
+        let (lat, lon) = coords.split_once(',')?;
+        let (lat, lon) = (lat.trim().parse::<f64>().ok()?, lon.trim().parse::<f64>().ok()?);
+        let name = name.trim();
+        if name.is_empty() || !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) { return None; }
+        Some(Waypoint { name: name.to_string(), lat, lon, height: 0.0 })
+    }
+
+    // Menu rows: each name with its distance from `eye`.
+    pub fn rows(&self, eye: DVec3, origin: (f64, f64)) -> Vec<String> {
+        self.list.iter().map(|w| format!("{}  ({})", w.name, distance_text(w.position(origin).distance(eye)))).collect()
+    }
+
+    // A glowing column over each waypoint in view, with its name and distance on top. Beacons
+    // show through buildings so they can be found from anywhere.
+    pub fn draw(&self, hud: &mut HudRenderer, text: &mut TextRenderer, camera: &Camera, viewport: [f32; 4], origin: (f64, f64)) {
+        let view_proj = camera.build_relative_view_projection_matrix();
+        let [x, y, w, h] = viewport;
+        let project = |p: DVec3| {
+            let clip = view_proj * (p - camera.eye).as_vec3().extend(1.0);
+            (clip.w > 0.0).then(|| {
+                let ndc = clip.truncate() / clip.w;
+                Vec3::new(x + (ndc.x * 0.5 + 0.5) * w, y + (0.5 - ndc.y * 0.5) * h, ndc.x.abs().max(ndc.y.abs()))
+            })
+        };
+        for waypoint in &self.list {
+            let foot = waypoint.position(origin);
+            let distance = foot.distance(camera.eye);
+            let (Some(base), Some(top)) = (project(foot), project(foot + DVec3::Y * config::WAYPOINT_BEACON_HEIGHT)) else { continue };
+            if base.z > 1.5 && top.z > 1.5 { continue; }
+            let half_width = (config::WAYPOINT_BEACON_WIDTH / distance.max(1.0) as f32 * h).clamp(1.0, 12.0) * 0.5;
+            let [r, g, b] = config::WAYPOINT_COLOR;
+            // In short pieces, so the column leans with the perspective and fades upward.
+            const PIECES: usize = 8;
+            for i in 0..PIECES {
+                let (t0, t1) = (i as f32 / PIECES as f32, (i + 1) as f32 / PIECES as f32);
+                let (lower, upper) = (base.lerp(top, t0), base.lerp(top, t1));
+                let cx = (lower.x + upper.x) * 0.5;
+                hud.rect([cx - half_width, upper.y], [cx + half_width, lower.y], [r, g, b, 0.5 * (1.0 - t0)]);
+            }
+
+            let label = format!("{}  {}", waypoint.name, distance_text(distance));
+            let size = 15.0;
+            let half_label = text.measure(&label, size) * 0.5 + 4.0;
+            // Kept on screen when the top of the column isn't.
+            let label_y = top.y.clamp(y + size + 8.0, base.y.max(y + size + 8.0));
+            hud.rect([top.x - half_label, label_y - size - 8.0], [top.x + half_label, label_y - 2.0], [0.0, 0.0, 0.0, 0.5]);
+            text.text_centered(&label, top.x, label_y - size - 5.0, size, [r, g, b, 1.0]);
+        }
+    }
+}
+
+fn distance_text(meters: f64) -> String {
+    if meters < 1000.0 { format!("{:.0} m", meters) } else { format!("{:.1} km", meters / 1000.0) }
+}
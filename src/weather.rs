@@ -0,0 +1,207 @@
+// weather.rs
+use std::time::Instant;
+use wgpu::util::DeviceExt;
+use crate::{config, post, shader, shader_cache};
+
+// F8 cycles through these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeatherKind {
+    Clear,
+    Rain,
+    Snow,
+}
+
+impl WeatherKind {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Clear => Self::Rain,
+            Self::Rain => Self::Snow,
+            Self::Snow => Self::Clear,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Clear => "Clear",
+            Self::Rain => "Rain",
+            Self::Snow => "Snow",
+        }
+    }
+
+    fn particles(self) -> u32 {
+        match self {
+            Self::Clear => 0,
+            Self::Rain => config::RAIN_PARTICLES,
+            Self::Snow => config::SNOW_PARTICLES,
+        }
+    }
+
+    fn fog_scale(self) -> f32 {
+        match self {
+            Self::Clear => 1.0,
+            Self::Rain => config::RAIN_FOG_SCALE,
+            Self::Snow => config::SNOW_FOG_SCALE,
+        }
+    }
+}
+
+// Current weather. Switching fades the new precipitation and fog in over
+// WEATHER_TRANSITION seconds; streets get wet while it rains and dry slowly afterward.
+pub struct Weather {
+    pub kind: WeatherKind,
+    intensity: f32,
+    // 0 dry, 1 soaked; darkens the ground in the scene shader.
+    pub wetness: f32,
+    previous_fog: f32,
+    // Multiplier on top of the weather's own fog, set from the developer panels.
+    pub fog_multiplier: f32,
+}
+
+impl Weather {
+    pub fn new() -> Self {
+        let kind = config::START_WEATHER;
+        Self { kind, intensity: 1.0, wetness: if kind == WeatherKind::Rain { 1.0 } else { 0.0 }, previous_fog: kind.fog_scale(), fog_multiplier: 1.0 }
+    }
+
+    pub fn cycle(&mut self) {
+        self.set(self.kind.next());
+    }
+
+    // Fades over to `kind`.
+    pub fn set(&mut self, kind: WeatherKind) {
+        if kind == self.kind { return; }
+        self.previous_fog = self.fog_scale();
+        self.kind = kind;
+        self.intensity = 0.0;
+        log::info!("Weather: {}", self.kind.name());
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        let dt = dt as f32;
+        self.intensity = (self.intensity + dt / config::WEATHER_TRANSITION).min(1.0);
+        self.wetness = if self.kind == WeatherKind::Rain {
+            (self.wetness + dt * self.intensity / config::WET_STREETS_TIME).min(1.0)
+        } else {
+            (self.wetness - dt / config::DRY_STREETS_TIME).max(0.0)
+        };
+    }
+
+    // Multiplier on the fog and haze densities.
+    pub fn fog_scale(&self) -> f32 {
+        self.previous_fog + (self.kind.fog_scale() - self.previous_fog) * self.intensity
+    }
+
+    fn particle_count(&self) -> u32 {
+        (self.kind.particles() as f32 * self.intensity) as u32
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct WeatherUniform {
+    // x: seconds since start, y: fall speed (m/s), z: edge of the box around the camera, w: 0 rain, 1 snow.
+    params: [f32; 4],
+    color: [f32; 4],
+    // x: particle width, y: particle length.
+    size: [f32; 4],
+}
+
+// Rain streaks and snow flakes as instanced quads. Positions are hashed from the instance
+// index and wrapped into a box that follows the camera, so nothing is simulated on the CPU.
+// Drawn in the water pass and depth-tested in the shader against the scene depth.
+pub struct WeatherParticles {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    uniform: WeatherUniform,
+    uniform_buffer: wgpu::Buffer,
+    start: Instant,
+    count: u32,
+}
+
+impl WeatherParticles {
+    pub fn new(device: &wgpu::Device, camera_layout: &wgpu::BindGroupLayout, depth: &wgpu::TextureView) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Depth, view_dimension: wgpu::TextureViewDimension::D2, multisampled: post::scene_samples() > 1 }, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }, count: None,
+                },
+            ],
+            label: Some("Weather Layout"),
+        });
+
+        let module = shader_cache::module(device, "Weather Shader", &post::scene_depth_shader(shader::WEATHER_SHADER));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: None, bind_group_layouts: &[camera_layout, &layout], push_constant_ranges: &[] });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Weather Pipeline"), layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState { module: &module, entry_point: "vs_main", buffers: &[] },
+            fragment: Some(wgpu::FragmentState {
+                module: &module, entry_point: "fs_main",
+                targets: &[
+                    Some(wgpu::ColorTargetState { format: post::HDR_FORMAT, blend: Some(wgpu::BlendState::ALPHA_BLENDING), write_mask: wgpu::ColorWrites::ALL }),
+                    Some(wgpu::ColorTargetState { format: post::NORMAL_FORMAT, blend: None, write_mask: wgpu::ColorWrites::empty() }),
+                ],
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, cull_mode: None, ..Default::default() },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let uniform = WeatherUniform { params: [0.0, 0.0, config::WEATHER_BOX_SIZE, 0.0], color: [0.0; 4], size: [0.0; 4] };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Weather Uniform"), contents: bytemuck::cast_slice(&[uniform]), usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = Self::create_bind_group(device, &layout, depth, &uniform_buffer);
+        Self { pipeline, layout, bind_group, uniform, uniform_buffer, start: Instant::now(), count: 0 }
+    }
+
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, depth: &wgpu::TextureView, uniform: &wgpu::Buffer) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(depth) },
+                wgpu::BindGroupEntry { binding: 1, resource: uniform.as_entire_binding() },
+            ],
+            label: Some("Weather Bind Group"),
+        })
+    }
+
+    // Rebinds the depth after it was recreated.
+    pub fn resize(&mut self, device: &wgpu::Device, depth: &wgpu::TextureView) {
+        self.bind_group = Self::create_bind_group(device, &self.layout, depth, &self.uniform_buffer);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    // Uploads this frame's precipitation; call before the water pass.
+    pub fn prepare(&mut self, queue: &wgpu::Queue, weather: &Weather) {
+        self.count = weather.particle_count();
+        if self.count == 0 { return; }
+        let snow = weather.kind == WeatherKind::Snow;
+        let (speed, size, color) = if snow {
+            (config::SNOW_FALL_SPEED, [config::SNOW_FLAKE_SIZE, config::SNOW_FLAKE_SIZE], config::SNOW_COLOR)
+        } else {
+            (config::RAIN_FALL_SPEED, [config::RAIN_DROP_WIDTH, config::RAIN_DROP_LENGTH], config::RAIN_COLOR)
+        };
+        self.uniform.params = [self.start.elapsed().as_secs_f32(), speed, config::WEATHER_BOX_SIZE, if snow { 1.0 } else { 0.0 }];
+        self.uniform.color = color;
+        self.uniform.size = [size[0], size[1], 0.0, 0.0];
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    // Draws one view's particles; the caller sets the viewport and camera bind group.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        if self.count == 0 { return; }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(1, &self.bind_group, &[]);
+        pass.draw(0..6, 0..self.count);
+    }
+}
@@ -1,12 +1,20 @@
 // world.rs
 use std::collections::HashMap;
-use crate::{config, vertex::Vertex};
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Instant;
+use crate::{config, config_file, elevator::Elevator, map_loader::LoaderError, poi::PoiIndex, mesh_arena::{MeshAllocation, MeshArena}, mesh_cache::{Evicted, MeshCache}, upload::{PendingMesh, Staging}, vertex::{PackedVertex, Vertex}};
 
 pub enum LoaderMessage {
     Status(String),
     Progress(f32),
+    // Points of interest, sent before the first batch.
+    Places(PoiIndex),
+    // Every chunk the loader will mesh, sent before the first batch.
+    Queued(Vec<(i32, i32)>),
     BatchLoaded(Vec<ChunkData>),
     Done,
+    Failed(LoaderError),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -14,32 +22,320 @@ pub struct WallCollider {
     pub start: glam::Vec2,
     pub end: glam::Vec2,
     pub height: f32,
+    // Bottom of the wall; players below it (in tunnels) pass underneath.
+    pub base: f32,
     pub min_x: f32, pub max_x: f32,
     pub min_z: f32, pub max_z: f32,
+    // Index in the chunk's building list; None for barriers.
+    pub building: Option<u32>,
+}
+
+impl WallCollider {
+    pub fn new(start: glam::Vec2, end: glam::Vec2, height: f32) -> Self {
+        let pad = config::WALL_THICKNESS as f32;
+        Self {
+            start, end, height, base: 0.0,
+            min_x: start.x.min(end.x) - pad, max_x: start.x.max(end.x) + pad,
+            min_z: start.y.min(end.y) - pad, max_z: start.y.max(end.y) + pad,
+            building: None,
+        }
+    }
+
+    pub fn of_building(self, index: usize) -> Self {
+        Self { building: Some(index as u32), ..self }
+    }
+
+    // Overlap with a vertical capsule of `radius` whose end spheres are centered at `low` and
+    // `high` above (x, z). Zero-length walls never touch anything.
+    fn capsule_contact(&self, flat: glam::DVec2, low: f64, high: f64, radius: f64) -> Option<WallHit> {
+        // Vertical gap between the capsule's axis and the wall, positive above it.
+        let (base, top) = (self.base as f64, self.height as f64);
+        let dy = if low > top { low - top } else if high < base { high - base } else { 0.0 };
+        if dy.abs() >= radius { return None; }
+
+        let a = self.start.as_dvec2();
+        let ab = self.end.as_dvec2() - a;
+        if ab.length_squared() < 1e-12 { return None; }
+        let t = ((flat - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0);
+        let closest = a + ab * t;
+        let horizontal = flat.distance(closest);
+        // Dead on the wall's line: out the side its winding faces.
+        let out = if horizontal > 1e-6 { (flat - closest) / horizontal } else { glam::DVec2::new(ab.y, -ab.x).normalize() };
+        let gap = (horizontal - config::WALL_THICKNESS).max(0.0);
+        let dist = (gap * gap + dy * dy).sqrt();
+        if dist >= radius { return None; }
+
+        // Inside the slab itself: push out sideways past its full thickness.
+        let (normal, depth) = if dist > 1e-9 {
+            (glam::DVec3::new(out.x * gap, dy, out.y * gap) / dist, radius - dist)
+        } else {
+            (glam::DVec3::new(out.x, 0.0, out.y), radius + config::WALL_THICKNESS - horizontal)
+        };
+        Some(WallHit { normal, depth, top: self.height })
+    }
+
+    // Distance along the unit vector `dir` to where a ray crosses the wall's face.
+    fn raycast(&self, origin: glam::Vec3, dir: glam::Vec3) -> Option<f32> {
+        let (o, d) = (glam::Vec2::new(origin.x, origin.z), glam::Vec2::new(dir.x, dir.z));
+        let edge = self.end - self.start;
+        let denom = d.perp_dot(edge);
+        if denom.abs() < 1e-9 { return None; }
+        let t = (self.start - o).perp_dot(edge) / denom;
+        let u = (self.start - o).perp_dot(d) / denom;
+        let y = origin.y + dir.y * t;
+        (t >= 0.0 && (0.0..=1.0).contains(&u) && (self.base..=self.height).contains(&y)).then_some(t)
+    }
+}
+
+// One triangle of a flat roof the player can land on, or of a water surface.
+#[derive(Debug, Clone, Copy)]
+pub struct RoofTriangle {
+    pub corners: [glam::Vec2; 3],
+    pub height: f32,
+    // Index in the chunk's building list; None for water.
+    pub building: Option<u32>,
+}
+
+impl RoofTriangle {
+    // Distance along the unit vector `dir` to where a ray crosses the triangle.
+    fn raycast(&self, origin: glam::Vec3, dir: glam::Vec3) -> Option<f32> {
+        if dir.y.abs() < 1e-6 { return None; }
+        let t = (self.height - origin.y) / dir.y;
+        let p = origin + dir * t;
+        (t >= 0.0 && self.contains(glam::Vec2::new(p.x, p.z))).then_some(t)
+    }
+
+    pub fn contains(&self, p: glam::Vec2) -> bool {
+        let [a, b, c] = self.corners;
+        let (d0, d1, d2) = ((b - a).perp_dot(p - a), (c - b).perp_dot(p - b), (a - c).perp_dot(p - c));
+        // Earcut output winds either way, so accept both signs.
+        (d0 >= 0.0 && d1 >= 0.0 && d2 >= 0.0) || (d0 <= 0.0 && d1 <= 0.0 && d2 <= 0.0)
+    }
+}
+
+// Walkable underground corridor: a subway tunnel, an entrance ramp or the connector
+// between them. The floor slopes linearly from start to end.
+#[derive(Debug, Clone, Copy)]
+pub struct TunnelSpan {
+    pub start: glam::Vec2,
+    pub end: glam::Vec2,
+    pub half_width: f32,
+    pub floor_start: f32,
+    pub floor_end: f32,
+    // Entrance ramps cut through the surface instead of having a ceiling.
+    pub open_top: bool,
+}
+
+impl TunnelSpan {
+    // Corners of the span's rectangle on the ground plane.
+    pub fn footprint(&self) -> [glam::Vec2; 4] {
+        let side = (self.end - self.start).normalize_or_zero().perp() * self.half_width;
+        [self.start + side, self.end + side, self.end - side, self.start - side]
+    }
+
+    // Floor height at `p` if it lies within the span's footprint.
+    pub fn floor_at(&self, p: glam::Vec2) -> Option<f32> {
+        let ab = self.end - self.start;
+        let len = ab.length();
+        if len < 1e-3 { return None; }
+        let along = (p - self.start).dot(ab) / len;
+        // Extend the ends by the half width so joints between segments have no gaps.
+        if along < -self.half_width || along > len + self.half_width { return None; }
+        let t = (along / len).clamp(0.0, 1.0);
+        if p.distance_squared(self.start + ab * t) > self.half_width * self.half_width { return None; }
+        Some(self.floor_start + (self.floor_end - self.floor_start) * t)
+    }
+}
+
+// Climbable strip up the outside of a wall, from the ground to the roof at `top`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ladder {
+    // Foot of the ladder on the wall's outer face.
+    pub center: glam::Vec2,
+    // Unit vector out of the wall.
+    pub normal: glam::Vec2,
+    pub top: f32,
+}
+
+impl Ladder {
+    // Where a climber's eye is pinned horizontally.
+    pub fn climb_position(&self) -> glam::Vec2 {
+        self.center + self.normal * (config::LADDER_STANDOFF + config::PLAYER_RADIUS as f32)
+    }
+
+    // Whether a body centered at `p` is within LADDER_REACH in front of the rungs and
+    // within the ladder's width.
+    pub fn in_reach(&self, p: glam::Vec2) -> bool {
+        let local = p - self.center;
+        let out = local.dot(self.normal);
+        out >= 0.0 && out <= config::LADDER_STANDOFF + config::PLAYER_RADIUS as f32 + config::LADDER_REACH
+            && local.perp_dot(self.normal).abs() <= config::LADDER_WIDTH * 0.5
+    }
+}
+
+// Straight piece of a drivable `highway=*` way, `half_width` to each side of its centerline.
+#[derive(Debug, Clone, Copy)]
+pub struct RoadSegment {
+    pub start: glam::Vec2,
+    pub end: glam::Vec2,
+    pub half_width: f32,
+}
+
+// Nearest road to a point: the closest point on its centerline and how far past the road's
+// edge the point lies (negative when on the road).
+#[derive(Debug, Clone, Copy)]
+pub struct RoadHit {
+    pub center: glam::Vec2,
+    // Unit vector along the road.
+    pub direction: glam::Vec2,
+    pub half_width: f32,
+    pub outside: f32,
+}
+
+// Where a building's roof and walls sit in the chunk's full-detail indices. Its colliders
+// refer to it by index in the chunk's list.
+#[derive(Debug, Clone)]
+pub struct BuildingInfo {
+    pub indices: Range<u32>,
+}
+
+// A building by its chunk and index in that chunk's building list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildingId {
+    pub coord: (i32, i32),
+    pub index: usize,
+}
+
+// Where a ray first met a wall or roof. The normal faces back along the ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub position: glam::Vec3,
+    pub normal: glam::Vec3,
+    pub distance: f32,
+    // None for barriers.
+    pub building: Option<BuildingId>,
+}
+
+// Walkable space below or at a point: the floor, and the ceiling if it's enclosed.
+#[derive(Debug, Clone, Copy)]
+pub struct TunnelHit {
+    pub floor: f32,
+    pub ceiling: Option<f32>,
+    // Vertical speed of the floor; nonzero on a moving elevator.
+    pub velocity: f32,
+}
+
+// Walls overlapping the player: the direction and distance to push out (tilted up or down at
+// the wall's top and bottom edges), and how high the walls reach so low ledges can be
+// stepped onto instead.
+#[derive(Debug, Clone, Copy)]
+pub struct WallHit {
+    pub normal: glam::DVec3,
+    pub depth: f64,
+    pub top: f32,
+}
+
+impl WallHit {
+    // Shortest push that clears every contact at once, so a body wedged into a corner isn't
+    // bounced between its walls, and the two halves of a split wall don't push twice. It's
+    // out of a single contact or out of two at once; walls on opposite sides that no push
+    // clears leave it to the deepest.
+    pub fn combine(contacts: &[WallHit]) -> Option<WallHit> {
+        let deepest = *contacts.iter().max_by(|a, b| a.depth.total_cmp(&b.depth))?;
+        let clears = |push: glam::DVec3| contacts.iter().all(|c| push.dot(c.normal) >= c.depth - 1e-6);
+        let mut best: Option<glam::DVec3> = None;
+        let mut consider = |push: glam::DVec3| {
+            if clears(push) && best.is_none_or(|b| push.length_squared() < b.length_squared()) { best = Some(push); }
+        };
+        for (i, a) in contacts.iter().enumerate() {
+            consider(a.normal * a.depth);
+            for b in &contacts[i + 1..] {
+                // Push along both normals that leaves exactly each one's depth.
+                let cos = a.normal.dot(b.normal);
+                let det = 1.0 - cos * cos;
+                if det < 1e-6 { continue; }
+                consider(a.normal * ((a.depth - cos * b.depth) / det) + b.normal * ((b.depth - cos * a.depth) / det));
+            }
+        }
+        let top = contacts.iter().map(|c| c.top).fold(f32::NEG_INFINITY, f32::max);
+        let Some(push) = best else { return Some(WallHit { top, ..deepest }) };
+        let depth = push.length();
+        Some(WallHit { normal: push / depth, depth, top })
+    }
 }
 
-#[derive(Clone)]
 pub struct ChunkData {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
-    pub walls: Vec<WallCollider>,
+    // Simplified mesh for distant chunks, indexing the same vertices.
+    pub lod_indices: Vec<u32>,
+    pub water_vertices: Vec<Vertex>,
+    pub water_indices: Vec<u32>,
+    // Street lamp positions.
+    pub lights: Vec<glam::Vec3>,
+    pub buildings: Vec<BuildingInfo>,
+    pub elevators: Vec<Elevator>,
+    // Baked on the loader thread so inserting a chunk is just a pointer move.
+    pub collision: Arc<LocalCollisionGrid>,
     pub coord: (i32, i32),
 }
 
+pub fn chunk_origin(coord: (i32, i32)) -> glam::Vec2 {
+    let cx = coord.0 as f32 * config_file::chunk_size() - (config_file::world_size() / 2.0);
+    let cz = coord.1 as f32 * config_file::chunk_size() - (config_file::world_size() / 2.0);
+    glam::Vec2::new(cx, cz)
+}
+
+// Joins runs of consecutive walls that continue in a straight line (OSM ways are often split
+// mid-wall), so fewer walls are stored and tested. Every corner joined away has to lie within
+// WALL_MERGE_TOLERANCE of the joined wall, which keeps its outline in place.
+fn merge_collinear(walls: &[WallCollider]) -> Vec<WallCollider> {
+    let mut merged: Vec<WallCollider> = Vec::with_capacity(walls.len());
+    // Corners inside the last merged wall, checked again each time it grows so a slow curve
+    // can't drift off it one corner at a time.
+    let mut corners: Vec<glam::Vec2> = Vec::new();
+    for wall in walls {
+        if let Some(last) = merged.last_mut()
+            && last.end.distance(wall.start) < 1e-4
+            && (last.height, last.base, last.building) == (wall.height, wall.base, wall.building)
+            && (last.end - last.start).dot(wall.end - wall.start) > 0.0 {
+            let (start, end) = (last.start, wall.end);
+            let along = (end - start).normalize_or_zero();
+            let on_line = |corner: &glam::Vec2| (*corner - start).perp_dot(along).abs() <= config::WALL_MERGE_TOLERANCE;
+            if on_line(&last.end) && corners.iter().all(on_line) {
+                corners.push(last.end);
+                *last = WallCollider { base: last.base, building: last.building, ..WallCollider::new(start, end, last.height) };
+                continue;
+            }
+        }
+        corners.clear();
+        merged.push(*wall);
+    }
+    merged
+}
+
 pub struct LocalCollisionGrid {
-    pub cells: Vec<Vec<WallCollider>>,
+    // Each wall once; cells list the indices of the walls overlapping them.
+    pub walls: Vec<WallCollider>,
+    pub cells: Vec<Vec<u32>>,
+    pub roof_cells: Vec<Vec<RoofTriangle>>,
+    pub water_cells: Vec<Vec<RoofTriangle>>,
     pub cell_size: f32,
     pub grid_dim: usize,
     pub chunk_offset: glam::Vec2,
+    pub tunnels: Vec<TunnelSpan>,
+    pub roads: Vec<RoadSegment>,
+    pub ladders: Vec<Ladder>,
 }
 
 impl LocalCollisionGrid {
-    pub fn new(walls: &[WallCollider], chunk_offset: glam::Vec2) -> Self {
+    pub fn new(walls: &[WallCollider], roofs: &[RoofTriangle], water: &[RoofTriangle], tunnels: Vec<TunnelSpan>, roads: Vec<RoadSegment>, ladders: Vec<Ladder>, chunk_offset: glam::Vec2) -> Self {
         let cell_size = config::PHYSICS_GRID_CELL_SIZE;
-        let grid_dim = (config::CHUNK_SIZE / cell_size).ceil() as usize;
+        let grid_dim = (config_file::chunk_size() / cell_size).ceil() as usize;
         let mut cells = vec![Vec::new(); grid_dim * grid_dim];
+        let walls = merge_collinear(walls);
 
-        for wall in walls {
+        for (index, wall) in walls.iter().enumerate() {
             let local_min_x = wall.min_x - chunk_offset.x;
             let local_max_x = wall.max_x - chunk_offset.x;
             let local_min_z = wall.min_z - chunk_offset.y;
@@ -54,76 +350,561 @@ impl LocalCollisionGrid {
                 for gz in min_gz..=max_gz {
                     if gx >= 0 && gx < grid_dim as i32 && gz >= 0 && gz < grid_dim as i32 {
                         let idx = (gz as usize) * grid_dim + (gx as usize);
-                        cells[idx].push(*wall);
+                        cells[idx].push(index as u32);
                     }
                 }
             }
         }
-        Self { cells, cell_size, grid_dim, chunk_offset }
+
+        let bin = |triangles: &[RoofTriangle]| {
+            let mut binned = vec![Vec::new(); grid_dim * grid_dim];
+            for tri in triangles {
+                let [a, b, c] = tri.corners;
+                let min = ((a.min(b).min(c) - chunk_offset) / cell_size).floor().as_ivec2().max(glam::IVec2::ZERO);
+                let max = ((a.max(b).max(c) - chunk_offset) / cell_size).floor().as_ivec2().min(glam::IVec2::splat(grid_dim as i32 - 1));
+                for gz in min.y..=max.y {
+                    for gx in min.x..=max.x {
+                        binned[gz as usize * grid_dim + gx as usize].push(*tri);
+                    }
+                }
+            }
+            binned
+        };
+        Self { walls, cells, roof_cells: bin(roofs), water_cells: bin(water), cell_size, grid_dim, chunk_offset, tunnels, roads, ladders }
     }
 
-    pub fn get_walls(&self, x: f32, z: f32) -> Option<&Vec<WallCollider>> {
+    fn cell_index(&self, x: f32, z: f32) -> Option<usize> {
         let lx = x - self.chunk_offset.x;
         let lz = z - self.chunk_offset.y;
         if lx < 0.0 || lz < 0.0 { return None; }
-        
-        let gx = (lx / self.cell_size).floor() as i32;
-        let gz = (lz / self.cell_size).floor() as i32;
+        let (gx, gz) = ((lx / self.cell_size).floor() as usize, (lz / self.cell_size).floor() as usize);
+        (gx < self.grid_dim && gz < self.grid_dim).then_some(gz * self.grid_dim + gx)
+    }
 
-        if gx >= 0 && gx < self.grid_dim as i32 && gz >= 0 && gz < self.grid_dim as i32 {
-            Some(&self.cells[(gz as usize) * self.grid_dim + (gx as usize)])
-        } else {
-            None
+    pub fn get_roofs(&self, x: f32, z: f32) -> Option<&Vec<RoofTriangle>> {
+        self.cell_index(x, z).map(|i| &self.roof_cells[i])
+    }
+
+    pub fn get_water(&self, x: f32, z: f32) -> Option<&Vec<RoofTriangle>> {
+        self.cell_index(x, z).map(|i| &self.water_cells[i])
+    }
+
+    pub fn get_walls(&self, x: f32, z: f32) -> Option<impl Iterator<Item = &WallCollider>> {
+        self.cell_index(x, z).map(|i| self.cells_walls(i))
+    }
+
+    fn cells_walls(&self, index: usize) -> impl Iterator<Item = &WallCollider> {
+        self.cells[index].iter().map(|&wall| &self.walls[wall as usize])
+    }
+
+    // Nearest wall or roof along the unit vector `dir` within `max_dist`: its distance,
+    // normal and building index. Walks the grid cells the ray crosses in order and stops at
+    // the first cell holding a hit.
+    pub fn raycast(&self, origin: glam::Vec3, dir: glam::Vec3, max_dist: f32) -> Option<(f32, glam::Vec3, Option<u32>)> {
+        let o = glam::Vec2::new(origin.x, origin.z) - self.chunk_offset;
+        let d = glam::Vec2::new(dir.x, dir.z);
+        let size = self.grid_dim as f32 * self.cell_size;
+
+        // Clip the ray to the grid square.
+        let (mut enter, mut exit) = (0.0f32, max_dist);
+        for axis in 0..2 {
+            if d[axis].abs() < 1e-9 {
+                if o[axis] < 0.0 || o[axis] > size { return None; }
+                continue;
+            }
+            let (t0, t1) = (-o[axis] / d[axis], (size - o[axis]) / d[axis]);
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+        }
+        if enter > exit { return None; }
+
+        let last = glam::IVec2::splat(self.grid_dim as i32 - 1);
+        let mut cell = ((o + d * enter) / self.cell_size).floor().as_ivec2().clamp(glam::IVec2::ZERO, last);
+        let step = glam::IVec2::new(if d.x > 0.0 { 1 } else { -1 }, if d.y > 0.0 { 1 } else { -1 });
+        let boundary = |c: i32, axis: usize| {
+            if d[axis].abs() < 1e-9 { return f32::INFINITY; }
+            let edge = if d[axis] > 0.0 { c + 1 } else { c } as f32 * self.cell_size;
+            (edge - o[axis]) / d[axis]
+        };
+        let mut next = glam::Vec2::new(boundary(cell.x, 0), boundary(cell.y, 1));
+        let delta = glam::Vec2::new(self.cell_size / d.x.abs(), self.cell_size / d.y.abs());
+
+        let mut best: Option<(f32, glam::Vec3, Option<u32>)> = None;
+        loop {
+            let index = cell.y as usize * self.grid_dim + cell.x as usize;
+            for wall in self.cells_walls(index) {
+                if let Some(t) = wall.raycast(origin, dir).filter(|&t| t < best.map_or(exit, |b| b.0)) {
+                    let edge = wall.end - wall.start;
+                    let normal = glam::Vec3::new(edge.y, 0.0, -edge.x).normalize_or_zero();
+                    best = Some((t, if normal.dot(dir) > 0.0 { -normal } else { normal }, wall.building));
+                }
+            }
+            for roof in &self.roof_cells[index] {
+                if let Some(t) = roof.raycast(origin, dir).filter(|&t| t < best.map_or(exit, |b| b.0)) {
+                    best = Some((t, glam::Vec3::Y * -dir.y.signum(), roof.building));
+                }
+            }
+
+            let cell_exit = next.min_element().min(exit);
+            if best.is_some_and(|b| b.0 <= cell_exit) || cell_exit >= exit { return best; }
+            let axis = if next.x < next.y { 0 } else { 1 };
+            cell[axis] += step[axis];
+            next[axis] += delta[axis];
+            if cell[axis] < 0 || cell[axis] > last[axis] { return best; }
         }
     }
 }
 
-pub struct Chunk {
-    pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
+pub struct ChunkMesh {
+    // Full-detail indices followed by the LOD indices, in a shared mesh page.
+    pub alloc: MeshAllocation,
     pub index_count: u32,
-    pub collision: LocalCollisionGrid,
+    pub lod_indices: Range<u32>,
+    // Water surfaces, drawn in their own pass after the scene.
+    pub water: Option<(MeshAllocation, u32)>,
+    pub gpu_bytes: u64,
+    // When the mesh finished streaming in; the chunk fades in from then.
+    pub uploaded: Instant,
+}
+
+impl ChunkMesh {
+    // Index range to draw for a chunk whose center is `distance` away from the camera,
+    // relative to the start of the chunk's indices. Beyond `lod_distance` the LOD mesh is used.
+    pub fn indices_at(&self, distance: f32, lod_distance: f32) -> Range<u32> {
+        if distance > lod_distance && !self.lod_indices.is_empty() { self.lod_indices.clone() } else { 0..self.index_count }
+    }
+
+    // (page, first index, index count, base vertex) of a draw of `range`.
+    pub fn draw(&self, range: Range<u32>) -> (usize, u32, u32, i32) {
+        (self.alloc.page, self.alloc.indices.start + range.start, range.len() as u32, self.alloc.vertices.start as i32)
+    }
+
+    pub fn water_draw(&self) -> Option<(usize, u32, u32, i32)> {
+        self.water.as_ref().map(|(alloc, count)| (alloc.page, alloc.indices.start, *count, alloc.vertices.start as i32))
+    }
+
+    // Arena pages holding the mesh; the water may sit in another page than the rest.
+    pub fn pages(&self) -> impl Iterator<Item = usize> {
+        std::iter::once(self.alloc.page).chain(self.water.as_ref().map(|(alloc, _)| alloc.page))
+    }
+
+    fn free(&self, meshes: &mut MeshArena) {
+        meshes.free(&self.alloc);
+        if let Some((water, _)) = &self.water { meshes.free(water); }
+    }
+}
+
+pub struct Chunk {
+    // None once evicted to stay inside the GPU memory budget; collision stays resident.
+    pub mesh: Option<ChunkMesh>,
+    pub collision: Arc<LocalCollisionGrid>,
     pub min: glam::Vec2,
     pub max: glam::Vec2,
+    // Vertical extent of the chunk's geometry, from tunnel floors to the tallest roof.
+    pub min_y: f32,
+    pub max_y: f32,
+    pub lights: Vec<glam::Vec3>,
+    // Kept with the collision so buildings can be picked even while the mesh is evicted.
+    pub buildings: Vec<BuildingInfo>,
+    pub elevators: Vec<Elevator>,
+    // When the chunk was last drawn; the GPU budget evicts the longest unseen first.
+    pub last_seen: Instant,
+}
+
+impl Chunk {
+    pub fn center(&self) -> glam::Vec2 {
+        (self.min + self.max) * 0.5
+    }
+
+    // The point the mesh's vertex positions are relative to.
+    pub fn origin(&self) -> glam::DVec3 {
+        glam::DVec3::new(self.min.x as f64, 0.0, self.min.y as f64)
+    }
 }
 
 pub struct World {
     pub chunks: HashMap<(i32, i32), Chunk>,
+    pub meshes: MeshArena,
+    // Bytes of chunk vertex and index data resident on the GPU. Freed ranges stay allocated
+    // until their page empties, so the budget is charged meshes.gpu_bytes() instead.
+    pub gpu_bytes: u64,
+    // Seconds of simulated time, advanced once per physics tick; drives the elevators.
+    pub clock: f64,
 }
 
 impl World {
     pub fn new() -> Self {
-        Self { chunks: HashMap::new() }
+        Self { chunks: HashMap::new(), meshes: MeshArena::new(), gpu_bytes: 0, clock: 0.0 }
+    }
+
+    pub fn tick(&mut self, dt: f64) {
+        self.clock += dt;
     }
 
-    pub fn insert_chunk(&mut self, device: &wgpu::Device, data: ChunkData) {
-        use wgpu::util::DeviceExt;
-        
+    // Corners of the box around a building's walls, from the ground to its top.
+    pub fn building_bounds(&self, id: BuildingId) -> Option<(glam::Vec3, glam::Vec3)> {
+        let chunk = self.chunks.get(&id.coord)?;
+        let mut walls = chunk.collision.walls.iter().filter(|w| w.building == Some(id.index as u32)).peekable();
+        walls.peek()?;
+        let (mut min, mut max) = (glam::Vec3::splat(f32::INFINITY), glam::Vec3::splat(f32::NEG_INFINITY));
+        for wall in walls {
+            for p in [wall.start, wall.end] {
+                min = min.min(glam::Vec3::new(p.x, wall.base, p.y));
+                max = max.max(glam::Vec3::new(p.x, wall.height, p.y));
+            }
+        }
+        Some((min, max))
+    }
+
+    // Frees the meshes of `coords`. With `copy` they're read back for the mesh cache, which
+    // gets them a frame or so later; without, the cache only notes them and the loader builds
+    // them again. Returns how many there were.
+    pub fn evict_meshes(&mut self, coords: &[(i32, i32)], cache: &mut MeshCache, device: &wgpu::Device, queue: &wgpu::Queue, copy: bool) -> usize {
+        let evicted: Vec<((i32, i32), ChunkMesh)> = coords.iter()
+            .filter_map(|coord| Some((*coord, self.chunks.get_mut(coord)?.mesh.take()?)))
+            .collect();
+        if copy {
+            let reads: Vec<(&MeshAllocation, u32)> = evicted.iter()
+                .flat_map(|(_, mesh)| std::iter::once((&mesh.alloc, mesh.lod_indices.end)).chain(mesh.water.as_ref().map(|(alloc, count)| (alloc, *count))))
+                .collect();
+            if let Some(readback) = self.meshes.read_back(device, queue, &reads) {
+                cache.read(readback, evicted.iter().map(|(coord, mesh)| Evicted {
+                    coord: *coord, index_count: mesh.index_count, lod_indices: mesh.lod_indices.clone(), gpu_bytes: mesh.gpu_bytes, water: mesh.water.is_some(),
+                }).collect());
+            }
+        }
+        for (coord, mesh) in &evicted {
+            if !copy { cache.drop_mesh(*coord, mesh.gpu_bytes); }
+            mesh.free(&mut self.meshes);
+            self.gpu_bytes -= mesh.gpu_bytes;
+        }
+        evicted.len()
+    }
+
+    // Drops a chunk outright, mesh and collision alike.
+    pub fn remove_chunk(&mut self, coord: (i32, i32)) {
+        let Some(mesh) = self.chunks.remove(&coord).and_then(|c| c.mesh) else { return };
+        mesh.free(&mut self.meshes);
+        self.gpu_bytes -= mesh.gpu_bytes;
+    }
+
+    // Puts a loaded chunk's collision, lights and buildings in place, replacing any earlier
+    // copy, and returns its mesh for ChunkUploads to copy to the GPU over the next frames.
+    pub fn insert_chunk(&mut self, data: ChunkData) -> Option<PendingMesh> {
         // Don't upload empty chunks
-        if data.indices.is_empty() { return; }
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(&format!("Chunk {:?} V", data.coord)),
-            contents: bytemuck::cast_slice(&data.vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(&format!("Chunk {:?} I", data.coord)),
-            contents: bytemuck::cast_slice(&data.indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-        
-        let cx = data.coord.0 as f32 * config::CHUNK_SIZE - (config::WORLD_SIZE / 2.0);
-        let cz = data.coord.1 as f32 * config::CHUNK_SIZE - (config::WORLD_SIZE / 2.0);
-        let offset = glam::Vec2::new(cx, cz);
+        if data.indices.is_empty() { return None; }
+
+        let index_count = data.indices.len() as u32;
+        let lod_indices = index_count..index_count + data.lod_indices.len() as u32;
+        // The LOD range goes after the full one in the same buffer, grown once in place.
+        let mut indices = data.indices;
+        indices.reserve_exact(data.lod_indices.len());
+        indices.extend_from_slice(&data.lod_indices);
+        // Stored relative to the chunk's corner; the renderer adds it back relative to the camera.
+        let offset = chunk_origin(data.coord);
+        let vertices: Vec<PackedVertex> = data.vertices.iter()
+            .map(|v| Vertex { position: [v.position[0] - offset.x, v.position[1], v.position[2] - offset.y], ..*v }.into())
+            .collect();
+        let water = (!data.water_indices.is_empty())
+            .then(|| (data.water_vertices.iter().map(|&v| v.into()).collect(), data.water_indices));
+
+        let (min_y, max_y) = data.vertices.iter().fold((f32::MAX, f32::MIN), |(lo, hi), v| (lo.min(v.position[1]), hi.max(v.position[1])));
 
         let chunk = Chunk {
-            vertex_buffer, index_buffer,
-            index_count: data.indices.len() as u32,
-            collision: LocalCollisionGrid::new(&data.walls, offset),
+            mesh: None,
+            collision: data.collision,
             min: offset,
-            max: offset + glam::Vec2::splat(config::CHUNK_SIZE),
+            max: offset + glam::Vec2::splat(config_file::chunk_size()),
+            min_y, max_y,
+            lights: data.lights,
+            buildings: data.buildings,
+            elevators: data.elevators,
+            last_seen: Instant::now(),
+        };
+        if let Some(old) = self.chunks.insert(data.coord, chunk).and_then(|c| c.mesh) {
+            old.free(&mut self.meshes);
+            self.gpu_bytes -= old.gpu_bytes;
+        }
+        Some(PendingMesh { coord: data.coord, vertices, indices, index_count, lod_indices, water })
+    }
+
+    // Copies a chunk's mesh into the arena, unless the chunk has gone since it was queued.
+    pub fn upload_mesh(&mut self, staging: &mut Staging, mesh: PendingMesh) {
+        let Some(chunk) = self.chunks.get_mut(&mesh.coord) else { return };
+        let gpu_bytes = mesh.gpu_bytes();
+        let alloc = self.meshes.upload(staging, &mesh.vertices, &mesh.indices);
+        let water = mesh.water.map(|(vertices, indices)| (self.meshes.upload(staging, &vertices, &indices), indices.len() as u32));
+        self.gpu_bytes += gpu_bytes;
+        let uploaded = ChunkMesh { alloc, index_count: mesh.index_count, lod_indices: mesh.lod_indices, water, gpu_bytes, uploaded: Instant::now() };
+        chunk.last_seen = Instant::now();
+        if let Some(old) = chunk.mesh.replace(uploaded) {
+            old.free(&mut self.meshes);
+            self.gpu_bytes -= old.gpu_bytes;
+        }
+    }
+
+    pub fn chunk_coord_at(x: f32, z: f32) -> (i32, i32) {
+        let center_offset = config_file::world_size() / 2.0;
+        (((x + center_offset) / config_file::chunk_size()).floor() as i32, ((z + center_offset) / config_file::chunk_size()).floor() as i32)
+    }
+
+    // The tunnel floor closest below `feet` among spans covering (x, z).
+    pub fn tunnel_at(&self, x: f32, z: f32, feet: f32) -> Option<TunnelHit> {
+        let p = glam::Vec2::new(x, z);
+        let (cx, cz) = Self::chunk_coord_at(x, z);
+        let mut best: Option<TunnelHit> = None;
+        for ox in -1..=1 {
+            for oz in -1..=1 {
+                let Some(chunk) = self.chunks.get(&(cx + ox, cz + oz)) else { continue };
+                for span in &chunk.collision.tunnels {
+                    let Some(floor) = span.floor_at(p) else { continue };
+                    if floor > feet + config::TUNNEL_STEP_TOLERANCE { continue; }
+                    if best.is_none_or(|b| floor > b.floor) {
+                        let ceiling = (!span.open_top).then_some(floor + config::TUNNEL_HEIGHT);
+                        best = Some(TunnelHit { floor, ceiling, velocity: 0.0 });
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    // The highest roof at (x, z) that isn't above `feet` by more than a step.
+    pub fn roof_at(&self, x: f32, z: f32, feet: f32) -> Option<f32> {
+        let p = glam::Vec2::new(x, z);
+        let (cx, cz) = Self::chunk_coord_at(x, z);
+        let mut best: Option<f32> = None;
+        for ox in -1..=1 {
+            for oz in -1..=1 {
+                let Some(roofs) = self.chunks.get(&(cx + ox, cz + oz)).and_then(|c| c.collision.get_roofs(x, z)) else { continue };
+                for roof in roofs {
+                    if roof.height > feet + config::TUNNEL_STEP_TOLERANCE || best.is_some_and(|b| b >= roof.height) { continue; }
+                    if roof.contains(p) { best = Some(roof.height); }
+                }
+            }
+        }
+        best
+    }
+
+    // Height of the water surface at (x, z), if it's inside a water area.
+    pub fn water_surface(&self, x: f32, z: f32) -> Option<f32> {
+        let p = glam::Vec2::new(x, z);
+        let (cx, cz) = Self::chunk_coord_at(x, z);
+        let water = self.chunks.get(&(cx, cz)).and_then(|c| c.collision.get_water(x, z))?;
+        water.iter().find(|tri| tri.contains(p)).map(|tri| tri.height)
+    }
+
+    // Ground under the player. The surface is solid except over entrance ramps and water
+    // basins, and roofs raise it; once underground, only tunnel spans are walkable and None
+    // means "outside the network".
+    pub fn floor_at(&self, eye: glam::DVec3, eye_height: f64) -> Option<TunnelHit> {
+        let feet = (eye.y - eye_height) as f32;
+        if let Some(surface) = self.water_surface(eye.x as f32, eye.z as f32) {
+            let bed = surface - config::WATER_DEPTH;
+            if feet >= bed - config::TUNNEL_STEP_TOLERANCE { return Some(TunnelHit { floor: bed, ceiling: None, velocity: 0.0 }); }
+        }
+        let underground = feet < config::UNDERGROUND_THRESHOLD;
+        let tunnel = self.tunnel_at(eye.x as f32, eye.z as f32, feet);
+        if underground { return tunnel; }
+        let roof = self.roof_at(eye.x as f32, eye.z as f32, feet);
+        let hit = match tunnel {
+            Some(hit) if hit.ceiling.is_none() && roof.is_none_or(|r| r <= hit.floor) => hit,
+            _ => TunnelHit { floor: roof.unwrap_or(0.0), ceiling: None, velocity: 0.0 },
         };
-        self.chunks.insert(data.coord, chunk);
+        match self.platform_at(eye.x as f32, eye.z as f32, feet) {
+            Some(platform) if platform.floor > hit.floor => Some(platform),
+            _ => Some(hit),
+        }
+    }
+
+    // A ladder within reach of a body at (x, z) whose feet are below its top.
+    pub fn ladder_at(&self, x: f32, z: f32, feet: f32) -> Option<Ladder> {
+        let p = glam::Vec2::new(x, z);
+        let (cx, cz) = Self::chunk_coord_at(x, z);
+        (-1..=1).flat_map(|ox| (-1..=1).map(move |oz| (cx + ox, cz + oz)))
+            .filter_map(|coord| self.chunks.get(&coord))
+            .flat_map(|chunk| chunk.collision.ladders.iter())
+            .find(|ladder| feet < ladder.top && ladder.in_reach(p))
+            .copied()
+    }
+
+    // The road nearest to (x, z) in this and the neighbouring chunks, by distance to its edge.
+    pub fn nearest_road(&self, x: f32, z: f32) -> Option<RoadHit> {
+        let p = glam::Vec2::new(x, z);
+        let (cx, cz) = Self::chunk_coord_at(x, z);
+        let mut best: Option<RoadHit> = None;
+        for ox in -1..=1 {
+            for oz in -1..=1 {
+                let Some(chunk) = self.chunks.get(&(cx + ox, cz + oz)) else { continue };
+                for road in &chunk.collision.roads {
+                    let ab = road.end - road.start;
+                    let t = ((p - road.start).dot(ab) / ab.length_squared().max(1e-6)).clamp(0.0, 1.0);
+                    let center = road.start + ab * t;
+                    let outside = p.distance(center) - road.half_width;
+                    if best.is_none_or(|b| outside < b.outside) {
+                        best = Some(RoadHit { center, direction: ab.normalize_or_zero(), half_width: road.half_width, outside });
+                    }
+                }
+            }
+        }
+        best
     }
-}
\ No newline at end of file
+
+    // The highest elevator platform at (x, z) that isn't above `feet` by more than a step.
+    pub fn platform_at(&self, x: f32, z: f32, feet: f32) -> Option<TunnelHit> {
+        let p = glam::Vec2::new(x, z);
+        let (cx, cz) = Self::chunk_coord_at(x, z);
+        let mut best: Option<TunnelHit> = None;
+        for ox in -1..=1 {
+            for oz in -1..=1 {
+                let Some(chunk) = self.chunks.get(&(cx + ox, cz + oz)) else { continue };
+                for elevator in chunk.elevators.iter().filter(|e| e.contains(p)) {
+                    let floor = elevator.height(self.clock);
+                    if floor > feet + config::TUNNEL_STEP_TOLERANCE || best.is_some_and(|b| b.floor >= floor) { continue; }
+                    best = Some(TunnelHit { floor, ceiling: None, velocity: elevator.velocity(self.clock) });
+                }
+            }
+        }
+        best
+    }
+
+    // First wall or roof a ray from `origin` along the unit vector `dir` hits within
+    // `max_dist`. The ground stops the ray, and nothing is hit from underground.
+    pub fn raycast(&self, origin: glam::Vec3, dir: glam::Vec3, max_dist: f32) -> Option<RayHit> {
+        if origin.y < 0.0 { return None; }
+        let max_dist = if dir.y < 0.0 { max_dist.min(origin.y / -dir.y) } else { max_dist };
+        let end = origin + dir * max_dist;
+        let (lo, hi) = (glam::Vec2::new(origin.x.min(end.x), origin.z.min(end.z)), glam::Vec2::new(origin.x.max(end.x), origin.z.max(end.z)));
+
+        let mut best: Option<RayHit> = None;
+        for (coord, chunk) in &self.chunks {
+            if chunk.max.cmplt(lo).any() || chunk.min.cmpgt(hi).any() { continue; }
+            let limit = best.map_or(max_dist, |b| b.distance);
+            if let Some((distance, normal, building)) = chunk.collision.raycast(origin, dir, limit) {
+                let building = building.map(|index| BuildingId { coord: *coord, index: index as usize });
+                best = Some(RayHit { position: origin + dir * distance, normal, distance, building });
+            }
+        }
+        best
+    }
+
+    // Deepest overlap between the player's body and a wall. The body is a vertical capsule
+    // from the feet to HEAD_CLEARANCE above the eye; walls are slabs WALL_THICKNESS thick
+    // from their base to their top. Touching a wall top with the rounded bottom pushes up
+    // as well as out, so the player can land on it.
+    pub fn check_collision(&self, new_pos: glam::DVec3, eye_height: f64) -> Option<WallHit> {
+        let feet = new_pos - glam::DVec3::Y * eye_height;
+        self.collide_capsule(feet, eye_height + config::HEAD_CLEARANCE, config::PLAYER_RADIUS)
+    }
+
+    // Push out of every wall overlapping a vertical capsule `height` tall standing at `feet`.
+    pub fn collide_capsule(&self, feet: glam::DVec3, height: f64, radius: f64) -> Option<WallHit> {
+        // Centers of the capsule's end spheres.
+        let low = feet.y + radius;
+        let high = (feet.y + height - radius).max(low);
+        let flat = glam::DVec2::new(feet.x, feet.z);
+        let (logic_cx, logic_cz) = Self::chunk_coord_at(feet.x as f32, feet.z as f32);
+
+        let mut contacts = Vec::new();
+        for ox in -1..=1 {
+            for oz in -1..=1 {
+                if let Some(chunk) = self.chunks.get(&(logic_cx + ox, logic_cz + oz))
+                    && let Some(walls) = chunk.collision.get_walls(feet.x as f32, feet.z as f32) {
+                    contacts.extend(walls.filter_map(|wall| wall.capsule_contact(flat, low, high, radius)));
+                }
+            }
+        }
+        WallHit::combine(&contacts)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{DVec2, Vec2};
+
+    fn wall(start: (f32, f32), end: (f32, f32)) -> WallCollider {
+        WallCollider::new(Vec2::from(start), Vec2::from(end), 10.0)
+    }
+
+    fn contacts(walls: &[WallCollider], flat: DVec2) -> Vec<WallHit> {
+        walls.iter().filter_map(|w| w.capsule_contact(flat, 1.0, 2.0, config::PLAYER_RADIUS)).collect()
+    }
+
+    // Pushes a player-sized capsule out of `walls` the way a physics step does.
+    fn resolve(walls: &[WallCollider], mut flat: DVec2) -> DVec2 {
+        for _ in 0..config::MAX_PHYSICS_STEPS {
+            let Some(hit) = WallHit::combine(&contacts(walls, flat)) else { break };
+            flat += DVec2::new(hit.normal.x, hit.normal.z) * (hit.depth + 0.0001);
+        }
+        flat
+    }
+
+    #[test]
+    fn split_wall_pushes_once() {
+        let whole = WallHit::combine(&contacts(&[wall((0.0, 0.0), (10.0, 0.0))], DVec2::new(5.0, 0.5))).unwrap();
+        let split = WallHit::combine(&contacts(&[wall((0.0, 0.0), (5.0, 0.0)), wall((5.0, 0.0), (10.0, 0.0))], DVec2::new(5.0, 0.5))).unwrap();
+        assert!((whole.depth - split.depth).abs() < 1e-9);
+        assert!(split.normal.distance(whole.normal) < 1e-9);
+    }
+
+    #[test]
+    fn right_angle_corner_clears_both_walls() {
+        let walls = [wall((0.0, 0.0), (10.0, 0.0)), wall((0.0, 10.0), (0.0, 0.0))];
+        let flat = resolve(&walls, DVec2::new(0.4, 0.4));
+        assert!(contacts(&walls, flat).is_empty(), "still overlapping at {flat}");
+        assert!(flat.x > 0.0 && flat.y > 0.0, "pushed through a wall to {flat}");
+    }
+
+    #[test]
+    fn acute_corner_pushes_out_the_open_end() {
+        let walls = [wall((0.0, 0.0), (10.0, 0.0)), wall((10.0, 3.0), (0.0, 0.0))];
+        let flat = resolve(&walls, DVec2::new(3.0, 0.45));
+        assert!(contacts(&walls, flat).is_empty(), "still overlapping at {flat}");
+        assert!(flat.y > 0.0 && flat.y < flat.x * 0.3, "pushed through a wall to {flat}");
+    }
+
+    #[test]
+    fn collinear_walls_merge() {
+        let walls = [wall((0.0, 0.0), (5.0, 0.0)), wall((5.0, 0.0), (10.0, 0.0)), wall((10.0, 0.0), (10.0, 5.0)), wall((10.0, 5.0), (10.0, 10.0))];
+        let merged = merge_collinear(&walls);
+        assert_eq!(merged.len(), 2);
+        assert_eq!((merged[0].start, merged[0].end), (Vec2::new(0.0, 0.0), Vec2::new(10.0, 0.0)));
+        assert_eq!((merged[1].start, merged[1].end), (Vec2::new(10.0, 0.0), Vec2::new(10.0, 10.0)));
+        assert!(merge_collinear(&[wall((0.0, 0.0), (5.0, 0.0)), wall((5.0, 0.0), (0.0, 0.0))]).len() == 2, "merged a wall doubling back");
+    }
+
+    #[test]
+    fn curving_walls_stay_on_their_corners() {
+        // Half-meter walls around a 2 km radius: each bend is far inside the tolerance, the arc isn't.
+        let radius = 2000.0;
+        let corners: Vec<Vec2> = (0..=100).map(|i| {
+            let angle = i as f32 * 0.5 / radius;
+            Vec2::new(radius * angle.sin(), radius * (angle.cos() - 1.0))
+        }).collect();
+        let walls: Vec<WallCollider> = corners.windows(2).map(|p| WallCollider::new(p[0], p[1], 10.0)).collect();
+        let merged = merge_collinear(&walls);
+        assert!(merged.len() > 1 && merged.len() < walls.len());
+        for corner in &corners {
+            let off = merged.iter().map(|w| {
+                let t = ((*corner - w.start).dot(w.end - w.start) / (w.end - w.start).length_squared()).clamp(0.0, 1.0);
+                corner.distance(w.start + (w.end - w.start) * t)
+            }).fold(f32::INFINITY, f32::min);
+            assert!(off <= config::WALL_MERGE_TOLERANCE + 1e-4, "corner {corner} is {off} m off the merged walls");
+        }
+    }
+
+    #[test]
+    fn zero_length_wall_is_ignored() {
+        let point = wall((2.0, 2.0), (2.0, 2.0));
+        assert!(point.capsule_contact(DVec2::new(2.0, 2.0), 1.0, 2.0, config::PLAYER_RADIUS).is_none());
+        let hit = WallHit::combine(&contacts(&[point, wall((0.0, 2.0), (4.0, 2.0))], DVec2::new(2.0, 2.3))).unwrap();
+        assert!(hit.normal.is_finite() && hit.depth.is_finite());
+    }
+
+    #[test]
+    fn opposite_walls_still_push() {
+        let walls = [wall((0.0, 0.0), (10.0, 0.0)), wall((10.0, 1.0), (0.0, 1.0))];
+        let hit = WallHit::combine(&contacts(&walls, DVec2::new(5.0, 0.5))).unwrap();
+        assert!(hit.depth > 0.0 && hit.normal.is_finite());
+    }
+}